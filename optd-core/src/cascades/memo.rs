@@ -7,6 +7,7 @@ use std::{
 use anyhow::{bail, Result};
 use itertools::Itertools;
 use std::any::Any;
+use std::mem::{size_of, size_of_val};
 
 use crate::{
     cost::Cost,
@@ -433,12 +434,46 @@ impl<T: RelNodeTyp> Memo<T> {
                     children,
                     data: expr.data.clone(),
                 });
+                let group_id = self.get_reduced_group_id(group_id).as_group_id();
                 return Ok(on_produce(node, group_id));
             }
         }
         bail!("no best group binding for group {}", group_id)
     }
 
+    /// Counts the references to each group from the winners of the groups in the best plan of
+    /// `group_id`, which is referenced once. The winner of a group referenced several times is
+    /// only visited once, as the plan is a DAG of groups.
+    pub fn get_best_group_ref_counts(&self, group_id: GroupId) -> Result<HashMap<GroupId, usize>> {
+        let mut ref_counts = HashMap::new();
+        self.count_best_group_refs(group_id, &mut ref_counts)?;
+        Ok(ref_counts)
+    }
+
+    fn count_best_group_refs(
+        &self,
+        group_id: GroupId,
+        ref_counts: &mut HashMap<GroupId, usize>,
+    ) -> Result<()> {
+        let group_id = self.get_reduced_group_id(group_id).as_group_id();
+        let ref_count = ref_counts.entry(group_id).or_insert(0);
+        *ref_count += 1;
+        if *ref_count > 1 {
+            return Ok(());
+        }
+        let Some(winner) = self
+            .get_group_info(group_id)
+            .winner
+            .filter(|x| !x.impossible)
+        else {
+            bail!("no best group binding for group {}", group_id)
+        };
+        for child in &self.get_expr_memoed(winner.expr_id).children {
+            self.count_best_group_refs(*child, ref_counts)?;
+        }
+        Ok(())
+    }
+
     pub fn clear_winner(&mut self) {
         for group in self.groups.values_mut() {
             group.info.winner = None;
@@ -449,4 +484,215 @@ impl<T: RelNodeTyp> Memo<T> {
     pub fn compute_plan_space(&self) -> usize {
         self.expr_id_to_expr_node.len()
     }
+
+    pub fn has_group(&self, group_id: GroupId) -> bool {
+        self.groups
+            .contains_key(&self.get_reduced_group_id(group_id))
+    }
+
+    pub fn has_expr(&self, expr_id: ExprId) -> bool {
+        self.expr_id_to_expr_node.contains_key(&expr_id)
+    }
+
+    /// Removes the groups unreachable from `roots` through the expressions of the groups, together
+    /// with their expressions. Returns the number of removed expressions.
+    pub fn gc(&mut self, roots: &[GroupId]) -> usize {
+        // A group merged into another one keeps its expressions, which belong to the other group.
+        let mut merged_into: HashMap<ReducedGroupId, Vec<ReducedGroupId>> = HashMap::new();
+        for group_id in self.groups.keys() {
+            merged_into
+                .entry(self.get_reduced_group_id(group_id.as_group_id()))
+                .or_default()
+                .push(*group_id);
+        }
+        let mut reachable = HashSet::new();
+        let mut stack = roots
+            .iter()
+            .map(|x| self.get_reduced_group_id(*x))
+            .collect_vec();
+        while let Some(group_id) = stack.pop() {
+            if !reachable.insert(group_id) {
+                continue;
+            }
+            for member in merged_into.get(&group_id).into_iter().flatten() {
+                for expr_id in &self.groups[member].group_exprs {
+                    for child in &self.expr_id_to_expr_node[expr_id].children {
+                        stack.push(self.get_reduced_group_id(*child));
+                    }
+                }
+            }
+        }
+
+        let unreachable_merged_groups = self
+            .merged_groups
+            .keys()
+            .filter(|x| !reachable.contains(&self.get_reduced_group_id(**x)))
+            .copied()
+            .collect_vec();
+        let unreachable_groups = self
+            .groups
+            .keys()
+            .filter(|x| !reachable.contains(&self.get_reduced_group_id(x.as_group_id())))
+            .copied()
+            .collect_vec();
+        for group_id in unreachable_merged_groups {
+            self.merged_groups.remove(&group_id);
+        }
+        let mut removed_exprs = 0;
+        for group_id in unreachable_groups {
+            let group = self.groups.remove(&group_id).unwrap();
+            for expr_id in group.group_exprs {
+                let expr = self.expr_id_to_expr_node.remove(&expr_id).unwrap();
+                self.expr_node_to_expr_id.remove(expr.as_ref());
+                self.expr_id_to_group_id.remove(&expr_id);
+                removed_exprs += 1;
+            }
+        }
+        removed_exprs
+    }
+
+    /// Get the approximate number of bytes held by the memo for each group: its expressions and
+    /// the shallow size of its properties.
+    pub fn get_memory_usage(&self) -> Vec<(GroupId, usize)> {
+        let mut usage = self
+            .groups
+            .iter()
+            .map(|(group_id, group)| {
+                let exprs: usize = group
+                    .group_exprs
+                    .iter()
+                    .map(|expr_id| Self::expr_memory_usage(&self.expr_id_to_expr_node[expr_id]))
+                    .sum();
+                let properties: usize = group
+                    .properties
+                    .iter()
+                    .map(|x| size_of_val(x) + size_of_val(x.as_ref()))
+                    .sum();
+                (
+                    group_id.as_group_id(),
+                    size_of::<Group>() + exprs + properties,
+                )
+            })
+            .collect_vec();
+        usage.sort();
+        usage
+    }
+
+    fn expr_memory_usage(expr: &RelMemoNode<T>) -> usize {
+        let data = match &expr.data {
+            Some(Value::String(x)) => x.len(),
+            Some(Value::Serialized(x)) => x.len(),
+            _ => 0,
+        };
+        // The node is stored both behind an `Arc` and as a key, and its id is stored four times.
+        2 * (size_of::<RelMemoNode<T>>() + expr.children.len() * size_of::<GroupId>())
+            + 4 * size_of::<ExprId>()
+            + size_of::<GroupId>()
+            + data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    enum TestTyp {
+        Group(GroupId),
+        List,
+        Scan,
+        Join,
+    }
+
+    impl Display for TestTyp {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self)
+        }
+    }
+
+    impl RelNodeTyp for TestTyp {
+        fn is_logical(&self) -> bool {
+            true
+        }
+
+        fn group_typ(group_id: GroupId) -> Self {
+            Self::Group(group_id)
+        }
+
+        fn extract_group(&self) -> Option<GroupId> {
+            match self {
+                Self::Group(group_id) => Some(*group_id),
+                _ => None,
+            }
+        }
+
+        fn list_typ() -> Self {
+            Self::List
+        }
+    }
+
+    fn new_memo() -> Memo<TestTyp> {
+        Memo::new(vec![].into())
+    }
+
+    fn scan(table: &str) -> RelNodeRef<TestTyp> {
+        Arc::new(RelNode {
+            typ: TestTyp::Scan,
+            children: vec![],
+            data: Some(Value::String(table.into())),
+        })
+    }
+
+    fn join(left: RelNodeRef<TestTyp>, right: RelNodeRef<TestTyp>) -> RelNodeRef<TestTyp> {
+        Arc::new(RelNode {
+            typ: TestTyp::Join,
+            children: vec![left, right],
+            data: None,
+        })
+    }
+
+    #[test]
+    fn gc_keeps_groups_reachable_from_roots() {
+        let mut memo = new_memo();
+        let (first, _) = memo.add_new_group_expr(join(scan("a"), scan("b")), None);
+        let (second, _) = memo.add_new_group_expr(join(scan("a"), scan("c")), None);
+        let (b, _) = memo.add_new_group_expr(scan("b"), None);
+        let (a, _) = memo.add_new_group_expr(scan("a"), None);
+        assert_eq!(memo.compute_plan_space(), 5);
+
+        // The first join and the scan of b are only reachable from the first join.
+        assert_eq!(memo.gc(&[second]), 2);
+        assert_eq!(memo.compute_plan_space(), 3);
+        assert!(!memo.has_group(first));
+        assert!(!memo.has_group(b));
+        assert!(memo.has_group(second));
+        assert!(memo.has_group(a));
+
+        // Removed expressions are added again to new groups.
+        let (b_again, _) = memo.add_new_group_expr(scan("b"), None);
+        assert_ne!(b_again, b);
+        assert_eq!(memo.compute_plan_space(), 4);
+    }
+
+    #[test]
+    fn gc_follows_merged_groups() {
+        let mut memo = new_memo();
+        let (join_group, _) = memo.add_new_group_expr(join(scan("a"), scan("b")), None);
+        let (c, _) = memo.add_new_group_expr(scan("c"), None);
+        let (d, _) = memo.add_new_group_expr(scan("d"), None);
+        // Adding the scan of c to the group of the join merges the groups.
+        memo.add_new_group_expr(scan("c"), Some(join_group));
+        assert_eq!(memo.get_all_group_ids().len(), 5);
+
+        assert_eq!(memo.gc(&[join_group]), 1);
+        assert!(memo.has_group(join_group));
+        assert!(memo.has_group(c));
+        assert!(!memo.has_group(d));
+        assert_eq!(memo.compute_plan_space(), 4);
+
+        assert_eq!(memo.gc(&[]), 4);
+        assert!(!memo.has_group(join_group));
+        assert!(memo.get_all_group_ids().is_empty());
+        assert!(memo.get_memory_usage().is_empty());
+    }
 }
@@ -7,7 +7,7 @@ use std::{
 use anyhow::Result;
 
 use crate::{
-    cost::CostModel,
+    cost::{Cost, CostModel},
     optimizer::Optimizer,
     property::{PropertyBuilder, PropertyBuilderAny},
     rel_node::{RelNodeRef, RelNodeTyp},
@@ -35,6 +35,12 @@ pub struct OptimizerProperties {
     pub partial_explore_iter: Option<usize>,
     /// Plan space can be expanded by this number of times before we stop applying logical rules.
     pub partial_explore_space: Option<usize>,
+    /// The number of most recently optimized plans whose groups are kept by
+    /// [`CascadesOptimizer::step_gc`].
+    pub gc_retained_roots: Option<usize>,
+    /// [`CascadesOptimizer::step_gc`] drops the least recently optimized plans until the memo
+    /// holds at most this number of expressions, keeping at least the last one.
+    pub memo_capacity: Option<usize>,
 }
 
 pub struct CascadesOptimizer<T: RelNodeTyp> {
@@ -42,6 +48,8 @@ pub struct CascadesOptimizer<T: RelNodeTyp> {
     pub(super) tasks: VecDeque<Box<dyn Task<T>>>,
     explored_group: HashSet<GroupId>,
     fired_rules: HashMap<ExprId, HashSet<RuleId>>,
+    /// The root groups of the optimized plans, from the least to the most recently optimized.
+    recent_roots: VecDeque<GroupId>,
     rules: Arc<[Arc<dyn Rule<T, Self>>]>,
     disabled_rules: HashSet<usize>,
     cost: Arc<dyn CostModel<T>>,
@@ -99,6 +107,7 @@ impl<T: RelNodeTyp> CascadesOptimizer<T> {
             tasks,
             explored_group: HashSet::new(),
             fired_rules: HashMap::new(),
+            recent_roots: VecDeque::new(),
             rules: rules.into(),
             cost: cost.into(),
             ctx: OptimizerContext::default(),
@@ -194,6 +203,7 @@ impl<T: RelNodeTyp> CascadesOptimizer<T> {
         self.memo = Memo::new(self.property_builders.clone());
         self.fired_rules.clear();
         self.explored_group.clear();
+        self.recent_roots.clear();
     }
 
     /// Clear the winner so that the optimizer can continue to explore the group.
@@ -204,10 +214,58 @@ impl<T: RelNodeTyp> CascadesOptimizer<T> {
     /// Optimize a `RelNode`.
     pub fn step_optimize_rel(&mut self, root_rel: RelNodeRef<T>) -> Result<GroupId> {
         let (group_id, _) = self.add_group_expr(root_rel, None);
+        self.recent_roots.retain(|x| *x != group_id);
+        self.recent_roots.push_back(group_id);
         self.fire_optimize_tasks(group_id)?;
         Ok(group_id)
     }
 
+    /// Remove the groups unreachable from the recently optimized plans, which are kept across
+    /// optimizations when only the winners are cleared, together with the optimizer states about
+    /// them. Returns the number of removed expressions.
+    pub fn step_gc(&mut self) -> usize {
+        assert!(
+            self.tasks.is_empty(),
+            "cannot collect the memo while optimizing"
+        );
+        if let Some(retained_roots) = self.prop.gc_retained_roots {
+            while self.recent_roots.len() > retained_roots.max(1) {
+                self.recent_roots.pop_front();
+            }
+        }
+        let mut removed_exprs = self.memo.gc(self.recent_roots.make_contiguous());
+        if let Some(memo_capacity) = self.prop.memo_capacity {
+            while self.memo.compute_plan_space() > memo_capacity && self.recent_roots.len() > 1 {
+                self.recent_roots.pop_front();
+                removed_exprs += self.memo.gc(self.recent_roots.make_contiguous());
+            }
+        }
+        if removed_exprs > 0 {
+            self.fired_rules
+                .retain(|expr_id, _| self.memo.has_expr(*expr_id));
+            self.explored_group
+                .retain(|group_id| self.memo.has_group(*group_id));
+        }
+        removed_exprs
+    }
+
+    /// Get the approximate number of bytes held by the memo for each group.
+    pub fn memo_memory_usage(&self) -> Vec<(GroupId, usize)> {
+        self.memo.get_memory_usage()
+    }
+
+    /// Check whether a group is still in the memo, i.e., it has not been collected.
+    pub fn has_group(&self, group_id: GroupId) -> bool {
+        self.memo.has_group(group_id)
+    }
+
+    /// Add a `RelNode` into the memo without optimizing it, returning its group. If `group_id` is
+    /// given, the expression is added to that group.
+    pub fn step_add_rel(&mut self, rel: RelNodeRef<T>, group_id: Option<GroupId>) -> GroupId {
+        let (group_id, _) = self.add_group_expr(rel, group_id);
+        group_id
+    }
+
     /// Get the group binding.
     pub fn step_get_optimize_rel(
         &self,
@@ -219,6 +277,12 @@ impl<T: RelNodeTyp> CascadesOptimizer<T> {
             .get_best_group_binding(group_id, &mut on_produce)?)
     }
 
+    /// Get the number of references to each group in the group binding, where a group shared by
+    /// several parents is counted once per parent.
+    pub fn step_get_group_ref_counts(&self, group_id: GroupId) -> Result<HashMap<GroupId, usize>> {
+        self.memo.get_best_group_ref_counts(group_id)
+    }
+
     fn fire_optimize_tasks(&mut self, group_id: GroupId) -> Result<()> {
         self.tasks
             .push_back(Box::new(OptimizeGroupTask::new(group_id)));
@@ -307,6 +371,11 @@ impl<T: RelNodeTyp> CascadesOptimizer<T> {
         self.memo.get_group_id(expr_id)
     }
 
+    /// Gets the groups of the children of an expression in the memo.
+    pub fn get_children_group_ids(&self, expr_id: ExprId) -> Vec<GroupId> {
+        self.memo.get_expr_memoed(expr_id).children.clone()
+    }
+
     pub(super) fn get_expr_memoed(&self, expr_id: ExprId) -> RelMemoNodeRef<T> {
         self.memo.get_expr_memoed(expr_id)
     }
@@ -347,6 +416,15 @@ impl<T: RelNodeTyp> CascadesOptimizer<T> {
             .insert(rule_id);
     }
 
+    /// Get the cost of the winner of a group, including its inputs.
+    pub fn get_winner_cost_of(&self, group_id: GroupId) -> Option<Cost> {
+        self.memo
+            .get_group_info(group_id)
+            .winner
+            .filter(|x| !x.impossible)
+            .map(|x| x.cost)
+    }
+
     pub fn get_cost_of(&self, group_id: GroupId) -> f64 {
         self.memo
             .get_group_info(group_id)
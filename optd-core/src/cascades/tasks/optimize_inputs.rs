@@ -153,7 +153,13 @@ impl<T: RelNodeTyp> Task<T> for OptimizeInputsTask {
             };
             if self.should_terminate(
                 cost.sum(
-                    &cost.compute_cost(&expr.typ, &expr.data, &input_cost, Some(context.clone())),
+                    &cost.compute_cost(
+                        &expr.typ,
+                        &expr.data,
+                        &input_cost,
+                        Some(context.clone()),
+                        Some(&*optimizer),
+                    ),
                     &input_cost,
                 )
                 .0[0],
@@ -178,6 +184,7 @@ impl<T: RelNodeTyp> Task<T> for OptimizeInputsTask {
                                     &expr.data,
                                     &input_cost,
                                     Some(context.clone()),
+                                    Some(&*optimizer),
                                 ),
                                 &input_cost,
                             )
@@ -249,6 +256,7 @@ impl<T: RelNodeTyp> Task<T> for OptimizeInputsTask {
                             &expr.data,
                             &input_cost,
                             Some(context.clone()),
+                            Some(&*optimizer),
                         ),
                         &input_cost,
                     ),
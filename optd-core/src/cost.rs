@@ -1,5 +1,5 @@
 use crate::{
-    cascades::RelNodeContext,
+    cascades::{CascadesOptimizer, RelNodeContext},
     rel_node::{RelNode, RelNodeTyp, Value},
 };
 
@@ -7,12 +7,16 @@ use crate::{
 pub struct Cost(pub Vec<f64>);
 
 pub trait CostModel<T: RelNodeTyp>: 'static + Send + Sync {
+    /// Computes the cost of a node from the costs of its children. When the node is costed in the
+    /// memo, `context` locates it and `optimizer` gives access to the properties of its group and
+    /// its children groups.
     fn compute_cost(
         &self,
         node: &T,
         data: &Option<Value>,
         children: &[Cost],
         context: Option<RelNodeContext>,
+        optimizer: Option<&CascadesOptimizer<T>>,
     ) -> Cost;
 
     fn compute_plan_node_cost(&self, node: &RelNode<T>) -> Cost;
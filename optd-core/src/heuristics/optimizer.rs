@@ -1,9 +1,10 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{any::Any, collections::HashMap, sync::Arc};
 
 use anyhow::Result;
 
 use crate::{
     optimizer::Optimizer,
+    property::PropertyBuilderAny,
     rel_node::{RelNode, RelNodeRef, RelNodeTyp},
     rules::{Rule, RuleMatcher},
 };
@@ -16,6 +17,7 @@ pub enum ApplyOrder {
 pub struct HeuristicsOptimizer<T: RelNodeTyp> {
     rules: Arc<[Arc<dyn Rule<T, Self>>]>,
     apply_order: ApplyOrder,
+    property_builders: Arc<[Box<dyn PropertyBuilderAny<T>>]>,
 }
 
 fn match_node<T: RelNodeTyp>(
@@ -106,12 +108,44 @@ fn match_and_pick<T: RelNodeTyp>(
 
 impl<T: RelNodeTyp> HeuristicsOptimizer<T> {
     pub fn new_with_rules(rules: Vec<Arc<dyn Rule<T, Self>>>, apply_order: ApplyOrder) -> Self {
+        Self::new_with_properties(rules, apply_order, vec![])
+    }
+
+    /// Creates an optimizer whose rules can get the properties of `property_builders`, which are
+    /// derived from the full plan on every request as there is no memo to keep them.
+    pub fn new_with_properties(
+        rules: Vec<Arc<dyn Rule<T, Self>>>,
+        apply_order: ApplyOrder,
+        property_builders: Vec<Box<dyn PropertyBuilderAny<T>>>,
+    ) -> Self {
         Self {
             rules: rules.into(),
             apply_order,
+            property_builders: property_builders.into(),
         }
     }
 
+    fn derive_property(
+        &self,
+        rel_node: &RelNodeRef<T>,
+        idx: usize,
+    ) -> Box<dyn Any + Send + Sync + 'static> {
+        let children = rel_node
+            .children
+            .iter()
+            .map(|child| self.derive_property(child, idx))
+            .collect::<Vec<_>>();
+        let children = children
+            .iter()
+            .map(|child| child.as_ref() as &dyn Any)
+            .collect::<Vec<_>>();
+        self.property_builders[idx].derive_any(
+            rel_node.typ.clone(),
+            rel_node.data.clone(),
+            &children,
+        )
+    }
+
     fn optimize_inputs(&mut self, inputs: &[RelNodeRef<T>]) -> Result<Vec<RelNodeRef<T>>> {
         let mut optimized_inputs = Vec::with_capacity(inputs.len());
         for input in inputs {
@@ -124,9 +158,12 @@ impl<T: RelNodeTyp> HeuristicsOptimizer<T> {
         for rule in self.rules.as_ref() {
             let matcher = rule.matcher();
             if let Some(picks) = match_and_pick(matcher, root_rel.clone()) {
+                // A rule returns nothing if it does not apply to the matched plan.
                 let mut results = rule.apply(&self, picks);
-                assert_eq!(results.len(), 1);
-                root_rel = results.remove(0).into();
+                assert!(results.len() <= 1);
+                if let Some(result) = results.pop() {
+                    root_rel = result.into();
+                }
             }
         }
         Ok(root_rel)
@@ -170,8 +207,9 @@ impl<T: RelNodeTyp> Optimizer<T> for HeuristicsOptimizer<T> {
         root_rel: RelNodeRef<T>,
         idx: usize,
     ) -> P::Prop {
-        let _ = root_rel;
-        let _ = idx;
-        unimplemented!()
+        *self
+            .derive_property(&root_rel, idx)
+            .downcast::<P::Prop>()
+            .expect("Failed to downcast property")
     }
 }
@@ -27,7 +27,12 @@ pub trait RelNodeTyp:
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Value {
+    /// A typed null. The type is carried by the node that owns the value.
+    Null,
     Int(i64),
+    UInt(u64),
+    /// 128-bit integers, used for decimals (the unscaled value) and month-day-nano intervals.
+    Int128(i128),
     Float(OrderedFloat<f64>),
     String(Arc<str>),
     Bool(bool),
@@ -37,7 +42,10 @@ pub enum Value {
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Self::Null => write!(f, "null"),
             Self::Int(x) => write!(f, "{x}"),
+            Self::UInt(x) => write!(f, "{x}"),
+            Self::Int128(x) => write!(f, "{x}"),
             Self::Float(x) => write!(f, "{x}"),
             Self::String(x) => write!(f, "\"{x}\""),
             Self::Bool(x) => write!(f, "{x}"),
@@ -54,6 +62,20 @@ impl Value {
         }
     }
 
+    pub fn as_u64(&self) -> u64 {
+        match self {
+            Value::UInt(i) => *i,
+            _ => panic!("Value is not an u64"),
+        }
+    }
+
+    pub fn as_i128(&self) -> i128 {
+        match self {
+            Value::Int128(i) => *i,
+            _ => panic!("Value is not an i128"),
+        }
+    }
+
     pub fn as_f64(&self) -> f64 {
         match self {
             Value::Float(i) => **i,
@@ -74,10 +96,22 @@ impl Value {
             _ => panic!("Value is not a string"),
         }
     }
+
+    pub fn as_bytes(&self) -> Arc<[u8]> {
+        match self {
+            Value::Serialized(i) => i.clone(),
+            _ => panic!("Value is not a byte array"),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
 }
 
-/// A RelNode is consisted of a plan node type and some children.
-#[derive(Clone, Debug)]
+/// A RelNode is consisted of a plan node type and some children. Rel nodes are compared
+/// structurally, i.e., by their types, data and children.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct RelNode<T: RelNodeTyp> {
     pub typ: T,
     pub children: Vec<RelNodeRef<T>>,
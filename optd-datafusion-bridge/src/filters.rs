@@ -0,0 +1,109 @@
+//! Conversions of optd predicates into DataFusion logical expressions, which is the form table
+//! providers accept filters in.
+
+use anyhow::{bail, Result};
+use datafusion::{
+    arrow::datatypes::Schema,
+    common::Column,
+    logical_expr::{
+        self,
+        expr::{Placeholder, ScalarFunction},
+    },
+    scalar::ScalarValue,
+};
+use optd_datafusion_repr::plan_nodes::{
+    BinOpExpr, ColumnRefExpr, ConstantExpr, Expr, FuncExpr, FuncType, LogOpExpr, LogOpType,
+    OptRelNode, OptRelNodeTyp, ParameterExpr, UnOpExpr, UnOpType,
+};
+
+use crate::types::{cast_scalar, value_into_scalar};
+
+/// Converts a predicate whose column references index `schema` into a logical expression. The
+/// columns are unqualified, as table providers resolve them against their own schema. Parameter
+/// `$i+1` is bound to `parameter_values[i]`, and parameters without a value become placeholders.
+pub fn from_optd_filter(
+    expr: Expr,
+    schema: &Schema,
+    parameter_values: &[ScalarValue],
+) -> Result<logical_expr::Expr> {
+    let expr = match expr.typ() {
+        OptRelNodeTyp::ColumnRef => {
+            let expr = ColumnRefExpr::from_rel_node(expr.into_rel_node()).unwrap();
+            let Some(field) = schema.fields().get(expr.index()) else {
+                bail!("column #{} out of range", expr.index())
+            };
+            logical_expr::Expr::Column(Column::from_name(field.name()))
+        }
+        OptRelNodeTyp::Constant(typ) => {
+            let expr = ConstantExpr::from_rel_node(expr.into_rel_node()).unwrap();
+            logical_expr::Expr::Literal(value_into_scalar(&typ, &expr.value())?)
+        }
+        OptRelNodeTyp::Parameter(typ) => {
+            let expr = ParameterExpr::from_rel_node(expr.into_rel_node()).unwrap();
+            match expr
+                .index()
+                .checked_sub(1)
+                .and_then(|idx| parameter_values.get(idx))
+            {
+                Some(value) => logical_expr::Expr::Literal(cast_scalar(value, &typ)?),
+                // Providers can only decide by the shape of a filter whose value is unknown.
+                None => logical_expr::Expr::Placeholder(Placeholder::new(
+                    format!("${}", expr.index()),
+                    Some(typ.into_data_type()?),
+                )),
+            }
+        }
+        OptRelNodeTyp::UnOp(op) => {
+            let expr = UnOpExpr::from_rel_node(expr.into_rel_node()).unwrap();
+            let child = Box::new(from_optd_filter(expr.child(), schema, parameter_values)?);
+            match op {
+                UnOpType::Not => logical_expr::Expr::Not(child),
+                UnOpType::Neg => logical_expr::Expr::Negative(child),
+            }
+        }
+        OptRelNodeTyp::BinOp(op) => {
+            let expr = BinOpExpr::from_rel_node(expr.into_rel_node()).unwrap();
+            let left = from_optd_filter(expr.left_child(), schema, parameter_values)?;
+            let right = from_optd_filter(expr.right_child(), schema, parameter_values)?;
+            logical_expr::binary_expr(left, op.into_operator(), right)
+        }
+        OptRelNodeTyp::LogOp(op) => {
+            let expr = LogOpExpr::from_rel_node(expr.into_rel_node()).unwrap();
+            let mut children = expr
+                .children()
+                .to_vec()
+                .into_iter()
+                .map(|expr| from_optd_filter(expr, schema, parameter_values));
+            let Some(first) = children.next() else {
+                bail!("empty {}", op)
+            };
+            children.try_fold(first?, |acc, expr| {
+                Ok::<_, anyhow::Error>(match op {
+                    LogOpType::And => logical_expr::and(acc, expr?),
+                    LogOpType::Or => logical_expr::or(acc, expr?),
+                })
+            })?
+        }
+        OptRelNodeTyp::Func(FuncType::Scalar(func)) => {
+            let expr = FuncExpr::from_rel_node(expr.into_rel_node()).unwrap();
+            let args = expr
+                .children()
+                .to_vec()
+                .into_iter()
+                .map(|expr| from_optd_filter(expr, schema, parameter_values))
+                .collect::<Result<Vec<_>>>()?;
+            logical_expr::Expr::ScalarFunction(ScalarFunction::new(func, args))
+        }
+        OptRelNodeTyp::Func(FuncType::Case) => {
+            let expr = FuncExpr::from_rel_node(expr.into_rel_node()).unwrap();
+            let args = expr.children();
+            logical_expr::when(
+                from_optd_filter(args.child(0), schema, parameter_values)?,
+                from_optd_filter(args.child(1), schema, parameter_values)?,
+            )
+            .otherwise(from_optd_filter(args.child(2), schema, parameter_values)?)?
+        }
+        typ => bail!("cannot convert {} into a filter", typ),
+    };
+    Ok(expr)
+}
@@ -5,14 +5,15 @@ use async_recursion::async_recursion;
 use datafusion::{
     arrow::{
         compute::kernels::filter,
-        datatypes::{Schema, SchemaRef},
+        datatypes::{Field as ArrowField, Schema, SchemaRef},
     },
     datasource::source_as_provider,
-    logical_expr::Operator,
     physical_expr,
     physical_plan::{
         self,
-        aggregates::AggregateMode,
+        aggregates::{AggregateExec, AggregateMode},
+        coalesce_partitions::CoalescePartitionsExec,
+        empty::EmptyExec,
         explain::ExplainExec,
         expressions::create_aggregate_expr,
         joins::{
@@ -20,21 +21,97 @@ use datafusion::{
             PartitionMode,
         },
         projection::ProjectionExec,
-        AggregateExpr, ExecutionPlan, PhysicalExpr,
+        repartition::RepartitionExec,
+        union::UnionExec,
+        values::ValuesExec,
+        AggregateExpr, ExecutionPlan, Partitioning, PhysicalExpr,
     },
-    scalar::ScalarValue,
 };
 use optd_datafusion_repr::{
     plan_nodes::{
-        BinOpExpr, BinOpType, ColumnRefExpr, ConstantExpr, ConstantType, Expr, FuncExpr, FuncType,
-        JoinType, LogOpExpr, LogOpType, OptRelNode, OptRelNodeRef, OptRelNodeTyp, PhysicalAgg,
-        PhysicalFilter, PhysicalHashJoin, PhysicalNestedLoopJoin, PhysicalProjection, PhysicalScan,
-        PhysicalSort, PlanNode, SortOrderExpr, SortOrderType,
+        BinOpExpr, ColumnRefExpr, ConstantExpr, ConstantType, ExchangeType, Expr, ExprList,
+        FuncExpr, FuncType, JoinType, LogOpExpr, LogOpType, OptRelNode, OptRelNodeRef,
+        OptRelNodeTyp, ParameterExpr, PhysicalAgg, PhysicalEmpty, PhysicalExchange, PhysicalFilter,
+        PhysicalHashJoin, PhysicalNestedLoopJoin, PhysicalProjection, PhysicalRuntimeFilter,
+        PhysicalScan, PhysicalSort, PhysicalStreamAgg, PhysicalTwoPhaseAgg, PhysicalUnion,
+        PhysicalValues, PlanNode, SortOrderExpr, SortOrderType,
     },
-    PhysicalCollector, Value,
+    properties::{
+        distribution::{Distribution, DistributionPropertyBuilder},
+        schema::{expr_name, Field, Schema as OptdSchema},
+    },
+    typing::{binary_operand_types, expr_type},
+    PhysicalCollector, PhysicalSpool, Value,
+};
+
+use crate::{
+    filters::from_optd_filter,
+    physical_collector::CollectorExec,
+    runtime_filter::{RuntimeFilterBuildExec, RuntimeFilterExec, RuntimeFilterState},
+    spool::SpoolExec,
+    types::{cast_scalar, value_into_scalar},
+    OptdPlanContext,
 };
 
-use crate::{physical_collector::CollectorExec, OptdPlanContext};
+/// Builds the arrow schema of an optd schema, which is kept by plan nodes without input.
+fn arrow_schema(schema: &OptdSchema) -> Result<SchemaRef> {
+    let fields = schema
+        .fields
+        .iter()
+        .map(|field| {
+            Ok(ArrowField::new(
+                &field.name,
+                field.typ.into_data_type()?,
+                field.nullable,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Arc::new(Schema::new(fields)))
+}
+
+/// Builds the optd schema of an arrow schema, which is used to name output columns.
+fn optd_schema(schema: &Schema) -> OptdSchema {
+    OptdSchema::new(
+        schema
+            .fields()
+            .iter()
+            .map(|field| {
+                let typ =
+                    ConstantType::from_data_type(field.data_type()).unwrap_or(ConstantType::Any);
+                Field::new(field.name(), typ, field.is_nullable())
+            })
+            .collect(),
+    )
+}
+
+/// Derives the distribution the optimizer chose for the output of `node`.
+fn distribution(node: &PlanNode) -> Distribution {
+    DistributionPropertyBuilder::new().derive_tree(&node.clone().into_rel_node())
+}
+
+/// How an aggregation is lowered.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AggStrategy {
+    Hash,
+    Stream,
+    TwoPhase,
+}
+
+/// Checks whether the output of `exec` is sorted on the group by expressions, in some order, so
+/// that rows of a group are adjacent.
+fn is_sorted_on(exec: &dyn ExecutionPlan, groups: &[(Arc<dyn PhysicalExpr>, String)]) -> bool {
+    let Some(ordering) = exec.output_ordering() else {
+        return false;
+    };
+    if exec.output_partitioning().partition_count() != 1 || ordering.len() < groups.len() {
+        return false;
+    }
+    ordering[..groups.len()].iter().all(|sort_expr| {
+        groups
+            .iter()
+            .any(|(expr, _)| expr.eq(sort_expr.expr.as_any()))
+    })
+}
 
 impl OptdPlanContext<'_> {
     #[async_recursion]
@@ -44,7 +121,33 @@ impl OptdPlanContext<'_> {
     ) -> Result<Arc<dyn ExecutionPlan + 'static>> {
         let source = self.tables.get(node.table().as_ref()).unwrap();
         let provider = source_as_provider(source)?;
-        let plan = provider.scan(self.session_state, None, &[], None).await?;
+        let projection = node
+            .projection()
+            .to_vec()
+            .into_iter()
+            .map(|expr| {
+                ColumnRefExpr::from_rel_node(expr.into_rel_node())
+                    .unwrap()
+                    .index()
+            })
+            .collect::<Vec<_>>();
+        let table_schema = provider.schema();
+        // The table is asked to apply all filters, as the inexact ones are evaluated again above.
+        let filters = node
+            .filters()
+            .to_vec()
+            .into_iter()
+            .chain(node.inexact_filters().to_vec())
+            .map(|expr| from_optd_filter(expr, &table_schema, &self.parameter_values))
+            .collect::<Result<Vec<_>>>()?;
+        let plan = provider
+            .scan(
+                self.session_state,
+                Some(&projection),
+                &filters,
+                node.fetch(),
+            )
+            .await?;
         Ok(plan)
     }
 
@@ -74,6 +177,7 @@ impl OptdPlanContext<'_> {
         expr: Expr,
         context: &SchemaRef,
     ) -> Result<Arc<dyn AggregateExpr>> {
+        let name = expr_name(&expr.clone().into_rel_node(), &optd_schema(context));
         let expr = FuncExpr::from_rel_node(expr.into_rel_node()).unwrap();
         let typ = expr.func();
         let FuncType::Agg(func) = typ else {
@@ -91,7 +195,7 @@ impl OptdPlanContext<'_> {
             &args,
             &[],
             &context,
-            "<agg_func>",
+            name,
         )?)
     }
 
@@ -106,22 +210,24 @@ impl OptdPlanContext<'_> {
             }
             OptRelNodeTyp::Constant(typ) => {
                 let expr = ConstantExpr::from_rel_node(expr.into_rel_node()).unwrap();
-                let value = expr.value();
-                let value = match typ {
-                    ConstantType::Bool => ScalarValue::Boolean(Some(value.as_bool())),
-                    ConstantType::Int => ScalarValue::Int64(Some(value.as_i64())),
-                    ConstantType::Decimal => {
-                        ScalarValue::Decimal128(Some(value.as_f64() as i128), 20, 0)
-                        // TODO(chi): no hard code decimal
-                    }
-                    ConstantType::Date => ScalarValue::Date32(Some(value.as_i64() as i32)),
-                    ConstantType::Utf8String => ScalarValue::Utf8(Some(value.as_str().to_string())),
-                    ConstantType::Any => unimplemented!(),
-                };
+                let value = value_into_scalar(&typ, &expr.value())?;
                 Ok(Arc::new(
                     datafusion::physical_plan::expressions::Literal::new(value),
                 ))
             }
+            OptRelNodeTyp::Parameter(typ) => {
+                let expr = ParameterExpr::from_rel_node(expr.into_rel_node()).unwrap();
+                let Some(value) = expr
+                    .index()
+                    .checked_sub(1)
+                    .and_then(|idx| self.parameter_values.get(idx))
+                else {
+                    bail!("parameter ${} is not bound", expr.index())
+                };
+                Ok(Arc::new(
+                    datafusion::physical_plan::expressions::Literal::new(cast_scalar(value, &typ)?),
+                ))
+            }
             OptRelNodeTyp::Func(_) => {
                 let expr = FuncExpr::from_rel_node(expr.into_rel_node()).unwrap();
                 let func = expr.func();
@@ -173,23 +279,33 @@ impl OptdPlanContext<'_> {
             }
             OptRelNodeTyp::BinOp(op) => {
                 let expr = BinOpExpr::from_rel_node(expr.into_rel_node()).unwrap();
-                let left = self.from_optd_expr(expr.left_child(), context)?;
-                let right = self.from_optd_expr(expr.right_child(), context)?;
-                let op = match op {
-                    BinOpType::Eq => Operator::Eq,
-                    BinOpType::Neq => Operator::NotEq,
-                    BinOpType::Leq => Operator::LtEq,
-                    BinOpType::Geq => Operator::GtEq,
-                    BinOpType::And => Operator::And,
-                    BinOpType::Add => Operator::Plus,
-                    BinOpType::Sub => Operator::Minus,
-                    BinOpType::Mul => Operator::Multiply,
-                    BinOpType::Div => Operator::Divide,
-                    op => unimplemented!("{}", op),
-                };
+                // The physical expressions of DataFusion expect coerced operands, so the operands
+                // are cast to the types the optimizer infers for them.
+                let input = optd_schema(context);
+                let operand_types = binary_operand_types(
+                    &expr_type(&expr.left_child().into_rel_node(), &input),
+                    op,
+                    &expr_type(&expr.right_child().into_rel_node(), &input),
+                );
+                let mut left = self.from_optd_expr(expr.left_child(), context)?;
+                let mut right = self.from_optd_expr(expr.right_child(), context)?;
+                if let Some((left_typ, right_typ)) = operand_types {
+                    left = physical_expr::expressions::cast(
+                        left,
+                        context,
+                        left_typ.into_data_type()?,
+                    )?;
+                    right = physical_expr::expressions::cast(
+                        right,
+                        context,
+                        right_typ.into_data_type()?,
+                    )?;
+                }
                 Ok(
                     Arc::new(datafusion::physical_plan::expressions::BinaryExpr::new(
-                        left, op, right,
+                        left,
+                        op.into_operator(),
+                        right,
                     )) as Arc<dyn PhysicalExpr>,
                 )
             }
@@ -262,43 +378,119 @@ impl OptdPlanContext<'_> {
         )
     }
 
+    /// Lowers an aggregation. A two-phase aggregation runs a partial aggregation on every input
+    /// partition and a final aggregation on the partial results repartitioned by the group by
+    /// columns. A single-phase aggregation aggregates each partition on its own when the optimizer
+    /// hash partitioned its input on the group by columns, and gathers its input into one
+    /// partition otherwise. A streaming
+    /// aggregation gets an input sorted on the group by columns, so that DataFusion emits every
+    /// group as soon as it ends instead of building a hash table.
     #[async_recursion]
     async fn from_optd_agg(
         &mut self,
-        node: PhysicalAgg,
+        child: PlanNode,
+        aggrs: ExprList,
+        groups: ExprList,
+        strategy: AggStrategy,
     ) -> Result<Arc<dyn ExecutionPlan + 'static>> {
-        let input_exec = self.from_optd_plan_node(node.child()).await?;
-        let agg_exprs = node
-            .aggrs()
+        let partitioned = matches!(distribution(&child), Distribution::Hash(_));
+        let mut input_exec = self.from_optd_plan_node(child).await?;
+        let target_partitions = self.session_state.config().target_partitions();
+        let two_phase = strategy == AggStrategy::TwoPhase;
+        if two_phase && input_exec.output_partitioning().partition_count() < target_partitions {
+            input_exec = Arc::new(RepartitionExec::try_new(
+                input_exec,
+                Partitioning::RoundRobinBatch(target_partitions),
+            )?);
+        }
+        let input_schema = input_exec.schema();
+        let agg_exprs = aggrs
             .to_vec()
             .into_iter()
-            .map(|expr| self.from_optd_agg_expr(expr, &input_exec.schema()))
+            .map(|expr| self.from_optd_agg_expr(expr, &input_schema))
             .collect::<Result<Vec<_>>>()?;
-        let group_exprs = node
-            .groups()
+        let optd_input_schema = optd_schema(&input_schema);
+        let group_exprs = groups
             .to_vec()
             .into_iter()
             .map(|expr| {
-                Ok((
-                    self.from_optd_expr(expr, &input_exec.schema())?,
-                    "<agg_expr>".to_string(),
-                ))
+                let name = expr_name(&expr.clone().into_rel_node(), &optd_input_schema);
+                Ok((self.from_optd_expr(expr, &input_schema)?, name))
             })
             .collect::<Result<Vec<_>>>()?;
-        let group_exprs = physical_plan::aggregates::PhysicalGroupBy::new_single(group_exprs);
+        if strategy == AggStrategy::Stream && !is_sorted_on(input_exec.as_ref(), &group_exprs) {
+            let sort_exprs = group_exprs
+                .iter()
+                .map(|(expr, _)| physical_expr::PhysicalSortExpr {
+                    expr: expr.clone(),
+                    options: Default::default(),
+                })
+                .collect();
+            if input_exec.output_partitioning().partition_count() > 1 {
+                input_exec = Arc::new(CoalescePartitionsExec::new(input_exec));
+            }
+            input_exec = Arc::new(datafusion::physical_plan::sorts::sort::SortExec::new(
+                sort_exprs, input_exec,
+            ));
+        }
+        let group_by = physical_plan::aggregates::PhysicalGroupBy::new_single(group_exprs);
         let agg_num = agg_exprs.len();
-        let schema = input_exec.schema().clone();
-        Ok(Arc::new(
-            datafusion::physical_plan::aggregates::AggregateExec::try_new(
-                AggregateMode::Single,
-                group_exprs,
+        if !two_phase {
+            // The optimizer requires the input in one partition unless it is hash partitioned on
+            // the group by columns.
+            let mode = if partitioned {
+                AggregateMode::SinglePartitioned
+            } else {
+                if input_exec.output_partitioning().partition_count() > 1 {
+                    input_exec = Arc::new(CoalescePartitionsExec::new(input_exec));
+                }
+                AggregateMode::Single
+            };
+            return Ok(Arc::new(AggregateExec::try_new(
+                mode,
+                group_by,
                 agg_exprs,
                 vec![None; agg_num],
                 vec![None; agg_num],
                 input_exec,
-                schema,
-            )?,
-        ) as Arc<dyn ExecutionPlan + 'static>)
+                input_schema,
+            )?) as Arc<dyn ExecutionPlan + 'static>);
+        }
+
+        let partial = Arc::new(AggregateExec::try_new(
+            AggregateMode::Partial,
+            group_by.clone(),
+            agg_exprs.clone(),
+            vec![None; agg_num],
+            vec![None; agg_num],
+            input_exec,
+            input_schema.clone(),
+        )?);
+        let final_group_by = group_by.as_final();
+        let (mode, final_input): (_, Arc<dyn ExecutionPlan>) = if groups.is_empty() {
+            (
+                AggregateMode::Final,
+                Arc::new(CoalescePartitionsExec::new(partial)),
+            )
+        } else {
+            let keys = final_group_by.input_exprs();
+            (
+                AggregateMode::FinalPartitioned,
+                Arc::new(RepartitionExec::try_new(
+                    partial,
+                    Partitioning::Hash(keys, target_partitions),
+                )?),
+            )
+        };
+        Ok(Arc::new(AggregateExec::try_new(
+            mode,
+            final_group_by,
+            agg_exprs,
+            vec![None; agg_num],
+            vec![None; agg_num],
+            final_input,
+            input_schema,
+        )?) as Arc<dyn ExecutionPlan + 'static>)
     }
 
     #[async_recursion]
@@ -323,6 +515,8 @@ impl OptdPlanContext<'_> {
         let join_type = match node.join_type() {
             JoinType::Inner => datafusion::logical_expr::JoinType::Inner,
             JoinType::LeftOuter => datafusion::logical_expr::JoinType::Left,
+            JoinType::RightOuter => datafusion::logical_expr::JoinType::Right,
+            JoinType::FullOuter => datafusion::logical_expr::JoinType::Full,
             _ => unimplemented!(),
         };
 
@@ -355,47 +549,216 @@ impl OptdPlanContext<'_> {
         &mut self,
         node: PhysicalHashJoin,
     ) -> Result<Arc<dyn ExecutionPlan + 'static>> {
-        let left_exec = self.from_optd_plan_node(node.left()).await?;
-        let right_exec = self.from_optd_plan_node(node.right()).await?;
+        // DataFusion always builds the hash table from its left input, so a join building its
+        // right side swaps the inputs and restores the column order with a projection.
+        let build_right = node.build_right();
+        let (build, probe, build_keys, probe_keys) = if build_right {
+            (
+                node.right(),
+                node.left(),
+                node.right_keys(),
+                node.left_keys(),
+            )
+        } else {
+            (
+                node.left(),
+                node.right(),
+                node.left_keys(),
+                node.right_keys(),
+            )
+        };
+        // The runtime filter is evaluated by the probe side, but is filled by the build side.
+        let (probe, runtime_filter) = if probe.typ() == OptRelNodeTyp::PhysicalRuntimeFilter {
+            let filter = PhysicalRuntimeFilter::from_rel_node(probe.into_rel_node()).unwrap();
+            (filter.child(), true)
+        } else {
+            (probe, false)
+        };
+        // Both sides are required to be hash partitioned on the join keys by the partitioned hash
+        // join rule, and otherwise the build side is collected into one hash table shared by all
+        // partitions.
+        let is_partitioned = |node: &PlanNode| matches!(distribution(node), Distribution::Hash(_));
+        let partition_mode = if is_partitioned(&build) && is_partitioned(&probe) {
+            PartitionMode::Partitioned
+        } else {
+            PartitionMode::CollectLeft
+        };
+        let mut build_exec = self.from_optd_plan_node(build).await?;
+        let mut probe_exec = self.from_optd_plan_node(probe).await?;
         let join_type = match node.join_type() {
             JoinType::Inner => datafusion::logical_expr::JoinType::Inner,
             _ => unimplemented!(),
         };
-        let left_exprs = node.left_keys().to_vec();
-        let right_exprs = node.right_keys().to_vec();
-        assert_eq!(left_exprs.len(), right_exprs.len());
-        let mut on = Vec::with_capacity(left_exprs.len());
-        for (left_expr, right_expr) in left_exprs.into_iter().zip(right_exprs.into_iter()) {
-            let Some(left_expr) = ColumnRefExpr::from_rel_node(left_expr.into_rel_node()) else {
-                bail!("left expr is not column ref")
+        let build_exprs = build_keys.to_vec();
+        let probe_exprs = probe_keys.to_vec();
+        assert_eq!(build_exprs.len(), probe_exprs.len());
+        let mut on = Vec::with_capacity(build_exprs.len());
+        for (build_expr, probe_expr) in build_exprs.into_iter().zip(probe_exprs.into_iter()) {
+            let Some(build_expr) = ColumnRefExpr::from_rel_node(build_expr.into_rel_node()) else {
+                bail!("build expr is not column ref")
             };
-            let Some(right_expr) = ColumnRefExpr::from_rel_node(right_expr.into_rel_node()) else {
-                bail!("right expr is not column ref")
+            let Some(probe_expr) = ColumnRefExpr::from_rel_node(probe_expr.into_rel_node()) else {
+                bail!("probe expr is not column ref")
             };
             on.push((
                 physical_expr::expressions::Column::new(
-                    left_exec.schema().field(left_expr.index()).name(),
-                    left_expr.index(),
+                    build_exec.schema().field(build_expr.index()).name(),
+                    build_expr.index(),
                 ),
                 physical_expr::expressions::Column::new(
-                    right_exec.schema().field(right_expr.index()).name(),
-                    right_expr.index(),
+                    probe_exec.schema().field(probe_expr.index()).name(),
+                    probe_expr.index(),
                 ),
             ));
         }
+        if runtime_filter {
+            let (build_keys, probe_keys): (Vec<Arc<dyn PhysicalExpr>>, Vec<Arc<dyn PhysicalExpr>>) =
+                on.iter()
+                    .map(|(build_col, probe_col)| {
+                        (
+                            Arc::new(build_col.clone()) as Arc<dyn PhysicalExpr>,
+                            Arc::new(probe_col.clone()) as Arc<dyn PhysicalExpr>,
+                        )
+                    })
+                    .unzip();
+            let state = Arc::new(RuntimeFilterState::try_new(
+                &build_keys,
+                &build_exec.schema(),
+                build_exec.output_partitioning().partition_count(),
+            )?);
+            build_exec = Arc::new(RuntimeFilterBuildExec::new(
+                build_exec,
+                build_keys,
+                state.clone(),
+            ));
+            probe_exec = Arc::new(RuntimeFilterExec::new(probe_exec, probe_keys, state));
+        }
+        let build_len = build_exec.schema().fields().len();
+        let probe_len = probe_exec.schema().fields().len();
+        let join_exec = Arc::new(datafusion::physical_plan::joins::HashJoinExec::try_new(
+            build_exec,
+            probe_exec,
+            on,
+            None,
+            &join_type,
+            partition_mode,
+            false,
+        )?) as Arc<dyn ExecutionPlan + 'static>;
+        if !build_right {
+            return Ok(join_exec);
+        }
+        let join_schema = join_exec.schema();
+        let exprs = (build_len..build_len + probe_len)
+            .chain(0..build_len)
+            .map(|idx| {
+                let name = join_schema.field(idx).name().to_string();
+                (
+                    Arc::new(physical_expr::expressions::Column::new(&name, idx))
+                        as Arc<dyn PhysicalExpr>,
+                    name,
+                )
+            })
+            .collect();
+        Ok(Arc::new(ProjectionExec::try_new(exprs, join_exec)?)
+            as Arc<dyn ExecutionPlan + 'static>)
+    }
+
+    /// Lowers an exchange. DataFusion has no broadcast operator; a broadcast input is gathered
+    /// into one partition, which a hash join collecting its build side shares with all partitions.
+    #[async_recursion]
+    async fn from_optd_exchange(
+        &mut self,
+        node: PhysicalExchange,
+    ) -> Result<Arc<dyn ExecutionPlan + 'static>> {
+        let input_exec = self.from_optd_plan_node(node.child()).await?;
+        let target_partitions = self.session_state.config().target_partitions();
+        let partitioning = match node.exchange_type() {
+            ExchangeType::Single | ExchangeType::Broadcast => {
+                if input_exec.output_partitioning().partition_count() == 1 {
+                    return Ok(input_exec);
+                }
+                return Ok(Arc::new(CoalescePartitionsExec::new(input_exec)));
+            }
+            ExchangeType::RoundRobin => Partitioning::RoundRobinBatch(target_partitions),
+            ExchangeType::Hash => {
+                let input_schema = input_exec.schema();
+                let keys = node
+                    .keys()
+                    .to_vec()
+                    .into_iter()
+                    .map(|expr| self.from_optd_expr(expr, &input_schema))
+                    .collect::<Result<Vec<_>>>()?;
+                Partitioning::Hash(keys, target_partitions)
+            }
+        };
         Ok(
-            Arc::new(datafusion::physical_plan::joins::HashJoinExec::try_new(
-                left_exec,
-                right_exec,
-                on,
-                None,
-                &join_type,
-                PartitionMode::CollectLeft,
-                false,
-            )?) as Arc<dyn ExecutionPlan + 'static>,
+            Arc::new(RepartitionExec::try_new(input_exec, partitioning)?)
+                as Arc<dyn ExecutionPlan + 'static>,
         )
     }
 
+    fn from_optd_empty(&mut self, node: PhysicalEmpty) -> Result<Arc<dyn ExecutionPlan + 'static>> {
+        Ok(
+            Arc::new(EmptyExec::new(false, arrow_schema(&node.schema())?))
+                as Arc<dyn ExecutionPlan + 'static>,
+        )
+    }
+
+    fn from_optd_values(
+        &mut self,
+        node: PhysicalValues,
+    ) -> Result<Arc<dyn ExecutionPlan + 'static>> {
+        let schema = arrow_schema(&node.schema())?;
+        let rows = node.rows();
+        if schema.fields().is_empty() {
+            // Arrow cannot build batches without columns, so a single row without columns is
+            // produced by an empty relation instead.
+            if rows.len() != 1 {
+                bail!("cannot produce {} rows without columns", rows.len());
+            }
+            return Ok(Arc::new(EmptyExec::new(true, schema)) as Arc<dyn ExecutionPlan + 'static>);
+        }
+        // The expressions of the rows do not refer to any column.
+        let context = Arc::new(Schema::empty());
+        let data = rows
+            .into_iter()
+            .map(|row| {
+                row.to_vec()
+                    .into_iter()
+                    .map(|expr| self.from_optd_expr(expr, &context))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Arc::new(ValuesExec::try_new(schema, data)?) as Arc<dyn ExecutionPlan + 'static>)
+    }
+
+    #[async_recursion]
+    async fn from_optd_union(
+        &mut self,
+        node: PhysicalUnion,
+    ) -> Result<Arc<dyn ExecutionPlan + 'static>> {
+        let left_exec = self.from_optd_plan_node(node.left()).await?;
+        let right_exec = self.from_optd_plan_node(node.right()).await?;
+        Ok(Arc::new(UnionExec::new(vec![left_exec, right_exec]))
+            as Arc<dyn ExecutionPlan + 'static>)
+    }
+
+    /// Lowers a spool. All spools of a group share one executor, whose input is lowered once.
+    #[async_recursion]
+    async fn from_optd_spool(
+        &mut self,
+        node: PhysicalSpool,
+    ) -> Result<Arc<dyn ExecutionPlan + 'static>> {
+        if let Some(spool_exec) = self.spools.get(&node.group_id()) {
+            return Ok(spool_exec.clone());
+        }
+        let input_exec = self.from_optd_plan_node(node.child()).await?;
+        let spool_exec =
+            Arc::new(SpoolExec::new(input_exec, node.group_id())) as Arc<dyn ExecutionPlan>;
+        self.spools.insert(node.group_id(), spool_exec.clone());
+        Ok(spool_exec)
+    }
+
     #[async_recursion]
     async fn from_optd_plan_node(&mut self, node: PlanNode) -> Result<Arc<dyn ExecutionPlan>> {
         let rel_node = node.into_rel_node();
@@ -405,6 +768,16 @@ impl OptdPlanContext<'_> {
                 self.from_optd_table_scan(PhysicalScan::from_rel_node(rel_node).unwrap())
                     .await
             }
+            OptRelNodeTyp::PhysicalEmpty => {
+                self.from_optd_empty(PhysicalEmpty::from_rel_node(rel_node).unwrap())
+            }
+            OptRelNodeTyp::PhysicalValues => {
+                self.from_optd_values(PhysicalValues::from_rel_node(rel_node).unwrap())
+            }
+            OptRelNodeTyp::PhysicalUnion => {
+                self.from_optd_union(PhysicalUnion::from_rel_node(rel_node).unwrap())
+                    .await
+            }
             OptRelNodeTyp::PhysicalProjection => {
                 self.from_optd_projection(PhysicalProjection::from_rel_node(rel_node).unwrap())
                     .await
@@ -418,9 +791,30 @@ impl OptdPlanContext<'_> {
                     .await
             }
             OptRelNodeTyp::PhysicalAgg => {
-                self.from_optd_agg(PhysicalAgg::from_rel_node(rel_node).unwrap())
+                let node = PhysicalAgg::from_rel_node(rel_node).unwrap();
+                self.from_optd_agg(node.child(), node.aggrs(), node.groups(), AggStrategy::Hash)
                     .await
             }
+            OptRelNodeTyp::PhysicalStreamAgg => {
+                let node = PhysicalStreamAgg::from_rel_node(rel_node).unwrap();
+                self.from_optd_agg(
+                    node.child(),
+                    node.aggrs(),
+                    node.groups(),
+                    AggStrategy::Stream,
+                )
+                .await
+            }
+            OptRelNodeTyp::PhysicalTwoPhaseAgg => {
+                let node = PhysicalTwoPhaseAgg::from_rel_node(rel_node).unwrap();
+                self.from_optd_agg(
+                    node.child(),
+                    node.aggrs(),
+                    node.groups(),
+                    AggStrategy::TwoPhase,
+                )
+                .await
+            }
             OptRelNodeTyp::PhysicalNestedLoopJoin(_) => {
                 self.from_optd_nested_loop_join(
                     PhysicalNestedLoopJoin::from_rel_node(rel_node).unwrap(),
@@ -431,6 +825,14 @@ impl OptdPlanContext<'_> {
                 self.from_optd_hash_join(PhysicalHashJoin::from_rel_node(rel_node).unwrap())
                     .await
             }
+            OptRelNodeTyp::PhysicalExchange(_) => {
+                self.from_optd_exchange(PhysicalExchange::from_rel_node(rel_node).unwrap())
+                    .await
+            }
+            OptRelNodeTyp::PhysicalSpool(_) => {
+                self.from_optd_spool(PhysicalSpool::from_rel_node(rel_node).unwrap())
+                    .await
+            }
             OptRelNodeTyp::PhysicalCollector(_) => {
                 let node = PhysicalCollector::from_rel_node(rel_node).unwrap();
                 let child = self.from_optd_plan_node(node.child()).await?;
@@ -450,3 +852,198 @@ impl OptdPlanContext<'_> {
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::{
+            array::{AsArray, Int32Array},
+            datatypes::{DataType, Int32Type},
+            record_batch::RecordBatch,
+        },
+        datasource::{provider_as_source, MemTable},
+        physical_plan::{common::collect, joins::HashJoinExec},
+        prelude::{SessionConfig, SessionContext},
+    };
+    use futures_lite::future::block_on;
+    use optd_core::rel_node::RelNode;
+    use optd_datafusion_repr::plan_nodes::LogicalScan;
+
+    use super::*;
+
+    #[test]
+    fn scan_projection_is_lowered_into_the_table_scan() {
+        let schema = Arc::new(Schema::new(
+            ["a", "b", "c"]
+                .into_iter()
+                .map(|name| ArrowField::new(name, DataType::Int32, true))
+                .collect::<Vec<_>>(),
+        ));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(Int32Array::from(vec![3, 4])),
+                Arc::new(Int32Array::from(vec![5, 6])),
+            ],
+        )
+        .unwrap();
+        let table = Arc::new(MemTable::try_new(schema, vec![vec![batch]]).unwrap());
+        let session_ctx = SessionContext::new();
+        let session_state = session_ctx.state();
+        let mut ctx = OptdPlanContext::new(&session_state);
+        ctx.tables
+            .insert("t".to_string(), provider_as_source(table));
+
+        let scan = LogicalScan::new(
+            "t".to_string(),
+            ExprList::new(vec![ColumnRefExpr::new(2).into_expr()]),
+        )
+        .into_rel_node();
+        let scan = RelNode {
+            typ: OptRelNodeTyp::PhysicalScan,
+            ..scan.as_ref().clone()
+        };
+        let scan = PhysicalScan::new(PlanNode::from_rel_node(scan.into()).unwrap());
+        let exec = block_on(ctx.from_optd_table_scan(scan)).unwrap();
+
+        // The table reads only the projected column.
+        let exec_schema = exec.schema();
+        assert_eq!(exec_schema.fields().len(), 1);
+        assert_eq!(exec_schema.field(0).name(), "c");
+        let batches = block_on(collect(exec.execute(0, session_ctx.task_ctx()).unwrap())).unwrap();
+        let values = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![5, 6]);
+    }
+
+    /// Builds a session running parallel operators on 16 partitions, with the table `t(a, b)` in
+    /// two partitions of two rows each.
+    fn partitioned_ctx() -> (
+        SessionContext,
+        Arc<dyn datafusion::logical_expr::TableSource>,
+    ) {
+        let schema = Arc::new(Schema::new(
+            ["a", "b"]
+                .into_iter()
+                .map(|name| ArrowField::new(name, DataType::Int32, true))
+                .collect::<Vec<_>>(),
+        ));
+        let batch = |a: Vec<i32>, b: Vec<i32>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from(a)), Arc::new(Int32Array::from(b))],
+            )
+            .unwrap()
+        };
+        let partitions = vec![
+            vec![batch(vec![1, 2], vec![10, 20])],
+            vec![batch(vec![1, 3], vec![30, 40])],
+        ];
+        let table = Arc::new(MemTable::try_new(schema.clone(), partitions).unwrap());
+        let config = SessionConfig::new().with_target_partitions(16);
+        (
+            SessionContext::new_with_config(config),
+            provider_as_source(table),
+        )
+    }
+
+    fn col(index: usize) -> Expr {
+        ColumnRefExpr::new(index).into_expr()
+    }
+
+    fn physical_scan() -> PlanNode {
+        let scan = LogicalScan::new("t".to_string(), ExprList::new(vec![col(0), col(1)]));
+        PhysicalScan(scan.into_plan_node()).into_plan_node()
+    }
+
+    fn hash_exchange(child: PlanNode) -> PlanNode {
+        PhysicalExchange::new(child, ExprList::new(vec![col(0)]), ExchangeType::Hash)
+            .into_plan_node()
+    }
+
+    /// Lowers `select a, count(b) from t group by a` over `child`.
+    fn lower_agg(child: PlanNode) -> (Arc<dyn ExecutionPlan>, usize) {
+        let (session_ctx, table) = partitioned_ctx();
+        let session_state = session_ctx.state();
+        let mut ctx = OptdPlanContext::new(&session_state);
+        ctx.tables.insert("t".to_string(), table);
+        let count = FuncExpr::new(
+            FuncType::new_agg(datafusion::logical_expr::AggregateFunction::Count),
+            ExprList::new(vec![col(1)]),
+        );
+        let agg = PhysicalAgg::new(
+            child,
+            ExprList::new(vec![count.into_expr()]),
+            ExprList::new(vec![col(0)]),
+        );
+        let exec = block_on(ctx.from_optd_plan_node(agg.into_plan_node())).unwrap();
+        let batches =
+            block_on(physical_plan::collect(exec.clone(), session_ctx.task_ctx())).unwrap();
+        let rows = batches.iter().map(|batch| batch.num_rows()).sum();
+        (exec, rows)
+    }
+
+    fn agg_mode(exec: &Arc<dyn ExecutionPlan>) -> AggregateMode {
+        *exec
+            .as_any()
+            .downcast_ref::<AggregateExec>()
+            .unwrap()
+            .mode()
+    }
+
+    #[test]
+    fn aggregations_over_hash_partitioned_inputs_stay_partitioned() {
+        let (exec, rows) = lower_agg(hash_exchange(physical_scan()));
+        assert_eq!(agg_mode(&exec), AggregateMode::SinglePartitioned);
+        assert_eq!(exec.output_partitioning().partition_count(), 16);
+        assert!(exec.children()[0]
+            .as_any()
+            .downcast_ref::<RepartitionExec>()
+            .is_some());
+        assert_eq!(rows, 3);
+    }
+
+    #[test]
+    fn aggregations_over_unpartitioned_inputs_are_gathered() {
+        // The optimizer never produces this plan, but the input is gathered into one partition
+        // whenever it is not hash partitioned on the group by columns.
+        let (exec, rows) = lower_agg(physical_scan());
+        assert_eq!(agg_mode(&exec), AggregateMode::Single);
+        assert_eq!(exec.output_partitioning().partition_count(), 1);
+        assert_eq!(rows, 3);
+    }
+
+    #[test]
+    fn joins_of_hash_partitioned_inputs_are_partitioned() {
+        let (session_ctx, table) = partitioned_ctx();
+        let session_state = session_ctx.state();
+        let mut ctx = OptdPlanContext::new(&session_state);
+        ctx.tables.insert("t".to_string(), table);
+        let join = PhysicalHashJoin::new(
+            hash_exchange(physical_scan()),
+            hash_exchange(physical_scan()),
+            ExprList::new(vec![col(0)]),
+            ExprList::new(vec![col(0)]),
+            JoinType::Inner,
+        );
+        let exec = block_on(ctx.from_optd_plan_node(join.into_plan_node())).unwrap();
+        let join_exec = exec.as_any().downcast_ref::<HashJoinExec>().unwrap();
+        assert_eq!(join_exec.partition_mode(), &PartitionMode::Partitioned);
+        assert_eq!(exec.output_partitioning().partition_count(), 16);
+        // a = 1 matches twice on each side.
+        let batches = block_on(physical_plan::collect(exec, session_ctx.task_ctx())).unwrap();
+        assert_eq!(
+            batches.iter().map(|batch| batch.num_rows()).sum::<usize>(),
+            6
+        );
+    }
+}
@@ -1,38 +1,139 @@
 use anyhow::{bail, Result};
 use datafusion::{
     common::DFSchema,
-    logical_expr::{self, logical_plan, LogicalPlan, Operator},
-    scalar::ScalarValue,
+    logical_expr::{self, logical_plan, LogicalPlan, Operator, TableProviderFilterPushDown},
 };
 use optd_core::rel_node::RelNode;
-use optd_datafusion_repr::plan_nodes::{
-    BinOpExpr, BinOpType, ColumnRefExpr, ConstantExpr, Expr, ExprList, FuncExpr, FuncType,
-    JoinType, LogOpExpr, LogOpType, LogicalAgg, LogicalFilter, LogicalJoin, LogicalProjection,
-    LogicalScan, LogicalSort, OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode, SortOrderExpr,
-    SortOrderType,
+use optd_datafusion_repr::{
+    plan_nodes::{
+        BinOpExpr, BinOpType, ColumnIdExpr, ColumnRefExpr, ConstantExpr, ConstantType, Expr,
+        ExprList, FuncExpr, FuncType, JoinType, LogOpExpr, LogOpType, LogicalAgg, LogicalEmpty,
+        LogicalFilter, LogicalJoin, LogicalProjection, LogicalScan, LogicalSort, LogicalUnion,
+        LogicalValues, OptRelNode, OptRelNodeRef, OptRelNodeTyp, ParameterExpr, PlanNode,
+        SortOrderExpr, SortOrderType,
+    },
+    properties::schema::{Field, Schema},
 };
 
-use crate::OptdPlanContext;
+use crate::{types::scalar_into_value, OptdPlanContext};
 
 impl OptdPlanContext<'_> {
-    fn into_optd_table_scan(&mut self, node: &logical_plan::TableScan) -> Result<PlanNode> {
-        let table_name = node.table_name.to_string();
-        if node.fetch.is_some() {
-            bail!("fetch")
+    /// Assigns ids to the columns `exprs` produce if column ids are enabled, and records them as
+    /// the output columns of the plan node being converted. A column reference keeps the id of the
+    /// column, and any other expression produces a new column.
+    fn assign_column_ids(&mut self, exprs: ExprList) -> ExprList {
+        let Some(next_column_id) = self.next_column_id.as_mut() else {
+            return exprs;
+        };
+        let ids = exprs
+            .to_vec()
+            .into_iter()
+            .map(
+                |expr| match ColumnIdExpr::from_rel_node(expr.into_rel_node()) {
+                    Some(column) => column.id(),
+                    None => {
+                        *next_column_id += 1;
+                        *next_column_id - 1
+                    }
+                },
+            )
+            .collect::<Vec<_>>();
+        self.column_ids = ids.clone();
+        exprs.with_column_ids(&ids)
+    }
+
+    /// Builds the optd schema of a plan node without input, assigning ids to its columns if column
+    /// ids are enabled.
+    fn into_optd_schema(&mut self, schema: &DFSchema) -> Result<Schema> {
+        let mut fields = schema
+            .fields()
+            .iter()
+            .map(|field| {
+                let typ = ConstantType::from_data_type(field.data_type())?;
+                Ok(Field {
+                    qualifier: field.qualifier().map(|qualifier| qualifier.to_string()),
+                    ..Field::new(field.name(), typ, field.is_nullable())
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(next_column_id) = self.next_column_id.as_mut() {
+            self.column_ids = (0..fields.len())
+                .map(|_| {
+                    *next_column_id += 1;
+                    *next_column_id - 1
+                })
+                .collect();
+            for (field, id) in fields.iter_mut().zip(&self.column_ids) {
+                field.id = Some(*id);
+            }
         }
-        if !node.filters.is_empty() {
-            bail!("no filters")
+        Ok(Schema::new(fields))
+    }
+
+    fn into_optd_empty_relation(&mut self, node: &logical_plan::EmptyRelation) -> Result<PlanNode> {
+        let schema = self.into_optd_schema(&node.schema)?;
+        if node.produce_one_row {
+            // A single row without columns, e.g., the input of `SELECT 1`.
+            let row = ExprList::new(vec![]);
+            return Ok(LogicalValues::new(&schema, vec![row]).into_plan_node());
+        }
+        Ok(LogicalEmpty::new(&schema).into_plan_node())
+    }
+
+    fn into_optd_values(&mut self, node: &logical_plan::Values) -> Result<PlanNode> {
+        // The expressions of the rows do not refer to any column.
+        let context = DFSchema::empty();
+        let rows = node
+            .values
+            .iter()
+            .map(|row| self.into_optd_expr_list(row, &context))
+            .collect::<Result<Vec<_>>>()?;
+        let schema = self.into_optd_schema(&node.schema)?;
+        if rows.is_empty() {
+            return Ok(LogicalEmpty::new(&schema).into_plan_node());
         }
+        Ok(LogicalValues::new(&schema, rows).into_plan_node())
+    }
+
+    fn into_optd_table_scan(&mut self, node: &logical_plan::TableScan) -> Result<PlanNode> {
+        let table_name = node.table_name.to_string();
         self.tables.insert(table_name.clone(), node.source.clone());
-        let scan = LogicalScan::new(table_name);
-        if let Some(ref projection) = node.projection {
-            let mut exprs = Vec::with_capacity(projection.len());
-            for &p in projection {
-                exprs.push(ColumnRefExpr::new(p).into_expr());
+        let projection = match node.projection {
+            Some(ref projection) => projection.clone(),
+            None => (0..node.source.schema().fields().len()).collect(),
+        };
+        let projection = projection
+            .into_iter()
+            .map(|p| ColumnRefExpr::new(p).into_expr())
+            .collect();
+        // The filters of a table scan refer to the columns of the table by position.
+        let table_schema =
+            DFSchema::try_from_qualified_schema(node.table_name.clone(), &node.source.schema())?;
+        // DataFusion keeps the filters the table applies inexactly in a filter above the scan.
+        let pushdown = node
+            .source
+            .supports_filters_pushdown(&node.filters.iter().collect::<Vec<_>>())?;
+        let mut filters = vec![];
+        let mut inexact_filters = vec![];
+        for (filter, pushdown) in node.filters.iter().zip(pushdown) {
+            if pushdown == TableProviderFilterPushDown::Exact {
+                filters.push(filter.clone());
+            } else {
+                inexact_filters.push(filter.clone());
             }
-            let projection = LogicalProjection::new(scan.into_plan_node(), ExprList::new(exprs));
-            return Ok(projection.into_plan_node());
         }
+        let next_column_id = self.next_column_id.take();
+        let filters = self.into_optd_expr_list(&filters, &table_schema)?;
+        let inexact_filters = self.into_optd_expr_list(&inexact_filters, &table_schema)?;
+        self.next_column_id = next_column_id;
+        let projection = self.assign_column_ids(ExprList::new(projection));
+        let scan = LogicalScan::new_with_pushdown(
+            table_name,
+            projection,
+            filters,
+            inexact_filters,
+            node.fetch,
+        );
         Ok(scan.into_plan_node())
     }
 
@@ -58,28 +159,37 @@ impl OptdPlanContext<'_> {
             }
             Expr::Column(col) => {
                 let idx = context.index_of_column(col)?;
-                Ok(ColumnRefExpr::new(idx).into_expr())
-            }
-            Expr::Literal(x) => match x {
-                ScalarValue::UInt8(x) => {
-                    let x = x.as_ref().unwrap();
-                    Ok(ConstantExpr::int(*x as i64).into_expr())
-                }
-                ScalarValue::Utf8(x) => {
-                    let x = x.as_ref().unwrap();
-                    Ok(ConstantExpr::string(x).into_expr())
-                }
-                ScalarValue::Date32(x) => {
-                    let x = x.as_ref().unwrap();
-                    Ok(ConstantExpr::date(*x as i64).into_expr())
+                if self.next_column_id.is_some() {
+                    Ok(ColumnIdExpr::new(self.column_ids[idx]).into_expr())
+                } else {
+                    Ok(ColumnRefExpr::new(idx).into_expr())
                 }
-                ScalarValue::Decimal128(x, p, s) => {
-                    let x = x.as_ref().unwrap();
-                    Ok(ConstantExpr::decimal(*x as f64).into_expr())
-                }
-                _ => bail!("{:?}", x),
-            },
+            }
+            Expr::Literal(x) => {
+                let (typ, value) = scalar_into_value(x)?;
+                Ok(ConstantExpr::new_with_type(value, typ).into_expr())
+            }
             Expr::Alias(x) => self.into_optd_expr(x.expr.as_ref(), context),
+            Expr::Placeholder(x) => {
+                let Some(index) =
+                    x.id.strip_prefix('$')
+                        .and_then(|id| id.parse::<usize>().ok())
+                else {
+                    bail!("invalid parameter {}", x.id)
+                };
+                // The type is either inferred from the expression around the parameter or
+                // declared by the prepared statement.
+                let data_type = x.data_type.as_ref().or_else(|| {
+                    index
+                        .checked_sub(1)
+                        .and_then(|idx| self.parameter_types.get(idx))
+                });
+                let Some(data_type) = data_type else {
+                    bail!("unknown type of parameter {}", x.id)
+                };
+                let typ = ConstantType::from_data_type(data_type)?;
+                Ok(ParameterExpr::new(index, typ).into_expr())
+            }
             Expr::ScalarFunction(x) => {
                 let args = self.into_optd_expr_list(&x.args, context)?;
                 Ok(FuncExpr::new(FuncType::new_scalar(x.fun), args).into_expr())
@@ -124,6 +234,7 @@ impl OptdPlanContext<'_> {
     ) -> Result<LogicalProjection> {
         let input = self.into_optd_plan_node(node.input.as_ref())?;
         let expr_list = self.into_optd_expr_list(&node.expr, node.input.schema())?;
+        let expr_list = self.assign_column_ids(expr_list);
         Ok(LogicalProjection::new(input, expr_list))
     }
 
@@ -151,10 +262,128 @@ impl OptdPlanContext<'_> {
         Ok(LogicalSort::new(input, expr_list))
     }
 
+    /// Builds a reference to the `idx`-th output column of the plan node just converted.
+    fn output_column(&self, idx: usize) -> Expr {
+        if self.next_column_id.is_some() {
+            ColumnIdExpr::new(self.column_ids[idx]).into_expr()
+        } else {
+            ColumnRefExpr::new(idx).into_expr()
+        }
+    }
+
+    /// Converts `DISTINCT` into an aggregation grouping by all columns of the input.
+    fn into_optd_distinct(&mut self, node: &logical_plan::Distinct) -> Result<LogicalAgg> {
+        let input = self.into_optd_plan_node(node.input.as_ref())?;
+        let group_exprs = (0..node.input.schema().fields().len())
+            .map(|idx| self.output_column(idx))
+            .collect();
+        let group_exprs = self.assign_column_ids(ExprList::new(group_exprs));
+        Ok(LogicalAgg::new(input, ExprList::new(vec![]), group_exprs))
+    }
+
+    /// Converts `UNION ALL` into a left-deep tree of binary unions. The output columns take the ids
+    /// of the columns of the first input.
+    fn into_optd_union(&mut self, node: &logical_plan::Union) -> Result<PlanNode> {
+        let mut inputs = node.inputs.iter();
+        let mut union = self.into_optd_plan_node(inputs.next().unwrap())?;
+        let column_ids = std::mem::take(&mut self.column_ids);
+        for input in inputs {
+            let input = self.into_optd_plan_node(input)?;
+            union = LogicalUnion::new(union, input).into_plan_node();
+        }
+        self.column_ids = column_ids;
+        Ok(union)
+    }
+
+    /// Gets the arguments of the aggregates of `aggr_expr` if they all aggregate distinct values
+    /// of the same arguments, e.g., `COUNT(DISTINCT x), SUM(DISTINCT x)`. Returns `None` if no
+    /// aggregate is distinct.
+    fn distinct_agg_args(
+        aggr_expr: &[logical_expr::Expr],
+    ) -> Result<Option<&[logical_expr::Expr]>> {
+        use logical_expr::Expr;
+        let aggs = aggr_expr
+            .iter()
+            .map(|expr| match expr {
+                Expr::Alias(x) => x.expr.as_ref(),
+                expr => expr,
+            })
+            .map(|expr| match expr {
+                Expr::AggregateFunction(x) => Ok(x),
+                _ => bail!("unsupported aggregate: {}", expr),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if aggs.iter().all(|agg| !agg.distinct) {
+            return Ok(None);
+        }
+        let args = &aggs[0].args;
+        if aggs.iter().any(|agg| {
+            !agg.distinct || &agg.args != args || agg.filter.is_some() || agg.order_by.is_some()
+        }) {
+            bail!("unsupported mix of distinct aggregates: {:?}", aggr_expr);
+        }
+        Ok(Some(args))
+    }
+
+    /// Converts an aggregation of distinct values into two aggregations: the inner one removes the
+    /// duplicate arguments within each group, and the outer one aggregates the remaining values.
+    fn into_optd_distinct_agg(
+        &mut self,
+        node: &logical_plan::Aggregate,
+        args: &[logical_expr::Expr],
+    ) -> Result<LogicalAgg> {
+        use logical_expr::Expr;
+        let input = self.into_optd_plan_node(node.input.as_ref())?;
+        let group_exprs = self.into_optd_expr_list(&node.group_expr, node.input.schema())?;
+        let arg_exprs = self.into_optd_expr_list(args, node.input.schema())?;
+        let inner_groups = [group_exprs.to_vec(), arg_exprs.to_vec()].concat();
+        let inner_groups = self.assign_column_ids(ExprList::new(inner_groups));
+        let inner = LogicalAgg::new(input, ExprList::new(vec![]), inner_groups);
+
+        let group_len = node.group_expr.len();
+        let group_exprs = (0..group_len).map(|idx| self.output_column(idx)).collect();
+        let arg_exprs = ExprList::new(
+            (group_len..group_len + args.len())
+                .map(|idx| self.output_column(idx))
+                .collect(),
+        );
+        let agg_exprs = node
+            .aggr_expr
+            .iter()
+            .map(|expr| match expr {
+                Expr::Alias(x) => x.expr.as_ref(),
+                expr => expr,
+            })
+            .map(|expr| match expr {
+                Expr::AggregateFunction(x) => {
+                    FuncExpr::new(FuncType::new_agg(x.fun.clone()), arg_exprs.clone()).into_expr()
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+        let group_exprs = self.assign_column_ids(ExprList::new(group_exprs));
+        let group_ids = std::mem::take(&mut self.column_ids);
+        let agg_exprs = self.assign_column_ids(ExprList::new(agg_exprs));
+        self.column_ids = [group_ids, std::mem::take(&mut self.column_ids)].concat();
+        Ok(LogicalAgg::new(
+            inner.into_plan_node(),
+            agg_exprs,
+            group_exprs,
+        ))
+    }
+
     fn into_optd_agg(&mut self, node: &logical_plan::Aggregate) -> Result<LogicalAgg> {
+        if let Some(args) = Self::distinct_agg_args(&node.aggr_expr)? {
+            return self.into_optd_distinct_agg(node, args);
+        }
         let input = self.into_optd_plan_node(node.input.as_ref())?;
         let agg_exprs = self.into_optd_expr_list(&node.aggr_expr, node.input.schema())?;
         let group_exprs = self.into_optd_expr_list(&node.group_expr, node.input.schema())?;
+        // The group by columns come first in the output.
+        let group_exprs = self.assign_column_ids(group_exprs);
+        let group_ids = std::mem::take(&mut self.column_ids);
+        let agg_exprs = self.assign_column_ids(agg_exprs);
+        self.column_ids = [group_ids, std::mem::take(&mut self.column_ids)].concat();
         Ok(LogicalAgg::new(input, agg_exprs, group_exprs))
     }
 
@@ -188,7 +417,9 @@ impl OptdPlanContext<'_> {
     fn into_optd_join(&mut self, node: &logical_plan::Join) -> Result<LogicalJoin> {
         use logical_plan::JoinType as DFJoinType;
         let left = self.into_optd_plan_node(node.left.as_ref())?;
+        let left_ids = std::mem::take(&mut self.column_ids);
         let right = self.into_optd_plan_node(node.right.as_ref())?;
+        let right_ids = std::mem::take(&mut self.column_ids);
         let join_type = match node.join_type {
             DFJoinType::Inner => JoinType::Inner,
             DFJoinType::Left => JoinType::LeftOuter,
@@ -202,13 +433,20 @@ impl OptdPlanContext<'_> {
         let mut log_ops = vec![];
         log_ops.reserve(node.on.len());
         for (left, right) in &node.on {
+            self.column_ids = left_ids.clone();
             let left = self.into_optd_expr(left, node.left.schema())?;
+            self.column_ids = right_ids.clone();
             let right = self.into_optd_expr(right, node.right.schema())?;
             let right = self.add_column_offset(node.left.schema().fields().len(), right);
             let op = BinOpType::Eq;
             let expr = BinOpExpr::new(left, right, op).into_expr();
             log_ops.push(expr);
         }
+        self.column_ids = match join_type {
+            JoinType::LeftSemi | JoinType::LeftAnti => left_ids,
+            JoinType::RightSemi | JoinType::RightAnti => right_ids,
+            _ => [left_ids, right_ids].concat(),
+        };
 
         if log_ops.is_empty() {
             Ok(LogicalJoin::new(
@@ -239,6 +477,14 @@ impl OptdPlanContext<'_> {
             LogicalPlan::SubqueryAlias(node) => self.into_optd_plan_node(node.input.as_ref())?,
             LogicalPlan::Join(node) => self.into_optd_join(node)?.into_plan_node(),
             LogicalPlan::Filter(node) => self.into_optd_filter(node)?.into_plan_node(),
+            LogicalPlan::EmptyRelation(node) => self.into_optd_empty_relation(node)?,
+            LogicalPlan::Values(node) => self.into_optd_values(node)?,
+            LogicalPlan::Distinct(node) => self.into_optd_distinct(node)?.into_plan_node(),
+            LogicalPlan::Union(node) => self.into_optd_union(node)?,
+            LogicalPlan::Prepare(node) => {
+                self.parameter_types = node.data_types.clone();
+                self.into_optd_plan_node(node.input.as_ref())?
+            }
             _ => bail!(
                 "unsupported plan node: {}",
                 format!("{:?}", node).split('\n').next().unwrap()
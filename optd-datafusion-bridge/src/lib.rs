@@ -1,39 +1,64 @@
 #![allow(clippy::new_without_default)]
 
+mod filters;
 mod from_optd;
 mod into_optd;
 mod physical_collector;
+mod runtime_filter;
+mod spool;
+mod types;
 
 use async_trait::async_trait;
 use datafusion::{
-    arrow::datatypes::DataType,
+    arrow::datatypes::{DataType, Schema as ArrowSchema},
     catalog::CatalogList,
+    common::Constraint,
+    datasource::TableProvider,
     error::Result,
     execution::context::{QueryPlanner, SessionState},
     logical_expr::{
-        Explain, LogicalPlan, PlanType, StringifiedPlan, TableSource, ToStringifiedPlan,
+        Explain, LogicalPlan, PlanType, StringifiedPlan, TableProviderFilterPushDown, TableSource,
+        ToStringifiedPlan,
     },
-    physical_plan::{displayable, explain::ExplainExec, ExecutionPlan},
+    physical_plan::{displayable, empty::EmptyExec, explain::ExplainExec, ExecutionPlan},
     physical_planner::{DefaultPhysicalPlanner, PhysicalPlanner},
+    scalar::ScalarValue,
 };
 use itertools::Itertools;
+use optd_core::cascades::GroupId;
 use optd_datafusion_repr::{
+    bind_parameters,
     plan_nodes::{
-        ConstantType, OptRelNode, OptRelNodeRef, OptRelNodeTyp, PhysicalHashJoin,
-        PhysicalNestedLoopJoin, PlanNode,
+        ColumnId, ConstantType, Expr, OptRelNode, OptRelNodeRef, OptRelNodeTyp, ParameterExpr,
+        PhysicalHashJoin, PhysicalNestedLoopJoin,
     },
-    properties::schema::Catalog,
-    DatafusionOptimizer,
+    properties::schema::{Catalog, Field, FilterPushDown, ForeignKey, Schema},
+    DatafusionOptimizer, PlanCacheStats,
 };
 use std::{
     collections::{BTreeSet, HashMap},
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
 };
+use types::{cast_scalar, scalar_into_value};
 
 struct OptdPlanContext<'a> {
     tables: HashMap<String, Arc<dyn TableSource>>,
     session_state: &'a SessionState,
     pub optimizer: Option<&'a DatafusionOptimizer>,
+    /// The next id to assign to a column, if columns are referred to by id.
+    next_column_id: Option<ColumnId>,
+    /// The ids of the output columns of the plan node converted last.
+    column_ids: Vec<ColumnId>,
+    /// The spools converted so far, which are shared by all references to their group.
+    spools: HashMap<GroupId, Arc<dyn ExecutionPlan>>,
+    /// The declared types of the parameters of the prepared statement being converted.
+    parameter_types: Vec<DataType>,
+    /// The values bound to the parameters of the plan, where `parameter_values[i]` is the value
+    /// of `$i+1`.
+    parameter_values: Vec<ScalarValue>,
 }
 
 impl<'a> OptdPlanContext<'a> {
@@ -42,45 +67,204 @@ impl<'a> OptdPlanContext<'a> {
             tables: HashMap::new(),
             session_state,
             optimizer: None,
+            next_column_id: None,
+            column_ids: vec![],
+            spools: HashMap::new(),
+            parameter_types: vec![],
+            parameter_values: vec![],
         }
     }
+
+    /// Makes `into_optd` assign an id to every column and refer to columns by id. The ids start
+    /// from zero for every plan, so that the same query is converted to the same plan.
+    pub fn enable_column_ids(&mut self) {
+        self.next_column_id = Some(0);
+    }
 }
 
+/// The catalog of the tables of a DataFusion session. Clones share the declared foreign keys and
+/// the version, so a clone kept outside the optimizer can register tables and declare keys.
+#[derive(Clone)]
 pub struct DatafusionCatalog {
     catalog: Arc<dyn CatalogList>,
+    /// The foreign keys of each table, which DataFusion has no constraint for.
+    foreign_keys: Arc<RwLock<HashMap<String, Vec<ForeignKey>>>>,
+    /// Bumped whenever a table is registered or a foreign key is declared through the catalog.
+    version: Arc<AtomicU64>,
 }
 
 impl DatafusionCatalog {
     pub fn new(catalog: Arc<dyn CatalogList>) -> Self {
-        Self { catalog }
+        Self {
+            catalog,
+            foreign_keys: Arc::default(),
+            version: Arc::default(),
+        }
+    }
+
+    /// Registers `table` as `name`, replacing the table registered as `name` before if any.
+    /// Tables registered directly with DataFusion do not change the version of the catalog, so
+    /// the plans cached for a replaced table must be invalidated by the caller.
+    pub fn register_table(
+        &self,
+        name: &str,
+        table: Arc<dyn TableProvider>,
+    ) -> anyhow::Result<Option<Arc<dyn TableProvider>>> {
+        let catalog = self.catalog.catalog("datafusion").unwrap();
+        let schema = catalog.schema("public").unwrap();
+        let replaced = schema.register_table(name.to_string(), table)?;
+        self.version.fetch_add(1, Ordering::SeqCst);
+        Ok(replaced)
+    }
+
+    /// Declares that the values of `columns` of `table` are either null or the values of the
+    /// unique key `referenced_columns` of a row in `referenced_table`. The optimizer relies on
+    /// foreign keys, e.g., to eliminate joins, but DataFusion does not enforce them.
+    pub fn add_foreign_key(
+        &self,
+        table: &str,
+        columns: &[&str],
+        referenced_table: &str,
+        referenced_columns: &[&str],
+    ) -> anyhow::Result<()> {
+        let column_indices = |table: &str, columns: &[&str]| {
+            let catalog = self.catalog.catalog("datafusion").unwrap();
+            let schema = catalog.schema("public").unwrap();
+            let Some(table) = futures_lite::future::block_on(schema.table(table)) else {
+                anyhow::bail!("table {} does not exist", table)
+            };
+            let schema = table.schema();
+            columns
+                .iter()
+                .map(|column| Ok(schema.index_of(column)?))
+                .collect::<anyhow::Result<Vec<_>>>()
+        };
+        let foreign_key = ForeignKey {
+            columns: column_indices(table, columns)?,
+            referenced_table: referenced_table.to_string(),
+            referenced_columns: column_indices(referenced_table, referenced_columns)?,
+        };
+        if foreign_key.columns.len() != foreign_key.referenced_columns.len() {
+            anyhow::bail!(
+                "foreign key of {} columns references {} columns",
+                columns.len(),
+                referenced_columns.len()
+            );
+        }
+        let referenced_key = foreign_key.referenced_columns.iter().copied().collect();
+        if !self.unique_keys(referenced_table).contains(&referenced_key) {
+            anyhow::bail!(
+                "columns {:?} of {} are not a unique key",
+                referenced_columns,
+                referenced_table
+            );
+        }
+        self.foreign_keys
+            .write()
+            .unwrap()
+            .entry(table.to_string())
+            .or_default()
+            .push(foreign_key);
+        self.version.fetch_add(1, Ordering::SeqCst);
+        Ok(())
     }
 }
 
 impl Catalog for DatafusionCatalog {
-    fn get(&self, name: &str) -> optd_datafusion_repr::properties::schema::Schema {
+    fn get(&self, name: &str) -> Schema {
         let catalog = self.catalog.catalog("datafusion").unwrap();
         let schema = catalog.schema("public").unwrap();
         let table = futures_lite::future::block_on(schema.table(name.as_ref())).unwrap();
         let fields = table.schema();
+        let column_stats = table
+            .statistics()
+            .and_then(|stats| stats.column_statistics)
+            .unwrap_or_default();
         let mut optd_schema = vec![];
-        for field in fields.fields() {
-            let dt = match field.data_type() {
-                DataType::Date32 => ConstantType::Date,
-                DataType::Int32 => ConstantType::Int,
-                DataType::Int64 => ConstantType::Int,
-                DataType::Float64 => ConstantType::Decimal,
-                DataType::Utf8 => ConstantType::Utf8String,
-                DataType::Decimal128(_, _) => ConstantType::Decimal,
-                dt => unimplemented!("{:?}", dt),
-            };
-            optd_schema.push(dt);
+        for (idx, field) in fields.fields().iter().enumerate() {
+            // The optimizer only reasons about the values of the types it knows, and columns of
+            // other types are typed as `Any` so that nothing is inferred from them.
+            let dt = ConstantType::from_data_type(field.data_type()).unwrap_or(ConstantType::Any);
+            let ndv = column_stats.get(idx).and_then(|stats| stats.distinct_count);
+            optd_schema.push(
+                Field::new(field.name(), dt, field.is_nullable())
+                    .with_qualifier(name)
+                    .with_ndv(ndv),
+            );
+        }
+        Schema::new(optd_schema)
+    }
+
+    fn filter_pushdown(&self, table: &str, filter: &Expr) -> FilterPushDown {
+        let catalog = self.catalog.catalog("datafusion").unwrap();
+        let schema = catalog.schema("public").unwrap();
+        let table = futures_lite::future::block_on(schema.table(table)).unwrap();
+        let Ok(filter) = filters::from_optd_filter(filter.clone(), &table.schema(), &[]) else {
+            return FilterPushDown::Unsupported;
+        };
+        match table.supports_filters_pushdown(&[&filter]).as_deref() {
+            Ok([TableProviderFilterPushDown::Exact]) => FilterPushDown::Exact,
+            Ok([TableProviderFilterPushDown::Inexact]) => FilterPushDown::Inexact,
+            _ => FilterPushDown::Unsupported,
         }
-        optd_datafusion_repr::properties::schema::Schema(optd_schema)
+    }
+
+    fn unique_keys(&self, table: &str) -> Vec<BTreeSet<usize>> {
+        let catalog = self.catalog.catalog("datafusion").unwrap();
+        let schema = catalog.schema("public").unwrap();
+        let table = futures_lite::future::block_on(schema.table(table)).unwrap();
+        let Some(constraints) = table.constraints() else {
+            return vec![];
+        };
+        constraints
+            .iter()
+            .map(|constraint| match constraint {
+                Constraint::PrimaryKey(columns) | Constraint::Unique(columns) => {
+                    columns.iter().copied().collect()
+                }
+            })
+            .collect()
+    }
+
+    /// Counts the tables registered and the foreign keys declared through the catalog. The
+    /// statements creating or dropping tables invalidate the plan cache when they are planned.
+    fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    fn foreign_keys(&self, table: &str) -> Vec<ForeignKey> {
+        self.foreign_keys
+            .read()
+            .unwrap()
+            .get(table)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// A prepared statement, which is optimized once for all values of its parameters.
+struct PreparedStatement {
+    /// The plan of the statement before optimization.
+    optd_rel: OptRelNodeRef,
+    optimized_rel: OptRelNodeRef,
+    tables: HashMap<String, Arc<dyn TableSource>>,
+}
+
+/// Gets the types of the parameters of a plan by their indices.
+fn collect_parameter_types(rel_node: &OptRelNodeRef, types: &mut HashMap<usize, ConstantType>) {
+    if let Some(parameter) = ParameterExpr::from_rel_node(rel_node.clone()) {
+        types.insert(parameter.index(), parameter.parameter_type());
+    }
+    for child in &rel_node.children {
+        collect_parameter_types(child, types);
     }
 }
 
 pub struct OptdQueryPlanner {
     pub optimizer: Arc<Mutex<Option<Box<DatafusionOptimizer>>>>,
+    column_ids: AtomicBool,
+    prepared: Mutex<HashMap<String, PreparedStatement>>,
+    replan_prepared: AtomicBool,
 }
 
 #[derive(Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
@@ -186,50 +370,268 @@ impl OptdQueryPlanner {
             .enable_adaptive(false);
     }
 
+    pub fn enable_join_enumeration(&self) {
+        self.optimizer
+            .lock()
+            .unwrap()
+            .as_mut()
+            .unwrap()
+            .enable_join_enumeration(true);
+    }
+
+    pub fn disable_join_enumeration(&self) {
+        self.optimizer
+            .lock()
+            .unwrap()
+            .as_mut()
+            .unwrap()
+            .enable_join_enumeration(false);
+    }
+
+    /// Caches up to `capacity` optimized plans. See [`DatafusionOptimizer::enable_plan_cache`].
+    pub fn enable_plan_cache(&self, capacity: usize) {
+        self.optimizer
+            .lock()
+            .unwrap()
+            .as_mut()
+            .unwrap()
+            .enable_plan_cache(capacity);
+    }
+
+    pub fn disable_plan_cache(&self) {
+        self.optimizer
+            .lock()
+            .unwrap()
+            .as_mut()
+            .unwrap()
+            .disable_plan_cache();
+    }
+
+    pub fn plan_cache_stats(&self) -> Option<PlanCacheStats> {
+        self.optimizer
+            .lock()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .plan_cache_stats()
+    }
+
+    /// Bounds the memo kept across queries in adaptive mode. See
+    /// [`DatafusionOptimizer::set_memo_capacity`].
+    pub fn set_memo_capacity(&self, retained_plans: Option<usize>, capacity: Option<usize>) {
+        self.optimizer
+            .lock()
+            .unwrap()
+            .as_mut()
+            .unwrap()
+            .set_memo_capacity(retained_plans, capacity);
+    }
+
+    /// Converts plans to optd with columns referred to by id instead of by position.
+    pub fn enable_column_ids(&self) {
+        self.column_ids.store(true, Ordering::Relaxed);
+    }
+
+    pub fn disable_column_ids(&self) {
+        self.column_ids.store(false, Ordering::Relaxed);
+    }
+
+    /// Optimizes prepared statements again when the values bound to their parameters let the
+    /// plans be simplified further, e.g., when a predicate becomes false. See
+    /// [`DatafusionOptimizer::is_parameter_sensitive`].
+    pub fn enable_prepared_replan(&self) {
+        self.replan_prepared.store(true, Ordering::Relaxed);
+    }
+
+    pub fn disable_prepared_replan(&self) {
+        self.replan_prepared.store(false, Ordering::Relaxed);
+    }
+
+    /// Optimizes a prepared statement once with its parameters unknown, so that every plan node
+    /// takes the same selectivity whatever the values. The statement is kept until it is
+    /// deallocated.
+    fn create_prepared_statement(
+        &self,
+        logical_plan: &LogicalPlan,
+        name: &str,
+        session_state: &SessionState,
+    ) -> anyhow::Result<Arc<dyn ExecutionPlan>> {
+        let mut ctx = OptdPlanContext::new(session_state);
+        if self.column_ids.load(Ordering::Relaxed) {
+            ctx.enable_column_ids();
+        }
+        let optd_rel = ctx.into_optd(logical_plan)?;
+        let (_, optimized_rel) = {
+            let mut optimizer = self.optimizer.lock().unwrap();
+            let optimizer = optimizer.as_mut().unwrap();
+            optimizer.set_parallelism(session_state.config().target_partitions());
+            optimizer.optimize(optd_rel.clone())?
+        };
+        self.prepared.lock().unwrap().insert(
+            name.to_string(),
+            PreparedStatement {
+                optd_rel,
+                optimized_rel,
+                tables: ctx.tables,
+            },
+        );
+        Ok(Arc::new(EmptyExec::new(
+            false,
+            Arc::new(ArrowSchema::empty()),
+        )))
+    }
+
+    /// Plans an execution of a prepared statement with `values` bound to its parameters, where
+    /// `values[i]` is the value of `$i+1`.
+    pub async fn execute_prepared(
+        &self,
+        name: &str,
+        values: Vec<ScalarValue>,
+        session_state: &SessionState,
+    ) -> anyhow::Result<Arc<dyn ExecutionPlan>> {
+        let (optd_rel, optimized_rel, tables) = {
+            let prepared = self.prepared.lock().unwrap();
+            let Some(statement) = prepared.get(name) else {
+                anyhow::bail!("prepared statement {} does not exist", name)
+            };
+            (
+                statement.optd_rel.clone(),
+                statement.optimized_rel.clone(),
+                statement.tables.clone(),
+            )
+        };
+        let mut optimizer = self.optimizer.lock().unwrap().take().unwrap();
+        optimizer.set_parallelism(session_state.config().target_partitions());
+        let optimized_rel = if optimizer.has_collector_groups(&optimized_rel) {
+            Ok(optimized_rel)
+        } else {
+            self.reoptimize_prepared(&mut optimizer, name, optd_rel.clone())
+        };
+        let optimized_rel = match optimized_rel {
+            Ok(optimized_rel) if self.replan_prepared.load(Ordering::Relaxed) => {
+                Self::replan_prepared(&mut optimizer, optd_rel, optimized_rel, &values)
+            }
+            optimized_rel => optimized_rel,
+        };
+        let physical_plan = match optimized_rel {
+            Ok(optimized_rel) => {
+                let mut ctx = OptdPlanContext::new(session_state);
+                ctx.tables = tables;
+                ctx.parameter_values = values;
+                ctx.optimizer = Some(&optimizer);
+                ctx.from_optd(optimized_rel).await
+            }
+            Err(err) => Err(err),
+        };
+        self.optimizer.lock().unwrap().replace(optimizer);
+        physical_plan
+    }
+
+    /// Optimizes a prepared statement again for all values, e.g., once the memo no longer holds
+    /// the groups of its plan, and keeps the new plan for later executions.
+    fn reoptimize_prepared(
+        &self,
+        optimizer: &mut DatafusionOptimizer,
+        name: &str,
+        optd_rel: OptRelNodeRef,
+    ) -> anyhow::Result<OptRelNodeRef> {
+        let (_, optimized_rel) = optimizer.optimize(optd_rel)?;
+        if let Some(statement) = self.prepared.lock().unwrap().get_mut(name) {
+            statement.optimized_rel = optimized_rel.clone();
+        }
+        Ok(optimized_rel)
+    }
+
+    /// Optimizes a prepared statement with `values` bound if they let the plan be simplified
+    /// further, or keeps the plan optimized for all values.
+    fn replan_prepared(
+        optimizer: &mut DatafusionOptimizer,
+        optd_rel: OptRelNodeRef,
+        optimized_rel: OptRelNodeRef,
+        values: &[ScalarValue],
+    ) -> anyhow::Result<OptRelNodeRef> {
+        let mut types = HashMap::new();
+        collect_parameter_types(&optd_rel, &mut types);
+        let values = values
+            .iter()
+            .enumerate()
+            .map(|(idx, value)| {
+                let value = match types.get(&(idx + 1)) {
+                    Some(typ) => cast_scalar(value, typ)?,
+                    None => value.clone(),
+                };
+                Ok(scalar_into_value(&value)?.1)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if !optimizer.is_parameter_sensitive(optd_rel.clone(), &values)? {
+            return Ok(optimized_rel);
+        }
+        let (_, optimized_rel) = optimizer.optimize(bind_parameters(&optd_rel, &values)?)?;
+        Ok(optimized_rel)
+    }
+
+    pub fn deallocate_prepared(&self, name: &str) {
+        self.prepared.lock().unwrap().remove(name);
+    }
+
     async fn create_physical_plan_inner(
         &self,
         logical_plan: &LogicalPlan,
         session_state: &SessionState,
     ) -> anyhow::Result<Arc<dyn ExecutionPlan>> {
-        if let LogicalPlan::Dml(_) | LogicalPlan::Ddl(_) | LogicalPlan::EmptyRelation(_) =
-            logical_plan
-        {
+        if let LogicalPlan::Dml(_) | LogicalPlan::Ddl(_) = logical_plan {
+            // The statistics of the tables may not reflect changes of their data, and the tables
+            // may be created or dropped, so the cached plans may be stale.
+            self.optimizer
+                .lock()
+                .unwrap()
+                .as_mut()
+                .unwrap()
+                .invalidate_plan_cache();
             let planner = DefaultPhysicalPlanner::default();
             return Ok(planner
                 .create_physical_plan(logical_plan, session_state)
                 .await?);
         }
+        if let LogicalPlan::Prepare(prepare) = logical_plan {
+            return self.create_prepared_statement(logical_plan, &prepare.name, session_state);
+        }
         let (mut explains, logical_plan) = match logical_plan {
             LogicalPlan::Explain(Explain { plan, .. }) => (Some(Vec::new()), plan.as_ref()),
             _ => (None, logical_plan),
         };
         let mut ctx = OptdPlanContext::new(session_state);
+        if self.column_ids.load(Ordering::Relaxed) {
+            ctx.enable_column_ids();
+        }
         if let Some(explains) = &mut explains {
             explains.push(logical_plan.to_stringified(PlanType::OptimizedLogicalPlan {
                 optimizer_name: "datafusion".to_string(),
             }));
         }
         let optd_rel = ctx.into_optd(logical_plan)?;
+        let mut optimizer = self.optimizer.lock().unwrap().take().unwrap();
         if let Some(explains) = &mut explains {
             explains.push(StringifiedPlan::new(
                 PlanType::OptimizedLogicalPlan {
                     optimizer_name: "optd".to_string(),
                 },
-                PlanNode::from_rel_node(optd_rel.clone())
-                    .unwrap()
-                    .explain_to_string(),
+                optimizer.explain_with_names(optd_rel.clone()),
             ));
         }
-        let mut optimizer = self.optimizer.lock().unwrap().take().unwrap();
-        let (group_id, optimized_rel) = optimizer.optimize(optd_rel)?;
+        optimizer.set_parallelism(session_state.config().target_partitions());
+        // Explaining a plan looks up its group in the memo, so the plan must not be cached.
+        let (group_id, optimized_rel) = if explains.is_some() {
+            optimizer.optimize_uncached(optd_rel)?
+        } else {
+            optimizer.optimize(optd_rel)?
+        };
         if let Some(explains) = &mut explains {
             explains.push(StringifiedPlan::new(
                 PlanType::OptimizedPhysicalPlan {
                     optimizer_name: "optd".to_string(),
                 },
-                PlanNode::from_rel_node(optimized_rel.clone())
-                    .unwrap()
-                    .explain_to_string(),
+                optimizer.explain_with_names(optimized_rel.clone()),
             ));
             let join_order = get_join_order(optimized_rel.clone());
             explains.push(StringifiedPlan::new(
@@ -295,6 +697,9 @@ impl OptdQueryPlanner {
     pub fn new(optimizer: DatafusionOptimizer) -> Self {
         Self {
             optimizer: Arc::new(Mutex::new(Some(Box::new(optimizer)))),
+            column_ids: AtomicBool::new(false),
+            prepared: Mutex::new(HashMap::new()),
+            replan_prepared: AtomicBool::new(false),
         }
     }
 }
@@ -312,3 +717,46 @@ impl QueryPlanner for OptdQueryPlanner {
             .unwrap())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::datatypes::{DataType, Field as ArrowField},
+        datasource::MemTable,
+        prelude::SessionContext,
+    };
+
+    use super::*;
+
+    fn table(columns: &[&str]) -> Arc<dyn TableProvider> {
+        let schema = Arc::new(ArrowSchema::new(
+            columns
+                .iter()
+                .map(|name| ArrowField::new(*name, DataType::Int32, true))
+                .collect::<Vec<_>>(),
+        ));
+        Arc::new(MemTable::try_new(schema, vec![vec![]]).unwrap())
+    }
+
+    #[test]
+    fn catalog_version_counts_registrations_and_foreign_keys() {
+        let ctx = SessionContext::new();
+        let catalog = DatafusionCatalog::new(ctx.state().catalog_list());
+        let empty = catalog.version();
+        assert_eq!(catalog.version(), empty);
+        catalog.register_table("t", table(&["a"])).unwrap();
+        let created = catalog.version();
+        assert_ne!(created, empty);
+        // Clones share the version.
+        catalog
+            .clone()
+            .register_table("t", table(&["a", "b"]))
+            .unwrap();
+        assert_ne!(catalog.version(), created);
+
+        // A foreign key referencing columns that are not a unique key is rejected.
+        let replaced = catalog.version();
+        assert!(catalog.add_foreign_key("t", &["a"], "t", &["b"]).is_err());
+        assert_eq!(catalog.version(), replaced);
+    }
+}
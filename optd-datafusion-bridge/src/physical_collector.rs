@@ -92,7 +92,9 @@ impl ExecutionPlan for CollectorExec {
         partition: usize,
         context: Arc<TaskContext>,
     ) -> Result<SendableRecordBatchStream> {
-        if 0 != partition {
+        // The row count is summed over the partitions of the input, so every partition must be
+        // one of them.
+        if partition >= self.input.output_partitioning().partition_count() {
             return internal_err!("CollectorExec invalid partition {partition}");
         }
 
@@ -135,11 +137,14 @@ impl Stream for CollectorReader {
             Poll::Ready(None) => {
                 self.done = true;
                 {
+                    // Every partition adds its rows to the count of the current iteration.
                     let mut guard = self.collect_into.lock().unwrap();
                     let iter_cnt = guard.iter_cnt;
-                    guard
-                        .history
-                        .insert(self.group_id, (self.row_cnt, iter_cnt));
+                    let entry = guard.history.entry(self.group_id).or_insert((0, iter_cnt));
+                    if entry.1 != iter_cnt {
+                        *entry = (0, iter_cnt);
+                    }
+                    entry.0 += self.row_cnt;
                 }
                 Poll::Ready(None)
             }
@@ -0,0 +1,531 @@
+//! Runtime filters of hash joins. While the build side of a join is consumed, its keys are
+//! collected into a bloom filter and a min-max range per key. Once every build partition is done,
+//! the probe side drops the rows whose keys cannot match. A probe batch that arrives before the
+//! filter is complete passes through unfiltered, so the filter never changes the join result.
+
+use std::{
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use ahash::RandomState;
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, BooleanArray},
+        compute::{
+            and, filter_record_batch,
+            kernels::cmp::{gt_eq, lt_eq},
+        },
+        datatypes::SchemaRef,
+        record_batch::RecordBatch,
+    },
+    common::hash_utils::create_hashes,
+    error::Result,
+    execution::TaskContext,
+    logical_expr::Accumulator,
+    physical_expr::expressions::{MaxAccumulator, MinAccumulator},
+    physical_plan::{
+        DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, PhysicalExpr, RecordBatchStream,
+        SendableRecordBatchStream, Statistics,
+    },
+    scalar::ScalarValue,
+};
+use futures_lite::Stream;
+use futures_util::stream::StreamExt;
+
+const BLOOM_FILTER_BITS: usize = 1 << 20;
+const BLOOM_FILTER_HASHES: u64 = 3;
+
+/// Both sides must hash keys the same way.
+fn random_state() -> RandomState {
+    RandomState::with_seeds(0, 0, 0, 0)
+}
+
+fn evaluate_keys(keys: &[Arc<dyn PhysicalExpr>], batch: &RecordBatch) -> Result<Vec<ArrayRef>> {
+    keys.iter()
+        .map(|key| Ok(key.evaluate(batch)?.into_array(batch.num_rows())))
+        .collect()
+}
+
+struct BloomFilter {
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    fn new() -> Self {
+        Self {
+            bits: vec![0; BLOOM_FILTER_BITS / 64],
+        }
+    }
+
+    fn bit_positions(hash: u64) -> impl Iterator<Item = usize> {
+        let (h1, h2) = (hash & 0xffff_ffff, hash >> 32);
+        (0..BLOOM_FILTER_HASHES)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) as usize) & (BLOOM_FILTER_BITS - 1))
+    }
+
+    fn insert(&mut self, hash: u64) {
+        for bit in Self::bit_positions(hash) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    fn contains(&self, hash: u64) -> bool {
+        Self::bit_positions(hash).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+}
+
+/// A complete runtime filter.
+struct RuntimeFilter {
+    bloom: BloomFilter,
+    /// The smallest and largest value of every key on the build side.
+    ranges: Vec<(ScalarValue, ScalarValue)>,
+}
+
+impl RuntimeFilter {
+    fn apply(&self, keys: &[Arc<dyn PhysicalExpr>], batch: &RecordBatch) -> Result<RecordBatch> {
+        let arrays = evaluate_keys(keys, batch)?;
+        let mut hashes = vec![0; batch.num_rows()];
+        create_hashes(&arrays, &random_state(), &mut hashes)?;
+        let mut mask: BooleanArray = hashes
+            .iter()
+            .map(|hash| Some(self.bloom.contains(*hash)))
+            .collect();
+        for (array, (min, max)) in arrays.iter().zip(&self.ranges) {
+            let above_min = gt_eq(array, &min.to_scalar())?;
+            let below_max = lt_eq(array, &max.to_scalar())?;
+            mask = and(&mask, &and(&above_min, &below_max)?)?;
+        }
+        Ok(filter_record_batch(batch, &mask)?)
+    }
+}
+
+struct RuntimeFilterBuilder {
+    bloom: BloomFilter,
+    ranges: Vec<(MinAccumulator, MaxAccumulator)>,
+    finished_partitions: usize,
+    failed: bool,
+    filter: Option<Arc<RuntimeFilter>>,
+}
+
+/// The runtime filter of one hash join, shared by its build and probe sides.
+pub struct RuntimeFilterState {
+    build_partitions: usize,
+    builder: Mutex<RuntimeFilterBuilder>,
+}
+
+impl std::fmt::Debug for RuntimeFilterState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RuntimeFilterState")
+    }
+}
+
+impl RuntimeFilterState {
+    /// Creates the state of a filter over `build_keys`, whose build side has `build_partitions`
+    /// partitions.
+    pub fn try_new(
+        build_keys: &[Arc<dyn PhysicalExpr>],
+        build_schema: &SchemaRef,
+        build_partitions: usize,
+    ) -> Result<Self> {
+        let ranges = build_keys
+            .iter()
+            .map(|key| {
+                let data_type = key.data_type(build_schema)?;
+                Ok((
+                    MinAccumulator::try_new(&data_type)?,
+                    MaxAccumulator::try_new(&data_type)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            build_partitions,
+            builder: Mutex::new(RuntimeFilterBuilder {
+                bloom: BloomFilter::new(),
+                ranges,
+                finished_partitions: 0,
+                failed: false,
+                filter: None,
+            }),
+        })
+    }
+
+    fn insert(&self, arrays: &[ArrayRef]) -> Result<()> {
+        let mut hashes = vec![0; arrays.first().map_or(0, |array| array.len())];
+        create_hashes(arrays, &random_state(), &mut hashes)?;
+        let mut builder = self.builder.lock().unwrap();
+        if builder.failed || builder.filter.is_some() {
+            return Ok(());
+        }
+        for hash in hashes {
+            builder.bloom.insert(hash);
+        }
+        for (array, (min, max)) in arrays.iter().zip(builder.ranges.iter_mut()) {
+            min.update_batch(&[array.clone()])?;
+            max.update_batch(&[array.clone()])?;
+        }
+        Ok(())
+    }
+
+    fn fail(&self) {
+        self.builder.lock().unwrap().failed = true;
+    }
+
+    fn finish_partition(&self) {
+        let mut builder = self.builder.lock().unwrap();
+        builder.finished_partitions += 1;
+        if builder.finished_partitions < self.build_partitions
+            || builder.failed
+            || builder.filter.is_some()
+        {
+            return;
+        }
+        let ranges = builder
+            .ranges
+            .iter()
+            .map(|(min, max)| Ok((min.evaluate()?, max.evaluate()?)))
+            .collect::<Result<Vec<_>>>();
+        match ranges {
+            Ok(ranges) => {
+                let bloom = std::mem::replace(&mut builder.bloom, BloomFilter { bits: vec![] });
+                builder.filter = Some(Arc::new(RuntimeFilter { bloom, ranges }));
+            }
+            Err(_) => builder.failed = true,
+        }
+    }
+
+    fn filter(&self) -> Option<Arc<RuntimeFilter>> {
+        self.builder.lock().unwrap().filter.clone()
+    }
+}
+
+/// Passes the build side of a hash join through while collecting its keys.
+#[derive(Debug)]
+pub struct RuntimeFilterBuildExec {
+    input: Arc<dyn ExecutionPlan>,
+    keys: Vec<Arc<dyn PhysicalExpr>>,
+    state: Arc<RuntimeFilterState>,
+}
+
+impl RuntimeFilterBuildExec {
+    pub fn new(
+        input: Arc<dyn ExecutionPlan>,
+        keys: Vec<Arc<dyn PhysicalExpr>>,
+        state: Arc<RuntimeFilterState>,
+    ) -> Self {
+        Self { input, keys, state }
+    }
+}
+
+impl DisplayAs for RuntimeFilterBuildExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "RuntimeFilterBuildExec keys={:?}", self.keys)
+    }
+}
+
+impl ExecutionPlan for RuntimeFilterBuildExec {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        self.input.output_partitioning()
+    }
+
+    fn output_ordering(&self) -> Option<&[datafusion::physical_expr::PhysicalSortExpr]> {
+        self.input.output_ordering()
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        assert_eq!(children.len(), 1);
+        Ok(Arc::new(Self::new(
+            children[0].clone(),
+            self.keys.clone(),
+            self.state.clone(),
+        )))
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.input.statistics()
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        Ok(Box::pin(RuntimeFilterBuildReader {
+            input: self.input.execute(partition, context)?,
+            keys: self.keys.clone(),
+            state: self.state.clone(),
+            done: false,
+        }))
+    }
+}
+
+struct RuntimeFilterBuildReader {
+    input: SendableRecordBatchStream,
+    keys: Vec<Arc<dyn PhysicalExpr>>,
+    state: Arc<RuntimeFilterState>,
+    done: bool,
+}
+
+impl Stream for RuntimeFilterBuildReader {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        match self.input.poll_next_unpin(cx) {
+            Poll::Ready(Some(Ok(batch))) => {
+                if evaluate_keys(&self.keys, &batch)
+                    .and_then(|arrays| self.state.insert(&arrays))
+                    .is_err()
+                {
+                    self.state.fail();
+                }
+                Poll::Ready(Some(Ok(batch)))
+            }
+            Poll::Ready(None) => {
+                self.done = true;
+                self.state.finish_partition();
+                Poll::Ready(None)
+            }
+            Poll::Ready(Some(Err(err))) => {
+                self.state.fail();
+                Poll::Ready(Some(Err(err)))
+            }
+            other => other,
+        }
+    }
+}
+
+impl RecordBatchStream for RuntimeFilterBuildReader {
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+}
+
+/// Filters the probe side of a hash join with the keys collected from its build side.
+#[derive(Debug)]
+pub struct RuntimeFilterExec {
+    input: Arc<dyn ExecutionPlan>,
+    keys: Vec<Arc<dyn PhysicalExpr>>,
+    state: Arc<RuntimeFilterState>,
+}
+
+impl RuntimeFilterExec {
+    pub fn new(
+        input: Arc<dyn ExecutionPlan>,
+        keys: Vec<Arc<dyn PhysicalExpr>>,
+        state: Arc<RuntimeFilterState>,
+    ) -> Self {
+        Self { input, keys, state }
+    }
+}
+
+impl DisplayAs for RuntimeFilterExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "RuntimeFilterExec keys={:?}", self.keys)
+    }
+}
+
+impl ExecutionPlan for RuntimeFilterExec {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        self.input.output_partitioning()
+    }
+
+    fn output_ordering(&self) -> Option<&[datafusion::physical_expr::PhysicalSortExpr]> {
+        self.input.output_ordering()
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        assert_eq!(children.len(), 1);
+        Ok(Arc::new(Self::new(
+            children[0].clone(),
+            self.keys.clone(),
+            self.state.clone(),
+        )))
+    }
+
+    fn statistics(&self) -> Statistics {
+        Statistics::default()
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        Ok(Box::pin(RuntimeFilterReader {
+            input: self.input.execute(partition, context)?,
+            keys: self.keys.clone(),
+            state: self.state.clone(),
+            filter: None,
+        }))
+    }
+}
+
+struct RuntimeFilterReader {
+    input: SendableRecordBatchStream,
+    keys: Vec<Arc<dyn PhysicalExpr>>,
+    state: Arc<RuntimeFilterState>,
+    filter: Option<Arc<RuntimeFilter>>,
+}
+
+impl Stream for RuntimeFilterReader {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        if self.filter.is_none() {
+            self.filter = self.state.filter();
+        }
+        match self.input.poll_next_unpin(cx) {
+            Poll::Ready(Some(Ok(batch))) => {
+                // The filter only drops rows that cannot match, so the batch is kept as is if the
+                // filter cannot be evaluated.
+                let batch = match &self.filter {
+                    Some(filter) => filter.apply(&self.keys, &batch).unwrap_or(batch),
+                    None => batch,
+                };
+                Poll::Ready(Some(Ok(batch)))
+            }
+            other => other,
+        }
+    }
+}
+
+impl RecordBatchStream for RuntimeFilterReader {
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::{
+            array::{AsArray, Int32Array},
+            datatypes::{DataType, Field, Int32Type, Schema},
+        },
+        physical_plan::{common::collect, expressions::Column, memory::MemoryExec},
+        prelude::SessionContext,
+    };
+    use futures_lite::future::block_on;
+
+    use super::*;
+
+    fn memory_exec(partitions: Vec<Vec<i32>>) -> Arc<dyn ExecutionPlan> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let partitions = partitions
+            .into_iter()
+            .map(|values| {
+                vec![
+                    RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(values))])
+                        .unwrap(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        Arc::new(MemoryExec::try_new(&partitions, schema, None).unwrap())
+    }
+
+    fn keys() -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![Arc::new(Column::new("a", 0))]
+    }
+
+    fn execute(exec: &dyn ExecutionPlan, partition: usize) -> Vec<i32> {
+        let task_ctx = SessionContext::new().task_ctx();
+        let batches = block_on(collect(exec.execute(partition, task_ctx).unwrap())).unwrap();
+        batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    fn build(partitions: Vec<Vec<i32>>) -> (RuntimeFilterBuildExec, Arc<RuntimeFilterState>) {
+        let input = memory_exec(partitions);
+        let state = Arc::new(
+            RuntimeFilterState::try_new(
+                &keys(),
+                &input.schema(),
+                input.output_partitioning().partition_count(),
+            )
+            .unwrap(),
+        );
+        (
+            RuntimeFilterBuildExec::new(input, keys(), state.clone()),
+            state,
+        )
+    }
+
+    #[test]
+    fn build_side_passes_through() {
+        let (build_exec, _) = build(vec![vec![3, 1, 2]]);
+        assert_eq!(execute(&build_exec, 0), vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn probe_side_keeps_matching_keys() {
+        let (build_exec, state) = build(vec![vec![10, 20], vec![30]]);
+        execute(&build_exec, 0);
+        execute(&build_exec, 1);
+        let probe_exec =
+            RuntimeFilterExec::new(memory_exec(vec![(0..100).collect()]), keys(), state);
+        // The range of the build keys rules out the false positives of the bloom filter outside
+        // of it.
+        let probe = execute(&probe_exec, 0);
+        assert!(probe.starts_with(&[10]) && probe.ends_with(&[30]));
+        for key in [10, 20, 30] {
+            assert!(probe.contains(&key));
+        }
+        assert!(probe.len() < 21);
+    }
+
+    #[test]
+    fn probe_side_is_unfiltered_until_build_side_is_done() {
+        let (build_exec, state) = build(vec![vec![10], vec![30]]);
+        execute(&build_exec, 0);
+        let probe_exec = RuntimeFilterExec::new(memory_exec(vec![vec![0, 10, 20]]), keys(), state);
+        assert_eq!(execute(&probe_exec, 0), vec![0, 10, 20]);
+    }
+}
@@ -0,0 +1,274 @@
+//! Spools share the output of a subplan referenced by several parents. The first execution of a
+//! partition collects the batches of the input partition, and every execution of the partition
+//! replays them, so the input partition runs once however many parents read it.
+
+use std::sync::{Arc, Mutex};
+
+use datafusion::{
+    arrow::record_batch::RecordBatch,
+    error::{DataFusionError, Result},
+    execution::TaskContext,
+    physical_plan::{
+        stream::RecordBatchStreamAdapter, DisplayAs, DisplayFormatType, ExecutionPlan,
+        Partitioning, SendableRecordBatchStream, Statistics,
+    },
+};
+use futures_util::{
+    future::{BoxFuture, FutureExt, Shared},
+    stream::{self, StreamExt, TryStreamExt},
+};
+use optd_core::cascades::GroupId;
+
+type SharedBatches =
+    Shared<BoxFuture<'static, Result<Arc<Vec<RecordBatch>>, Arc<DataFusionError>>>>;
+
+pub struct SpoolExec {
+    group_id: GroupId,
+    input: Arc<dyn ExecutionPlan>,
+    /// The collected output of each input partition, once it is executed.
+    partitions: Mutex<Vec<Option<SharedBatches>>>,
+}
+
+impl std::fmt::Debug for SpoolExec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SpoolExec")
+    }
+}
+
+impl DisplayAs for SpoolExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "SpoolExec group_id={}", self.group_id)
+    }
+}
+
+impl SpoolExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, group_id: GroupId) -> Self {
+        let partition_cnt = input.output_partitioning().partition_count();
+        Self {
+            group_id,
+            input,
+            partitions: Mutex::new(vec![None; partition_cnt]),
+        }
+    }
+}
+
+impl ExecutionPlan for SpoolExec {
+    fn schema(&self) -> datafusion::arrow::datatypes::SchemaRef {
+        self.input.schema()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        self.input.output_partitioning()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        assert_eq!(children.len(), 1);
+        Ok(Arc::new(Self::new(children[0].clone(), self.group_id)))
+    }
+
+    fn output_ordering(&self) -> Option<&[datafusion::physical_expr::PhysicalSortExpr]> {
+        self.input.output_ordering()
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.input.statistics()
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let batches = {
+            let mut partitions = self.partitions.lock().unwrap();
+            match &partitions[partition] {
+                Some(batches) => batches.clone(),
+                None => {
+                    let input = self.input.execute(partition, context)?;
+                    let batches = async move {
+                        input
+                            .try_collect::<Vec<_>>()
+                            .await
+                            .map(Arc::new)
+                            .map_err(Arc::new)
+                    }
+                    .boxed()
+                    .shared();
+                    partitions[partition] = Some(batches.clone());
+                    batches
+                }
+            }
+        };
+        let output = stream::once(batches).flat_map(|batches| match batches {
+            Ok(batches) => stream::iter((*batches).clone().into_iter().map(Ok)).boxed(),
+            Err(err) => {
+                stream::once(async move { Err(DataFusionError::External(Box::new(err))) }).boxed()
+            }
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            output,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use datafusion::{
+        arrow::{
+            array::{AsArray, Int32Array},
+            datatypes::{DataType, Field, Int32Type, Schema},
+        },
+        physical_plan::{common::collect, memory::MemoryExec},
+        prelude::SessionContext,
+    };
+    use futures_lite::future::block_on;
+
+    use super::*;
+
+    /// Counts the executions of the partitions of its input.
+    #[derive(Debug)]
+    struct CountingExec {
+        input: Arc<dyn ExecutionPlan>,
+        executions: Vec<AtomicUsize>,
+    }
+
+    impl CountingExec {
+        fn new(partitions: Vec<Vec<i32>>) -> Self {
+            let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+            let executions = partitions.iter().map(|_| AtomicUsize::new(0)).collect();
+            let partitions = partitions
+                .into_iter()
+                .map(|values| {
+                    vec![RecordBatch::try_new(
+                        schema.clone(),
+                        vec![Arc::new(Int32Array::from(values))],
+                    )
+                    .unwrap()]
+                })
+                .collect::<Vec<_>>();
+            Self {
+                input: Arc::new(MemoryExec::try_new(&partitions, schema, None).unwrap()),
+                executions,
+            }
+        }
+
+        fn executions(&self) -> Vec<usize> {
+            self.executions
+                .iter()
+                .map(|cnt| cnt.load(Ordering::SeqCst))
+                .collect()
+        }
+    }
+
+    impl DisplayAs for CountingExec {
+        fn fmt_as(&self, _t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "CountingExec")
+        }
+    }
+
+    impl ExecutionPlan for CountingExec {
+        fn schema(&self) -> datafusion::arrow::datatypes::SchemaRef {
+            self.input.schema()
+        }
+
+        fn output_partitioning(&self) -> Partitioning {
+            self.input.output_partitioning()
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+            vec![]
+        }
+
+        fn with_new_children(
+            self: Arc<Self>,
+            _children: Vec<Arc<dyn ExecutionPlan>>,
+        ) -> Result<Arc<dyn ExecutionPlan>> {
+            Ok(self)
+        }
+
+        fn output_ordering(&self) -> Option<&[datafusion::physical_expr::PhysicalSortExpr]> {
+            None
+        }
+
+        fn statistics(&self) -> Statistics {
+            self.input.statistics()
+        }
+
+        fn execute(
+            &self,
+            partition: usize,
+            context: Arc<TaskContext>,
+        ) -> Result<SendableRecordBatchStream> {
+            self.executions[partition].fetch_add(1, Ordering::SeqCst);
+            self.input.execute(partition, context)
+        }
+    }
+
+    fn execute(exec: &dyn ExecutionPlan, partition: usize) -> Vec<i32> {
+        let task_ctx = SessionContext::new().task_ctx();
+        let batches = block_on(collect(exec.execute(partition, task_ctx).unwrap())).unwrap();
+        batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_reader_gets_the_output_of_one_execution() {
+        let input = Arc::new(CountingExec::new(vec![vec![1, 2, 3]]));
+        let spool = SpoolExec::new(input.clone(), GroupId::default());
+        assert_eq!(execute(&spool, 0), vec![1, 2, 3]);
+        assert_eq!(execute(&spool, 0), vec![1, 2, 3]);
+        assert_eq!(input.executions(), vec![1]);
+    }
+
+    #[test]
+    fn partitions_are_spooled_on_first_execution() {
+        let input = Arc::new(CountingExec::new(vec![vec![1, 2], vec![3]]));
+        let spool = SpoolExec::new(input.clone(), GroupId::default());
+        assert_eq!(input.executions(), vec![0, 0]);
+        assert_eq!(execute(&spool, 1), vec![3]);
+        assert_eq!(input.executions(), vec![0, 1]);
+        assert_eq!(execute(&spool, 0), vec![1, 2]);
+        assert_eq!(execute(&spool, 1), vec![3]);
+        assert_eq!(input.executions(), vec![1, 1]);
+    }
+
+    #[test]
+    fn readers_may_run_concurrently() {
+        let input = Arc::new(CountingExec::new(vec![vec![1, 2, 3]]));
+        let spool = SpoolExec::new(input.clone(), GroupId::default());
+        let task_ctx = SessionContext::new().task_ctx();
+        // Both streams are created before either is polled.
+        let first = spool.execute(0, task_ctx.clone()).unwrap();
+        let second = spool.execute(0, task_ctx).unwrap();
+        let first = block_on(collect(first)).unwrap();
+        let second = block_on(collect(second)).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(input.executions(), vec![1]);
+    }
+}
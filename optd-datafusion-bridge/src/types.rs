@@ -0,0 +1,146 @@
+//! Conversions between DataFusion scalars and optd constant values.
+
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use datafusion::{arrow::compute::cast, scalar::ScalarValue};
+use optd_core::rel_node::Value;
+use optd_datafusion_repr::plan_nodes::{ConstantType, IntervalUnit, TimeUnit};
+
+/// Converts a DataFusion scalar into an optd constant type and value. The conversion is lossless,
+/// and `value_into_scalar` restores the original scalar.
+pub fn scalar_into_value(scalar: &ScalarValue) -> Result<(ConstantType, Value)> {
+    let typ = ConstantType::from_data_type(&scalar.get_datatype())?;
+    if scalar.is_null() {
+        return Ok((typ, Value::Null));
+    }
+    let value = match scalar {
+        ScalarValue::Boolean(Some(x)) => Value::Bool(*x),
+        ScalarValue::Int8(Some(x)) => Value::Int(*x as i64),
+        ScalarValue::Int16(Some(x)) => Value::Int(*x as i64),
+        ScalarValue::Int32(Some(x)) => Value::Int(*x as i64),
+        ScalarValue::Int64(Some(x)) => Value::Int(*x),
+        ScalarValue::UInt8(Some(x)) => Value::UInt(*x as u64),
+        ScalarValue::UInt16(Some(x)) => Value::UInt(*x as u64),
+        ScalarValue::UInt32(Some(x)) => Value::UInt(*x as u64),
+        ScalarValue::UInt64(Some(x)) => Value::UInt(*x),
+        ScalarValue::Float32(Some(x)) => Value::Float((*x as f64).into()),
+        ScalarValue::Float64(Some(x)) => Value::Float((*x).into()),
+        ScalarValue::Decimal128(Some(x), _, _) => Value::Int128(*x),
+        ScalarValue::Date32(Some(x)) => Value::Int(*x as i64),
+        ScalarValue::Date64(Some(x)) => Value::Int(*x),
+        ScalarValue::TimestampSecond(Some(x), _)
+        | ScalarValue::TimestampMillisecond(Some(x), _)
+        | ScalarValue::TimestampMicrosecond(Some(x), _)
+        | ScalarValue::TimestampNanosecond(Some(x), _) => Value::Int(*x),
+        ScalarValue::IntervalYearMonth(Some(x)) => Value::Int(*x as i64),
+        ScalarValue::IntervalDayTime(Some(x)) => Value::Int(*x),
+        ScalarValue::IntervalMonthDayNano(Some(x)) => Value::Int128(*x),
+        ScalarValue::Utf8(Some(x)) => Value::String(x.as_str().into()),
+        ScalarValue::Binary(Some(x)) => Value::Serialized(x.as_slice().into()),
+        x => bail!("unsupported scalar: {:?}", x),
+    };
+    Ok((typ, value))
+}
+
+/// Converts an optd constant back into a DataFusion scalar.
+pub fn value_into_scalar(typ: &ConstantType, value: &Value) -> Result<ScalarValue> {
+    if value.is_null() {
+        return Ok(ScalarValue::try_from(&typ.into_data_type()?)?);
+    }
+    let scalar = match typ {
+        ConstantType::Any | ConstantType::Null => bail!("{:?} constant with value {}", typ, value),
+        ConstantType::Bool => ScalarValue::Boolean(Some(value.as_bool())),
+        ConstantType::Int8 => ScalarValue::Int8(Some(value.as_i64() as i8)),
+        ConstantType::Int16 => ScalarValue::Int16(Some(value.as_i64() as i16)),
+        ConstantType::Int32 => ScalarValue::Int32(Some(value.as_i64() as i32)),
+        ConstantType::Int64 => ScalarValue::Int64(Some(value.as_i64())),
+        ConstantType::UInt8 => ScalarValue::UInt8(Some(value.as_u64() as u8)),
+        ConstantType::UInt16 => ScalarValue::UInt16(Some(value.as_u64() as u16)),
+        ConstantType::UInt32 => ScalarValue::UInt32(Some(value.as_u64() as u32)),
+        ConstantType::UInt64 => ScalarValue::UInt64(Some(value.as_u64())),
+        ConstantType::Float32 => ScalarValue::Float32(Some(value.as_f64() as f32)),
+        ConstantType::Float64 => ScalarValue::Float64(Some(value.as_f64())),
+        ConstantType::Decimal128(p, s) => ScalarValue::Decimal128(Some(value.as_i128()), *p, *s),
+        ConstantType::Date32 => ScalarValue::Date32(Some(value.as_i64() as i32)),
+        ConstantType::Date64 => ScalarValue::Date64(Some(value.as_i64())),
+        ConstantType::Timestamp(unit, tz) => {
+            let x = Some(value.as_i64());
+            let tz: Option<Arc<str>> = tz.clone();
+            match unit {
+                TimeUnit::Second => ScalarValue::TimestampSecond(x, tz),
+                TimeUnit::Millisecond => ScalarValue::TimestampMillisecond(x, tz),
+                TimeUnit::Microsecond => ScalarValue::TimestampMicrosecond(x, tz),
+                TimeUnit::Nanosecond => ScalarValue::TimestampNanosecond(x, tz),
+            }
+        }
+        ConstantType::Interval(IntervalUnit::YearMonth) => {
+            ScalarValue::IntervalYearMonth(Some(value.as_i64() as i32))
+        }
+        ConstantType::Interval(IntervalUnit::DayTime) => {
+            ScalarValue::IntervalDayTime(Some(value.as_i64()))
+        }
+        ConstantType::Interval(IntervalUnit::MonthDayNano) => {
+            ScalarValue::IntervalMonthDayNano(Some(value.as_i128()))
+        }
+        ConstantType::Utf8String => ScalarValue::Utf8(Some(value.as_str().to_string())),
+        ConstantType::Binary => ScalarValue::Binary(Some(value.as_bytes().to_vec())),
+    };
+    Ok(scalar)
+}
+
+/// Casts a value bound to a parameter into the type of the parameter.
+pub fn cast_scalar(scalar: &ScalarValue, typ: &ConstantType) -> Result<ScalarValue> {
+    let data_type = typ.into_data_type()?;
+    if scalar.get_datatype() == data_type {
+        return Ok(scalar.clone());
+    }
+    let array = cast(&scalar.to_array(), &data_type)?;
+    Ok(ScalarValue::try_from_array(&array, 0)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_round_trip(scalar: ScalarValue) {
+        let (typ, value) = scalar_into_value(&scalar).unwrap();
+        assert_eq!(value_into_scalar(&typ, &value).unwrap(), scalar);
+    }
+
+    #[test]
+    fn round_trip_nulls() {
+        assert_round_trip(ScalarValue::Null);
+        assert_round_trip(ScalarValue::Int32(None));
+        assert_round_trip(ScalarValue::UInt64(None));
+        assert_round_trip(ScalarValue::Decimal128(None, 20, 2));
+        assert_round_trip(ScalarValue::Utf8(None));
+    }
+
+    #[test]
+    fn round_trip_unsigned() {
+        assert_round_trip(ScalarValue::UInt8(Some(u8::MAX)));
+        assert_round_trip(ScalarValue::UInt64(Some(u64::MAX)));
+        let (_, value) = scalar_into_value(&ScalarValue::UInt64(Some(u64::MAX))).unwrap();
+        assert_eq!(value, Value::UInt(u64::MAX));
+    }
+
+    #[test]
+    fn round_trip_int128() {
+        assert_round_trip(ScalarValue::IntervalMonthDayNano(Some(i128::MIN)));
+        assert_round_trip(ScalarValue::IntervalMonthDayNano(Some(i128::MAX)));
+    }
+
+    #[test]
+    fn round_trip_decimal128() {
+        assert_round_trip(ScalarValue::Decimal128(
+            Some(-123_456_789_012_345_678_901),
+            38,
+            10,
+        ));
+        assert_round_trip(ScalarValue::Decimal128(Some(12345), 5, -2));
+        let (typ, value) = scalar_into_value(&ScalarValue::Decimal128(Some(12345), 10, 2)).unwrap();
+        assert_eq!(typ, ConstantType::Decimal128(10, 2));
+        assert_eq!(value, Value::Int128(12345));
+    }
+}
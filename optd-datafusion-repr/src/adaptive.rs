@@ -7,6 +7,7 @@ use pretty_xmlish::Pretty;
 use optd_core::rel_node::RelNode;
 
 use crate::plan_nodes::{OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode};
+use crate::properties::schema::SchemaPropertyBuilder;
 
 #[derive(Clone, Debug)]
 pub struct PhysicalCollector(pub PlanNode);
@@ -31,6 +32,10 @@ impl OptRelNode for PhysicalCollector {
         // )
         self.child().explain()
     }
+
+    fn dispatch_explain_with_names(&self, builder: &SchemaPropertyBuilder) -> Pretty<'static> {
+        self.child().explain_with_names(builder)
+    }
 }
 
 impl PhysicalCollector {
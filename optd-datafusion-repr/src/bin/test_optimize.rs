@@ -7,14 +7,18 @@ use optd_core::{
 use optd_datafusion_repr::{
     cost::OptCostModel,
     plan_nodes::{
-        BinOpExpr, BinOpType, ColumnRefExpr, ConstantExpr, JoinType, LogicalFilter, LogicalJoin,
-        LogicalScan, OptRelNode, OptRelNodeTyp, PlanNode,
+        BinOpExpr, BinOpType, ColumnRefExpr, ConstantExpr, ExprList, JoinType, LogicalFilter,
+        LogicalJoin, LogicalScan, OptRelNode, OptRelNodeTyp, PlanNode,
     },
     rules::{HashJoinRule, JoinAssocRule, JoinCommuteRule, PhysicalConversionRule},
 };
 
 use tracing::Level;
 
+fn columns(cnt: usize) -> ExprList {
+    ExprList::new((0..cnt).map(|x| ColumnRefExpr::new(x).0).collect())
+}
+
 pub fn main() {
     tracing_subscriber::fmt()
         .with_max_level(Level::DEBUG)
@@ -43,16 +47,16 @@ pub fn main() {
     );
 
     // The plan: (filter (scan t1) #1=2) join (scan t2) join (scan t3)
-    let scan1 = LogicalScan::new("t1".into());
+    let scan1 = LogicalScan::new("t1".into(), columns(2));
     let filter_cond = BinOpExpr::new(
         ColumnRefExpr::new(1).0,
         ConstantExpr::new(Value::Int(2)).0,
         BinOpType::Eq,
     );
     let filter1 = LogicalFilter::new(scan1.0, filter_cond.0);
-    let scan2 = LogicalScan::new("t2".into());
+    let scan2 = LogicalScan::new("t2".into(), columns(2));
     let join_cond = ConstantExpr::new(Value::Bool(true));
-    let scan3 = LogicalScan::new("t3".into());
+    let scan3 = LogicalScan::new("t3".into(), columns(2));
     let join_filter = LogicalJoin::new(filter1.0, scan2.0, join_cond.clone().0, JoinType::Inner);
     let fnal = LogicalJoin::new(scan3.0, join_filter.0, join_cond.0, JoinType::Inner);
     let node = optimizer.optimize(fnal.0.clone().into_rel_node());
@@ -0,0 +1,190 @@
+//! Resolution of column id references. A plan built with column ids refers to the columns of the
+//! inputs of a plan node by id instead of by position, so that it can be assembled without
+//! tracking where each column ends up. The ids are resolved to positions against the schema
+//! property before the plan is added to the memo. The columns keep their ids in the schema, as
+//! the lists producing them carry the ids.
+//!
+//! Column ids only spare the builders of plans from index arithmetic. The memo, the rules and the
+//! lowering to DataFusion still refer to columns by position, and the rules remap positions when
+//! they move plan nodes.
+
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use optd_core::{property::PropertyBuilder, rel_node::RelNode};
+
+use crate::{
+    plan_nodes::{ColumnIdExpr, ColumnRefExpr, OptRelNode, OptRelNodeRef, OptRelNodeTyp},
+    properties::schema::{Schema, SchemaPropertyBuilder},
+};
+
+/// Replaces the column id references in `expr` with references to the positions of the columns
+/// in `input`.
+fn resolve_expr(expr: &OptRelNodeRef, input: &Schema) -> Result<OptRelNodeRef> {
+    if let Some(column) = ColumnIdExpr::from_rel_node(expr.clone()) {
+        let Some(idx) = input.index_of_id(column.id()) else {
+            bail!("column ${} not found in the input", column.id());
+        };
+        return Ok(ColumnRefExpr::new(idx).into_rel_node());
+    }
+    let children = expr
+        .children
+        .iter()
+        .map(|child| resolve_expr(child, input))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Arc::new(RelNode {
+        typ: expr.typ.clone(),
+        children,
+        data: expr.data.clone(),
+    }))
+}
+
+/// Resolves the column id references in the plan rooted at `node`. The expressions of a plan
+/// node are evaluated over its plan node children, whose columns are concatenated, e.g., the
+/// condition of a join refers to the columns of the left input followed by the right input.
+/// Returns the resolved plan and its schema.
+pub(crate) fn resolve_column_ids(
+    builder: &SchemaPropertyBuilder,
+    node: &OptRelNodeRef,
+) -> Result<(OptRelNodeRef, Schema)> {
+    let mut input = Schema::new(vec![]);
+    let mut children = Vec::with_capacity(node.children.len());
+    let mut schemas = Vec::with_capacity(node.children.len());
+    for child in &node.children {
+        let (child, schema) = if child.typ.is_plan_node() {
+            let (child, schema) = resolve_column_ids(builder, child)?;
+            input = input.concat(&schema);
+            (child, schema)
+        } else {
+            let child = resolve_expr(child, &input)?;
+            let schema = builder.derive_tree(&child);
+            (child, schema)
+        };
+        children.push(child);
+        schemas.push(schema);
+    }
+    let schema = builder.derive(
+        node.typ.clone(),
+        node.data.clone(),
+        &schemas.iter().collect::<Vec<_>>(),
+    );
+    let node = Arc::new(RelNode {
+        typ: node.typ.clone(),
+        children,
+        data: node.data.clone(),
+    });
+    Ok((node, schema))
+}
+
+/// Checks whether the plan rooted at `node` refers to any column by id.
+pub(crate) fn has_column_ids(node: &OptRelNodeRef) -> bool {
+    node.typ == OptRelNodeTyp::ColumnId || node.children.iter().any(has_column_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan_nodes::{
+        ColumnId, Expr, ExprList, JoinType, LogicalJoin, LogicalProjection, LogicalScan, PlanNode,
+    };
+    use crate::properties::schema::Catalog;
+    use crate::testing::{col, eq, scan, TestCatalog};
+    use crate::DatafusionOptimizer;
+
+    fn id(id: ColumnId) -> Expr {
+        ColumnIdExpr::new(id).into_expr()
+    }
+
+    /// Scans the columns `a` and `b` of `table`, which get the ids `first_id` and `first_id + 1`.
+    fn scan_with_ids(table: &str, first_id: ColumnId) -> PlanNode {
+        let projection =
+            ExprList::new(vec![col(0), col(1)]).with_column_ids(&[first_id, first_id + 1]);
+        LogicalScan::new(table.to_string(), projection).into_plan_node()
+    }
+
+    /// Projects `exprs` over the join of `t1` and `t2` on `cond`.
+    fn join_plan(t1: PlanNode, t2: PlanNode, cond: Expr, exprs: Vec<Expr>) -> OptRelNodeRef {
+        let join = LogicalJoin::new(t1, t2, cond, JoinType::Inner);
+        LogicalProjection::new(join.into_plan_node(), ExprList::new(exprs)).into_rel_node()
+    }
+
+    /// `select t2.a, t1.a from t1 join t2 on t1.b = t2.a`, with the columns referred to by id.
+    fn id_plan() -> OptRelNodeRef {
+        join_plan(
+            scan_with_ids("t1", 10),
+            scan_with_ids("t2", 20),
+            eq(id(11), id(20)),
+            vec![id(20), id(10)],
+        )
+    }
+
+    /// Drops the ids the lists assign to the columns they produce.
+    fn without_column_ids(node: &OptRelNodeRef) -> OptRelNodeRef {
+        Arc::new(RelNode {
+            typ: node.typ.clone(),
+            children: node.children.iter().map(without_column_ids).collect(),
+            data: if node.typ == OptRelNodeTyp::List {
+                None
+            } else {
+                node.data.clone()
+            },
+        })
+    }
+
+    #[test]
+    fn ids_are_resolved_to_positions() {
+        let catalog: Arc<dyn Catalog> = Arc::new(TestCatalog);
+        let builder = SchemaPropertyBuilder::new(catalog);
+        let (resolved, schema) = resolve_column_ids(&builder, &id_plan()).unwrap();
+        let expected = join_plan(
+            scan_with_ids("t1", 10),
+            scan_with_ids("t2", 20),
+            eq(col(1), col(2)),
+            vec![col(2), col(0)],
+        );
+        assert_eq!(resolved, expected);
+        assert!(!has_column_ids(&resolved));
+        assert_eq!(schema.len(), 2);
+    }
+
+    #[test]
+    fn unknown_ids_are_rejected() {
+        let catalog: Arc<dyn Catalog> = Arc::new(TestCatalog);
+        let builder = SchemaPropertyBuilder::new(catalog);
+        let plan = join_plan(
+            scan_with_ids("t1", 10),
+            scan_with_ids("t2", 20),
+            eq(id(11), id(30)),
+            vec![id(20)],
+        );
+        assert!(resolve_column_ids(&builder, &plan).is_err());
+    }
+
+    #[test]
+    fn ids_are_resolved_before_the_memo() {
+        let optimize = |plan: OptRelNodeRef| {
+            let mut optimizer = DatafusionOptimizer::new_physical(Box::new(TestCatalog));
+            optimizer.enable_adaptive(false);
+            let (_, optimized) = optimizer.optimize(plan).unwrap();
+            (optimizer, optimized)
+        };
+        let (optimizer, optimized) = optimize(id_plan());
+        // No expression in the memo refers to a column by id.
+        let memo = optimizer.optd_optimizer();
+        for (group_id, _) in memo.memo_memory_usage() {
+            let expr = memo
+                .get_property_by_group::<SchemaPropertyBuilder>(group_id, 0)
+                .expr;
+            assert!(!expr.is_some_and(|expr| has_column_ids(&expr)));
+        }
+        // The plan is optimized as if it was built with positions.
+        let positional = join_plan(
+            scan("t1", 2),
+            scan("t2", 2),
+            eq(col(1), col(2)),
+            vec![col(2), col(0)],
+        );
+        let (_, expected) = optimize(positional);
+        assert_eq!(without_column_ids(&optimized), expected);
+    }
+}
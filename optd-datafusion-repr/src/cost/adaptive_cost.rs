@@ -1,11 +1,14 @@
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex},
+    sync::{atomic::AtomicUsize, Arc, Mutex},
 };
 
-use crate::{cost::OptCostModel, plan_nodes::OptRelNodeTyp};
+use crate::{
+    cost::{OptCostModel, ROW_COUNT},
+    plan_nodes::OptRelNodeTyp,
+};
 use optd_core::{
-    cascades::{GroupId, RelNodeContext},
+    cascades::{CascadesOptimizer, GroupId, RelNodeContext},
     cost::{Cost, CostModel},
     rel_node::{RelNode, Value},
 };
@@ -43,32 +46,51 @@ impl CostModel<OptRelNodeTyp> for AdaptiveCostModel {
         data: &Option<Value>,
         children: &[Cost],
         context: Option<RelNodeContext>,
+        optimizer: Option<&CascadesOptimizer<OptRelNodeTyp>>,
     ) -> Cost {
         if let OptRelNodeTyp::PhysicalScan = node {
+            let child_schemas = OptCostModel::child_schemas(context, optimizer);
+            let row_width = OptCostModel::scan_row_width(&child_schemas, context, optimizer);
+            let filter_cnt = OptCostModel::list_len(&child_schemas, 1).unwrap_or(0) as f64;
+            let fetch = OptCostModel::fetch(&child_schemas, 2);
             let guard = self.runtime_row_cnt.lock().unwrap();
             if let Some((runtime_row_cnt, iter)) = guard.history.get(&context.unwrap().group_id) {
                 if *iter + self.decay >= guard.iter_cnt {
                     let runtime_row_cnt = (*runtime_row_cnt).max(1) as f64;
-                    return OptCostModel::cost(runtime_row_cnt, 0.0, runtime_row_cnt);
+                    // The observed row count already reflects the filters pushed into the scan.
+                    let (_, _, io_cost) = OptCostModel::cost_tuple(&OptCostModel::scan_cost(
+                        runtime_row_cnt,
+                        row_width,
+                        filter_cnt,
+                        fetch,
+                    ));
+                    return OptCostModel::cost(runtime_row_cnt, 0.0, io_cost);
                 } else {
-                    return OptCostModel::cost(1.0, 0.0, 1.0);
+                    return OptCostModel::scan_cost(1.0, row_width, filter_cnt, fetch);
                 }
             } else {
-                return OptCostModel::cost(1.0, 0.0, 1.0);
+                return OptCostModel::scan_cost(1.0, row_width, filter_cnt, fetch);
             }
         }
-        let (mut row_cnt, compute_cost, io_cost) =
-            OptCostModel::cost_tuple(&self.base_model.compute_cost(node, data, children, None));
+        let mut cost = self
+            .base_model
+            .compute_cost(node, data, children, context, optimizer);
         if let Some(context) = context {
             let guard = self.runtime_row_cnt.lock().unwrap();
             if let Some((runtime_row_cnt, iter)) = guard.history.get(&context.group_id) {
                 if *iter + self.decay >= guard.iter_cnt {
                     let runtime_row_cnt = (*runtime_row_cnt).max(1) as f64;
-                    row_cnt = runtime_row_cnt;
+                    // The other components, e.g., the partition count, are kept as estimated.
+                    cost.0[ROW_COUNT] = runtime_row_cnt;
+                    cost.0[0] = OptCostModel::weighted_cost(
+                        runtime_row_cnt,
+                        OptCostModel::compute_cost(&cost),
+                        OptCostModel::io_cost(&cost),
+                    );
                 }
             }
         }
-        OptCostModel::cost(row_cnt, compute_cost, io_cost)
+        cost
     }
 
     fn compute_plan_node_cost(&self, node: &RelNode<OptRelNodeTyp>) -> Cost {
@@ -88,4 +110,8 @@ impl AdaptiveCostModel {
     pub fn get_runtime_map(&self) -> RuntimeAdaptionStorage {
         self.runtime_row_cnt.clone()
     }
+
+    pub fn get_parallelism(&self) -> Arc<AtomicUsize> {
+        self.base_model.get_parallelism()
+    }
 }
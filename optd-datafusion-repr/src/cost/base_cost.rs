@@ -1,16 +1,19 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
-use crate::plan_nodes::OptRelNodeTyp;
+use crate::plan_nodes::{ColumnRefExpr, ExchangeType, OptRelNode, OptRelNodeTyp};
+use crate::properties::schema::{Schema, SchemaPropertyBuilder};
 use itertools::Itertools;
 use optd_core::{
-    cascades::RelNodeContext,
+    cascades::{CascadesOptimizer, RelNodeContext},
     cost::{Cost, CostModel},
-    rel_node::{RelNode, RelNodeTyp, Value},
+    rel_node::{RelNode, Value},
 };
 
-fn compute_plan_node_cost<T: RelNodeTyp, C: CostModel<T>>(
-    model: &C,
-    node: &RelNode<T>,
+fn compute_plan_node_cost(
+    model: &OptCostModel,
+    node: &RelNode<OptRelNodeTyp>,
     total_cost: &mut Cost,
 ) -> Cost {
     let children = node
@@ -18,18 +21,47 @@ fn compute_plan_node_cost<T: RelNodeTyp, C: CostModel<T>>(
         .iter()
         .map(|child| compute_plan_node_cost(model, child, total_cost))
         .collect_vec();
-    let cost = model.compute_cost(&node.typ, &node.data, &children, None);
+    let child_schemas = node
+        .children
+        .iter()
+        .cloned()
+        .map(Schema::from_expr)
+        .collect_vec();
+    let cost =
+        model.compute_cost_with_schemas(&node.typ, &node.data, &children, &child_schemas, None);
     model.accumulate(total_cost, &cost);
     cost
 }
 
 pub struct OptCostModel {
     table_stat: HashMap<String, usize>,
+    /// The number of partitions parallel operators run on, which is shared with the optimizer to
+    /// follow the settings of the execution engine.
+    parallelism: Arc<AtomicUsize>,
 }
 
+/// The number of partitions parallel operators are assumed to run on until it is set.
+const DEFAULT_PARALLELISM: usize = 16;
+
+/// The fraction of input rows assumed to be distinct groups when the NDVs of the group by columns
+/// are unknown.
+const DEFAULT_GROUP_SELECTIVITY: f64 = 0.1;
+
+/// The cost of inserting a row into a hash table, relative to probing it.
+const HASH_BUILD_COST: f64 = 2.0;
+
+/// The fraction of probe rows assumed to pass a runtime filter built from the other join side.
+const RUNTIME_FILTER_SELECTIVITY: f64 = 0.5;
+
+/// The width in bytes assumed for a column whose type is unknown.
+const DEFAULT_COLUMN_WIDTH: f64 = 8.0;
+
 pub const ROW_COUNT: usize = 1;
 pub const COMPUTE_COST: usize = 2;
 pub const IO_COST: usize = 3;
+/// The number of partitions the output rows are spread across. Operators divide their compute
+/// cost by the partition count of their input, as each partition is processed in parallel.
+pub const PARTITION_CNT: usize = 4;
 
 impl OptCostModel {
     pub fn row_cnt(Cost(cost): &Cost) -> f64 {
@@ -44,6 +76,10 @@ impl OptCostModel {
         cost[IO_COST]
     }
 
+    pub fn partition_cnt(Cost(cost): &Cost) -> f64 {
+        cost[PARTITION_CNT]
+    }
+
     pub fn cost_tuple(Cost(cost): &Cost) -> (f64, f64, f64) {
         (cost[ROW_COUNT], cost[COMPUTE_COST], cost[IO_COST])
     }
@@ -54,11 +90,21 @@ impl OptCostModel {
     }
 
     pub fn cost(row_cnt: f64, compute_cost: f64, io_cost: f64) -> Cost {
+        Self::partitioned_cost(row_cnt, compute_cost, io_cost, 1.0)
+    }
+
+    pub fn partitioned_cost(
+        row_cnt: f64,
+        compute_cost: f64,
+        io_cost: f64,
+        partition_cnt: f64,
+    ) -> Cost {
         Cost(vec![
             Self::weighted_cost(row_cnt, compute_cost, io_cost),
             row_cnt,
             compute_cost,
             io_cost,
+            partition_cnt,
         ])
     }
 }
@@ -75,7 +121,7 @@ impl CostModel<OptRelNodeTyp> for OptCostModel {
     }
 
     fn accumulate(&self, total_cost: &mut Cost, cost: &Cost) {
-        // do not accumulate row count
+        // do not accumulate row count and partition count
         total_cost.0[COMPUTE_COST] += Self::compute_cost(cost);
         total_cost.0[IO_COST] += Self::io_cost(cost);
         total_cost.0[0] = Self::weighted_cost(
@@ -94,8 +140,60 @@ impl CostModel<OptRelNodeTyp> for OptCostModel {
         node: &OptRelNodeTyp,
         data: &Option<Value>,
         children: &[Cost],
-        _context: Option<RelNodeContext>,
+        context: Option<RelNodeContext>,
+        optimizer: Option<&CascadesOptimizer<OptRelNodeTyp>>,
     ) -> Cost {
+        // Only scans, aggregations and values look into the schemas and expressions of their
+        // children.
+        let child_schemas = match node {
+            OptRelNodeTyp::PhysicalScan
+            | OptRelNodeTyp::PhysicalAgg
+            | OptRelNodeTyp::PhysicalStreamAgg
+            | OptRelNodeTyp::PhysicalTwoPhaseAgg
+            | OptRelNodeTyp::PhysicalValues => Self::child_schemas(context, optimizer),
+            _ => vec![],
+        };
+        let row_width = match node {
+            OptRelNodeTyp::PhysicalScan => {
+                Some(Self::scan_row_width(&child_schemas, context, optimizer))
+            }
+            _ => None,
+        };
+        self.compute_cost_with_schemas(node, data, children, &child_schemas, row_width)
+    }
+
+    fn compute_plan_node_cost(&self, node: &RelNode<OptRelNodeTyp>) -> Cost {
+        let mut cost = self.zero();
+        let top = compute_plan_node_cost(self, node, &mut cost);
+        cost.0[ROW_COUNT] = top.0[ROW_COUNT];
+        cost
+    }
+}
+
+impl OptCostModel {
+    pub fn new(table_stat: HashMap<String, usize>) -> Self {
+        Self {
+            table_stat,
+            parallelism: Arc::new(AtomicUsize::new(DEFAULT_PARALLELISM)),
+        }
+    }
+
+    pub fn get_parallelism(&self) -> Arc<AtomicUsize> {
+        self.parallelism.clone()
+    }
+
+    /// Computes the cost of a node whose children have the schema properties in `child_schemas`,
+    /// which are empty if they are unknown. The row width of a scan is computed from its
+    /// projection if it is not given.
+    fn compute_cost_with_schemas(
+        &self,
+        node: &OptRelNodeTyp,
+        data: &Option<Value>,
+        children: &[Cost],
+        child_schemas: &[Schema],
+        row_width: Option<f64>,
+    ) -> Cost {
+        let parallelism = self.parallelism.load(Ordering::Relaxed).max(1) as f64;
         match node {
             OptRelNodeTyp::PhysicalScan => {
                 let table_name = data.as_ref().unwrap().as_str();
@@ -104,16 +202,23 @@ impl CostModel<OptRelNodeTyp> for OptCostModel {
                     .get(table_name.as_ref())
                     .copied()
                     .unwrap_or(1) as f64;
-                Self::cost(row_cnt, 0.0, row_cnt)
+                Self::scan_cost(
+                    row_cnt,
+                    row_width.unwrap_or_else(|| Self::scan_row_width(child_schemas, None, None)),
+                    Self::list_len(child_schemas, 1).unwrap_or(0) as f64,
+                    Self::fetch(child_schemas, 2),
+                )
             }
             OptRelNodeTyp::PhysicalFilter => {
                 let (row_cnt, _, _) = Self::cost_tuple(&children[0]);
                 let (_, compute_cost, _) = Self::cost_tuple(&children[1]);
+                let partition_cnt = Self::partition_cnt(&children[0]);
                 let selectivity = 0.001;
-                Self::cost(
+                Self::partitioned_cost(
                     (row_cnt * selectivity).max(1.0),
-                    row_cnt * compute_cost,
+                    row_cnt * compute_cost / partition_cnt,
                     0.0,
+                    partition_cnt,
                 )
             }
             OptRelNodeTyp::PhysicalNestedLoopJoin(_) => {
@@ -130,17 +235,60 @@ impl CostModel<OptRelNodeTyp> for OptCostModel {
             OptRelNodeTyp::PhysicalProjection => {
                 let (row_cnt, _, _) = Self::cost_tuple(&children[0]);
                 let (_, compute_cost, _) = Self::cost_tuple(&children[1]);
-                Self::cost(row_cnt, compute_cost * row_cnt, 0.0)
+                let partition_cnt = Self::partition_cnt(&children[0]);
+                Self::partitioned_cost(
+                    row_cnt,
+                    compute_cost * row_cnt / partition_cnt,
+                    0.0,
+                    partition_cnt,
+                )
             }
             OptRelNodeTyp::PhysicalHashJoin(_) => {
                 let (row_cnt_1, _, _) = Self::cost_tuple(&children[0]);
                 let (row_cnt_2, _, _) = Self::cost_tuple(&children[1]);
-                Self::cost(
+                let build_right = data.as_ref().is_some_and(|data| data.as_bool());
+                let (build, probe) = if build_right {
+                    (&children[1], &children[0])
+                } else {
+                    (&children[0], &children[1])
+                };
+                let build_row_cnt = Self::row_cnt(build);
+                // Inserting into the hash table gets slower as it outgrows the caches. The build
+                // side is collected into one hash table unless it is partitioned, and the output
+                // keeps the partitioning of the probe side.
+                let build_cost =
+                    build_row_cnt * (HASH_BUILD_COST + 0.1 * build_row_cnt.log2().max(0.0));
+                Self::partitioned_cost(
                     row_cnt_1.min(row_cnt_2).max(1.0),
-                    row_cnt_1 * 2.0 + row_cnt_2,
+                    build_cost / Self::partition_cnt(build)
+                        + Self::row_cnt(probe) / Self::partition_cnt(probe),
+                    0.0,
+                    Self::partition_cnt(probe),
+                )
+            }
+            OptRelNodeTyp::PhysicalRuntimeFilter => {
+                let (row_cnt, _, _) = Self::cost_tuple(&children[0]);
+                let (_, compute_cost, _) = Self::cost_tuple(&children[1]);
+                let partition_cnt = Self::partition_cnt(&children[0]);
+                // Every row computes its keys and probes the bloom filter.
+                Self::partitioned_cost(
+                    (row_cnt * RUNTIME_FILTER_SELECTIVITY).max(1.0),
+                    row_cnt * (compute_cost + 0.5) / partition_cnt,
                     0.0,
+                    partition_cnt,
                 )
             }
+            OptRelNodeTyp::PhysicalExchange(exchange_type) => {
+                let (row_cnt, _, _) = Self::cost_tuple(&children[0]);
+                let (_, compute_cost, _) = Self::cost_tuple(&children[1]);
+                let (compute_cost, partition_cnt) = match exchange_type {
+                    ExchangeType::Hash => (row_cnt * (compute_cost + 1.0), parallelism),
+                    ExchangeType::RoundRobin => (row_cnt * 0.5, parallelism),
+                    ExchangeType::Single => (row_cnt * 0.5, 1.0),
+                    ExchangeType::Broadcast => (row_cnt * parallelism * 0.5, 1.0),
+                };
+                Self::partitioned_cost(row_cnt, compute_cost, 0.0, partition_cnt)
+            }
 
             OptRelNodeTyp::PhysicalSort => {
                 let (row_cnt, _, _) = Self::cost_tuple(&children[0]);
@@ -150,7 +298,56 @@ impl CostModel<OptRelNodeTyp> for OptCostModel {
                 let (row_cnt, _, _) = Self::cost_tuple(&children[0]);
                 let (_, compute_cost_1, _) = Self::cost_tuple(&children[1]);
                 let (_, compute_cost_2, _) = Self::cost_tuple(&children[2]);
-                Self::cost(row_cnt, row_cnt * (compute_cost_1 + compute_cost_2), 0.0)
+                let partition_cnt = Self::partition_cnt(&children[0]);
+                // Every input row is hashed and probed into the hash table.
+                Self::partitioned_cost(
+                    Self::agg_row_cnt(row_cnt, child_schemas),
+                    row_cnt * (compute_cost_1 + compute_cost_2 + 1.0) / partition_cnt,
+                    0.0,
+                    partition_cnt,
+                )
+            }
+            OptRelNodeTyp::PhysicalStreamAgg => {
+                let (row_cnt, _, _) = Self::cost_tuple(&children[0]);
+                let (_, compute_cost_1, _) = Self::cost_tuple(&children[1]);
+                let (_, compute_cost_2, _) = Self::cost_tuple(&children[2]);
+                Self::cost(
+                    Self::agg_row_cnt(row_cnt, child_schemas),
+                    row_cnt * (compute_cost_1 + compute_cost_2),
+                    0.0,
+                )
+            }
+            OptRelNodeTyp::PhysicalTwoPhaseAgg => {
+                let (row_cnt, _, _) = Self::cost_tuple(&children[0]);
+                let (_, compute_cost_1, _) = Self::cost_tuple(&children[1]);
+                let (_, compute_cost_2, _) = Self::cost_tuple(&children[2]);
+                let out_row_cnt = Self::agg_row_cnt(row_cnt, child_schemas);
+                // Each partition produces at most one row per group, which are then repartitioned
+                // and aggregated again.
+                let partial_row_cnt = (out_row_cnt * parallelism).min(row_cnt);
+                let per_row_cost = compute_cost_1 + compute_cost_2 + 1.0;
+                Self::cost(
+                    out_row_cnt,
+                    row_cnt * per_row_cost / parallelism + partial_row_cnt * (per_row_cost + 1.0),
+                    0.0,
+                )
+            }
+            OptRelNodeTyp::PhysicalEmpty => Self::cost(0.0, 0.0, 0.0),
+            OptRelNodeTyp::PhysicalUnion => {
+                let (left_row_cnt, _, _) = Self::cost_tuple(&children[0]);
+                let (right_row_cnt, _, _) = Self::cost_tuple(&children[1]);
+                // The partitions of both inputs are kept side by side.
+                Self::partitioned_cost(
+                    left_row_cnt + right_row_cnt,
+                    0.0,
+                    0.0,
+                    Self::partition_cnt(&children[0]) + Self::partition_cnt(&children[1]),
+                )
+            }
+            OptRelNodeTyp::PhysicalValues => {
+                let (_, compute_cost, _) = Self::cost_tuple(&children[0]);
+                let row_cnt = Self::list_len(child_schemas, 0).unwrap_or(1);
+                Self::cost(row_cnt as f64, compute_cost, 0.0)
             }
             OptRelNodeTyp::List => {
                 let compute_cost = children
@@ -163,6 +360,7 @@ impl CostModel<OptRelNodeTyp> for OptCostModel {
                 Self::cost(1.0, compute_cost + 0.01, 0.0)
             }
             OptRelNodeTyp::ColumnRef => Self::cost(1.0, 0.01, 0.0),
+            OptRelNodeTyp::Constant(_) => Self::cost(1.0, 1.0, 0.0),
             _ if node.is_expression() => {
                 let compute_cost = children
                     .iter()
@@ -177,16 +375,138 @@ impl CostModel<OptRelNodeTyp> for OptCostModel {
         }
     }
 
-    fn compute_plan_node_cost(&self, node: &RelNode<OptRelNodeTyp>) -> Cost {
-        let mut cost = self.zero();
-        let top = compute_plan_node_cost(self, node, &mut cost);
-        cost.0[ROW_COUNT] = top.0[ROW_COUNT];
-        cost
+    /// Gets the schema properties of the children of the expression costed in the memo, which
+    /// hold the expression trees of the children that are expressions, or nothing if the node is
+    /// costed outside of the memo.
+    pub fn child_schemas(
+        context: Option<RelNodeContext>,
+        optimizer: Option<&CascadesOptimizer<OptRelNodeTyp>>,
+    ) -> Vec<Schema> {
+        let (Some(context), Some(optimizer)) = (context, optimizer) else {
+            return vec![];
+        };
+        optimizer
+            .get_children_group_ids(context.expr_id)
+            .into_iter()
+            .map(|group_id| optimizer.get_property_by_group::<SchemaPropertyBuilder>(group_id, 0))
+            .collect()
+    }
+
+    /// Gets the number of items of the list at child `idx`, e.g., the columns, predicates or rows
+    /// it holds, if its expression tree is known.
+    pub fn list_len(child_schemas: &[Schema], idx: usize) -> Option<usize> {
+        let expr = child_schemas.get(idx)?.expr.as_ref()?;
+        (expr.typ == OptRelNodeTyp::List).then(|| expr.children.len())
+    }
+
+    /// Gets the number of rows a scan fetches from the constant at child `idx`, which is infinite
+    /// if the scan is not limited or the constant is unknown.
+    pub fn fetch(child_schemas: &[Schema], idx: usize) -> f64 {
+        match child_schemas
+            .get(idx)
+            .and_then(|schema| schema.expr.as_ref())
+        {
+            Some(expr) => match &expr.data {
+                Some(Value::UInt(fetch)) => *fetch as f64,
+                _ => f64::INFINITY,
+            },
+            None => f64::INFINITY,
+        }
+    }
+
+    /// Estimates the number of groups of an aggregation as the product of the NDVs of its group
+    /// by columns in its input. Returns `None` if any group by expression is not a column or has
+    /// no NDV.
+    fn group_ndv(child_schemas: &[Schema]) -> Option<usize> {
+        let input = child_schemas.first()?;
+        let groups = child_schemas.get(2)?.expr.as_ref()?;
+        groups.children.iter().try_fold(1usize, |acc, expr| {
+            let col = ColumnRefExpr::from_rel_node(expr.clone())?;
+            Some(acc.saturating_mul(input.fields.get(col.index())?.ndv?))
+        })
+    }
+
+    /// Estimates the output row count of an aggregation from its input row count and the NDVs of
+    /// its group by columns.
+    fn agg_row_cnt(row_cnt: f64, child_schemas: &[Schema]) -> f64 {
+        if Self::list_len(child_schemas, 2) == Some(0) {
+            return 1.0;
+        }
+        match Self::group_ndv(child_schemas) {
+            Some(ndv) => (ndv as f64).min(row_cnt),
+            None => row_cnt * DEFAULT_GROUP_SELECTIVITY,
+        }
+        .max(1.0)
+    }
+
+    /// Gets the number of bytes of a row of `schema`, from the widths of the types of its columns.
+    pub fn row_width(schema: &Schema) -> f64 {
+        schema
+            .fields
+            .iter()
+            .map(|field| field.typ.byte_width() as f64)
+            .sum()
+    }
+
+    /// Gets the number of bytes of a row read by a scan, from the schema of its group if it is
+    /// costed in the memo, or from the length of its projection list otherwise.
+    pub fn scan_row_width(
+        child_schemas: &[Schema],
+        context: Option<RelNodeContext>,
+        optimizer: Option<&CascadesOptimizer<OptRelNodeTyp>>,
+    ) -> f64 {
+        match (context, optimizer) {
+            (Some(context), Some(optimizer)) => Self::row_width(
+                &optimizer.get_property_by_group::<SchemaPropertyBuilder>(context.group_id, 0),
+            ),
+            _ => Self::list_len(child_schemas, 0).unwrap_or(0) as f64 * DEFAULT_COLUMN_WIDTH,
+        }
+    }
+
+    /// Computes the cost of scanning a table of `row_cnt` rows of `row_width` bytes, applying
+    /// `filter_cnt` pushed-down filters exactly and stopping after `fetch` rows, which is infinite
+    /// if the scan is not limited. The IO cost grows with the width of the rows read, where every
+    /// eight bytes cost a tenth of a row. Filters the table applies inexactly are not counted, as
+    /// they are evaluated again above the scan.
+    pub fn scan_cost(row_cnt: f64, row_width: f64, filter_cnt: f64, fetch: f64) -> Cost {
+        let filtered_row_cnt = if filter_cnt > 0.0 {
+            (row_cnt * 0.001).max(1.0)
+        } else {
+            row_cnt
+        };
+        let out_row_cnt = filtered_row_cnt.min(fetch).max(1.0);
+        // A limited scan stops reading once enough rows passed the filters.
+        let read_row_cnt = row_cnt * (out_row_cnt / filtered_row_cnt).min(1.0);
+        Self::cost(
+            out_row_cnt,
+            0.0,
+            read_row_cnt * (1.0 + 0.1 * row_width / 8.0),
+        )
     }
 }
 
-impl OptCostModel {
-    pub fn new(table_stat: HashMap<String, usize>) -> Self {
-        Self { table_stat }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scans_stop_reading_at_their_fetch() {
+        let full = OptCostModel::scan_cost(1000.0, 8.0, 0.0, f64::INFINITY);
+        let limited = OptCostModel::scan_cost(1000.0, 8.0, 0.0, 10.0);
+        assert_eq!(OptCostModel::row_cnt(&full), 1000.0);
+        assert_eq!(OptCostModel::row_cnt(&limited), 10.0);
+        assert!(OptCostModel::io_cost(&limited) < OptCostModel::io_cost(&full));
+    }
+
+    #[test]
+    fn filtered_scans_read_past_their_fetch() {
+        // Only one in a thousand rows passes the filters, so fetching a row reads a thousand.
+        let filtered = OptCostModel::scan_cost(1_000_000.0, 8.0, 1.0, 1.0);
+        assert_eq!(OptCostModel::row_cnt(&filtered), 1.0);
+        let unfiltered = OptCostModel::scan_cost(1_000.0, 8.0, 0.0, f64::INFINITY);
+        assert_eq!(
+            OptCostModel::io_cost(&filtered),
+            OptCostModel::io_cost(&unfiltered)
+        );
     }
 }
@@ -0,0 +1,462 @@
+//! Simplification of expressions. Operators over constants are evaluated with the arrow kernels the
+//! executor uses, `AND` and `OR` are flattened into n-ary logical operators with neutral, absorbing
+//! and duplicate operands removed, and predicates are normalized into conjunctive normal form.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{
+        new_null_array, Array, ArrayRef, AsArray, BooleanArray, Date32Array, Date64Array,
+        Decimal128Array, Float32Array, Float64Array, Int16Array, Int32Array, Int64Array, Int8Array,
+        StringArray, UInt16Array, UInt32Array, UInt64Array, UInt8Array,
+    },
+    compute::{
+        cast,
+        kernels::{cmp, numeric},
+    },
+    datatypes::{
+        DataType, Date32Type, Date64Type, Decimal128Type, Float32Type, Float64Type, Int16Type,
+        Int32Type, Int64Type, Int8Type, UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+    },
+};
+use itertools::Itertools;
+use optd_core::rel_node::{RelNode, Value};
+
+use crate::plan_nodes::{
+    BinOpType, ConstantExpr, ConstantType, Expr, ExprList, FuncExpr, FuncType, LogOpExpr,
+    LogOpType, OptRelNode, OptRelNodeRef, OptRelNodeTyp, UnOpExpr, UnOpType,
+};
+use crate::properties::schema::Schema;
+use crate::typing::try_expr_type;
+
+/// Predicates whose conjunctive normal form has more conjuncts are kept as they are.
+const MAX_CNF_CONJUNCTS: usize = 32;
+
+/// Simplifies an expression bottom-up. The result evaluates to the same value for every row.
+pub fn simplify_expr(expr: &OptRelNodeRef) -> OptRelNodeRef {
+    let children = expr.children.iter().map(simplify_expr).collect_vec();
+    match &expr.typ {
+        OptRelNodeTyp::BinOp(BinOpType::And) => simplify_log_op(LogOpType::And, children),
+        OptRelNodeTyp::BinOp(BinOpType::Or) => simplify_log_op(LogOpType::Or, children),
+        OptRelNodeTyp::LogOp(op) => simplify_log_op(*op, children[0].children.clone()),
+        OptRelNodeTyp::BinOp(op) => simplify_bin_op(*op, children),
+        OptRelNodeTyp::UnOp(op) => simplify_un_op(*op, children.into_iter().next().unwrap()),
+        OptRelNodeTyp::Func(FuncType::Case) => simplify_case(children.into_iter().next().unwrap()),
+        typ => Arc::new(RelNode {
+            typ: typ.clone(),
+            children,
+            data: expr.data.clone(),
+        }),
+    }
+}
+
+/// Simplifies a predicate and normalizes it into conjunctive normal form, so that the conjuncts
+/// can be pushed down separately.
+pub fn simplify_predicate(expr: &OptRelNodeRef) -> OptRelNodeRef {
+    let expr = simplify_expr(expr);
+    let Some(conjuncts) = cnf_conjuncts(&expr) else {
+        return expr;
+    };
+    let conjuncts = conjuncts
+        .into_iter()
+        .map(|disjuncts| simplify_log_op(LogOpType::Or, disjuncts))
+        .collect_vec();
+    simplify_log_op(LogOpType::And, conjuncts)
+}
+
+/// Gets the boolean value of a constant, where a null of any type is `None`.
+pub fn constant_bool(expr: &OptRelNodeRef) -> Option<Option<bool>> {
+    let OptRelNodeTyp::Constant(typ) = &expr.typ else {
+        return None;
+    };
+    match expr.data.as_ref().unwrap() {
+        Value::Null => Some(None),
+        Value::Bool(value) if *typ == ConstantType::Bool => Some(Some(*value)),
+        _ => None,
+    }
+}
+
+fn constant_value(expr: &OptRelNodeRef) -> Option<(Value, ConstantType)> {
+    let OptRelNodeTyp::Constant(typ) = &expr.typ else {
+        return None;
+    };
+    Some((expr.data.clone().unwrap(), typ.clone()))
+}
+
+fn null_bool() -> OptRelNodeRef {
+    ConstantExpr::null(ConstantType::Bool).into_rel_node()
+}
+
+fn simplify_log_op(op: LogOpType, operands: Vec<OptRelNodeRef>) -> OptRelNodeRef {
+    // `false` absorbs a conjunction and `true` a disjunction, while the other value is neutral.
+    let absorbing = op == LogOpType::Or;
+    let mut flattened = vec![];
+    let mut stack = operands;
+    stack.reverse();
+    while let Some(operand) = stack.pop() {
+        match &operand.typ {
+            OptRelNodeTyp::LogOp(child_op) if *child_op == op => {
+                stack.extend(operand.child(0).children.iter().rev().cloned())
+            }
+            OptRelNodeTyp::BinOp(BinOpType::And) if op == LogOpType::And => {
+                stack.extend(operand.children.iter().rev().cloned())
+            }
+            OptRelNodeTyp::BinOp(BinOpType::Or) if op == LogOpType::Or => {
+                stack.extend(operand.children.iter().rev().cloned())
+            }
+            _ => match constant_bool(&operand) {
+                Some(Some(value)) if value == absorbing => {
+                    return ConstantExpr::bool(absorbing).into_rel_node()
+                }
+                Some(Some(_)) => {}
+                _ => flattened.push(operand),
+            },
+        }
+    }
+    let mut operands = flattened
+        .into_iter()
+        .unique_by(|operand| operand.to_string())
+        .collect_vec();
+    // Nulls are only kept once, as any of them makes the result null unless it is absorbed.
+    let nulls = operands
+        .iter()
+        .filter(|operand| constant_bool(operand).is_some())
+        .count();
+    if nulls > 1 {
+        operands.retain(|operand| constant_bool(operand).is_none());
+        operands.push(null_bool());
+    }
+    match operands.len() {
+        0 => ConstantExpr::bool(!absorbing).into_rel_node(),
+        1 => operands.remove(0),
+        _ => LogOpExpr::new(
+            op,
+            ExprList::new(
+                operands
+                    .into_iter()
+                    .map(|operand| Expr::from_rel_node(operand).unwrap())
+                    .collect(),
+            ),
+        )
+        .into_rel_node(),
+    }
+}
+
+fn simplify_bin_op(op: BinOpType, children: Vec<OptRelNodeRef>) -> OptRelNodeRef {
+    let rebuild = |children| {
+        Arc::new(RelNode {
+            typ: OptRelNodeTyp::BinOp(op),
+            children,
+            data: None,
+        })
+    };
+    let (Some(left), Some(right)) = (constant_value(&children[0]), constant_value(&children[1]))
+    else {
+        // A comparison with null is null, whatever the other operand is.
+        let has_null = children
+            .iter()
+            .any(|child| constant_bool(child) == Some(None));
+        if op.is_comparison() && has_null {
+            return null_bool();
+        }
+        return rebuild(children);
+    };
+    if op.is_comparison() && (left.0.is_null() || right.0.is_null()) {
+        return null_bool();
+    }
+    // The arrow kernels may pick another result type than the planner, e.g., another precision
+    // for decimals, so the result is cast to the type the operands are coerced to.
+    let Ok(typ) = try_expr_type(&rebuild(children.clone()), &Schema::new(vec![])) else {
+        return rebuild(children);
+    };
+    match fold_bin_op(op, left, right, &typ) {
+        Some((value, typ)) => ConstantExpr::new_with_type(value, typ).into_rel_node(),
+        None => rebuild(children),
+    }
+}
+
+fn simplify_un_op(op: UnOpType, child: OptRelNodeRef) -> OptRelNodeRef {
+    match op {
+        UnOpType::Not => {
+            if let OptRelNodeTyp::UnOp(UnOpType::Not) = child.typ {
+                return child.child(0);
+            }
+            match constant_bool(&child) {
+                Some(Some(value)) => ConstantExpr::bool(!value).into_rel_node(),
+                Some(None) => null_bool(),
+                None => UnOpExpr::new(Expr::from_rel_node(child).unwrap(), op).into_rel_node(),
+            }
+        }
+        UnOpType::Neg => {
+            let folded = constant_value(&child).and_then(|(value, typ)| {
+                let array = value_into_array(&value, &typ)?;
+                array_into_value(&numeric::neg(&array).ok()?)
+            });
+            match folded {
+                Some((value, typ)) => ConstantExpr::new_with_type(value, typ).into_rel_node(),
+                None => UnOpExpr::new(Expr::from_rel_node(child).unwrap(), op).into_rel_node(),
+            }
+        }
+    }
+}
+
+/// CASE takes [when, then, else], and picks a branch if `when` is a constant. A branch that is an
+/// untyped null is only picked if the type of the other branch is known.
+fn simplify_case(args: OptRelNodeRef) -> OptRelNodeRef {
+    let rebuild = |args: OptRelNodeRef| {
+        FuncExpr::new(FuncType::Case, ExprList::from_rel_node(args).unwrap()).into_rel_node()
+    };
+    let Some(when) = constant_bool(&args.child(0)) else {
+        return rebuild(args);
+    };
+    let (picked, other) = if when == Some(true) {
+        (args.child(1), args.child(2))
+    } else {
+        (args.child(2), args.child(1))
+    };
+    if picked.typ != OptRelNodeTyp::Constant(ConstantType::Null) {
+        return picked;
+    }
+    match &other.typ {
+        OptRelNodeTyp::Constant(typ) => ConstantExpr::null(typ.clone()).into_rel_node(),
+        _ => rebuild(args),
+    }
+}
+
+/// Gets the conjunctive normal form of a predicate as a list of conjuncts, each of which is a list
+/// of disjuncts. Returns `None` once there are more than `MAX_CNF_CONJUNCTS` conjuncts.
+fn cnf_conjuncts(expr: &OptRelNodeRef) -> Option<Vec<Vec<OptRelNodeRef>>> {
+    let conjuncts = match &expr.typ {
+        OptRelNodeTyp::LogOp(LogOpType::And) => {
+            let mut conjuncts = vec![];
+            for child in &expr.child(0).children {
+                conjuncts.extend(cnf_conjuncts(child)?);
+                if conjuncts.len() > MAX_CNF_CONJUNCTS {
+                    return None;
+                }
+            }
+            conjuncts
+        }
+        OptRelNodeTyp::LogOp(LogOpType::Or) => {
+            let mut conjuncts = vec![vec![]];
+            for child in &expr.child(0).children {
+                let child_conjuncts = cnf_conjuncts(child)?;
+                if conjuncts.len() * child_conjuncts.len() > MAX_CNF_CONJUNCTS {
+                    return None;
+                }
+                conjuncts = conjuncts
+                    .iter()
+                    .cartesian_product(&child_conjuncts)
+                    .map(|(disjuncts, child_disjuncts)| {
+                        disjuncts.iter().chain(child_disjuncts).cloned().collect()
+                    })
+                    .collect();
+            }
+            conjuncts
+        }
+        _ => vec![vec![expr.clone()]],
+    };
+    Some(conjuncts)
+}
+
+fn fold_bin_op(
+    op: BinOpType,
+    left: (Value, ConstantType),
+    right: (Value, ConstantType),
+    typ: &ConstantType,
+) -> Option<(Value, ConstantType)> {
+    let left = value_into_array(&left.0, &left.1)?;
+    let right = value_into_array(&right.0, &right.1)?;
+    let cmp_result = |result: Result<BooleanArray, _>| result.map(|x| Arc::new(x) as ArrayRef);
+    let result = match op {
+        BinOpType::Add => numeric::add(&left, &right),
+        BinOpType::Sub => numeric::sub(&left, &right),
+        BinOpType::Mul => numeric::mul(&left, &right),
+        BinOpType::Div => numeric::div(&left, &right),
+        BinOpType::Mod => numeric::rem(&left, &right),
+        BinOpType::Eq => cmp_result(cmp::eq(&left, &right)),
+        BinOpType::Neq => cmp_result(cmp::neq(&left, &right)),
+        BinOpType::Lt => cmp_result(cmp::lt(&left, &right)),
+        BinOpType::Leq => cmp_result(cmp::lt_eq(&left, &right)),
+        BinOpType::Gt => cmp_result(cmp::gt(&left, &right)),
+        BinOpType::Geq => cmp_result(cmp::gt_eq(&left, &right)),
+        // Logical operators are simplified as `LogOpExpr`s.
+        BinOpType::And | BinOpType::Or | BinOpType::Xor => return None,
+    };
+    array_into_value(&cast(&result.ok()?, &typ.into_data_type().ok()?).ok()?)
+}
+
+/// Builds a single-element array of a constant. Returns `None` for types that are not folded.
+fn value_into_array(value: &Value, typ: &ConstantType) -> Option<ArrayRef> {
+    if value.is_null() {
+        return Some(new_null_array(&typ.into_data_type().ok()?, 1));
+    }
+    let array: ArrayRef = match typ {
+        ConstantType::Bool => Arc::new(BooleanArray::from(vec![value.as_bool()])),
+        ConstantType::Int8 => Arc::new(Int8Array::from(vec![value.as_i64() as i8])),
+        ConstantType::Int16 => Arc::new(Int16Array::from(vec![value.as_i64() as i16])),
+        ConstantType::Int32 => Arc::new(Int32Array::from(vec![value.as_i64() as i32])),
+        ConstantType::Int64 => Arc::new(Int64Array::from(vec![value.as_i64()])),
+        ConstantType::UInt8 => Arc::new(UInt8Array::from(vec![value.as_u64() as u8])),
+        ConstantType::UInt16 => Arc::new(UInt16Array::from(vec![value.as_u64() as u16])),
+        ConstantType::UInt32 => Arc::new(UInt32Array::from(vec![value.as_u64() as u32])),
+        ConstantType::UInt64 => Arc::new(UInt64Array::from(vec![value.as_u64()])),
+        ConstantType::Float32 => Arc::new(Float32Array::from(vec![value.as_f64() as f32])),
+        ConstantType::Float64 => Arc::new(Float64Array::from(vec![value.as_f64()])),
+        ConstantType::Date32 => Arc::new(Date32Array::from(vec![value.as_i64() as i32])),
+        ConstantType::Date64 => Arc::new(Date64Array::from(vec![value.as_i64()])),
+        ConstantType::Decimal128(precision, scale) => Arc::new(
+            Decimal128Array::from(vec![value.as_i128()])
+                .with_precision_and_scale(*precision, *scale)
+                .ok()?,
+        ),
+        ConstantType::Utf8String => Arc::new(StringArray::from(vec![value.as_str().as_ref()])),
+        _ => return None,
+    };
+    Some(array)
+}
+
+/// Reads the constant in a single-element array. Returns `None` for types that are not folded.
+fn array_into_value(array: &ArrayRef) -> Option<(Value, ConstantType)> {
+    let typ = ConstantType::from_data_type(array.data_type()).ok()?;
+    if array.is_null(0) {
+        return Some((Value::Null, typ));
+    }
+    let value = match array.data_type() {
+        DataType::Boolean => Value::Bool(array.as_boolean().value(0)),
+        DataType::Int8 => Value::Int(array.as_primitive::<Int8Type>().value(0) as i64),
+        DataType::Int16 => Value::Int(array.as_primitive::<Int16Type>().value(0) as i64),
+        DataType::Int32 => Value::Int(array.as_primitive::<Int32Type>().value(0) as i64),
+        DataType::Int64 => Value::Int(array.as_primitive::<Int64Type>().value(0)),
+        DataType::UInt8 => Value::UInt(array.as_primitive::<UInt8Type>().value(0) as u64),
+        DataType::UInt16 => Value::UInt(array.as_primitive::<UInt16Type>().value(0) as u64),
+        DataType::UInt32 => Value::UInt(array.as_primitive::<UInt32Type>().value(0) as u64),
+        DataType::UInt64 => Value::UInt(array.as_primitive::<UInt64Type>().value(0)),
+        DataType::Float32 => {
+            Value::Float((array.as_primitive::<Float32Type>().value(0) as f64).into())
+        }
+        DataType::Float64 => Value::Float(array.as_primitive::<Float64Type>().value(0).into()),
+        DataType::Date32 => Value::Int(array.as_primitive::<Date32Type>().value(0) as i64),
+        DataType::Date64 => Value::Int(array.as_primitive::<Date64Type>().value(0)),
+        DataType::Decimal128(_, _) => {
+            Value::Int128(array.as_primitive::<Decimal128Type>().value(0))
+        }
+        DataType::Utf8 => Value::String(array.as_string::<i32>().value(0).into()),
+        _ => return None,
+    };
+    Some((value, typ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan_nodes::BinOpExpr;
+    use crate::testing::{col, eq};
+
+    fn int(value: i64) -> Expr {
+        ConstantExpr::int(value).into_expr()
+    }
+
+    fn bin_op(left: Expr, right: Expr, op: BinOpType) -> OptRelNodeRef {
+        BinOpExpr::new(left, right, op).into_rel_node()
+    }
+
+    fn log_op(op: LogOpType, exprs: Vec<Expr>) -> Expr {
+        LogOpExpr::new(op, ExprList::new(exprs)).into_expr()
+    }
+
+    fn assert_simplified(expr: OptRelNodeRef, expected: OptRelNodeRef) {
+        assert_eq!(simplify_predicate(&expr).to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn fold_arithmetic() {
+        assert_simplified(
+            bin_op(int(1), int(2), BinOpType::Add),
+            ConstantExpr::int(3).into_rel_node(),
+        );
+        assert_simplified(
+            bin_op(int(7), int(2), BinOpType::Mod),
+            ConstantExpr::int(1).into_rel_node(),
+        );
+        // Division by zero is left to the executor.
+        let div = bin_op(int(1), int(0), BinOpType::Div);
+        assert_simplified(div.clone(), div);
+    }
+
+    #[test]
+    fn fold_decimals_into_coerced_type() {
+        let decimal = |value| ConstantExpr::decimal(value, 5, 2).into_expr();
+        assert_simplified(
+            bin_op(decimal(150), decimal(250), BinOpType::Add),
+            ConstantExpr::decimal(400, 6, 2).into_rel_node(),
+        );
+        assert_simplified(
+            bin_op(decimal(150), decimal(250), BinOpType::Mul),
+            ConstantExpr::decimal(37500, 11, 4).into_rel_node(),
+        );
+    }
+
+    #[test]
+    fn fold_comparisons() {
+        assert_simplified(
+            bin_op(int(1), int(2), BinOpType::Lt),
+            ConstantExpr::bool(true).into_rel_node(),
+        );
+        let null = ConstantExpr::null(ConstantType::Int64).into_expr();
+        assert_simplified(bin_op(col(0), null, BinOpType::Eq), null_bool());
+    }
+
+    #[test]
+    fn remove_neutral_and_absorbing_operands() {
+        let pred = eq(col(0), int(1));
+        let t = ConstantExpr::bool(true).into_expr();
+        assert_simplified(
+            log_op(LogOpType::And, vec![pred.clone(), t.clone(), pred.clone()]).into_rel_node(),
+            pred.clone().into_rel_node(),
+        );
+        assert_simplified(
+            log_op(LogOpType::Or, vec![pred, t.clone()]).into_rel_node(),
+            t.into_rel_node(),
+        );
+    }
+
+    #[test]
+    fn normalize_into_cnf() {
+        let (a, b, c) = (eq(col(0), int(1)), eq(col(1), int(1)), eq(col(2), int(1)));
+        let pred = log_op(
+            LogOpType::Or,
+            vec![
+                log_op(LogOpType::And, vec![a.clone(), b.clone()]),
+                c.clone(),
+            ],
+        );
+        let expected = log_op(
+            LogOpType::And,
+            vec![
+                log_op(LogOpType::Or, vec![a, c.clone()]),
+                log_op(LogOpType::Or, vec![b, c]),
+            ],
+        );
+        assert_simplified(pred.into_rel_node(), expected.into_rel_node());
+    }
+
+    #[test]
+    fn keep_predicates_with_large_cnf() {
+        // The CNF of a disjunction of 6 conjunctions of 2 predicates has 2^6 conjuncts.
+        let pred = log_op(
+            LogOpType::Or,
+            (0..6)
+                .map(|i| {
+                    log_op(
+                        LogOpType::And,
+                        vec![eq(col(2 * i), int(1)), eq(col(2 * i + 1), int(1))],
+                    )
+                })
+                .collect(),
+        )
+        .into_rel_node();
+        assert!(cnf_conjuncts(&pred).is_none());
+        assert_simplified(pred.clone(), pred);
+    }
+}
@@ -0,0 +1,688 @@
+//! Join order enumeration with DPhyp (Moerkotte and Neumann, "Dynamic Programming Strikes Back").
+//!
+//! Instead of deriving join orders with commute and associate rules, every tree of inner joins in
+//! a plan is flattened into a join graph whose nodes are the inputs of the tree and whose
+//! (hyper)edges are the conjuncts of the join conditions. DPhyp then visits every pair of
+//! connected sets of inputs that are connected to each other exactly once, and the join of each
+//! pair is added to the group of the union of the pair. The memo thus holds every join tree
+//! without cross products, and nothing else.
+//!
+//! The number of such trees grows exponentially, so join trees with more inputs than a threshold
+//! are ordered greedily (GOO, Fegaras, "A New Heuristic for Optimizing Large Queries") instead.
+//! The greedy tree fixes an order of the inputs, and every bushy tree joining neighbouring ranges
+//! of that order is added to the memo (linearized DP, Neumann and Radke, "Adaptive Optimization
+//! of Very Large Join Queries"), which seeds the memo with a good plan before exploration.
+
+use std::collections::{BTreeSet, HashMap};
+
+use itertools::Itertools;
+
+use optd_core::{
+    cascades::{CascadesOptimizer, GroupId},
+    rel_node::RelNode,
+};
+
+use crate::{
+    plan_nodes::{
+        join_mapping, BinOpExpr, BinOpType, ConstantExpr, Expr, JoinType, LogicalFilter,
+        LogicalJoin, OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode,
+    },
+    properties::schema::SchemaPropertyBuilder,
+    rules::{and_conjuncts, split_conjuncts},
+    Value,
+};
+
+/// A set of inputs of a join graph, one bit per input.
+type RelSet = u64;
+
+/// Join trees with more inputs are left to the transformation rules.
+const MAX_RELATIONS: usize = RelSet::BITS as usize;
+
+/// The row count assumed for inputs that have not been executed yet.
+const DEFAULT_ROW_CNT: f64 = 1000.0;
+
+/// The selectivity assumed for filters on an input, and for join conditions other than
+/// equalities of columns.
+const DEFAULT_SELECTIVITY: f64 = 0.1;
+
+/// Controls which join trees [`enumerate_join_orders`] adds to the memo.
+pub(crate) struct JoinOrderOptions {
+    /// Enumerates all join trees without cross products up to `greedy_threshold` inputs.
+    pub dphyp: bool,
+    /// Join trees with more inputs are ordered greedily.
+    pub greedy_threshold: usize,
+    /// The observed row counts of groups, used to estimate the sizes of joins.
+    pub row_cnts: HashMap<GroupId, usize>,
+}
+
+fn is_subset(set: RelSet, of: RelSet) -> bool {
+    set & !of == 0
+}
+
+fn lowest(set: RelSet) -> RelSet {
+    set & set.wrapping_neg()
+}
+
+/// The inputs with an index less than or equal to the lowest input of `set`.
+fn up_to_lowest(set: RelSet) -> RelSet {
+    (lowest(set) << 1).wrapping_sub(1)
+}
+
+/// Iterates over the non-empty subsets of `set` in increasing order, so that every subset comes
+/// after its own subsets.
+fn subsets(set: RelSet) -> impl Iterator<Item = RelSet> {
+    let mut subset: RelSet = 0;
+    std::iter::from_fn(move || {
+        subset = subset.wrapping_sub(set) & set;
+        (subset != 0).then_some(subset)
+    })
+}
+
+/// Checks whether `node` is an inner join outputting the columns of its inputs in order.
+fn is_inner_join(node: &OptRelNodeRef) -> bool {
+    node.typ == OptRelNodeTyp::Join(JoinType::Inner) && join_mapping(&node.data).is_none()
+}
+
+/// Checks whether `node` is the root of a tree of inner joins, which may have filters on top of
+/// its joins.
+fn is_join_tree(node: &OptRelNodeRef) -> bool {
+    is_inner_join(node) || (node.typ == OptRelNodeTyp::Filter && is_inner_join(&node.child(0)))
+}
+
+/// Collects the inputs of the join tree rooted at `node`, i.e., the plan nodes below its joins
+/// and filters that are not part of the tree.
+fn join_tree_inputs(node: &OptRelNodeRef, inputs: &mut Vec<OptRelNodeRef>) {
+    if is_inner_join(node) {
+        join_tree_inputs(&node.child(0), inputs);
+        join_tree_inputs(&node.child(1), inputs);
+    } else if is_join_tree(node) {
+        join_tree_inputs(&node.child(0), inputs);
+    } else {
+        inputs.push(node.clone());
+    }
+}
+
+/// A conjunct of the join conditions, with its columns numbered across all inputs of the graph.
+/// It can be evaluated by a join of `left` and `right`, or of any of their supersets.
+struct Edge {
+    left: RelSet,
+    right: RelSet,
+    cond: Expr,
+}
+
+struct JoinGraph<'a> {
+    optimizer: &'a mut CascadesOptimizer<OptRelNodeTyp>,
+    options: &'a JoinOrderOptions,
+    /// The first column of each input in the output of the join tree.
+    offsets: Vec<usize>,
+    widths: Vec<usize>,
+    edges: Vec<Edge>,
+    /// The group of every set of inputs that has a join tree without cross products.
+    groups: HashMap<RelSet, GroupId>,
+    /// The group the join trees of all inputs are added to.
+    root_group: Option<GroupId>,
+    /// The number of pairs of join trees joined, each adding a join in both orders to the memo.
+    pair_cnt: usize,
+}
+
+impl JoinGraph<'_> {
+    /// Flattens the join tree rooted at `node`, adding its inputs to the memo. The conjuncts of
+    /// the join conditions are collected into `conds`.
+    fn collect(&mut self, node: OptRelNodeRef, inputs: &mut Vec<GroupId>, conds: &mut Vec<Expr>) {
+        let offset = self.widths.iter().sum::<usize>();
+        let cond = if is_inner_join(&node) {
+            let join = LogicalJoin::from_rel_node(node).unwrap();
+            self.collect(join.left().into_rel_node(), inputs, conds);
+            self.collect(join.right().into_rel_node(), inputs, conds);
+            join.cond()
+        } else if is_join_tree(&node) {
+            let filter = LogicalFilter::from_rel_node(node).unwrap();
+            self.collect(filter.child().into_rel_node(), inputs, conds);
+            filter.cond()
+        } else {
+            self.pair_cnt += enumerate_join_orders(self.optimizer, node.clone(), self.options);
+            let group_id = self.optimizer.step_add_rel(node, None);
+            let schema = self
+                .optimizer
+                .get_property_by_group::<SchemaPropertyBuilder>(group_id, 0);
+            inputs.push(group_id);
+            self.offsets.push(offset);
+            self.widths.push(schema.len());
+            return;
+        };
+        let mut conjuncts = vec![];
+        split_conjuncts(cond, &mut conjuncts);
+        conds.extend(
+            conjuncts
+                .into_iter()
+                .map(|cond| cond.rewrite_column_refs(&|col| Some(col + offset)).unwrap()),
+        );
+    }
+
+    fn input_of(&self, col: usize) -> usize {
+        self.offsets.partition_point(|offset| *offset <= col) - 1
+    }
+
+    fn inputs_of(&self, expr: &Expr) -> RelSet {
+        let mut cols = BTreeSet::new();
+        expr.collect_column_refs(&mut cols);
+        cols.into_iter()
+            .fold(0, |set, col| set | (1 << self.input_of(col)))
+    }
+
+    /// The output columns of a join tree of `set`, in the order of the inputs.
+    fn columns(&self, set: RelSet) -> Vec<usize> {
+        (0..self.offsets.len())
+            .filter(|input| set & (1 << input) != 0)
+            .flat_map(|input| self.offsets[input]..self.offsets[input] + self.widths[input])
+            .collect()
+    }
+
+    /// Finds the inputs of the edges from `set` that do not touch `set` or `excluded`, taking the
+    /// lowest input of each hyperedge.
+    fn neighborhood(&self, set: RelSet, excluded: RelSet) -> RelSet {
+        let forbidden = set | excluded;
+        self.edges.iter().fold(0, |neighbors, edge| {
+            if is_subset(edge.left, set) && edge.right & forbidden == 0 {
+                neighbors | lowest(edge.right)
+            } else if is_subset(edge.right, set) && edge.left & forbidden == 0 {
+                neighbors | lowest(edge.left)
+            } else {
+                neighbors
+            }
+        })
+    }
+
+    fn is_connected(&self, left: RelSet, right: RelSet) -> bool {
+        self.edges.iter().any(|edge| {
+            (is_subset(edge.left, left) && is_subset(edge.right, right))
+                || (is_subset(edge.left, right) && is_subset(edge.right, left))
+        })
+    }
+
+    fn solve(&mut self) {
+        for input in (0..self.offsets.len()).rev() {
+            let set = 1 << input;
+            self.emit_csg(set);
+            self.enumerate_csg_rec(set, up_to_lowest(set));
+        }
+    }
+
+    fn enumerate_csg_rec(&mut self, set: RelSet, excluded: RelSet) {
+        let neighbors = self.neighborhood(set, excluded);
+        for subset in subsets(neighbors) {
+            if self.groups.contains_key(&(set | subset)) {
+                self.emit_csg(set | subset);
+            }
+        }
+        for subset in subsets(neighbors) {
+            self.enumerate_csg_rec(set | subset, excluded | neighbors);
+        }
+    }
+
+    fn emit_csg(&mut self, left: RelSet) {
+        let excluded = left | up_to_lowest(left);
+        let neighbors = self.neighborhood(left, excluded);
+        for input in (0..self.offsets.len()).rev() {
+            let right: RelSet = 1 << input;
+            if neighbors & right == 0 {
+                continue;
+            }
+            if self.is_connected(left, right) {
+                self.emit_csg_cmp(left, right);
+            }
+            let below = neighbors & up_to_lowest(right);
+            self.enumerate_cmp_rec(left, right, excluded | below);
+        }
+    }
+
+    fn enumerate_cmp_rec(&mut self, left: RelSet, right: RelSet, excluded: RelSet) {
+        let neighbors = self.neighborhood(right, excluded);
+        for subset in subsets(neighbors) {
+            let right = right | subset;
+            if self.groups.contains_key(&right) && self.is_connected(left, right) {
+                self.emit_csg_cmp(left, right);
+            }
+        }
+        for subset in subsets(neighbors) {
+            self.enumerate_cmp_rec(left, right | subset, excluded | neighbors);
+        }
+    }
+
+    /// Estimates the fraction of the cross product of `left` and `right` their join returns,
+    /// assuming that an equality of columns joins a foreign key to the key of the larger input.
+    fn selectivity(&self, left: RelSet, right: RelSet, row_cnts: &[f64]) -> f64 {
+        self.edges
+            .iter()
+            .filter(|edge| {
+                (is_subset(edge.left, left) && is_subset(edge.right, right))
+                    || (is_subset(edge.left, right) && is_subset(edge.right, left))
+            })
+            .map(|edge| {
+                if edge.cond.typ() == OptRelNodeTyp::BinOp(BinOpType::Eq) {
+                    let row_cnt = |set: RelSet| row_cnts[set.trailing_zeros() as usize];
+                    1.0 / row_cnt(edge.left).max(row_cnt(edge.right))
+                } else {
+                    DEFAULT_SELECTIVITY
+                }
+            })
+            .product()
+    }
+
+    /// Orders the inputs by repeatedly joining the two connected join trees with the smallest
+    /// estimated result. Returns `None` if the join graph is not connected.
+    fn greedy_order(&self, row_cnts: &[f64]) -> Option<Vec<usize>> {
+        let mut trees = (0..self.offsets.len())
+            .map(|input| (1 << input, row_cnts[input], vec![input]))
+            .collect_vec();
+        while trees.len() > 1 {
+            let mut best: Option<(usize, usize, f64)> = None;
+            for (i, j) in (0..trees.len()).tuple_combinations() {
+                let (left, left_row_cnt, _) = &trees[i];
+                let (right, right_row_cnt, _) = &trees[j];
+                if !self.is_connected(*left, *right) {
+                    continue;
+                }
+                let row_cnt =
+                    left_row_cnt * right_row_cnt * self.selectivity(*left, *right, row_cnts);
+                if best.is_some_and(|(_, _, best_row_cnt)| best_row_cnt <= row_cnt) {
+                    continue;
+                }
+                best = Some((i, j, row_cnt));
+            }
+            let (i, j, row_cnt) = best?;
+            let (right, _, right_order) = trees.swap_remove(j);
+            let (left, left_row_cnt, left_order) = &mut trees[i];
+            *left |= right;
+            *left_row_cnt = row_cnt;
+            left_order.extend(right_order);
+        }
+        Some(trees.remove(0).2)
+    }
+
+    /// Adds every join of two neighbouring ranges of `order` to the memo, from the shortest
+    /// ranges to the longest.
+    fn solve_linearized(&mut self, order: &[usize]) {
+        let range = |from: usize, to: usize| {
+            order[from..=to]
+                .iter()
+                .fold(0, |set: RelSet, input| set | (1 << input))
+        };
+        for len in 2..=order.len() {
+            for from in 0..=order.len() - len {
+                let to = from + len - 1;
+                for mid in from..to {
+                    let (left, right) = (range(from, mid), range(mid + 1, to));
+                    if self.groups.contains_key(&left)
+                        && self.groups.contains_key(&right)
+                        && self.is_connected(left, right)
+                    {
+                        self.emit_csg_cmp(left, right);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds the joins of `left` and `right` in both orders to the group of their union. A join
+    /// whose columns are not in the order of the inputs maps them back to that order.
+    fn emit_csg_cmp(&mut self, left: RelSet, right: RelSet) {
+        self.pair_cnt += 1;
+        let set = left | right;
+        let conds = self
+            .edges
+            .iter()
+            .filter(|edge| {
+                let inputs = edge.left | edge.right;
+                !is_subset(inputs, left) && !is_subset(inputs, right)
+            })
+            .filter(|edge| is_subset(edge.left | edge.right, set))
+            .map(|edge| edge.cond.clone())
+            .collect::<Vec<_>>();
+        let columns = self.columns(set);
+        for (left, right) in [(left, right), (right, left)] {
+            let join_columns = [self.columns(left), self.columns(right)].concat();
+            let position = |col: usize| join_columns.iter().position(|x| *x == col);
+            let cond = and_conjuncts(
+                conds
+                    .iter()
+                    .map(|cond| cond.rewrite_column_refs(&position).unwrap())
+                    .collect(),
+            );
+            let mapping = columns
+                .iter()
+                .map(|col| position(*col).unwrap())
+                .collect::<Vec<_>>();
+            let node = LogicalJoin::new(
+                PlanNode::from_group(RelNode::new_group(self.groups[&left]).into()),
+                PlanNode::from_group(RelNode::new_group(self.groups[&right]).into()),
+                cond,
+                JoinType::Inner,
+            )
+            .with_mapping(&mapping);
+            let group_id = match self.groups.get(&set) {
+                Some(group_id) => Some(*group_id),
+                None if set.count_ones() as usize == self.offsets.len() => self.root_group,
+                None => None,
+            };
+            let group_id = self.optimizer.step_add_rel(node.into_rel_node(), group_id);
+            self.groups.insert(set, group_id);
+        }
+    }
+}
+
+/// Adds the join orders of every tree of inner joins in `node` to the memo. Returns the number of
+/// pairs of join trees joined.
+pub(crate) fn enumerate_join_orders(
+    optimizer: &mut CascadesOptimizer<OptRelNodeTyp>,
+    node: OptRelNodeRef,
+    options: &JoinOrderOptions,
+) -> usize {
+    if !is_join_tree(&node) {
+        return node
+            .children
+            .iter()
+            .filter(|child| child.typ.is_plan_node())
+            .map(|child| enumerate_join_orders(optimizer, child.clone(), options))
+            .sum();
+    }
+
+    // The join tree is left to the transformation rules, but the join trees below its inputs may
+    // still be enumerated.
+    let mut tree_inputs = vec![];
+    join_tree_inputs(&node, &mut tree_inputs);
+    let greedy = tree_inputs.len() > options.greedy_threshold;
+    if tree_inputs.len() > MAX_RELATIONS || !(greedy || options.dphyp) {
+        return tree_inputs
+            .into_iter()
+            .map(|input| enumerate_join_orders(optimizer, input, options))
+            .sum();
+    }
+
+    let mut graph = JoinGraph {
+        optimizer,
+        options,
+        offsets: vec![],
+        widths: vec![],
+        edges: vec![],
+        groups: HashMap::new(),
+        root_group: None,
+        pair_cnt: 0,
+    };
+    let mut inputs = vec![];
+    let mut conds = vec![];
+    graph.collect(node.clone(), &mut inputs, &mut conds);
+
+    // Conjuncts on a single input are evaluated below the joins, and conjuncts without columns
+    // above them.
+    let mut input_conds = vec![vec![]; inputs.len()];
+    let mut root_conds = vec![];
+    for cond in conds {
+        let set = graph.inputs_of(&cond);
+        match set.count_ones() {
+            0 => {
+                let is_true = ConstantExpr::from_rel_node(cond.clone().into_rel_node())
+                    .is_some_and(|constant| matches!(constant.value(), Value::Bool(true)));
+                if !is_true {
+                    root_conds.push(cond);
+                }
+            }
+            1 => input_conds[set.trailing_zeros() as usize].push(cond),
+            _ => {
+                let (left, right) = BinOpExpr::from_rel_node(cond.clone().into_rel_node())
+                    .map(|op| {
+                        (
+                            graph.inputs_of(&op.left_child()),
+                            graph.inputs_of(&op.right_child()),
+                        )
+                    })
+                    .filter(|(left, right)| *left != 0 && *right != 0 && left & right == 0)
+                    .unwrap_or((lowest(set), set & !lowest(set)));
+                graph.edges.push(Edge { left, right, cond });
+            }
+        }
+    }
+    let mut row_cnts = Vec::with_capacity(inputs.len());
+    for (input, (group_id, conds)) in inputs.into_iter().zip(input_conds).enumerate() {
+        let row_cnt = options
+            .row_cnts
+            .get(&group_id)
+            .map_or(DEFAULT_ROW_CNT, |row_cnt| *row_cnt as f64);
+        let group_id = if conds.is_empty() {
+            row_cnts.push(row_cnt);
+            group_id
+        } else {
+            let offset = graph.offsets[input];
+            let cond = and_conjuncts(conds)
+                .rewrite_column_refs(&|col| Some(col - offset))
+                .unwrap();
+            let filter = LogicalFilter::new(
+                PlanNode::from_group(RelNode::new_group(group_id).into()),
+                cond,
+            );
+            let group_id = graph
+                .optimizer
+                .step_add_rel(filter.into_plan_node().into_rel_node(), None);
+            row_cnts.push(
+                options
+                    .row_cnts
+                    .get(&group_id)
+                    .map_or(row_cnt * DEFAULT_SELECTIVITY, |row_cnt| *row_cnt as f64),
+            );
+            group_id
+        };
+        graph.groups.insert(1 << input, group_id);
+    }
+
+    let root_group = graph.optimizer.step_add_rel(node, None);
+    if root_conds.is_empty() {
+        graph.root_group = Some(root_group);
+    }
+    if !greedy {
+        graph.solve();
+    } else if let Some(order) = graph.greedy_order(&row_cnts) {
+        graph.solve_linearized(&order);
+    }
+    let all = RelSet::MAX >> (MAX_RELATIONS - graph.offsets.len());
+    if root_conds.is_empty() {
+        return graph.pair_cnt;
+    }
+    if let Some(group_id) = graph.groups.get(&all) {
+        let filter = LogicalFilter::new(
+            PlanNode::from_group(RelNode::new_group(*group_id).into()),
+            and_conjuncts(root_conds),
+        );
+        graph
+            .optimizer
+            .step_add_rel(filter.into_plan_node().into_rel_node(), Some(root_group));
+    }
+    graph.pair_cnt
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::testing::{col, eq, new_optimizer, scan};
+
+    /// Builds a left-deep tree of inner joins of `n` single-column tables, where the join with
+    /// table `k` has the conditions `col(i) = col(k)` for every `i` in `edges(k)`.
+    fn join_tree(n: usize, edges: impl Fn(usize) -> Vec<usize>) -> OptRelNodeRef {
+        let mut plan = scan("t0", 1);
+        for k in 1..n {
+            let cond = and_conjuncts(edges(k).into_iter().map(|i| eq(col(i), col(k))).collect());
+            plan = LogicalJoin::new(plan, scan(&format!("t{}", k), 1), cond, JoinType::Inner)
+                .into_plan_node();
+        }
+        plan.into_rel_node()
+    }
+
+    fn chain(n: usize) -> OptRelNodeRef {
+        join_tree(n, |k| vec![k - 1])
+    }
+
+    fn star(n: usize) -> OptRelNodeRef {
+        join_tree(n, |_| vec![0])
+    }
+
+    fn clique(n: usize) -> OptRelNodeRef {
+        join_tree(n, |k| (0..k).collect())
+    }
+
+    fn options(dphyp: bool) -> JoinOrderOptions {
+        JoinOrderOptions {
+            dphyp,
+            greedy_threshold: MAX_RELATIONS,
+            row_cnts: HashMap::new(),
+        }
+    }
+
+    fn pair_cnt(plan: OptRelNodeRef) -> usize {
+        enumerate_join_orders(&mut new_optimizer(), plan, &options(true))
+    }
+
+    #[test]
+    fn chain_pair_cnt() {
+        for n in 2..=10 {
+            assert_eq!(pair_cnt(chain(n)), (n * n * n - n) / 6, "chain of {}", n);
+        }
+    }
+
+    #[test]
+    fn star_pair_cnt() {
+        for n in 2..=8 {
+            assert_eq!(pair_cnt(star(n)), (n - 1) << (n - 2), "star of {}", n);
+        }
+    }
+
+    #[test]
+    fn clique_pair_cnt() {
+        for n in 2..=6u32 {
+            let expected = (3usize.pow(n) - 2usize.pow(n + 1) + 1) / 2;
+            assert_eq!(pair_cnt(clique(n as usize)), expected, "clique of {}", n);
+        }
+    }
+
+    #[test]
+    fn disabled_enumeration_adds_nothing() {
+        let mut optimizer = new_optimizer();
+        assert_eq!(
+            enumerate_join_orders(&mut optimizer, chain(6), &options(false)),
+            0
+        );
+        assert!(optimizer.memo_memory_usage().is_empty());
+    }
+
+    #[test]
+    fn greedy_pair_cnt() {
+        // The linearized DP joins neighbouring ranges of the greedy order, which builds at least
+        // one tree of all inputs and a subset of the trees DPhyp builds.
+        let mut options = options(true);
+        options.greedy_threshold = 4;
+        let n = 12;
+        let pair_cnt = enumerate_join_orders(&mut new_optimizer(), chain(n), &options);
+        assert!(pair_cnt >= n - 1);
+        assert!(pair_cnt <= (n * n * n - n) / 6);
+    }
+
+    /// Builds the join graph of `n` single-column tables with the edges `col(i) = col(j)` for
+    /// every `(i, j)` in `edges`, with every table added to the memo.
+    fn graph<'a>(
+        optimizer: &'a mut CascadesOptimizer<OptRelNodeTyp>,
+        options: &'a JoinOrderOptions,
+        n: usize,
+        edges: &[(usize, usize)],
+    ) -> JoinGraph<'a> {
+        let groups = (0..n)
+            .map(|i| {
+                let table = scan(&format!("t{}", i), 1).into_rel_node();
+                (1 << i, optimizer.step_add_rel(table, None))
+            })
+            .collect();
+        JoinGraph {
+            optimizer,
+            options,
+            offsets: (0..n).collect(),
+            widths: vec![1; n],
+            edges: edges
+                .iter()
+                .map(|&(i, j)| Edge {
+                    left: 1 << i,
+                    right: 1 << j,
+                    cond: eq(col(i), col(j)),
+                })
+                .collect(),
+            groups,
+            root_group: None,
+            pair_cnt: 0,
+        }
+    }
+
+    /// The sets of inputs `graph` has join trees of, from the smallest.
+    fn joined_sets(graph: &JoinGraph) -> Vec<RelSet> {
+        graph
+            .groups
+            .keys()
+            .copied()
+            .filter(|set| set.count_ones() > 1)
+            .sorted_by_key(|set| (set.count_ones(), *set))
+            .collect()
+    }
+
+    #[test]
+    fn greedy_order_joins_the_smallest_results_first() {
+        // A fact table of a million rows joined to dimensions of 10, 1000 and 100 rows on their
+        // keys, where every join returns as many rows as its dimension.
+        let mut optimizer = new_optimizer();
+        let options = options(true);
+        let graph = graph(&mut optimizer, &options, 4, &[(0, 1), (0, 2), (0, 3)]);
+        let row_cnts = [1e6, 10.0, 1000.0, 100.0];
+        assert_eq!(graph.greedy_order(&row_cnts), Some(vec![0, 1, 3, 2]));
+    }
+
+    #[test]
+    fn greedy_order_needs_a_connected_graph() {
+        let mut optimizer = new_optimizer();
+        let options = options(true);
+        let graph = graph(&mut optimizer, &options, 3, &[(0, 1)]);
+        assert_eq!(graph.greedy_order(&[1.0, 1.0, 1.0]), None);
+    }
+
+    #[test]
+    fn linearized_dp_joins_neighbouring_ranges() {
+        // Only the left-deep tree of the greedy order of a star avoids cross products.
+        let mut optimizer = new_optimizer();
+        let options = options(true);
+        let mut star = graph(&mut optimizer, &options, 4, &[(0, 1), (0, 2), (0, 3)]);
+        star.solve_linearized(&[0, 1, 3, 2]);
+        assert_eq!(star.pair_cnt, 3);
+        assert_eq!(joined_sets(&star), vec![0b0011, 0b1011, 0b1111]);
+
+        // Every connected range of a chain in order is joined, as DPhyp does.
+        let mut optimizer = new_optimizer();
+        let mut chain = graph(&mut optimizer, &options, 4, &[(0, 1), (1, 2), (2, 3)]);
+        chain.solve_linearized(&[0, 1, 2, 3]);
+        assert_eq!(chain.pair_cnt, 10);
+        assert_eq!(
+            joined_sets(&chain),
+            vec![0b0011, 0b0110, 0b1100, 0b0111, 0b1110, 0b1111]
+        );
+    }
+
+    #[test]
+    fn enumerates_within_milliseconds() {
+        for n in 6..=12 {
+            for plan in [chain(n), star(n.min(10))] {
+                let start = Instant::now();
+                pair_cnt(plan);
+                assert!(
+                    start.elapsed() < Duration::from_millis(200),
+                    "{} tables took {:?}",
+                    n,
+                    start.elapsed()
+                );
+            }
+        }
+    }
+}
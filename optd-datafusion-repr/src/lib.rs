@@ -1,29 +1,118 @@
 #![allow(clippy::new_without_default)]
 
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use anyhow::Result;
 use cost::{AdaptiveCostModel, RuntimeAdaptionStorage};
+use itertools::Itertools;
+use join_enumeration::JoinOrderOptions;
 use optd_core::cascades::{CascadesOptimizer, GroupId, OptimizerProperties};
+use optd_core::heuristics::{ApplyOrder, HeuristicsOptimizer};
+use optd_core::optimizer::Optimizer;
+use optd_core::rules::Rule;
+use plan_cache::PlanCache;
 use plan_nodes::{OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode};
-use properties::schema::{Catalog, SchemaPropertyBuilder};
+use properties::{
+    distribution::DistributionPropertyBuilder,
+    equivalence::EquivalencePropertyBuilder,
+    keys::KeyPropertyBuilder,
+    ordering::OrderingPropertyBuilder,
+    schema::{Catalog, SchemaPropertyBuilder},
+};
 use rules::{
-    HashJoinRule, JoinAssocRule, JoinCommuteRule, PhysicalConversionRule, ProjectionPullUpJoin,
+    AggOnKeyRemovalRule, DistinctUnionRule, EagerAggregationRule, EmptyPropagationRule,
+    FilterPushDownScan, HashAggRule, HashJoinRule, JoinAssocReverseRule, JoinAssocRule,
+    JoinCommuteRule, JoinEliminationRule, LazyAggregationRule, OuterJoinAssocReverseRule,
+    OuterJoinAssocRule, OuterJoinCommuteRule, OuterJoinSimplifyRule, PartitionedAggRule,
+    PartitionedHashJoinRule, PhysicalConversionRule, ProjectionMergeRule, ProjectionPullUpJoin,
+    ProjectionPushDownAgg, ProjectionPushDownFilter, ProjectionPushDownJoin,
+    ProjectionPushDownScan, RuntimeFilterHashJoinRule, SemiJoinDistinctRule, SimplifyFilterRule,
+    SimplifyJoinRule, StreamAggRule, TwoPhaseAggRule,
 };
 
 pub use adaptive::PhysicalCollector;
 pub use optd_core::rel_node::Value;
+pub use parameters::bind_parameters;
+pub use plan_cache::PlanCacheStats;
+pub use spool::PhysicalSpool;
 
 mod adaptive;
+mod column_ids;
 pub mod cost;
+mod expr_simplifier;
+mod join_enumeration;
+mod parameters;
+mod plan_cache;
 pub mod plan_nodes;
 pub mod properties;
 pub mod rules;
+mod spool;
+#[cfg(test)]
+mod testing;
+pub mod typing;
 
 pub struct DatafusionOptimizer {
     optimizer: CascadesOptimizer<OptRelNodeTyp>,
+    /// Rewrites the plan before it is added to the memo, e.g., to simplify expressions.
+    heuristic_optimizer: HeuristicsOptimizer<OptRelNodeTyp>,
     pub runtime_statistics: RuntimeAdaptionStorage,
+    /// The number of partitions parallel operators run on, which is shared with the cost model.
+    parallelism: Arc<AtomicUsize>,
     enable_adaptive: bool,
+    enable_join_enumeration: bool,
+    greedy_join_threshold: usize,
+    /// The rules deriving join orders, which are replaced by join enumeration.
+    join_order_rules: Vec<usize>,
+    catalog: Arc<dyn Catalog>,
+    /// The cached plans, if plan caching is enabled.
+    plan_cache: Option<PlanCache>,
+}
+
+/// Join trees with more inputs are ordered greedily by default.
+const DEFAULT_GREEDY_JOIN_THRESHOLD: usize = 14;
+
+/// The number of most recently optimized plans kept in the memo in adaptive mode.
+const DEFAULT_GC_RETAINED_ROOTS: usize = 16;
+
+/// The number of expressions above which the memo drops the least recently optimized plans in
+/// adaptive mode.
+const DEFAULT_MEMO_CAPACITY: usize = 1 << 16;
+
+/// Gets the ids of the rules deriving join orders.
+fn join_order_rules(
+    rules: &[Arc<dyn Rule<OptRelNodeTyp, CascadesOptimizer<OptRelNodeTyp>>>],
+) -> Vec<usize> {
+    rules
+        .iter()
+        .positions(|rule| {
+            matches!(
+                rule.name(),
+                "join_commute_rule"
+                    | "join_assoc_rule"
+                    | "join_assoc_reverse_rule"
+                    | "projection_pull_up_join"
+            )
+        })
+        .collect()
+}
+
+/// Builds the optimizer of the heuristic stage, which simplifies the expressions of the plan and
+/// removes the plan nodes producing no rows.
+fn heuristic_optimizer(catalog: Arc<dyn Catalog>) -> HeuristicsOptimizer<OptRelNodeTyp> {
+    let mut rules: Vec<Arc<dyn Rule<OptRelNodeTyp, HeuristicsOptimizer<OptRelNodeTyp>>>> =
+        vec![Arc::new(SimplifyFilterRule::new())];
+    rules.extend(SimplifyJoinRule::all());
+    rules.extend(EmptyPropagationRule::all());
+    rules.push(Arc::new(DistinctUnionRule::new()));
+    HeuristicsOptimizer::new_with_properties(
+        rules,
+        ApplyOrder::BottomUp,
+        vec![
+            Box::new(SchemaPropertyBuilder::new(catalog.clone())),
+            Box::new(KeyPropertyBuilder::new(catalog)),
+        ],
+    )
 }
 
 impl DatafusionOptimizer {
@@ -31,59 +120,200 @@ impl DatafusionOptimizer {
         self.enable_adaptive = enable;
     }
 
+    /// Enumerates the join orders of every tree of inner joins with DPhyp instead of exploring
+    /// them with the join commute and associate rules. Join trees that need cross products keep
+    /// their original order, and trees with more inputs than the greedy join threshold are
+    /// ordered greedily.
+    pub fn enable_join_enumeration(&mut self, enable: bool) {
+        self.invalidate_plan_cache();
+        self.enable_join_enumeration = enable;
+        for rule_id in &self.join_order_rules {
+            if enable {
+                self.optimizer.disable_rule(*rule_id);
+            } else {
+                self.optimizer.enable_rule(*rule_id);
+            }
+        }
+    }
+
+    /// Sets the number of inputs above which the join order of a tree of inner joins is chosen
+    /// greedily, and the memo is seeded with the joins of neighbouring inputs in that order.
+    pub fn set_greedy_join_threshold(&mut self, threshold: usize) {
+        self.invalidate_plan_cache();
+        self.greedy_join_threshold = threshold;
+    }
+
+    /// Sets the number of partitions parallel operators run on, e.g., the target partitions of the
+    /// execution engine.
+    pub fn set_parallelism(&mut self, parallelism: usize) {
+        if self.parallelism.swap(parallelism, Ordering::Relaxed) != parallelism {
+            self.invalidate_plan_cache();
+        }
+    }
+
     pub fn optd_optimizer(&self) -> &CascadesOptimizer<OptRelNodeTyp> {
         &self.optimizer
     }
 
+    /// The cached plans are dropped, as the rules or settings of the optimizer may be changed.
     pub fn optd_optimizer_mut(&mut self) -> &mut CascadesOptimizer<OptRelNodeTyp> {
+        self.invalidate_plan_cache();
         &mut self.optimizer
     }
 
+    /// Caches up to `capacity` optimized plans by the shape of the plans they were optimized
+    /// from, so that a plan differing from a cached one only by its constants is not optimized
+    /// again. Plans are not cached in adaptive mode, which optimizes every plan with the row
+    /// counts observed in the last runs.
+    pub fn enable_plan_cache(&mut self, capacity: usize) {
+        self.plan_cache = Some(PlanCache::new(capacity));
+    }
+
+    pub fn disable_plan_cache(&mut self) {
+        self.plan_cache = None;
+    }
+
+    /// Drops the cached plans, e.g., after the data of a table changed.
+    pub fn invalidate_plan_cache(&mut self) {
+        if let Some(plan_cache) = &mut self.plan_cache {
+            plan_cache.invalidate();
+        }
+    }
+
+    pub fn plan_cache_stats(&self) -> Option<PlanCacheStats> {
+        self.plan_cache.as_ref().map(PlanCache::stats)
+    }
+
+    /// Bounds the memo kept across optimizations in adaptive mode: the groups unreachable from
+    /// the last `retained_plans` optimized plans are dropped, as are the least recently optimized
+    /// plans while the memo holds more than `capacity` expressions.
+    pub fn set_memo_capacity(&mut self, retained_plans: Option<usize>, capacity: Option<usize>) {
+        self.optimizer.prop.gc_retained_roots = retained_plans;
+        self.optimizer.prop.memo_capacity = capacity;
+    }
+
     /// Create an optimizer with default settings: adaptive + partial explore.
     pub fn new_physical(catalog: Box<dyn Catalog>) -> Self {
+        let catalog: Arc<dyn Catalog> = catalog.into();
         let mut rules = PhysicalConversionRule::all_conversions();
         rules.push(Arc::new(HashJoinRule::new()));
+        rules.push(Arc::new(HashAggRule::new()));
+        rules.push(Arc::new(StreamAggRule::new()));
+        rules.push(Arc::new(TwoPhaseAggRule::new()));
+        rules.push(Arc::new(PartitionedHashJoinRule::new()));
+        rules.push(Arc::new(RuntimeFilterHashJoinRule::new()));
+        rules.push(Arc::new(PartitionedAggRule::new()));
         rules.push(Arc::new(JoinCommuteRule::new()));
         rules.push(Arc::new(JoinAssocRule::new()));
+        rules.push(Arc::new(JoinAssocReverseRule::new()));
         rules.push(Arc::new(ProjectionPullUpJoin::new()));
+        rules.extend(OuterJoinSimplifyRule::all());
+        rules.extend(OuterJoinCommuteRule::all());
+        rules.extend(OuterJoinAssocRule::all());
+        rules.extend(OuterJoinAssocReverseRule::all());
+        rules.push(Arc::new(ProjectionMergeRule::new()));
+        rules.push(Arc::new(ProjectionPushDownScan::new()));
+        rules.push(Arc::new(ProjectionPushDownFilter::new()));
+        rules.push(Arc::new(ProjectionPushDownJoin::new()));
+        rules.push(Arc::new(ProjectionPushDownAgg::new()));
+        rules.push(Arc::new(FilterPushDownScan::new(catalog.clone())));
+        rules.push(Arc::new(EagerAggregationRule::new()));
+        rules.push(Arc::new(LazyAggregationRule::new()));
+        rules.push(Arc::new(JoinEliminationRule::new()));
+        rules.push(Arc::new(AggOnKeyRemovalRule::new()));
+        rules.push(Arc::new(SemiJoinDistinctRule::new()));
         let cost_model = AdaptiveCostModel::new(50);
+        let join_order_rules = join_order_rules(&rules);
         Self {
             runtime_statistics: cost_model.get_runtime_map(),
+            parallelism: cost_model.get_parallelism(),
             optimizer: CascadesOptimizer::new_with_prop(
                 rules,
                 Box::new(cost_model),
-                vec![Box::new(SchemaPropertyBuilder::new(catalog))],
+                vec![
+                    Box::new(SchemaPropertyBuilder::new(catalog.clone())),
+                    Box::new(KeyPropertyBuilder::new(catalog.clone())),
+                    Box::new(DistributionPropertyBuilder::new()),
+                    Box::new(EquivalencePropertyBuilder::new()),
+                    Box::new(OrderingPropertyBuilder::new()),
+                ],
                 OptimizerProperties {
                     partial_explore_iter: Some(1 << 20),
                     partial_explore_space: Some(1 << 10),
+                    gc_retained_roots: Some(DEFAULT_GC_RETAINED_ROOTS),
+                    memo_capacity: Some(DEFAULT_MEMO_CAPACITY),
                 },
             ),
+            heuristic_optimizer: heuristic_optimizer(catalog.clone()),
             enable_adaptive: true,
+            enable_join_enumeration: false,
+            greedy_join_threshold: DEFAULT_GREEDY_JOIN_THRESHOLD,
+            join_order_rules,
+            catalog,
+            plan_cache: None,
         }
     }
 
     /// The optimizer settings for three-join demo as a perfect optimizer.
     pub fn new_alternative_physical_for_demo(catalog: Box<dyn Catalog>) -> Self {
+        let catalog: Arc<dyn Catalog> = catalog.into();
         let mut rules = PhysicalConversionRule::all_conversions();
         rules.push(Arc::new(HashJoinRule::new()));
+        rules.push(Arc::new(HashAggRule::new()));
         rules.insert(0, Arc::new(JoinCommuteRule::new()));
         rules.insert(1, Arc::new(JoinAssocRule::new()));
         rules.insert(2, Arc::new(ProjectionPullUpJoin::new()));
+        let join_order_rules = join_order_rules(&rules);
         let cost_model = AdaptiveCostModel::new(1000); // very large decay
         let runtime_statistics = cost_model.get_runtime_map();
+        let parallelism = cost_model.get_parallelism();
         let optimizer = CascadesOptimizer::new(
             rules,
             Box::new(cost_model),
-            vec![Box::new(SchemaPropertyBuilder::new(catalog))],
+            vec![
+                Box::new(SchemaPropertyBuilder::new(catalog.clone())),
+                Box::new(KeyPropertyBuilder::new(catalog.clone())),
+                Box::new(DistributionPropertyBuilder::new()),
+                Box::new(EquivalencePropertyBuilder::new()),
+                Box::new(OrderingPropertyBuilder::new()),
+            ],
         );
         Self {
             runtime_statistics,
+            parallelism,
             optimizer,
+            heuristic_optimizer: heuristic_optimizer(catalog.clone()),
             enable_adaptive: true,
+            enable_join_enumeration: false,
+            greedy_join_threshold: DEFAULT_GREEDY_JOIN_THRESHOLD,
+            join_order_rules,
+            catalog,
+            plan_cache: None,
         }
     }
 
+    /// Optimizes a plan, which may refer to columns by id. See [`plan_nodes::ColumnIdExpr`].
+    ///
+    /// If the plan cache is enabled, a cached plan may be returned together with the group it was
+    /// optimized in, which the memo may no longer hold.
     pub fn optimize(&mut self, root_rel: OptRelNodeRef) -> Result<(GroupId, OptRelNodeRef)> {
+        self.optimize_inner(root_rel, true)
+    }
+
+    /// Optimizes a plan without looking up the plan cache, so that the group of the plan can be
+    /// inspected in the memo.
+    pub fn optimize_uncached(
+        &mut self,
+        root_rel: OptRelNodeRef,
+    ) -> Result<(GroupId, OptRelNodeRef)> {
+        self.optimize_inner(root_rel, false)
+    }
+
+    fn optimize_inner(
+        &mut self,
+        root_rel: OptRelNodeRef,
+        use_plan_cache: bool,
+    ) -> Result<(GroupId, OptRelNodeRef)> {
         if self.enable_adaptive {
             self.runtime_statistics.lock().unwrap().iter_cnt += 1;
             self.optimizer.step_clear_winner();
@@ -91,13 +321,95 @@ impl DatafusionOptimizer {
             self.optimizer.step_clear();
         }
 
+        let root_rel = self.simplify(root_rel)?;
+
+        // Adaptive mode bypasses the plan cache: every run may have observed other row counts,
+        // with which the plan is optimized again.
+        if self.enable_adaptive {
+            let plan = self.optimize_in_memo(root_rel)?;
+            self.collect_memo_garbage();
+            return Ok(plan);
+        }
+
+        // The plans optimized from the same simplified plan up to its constants are cached. The
+        // plan is optimized with its constants as parameters, which a cached plan binds to the
+        // constants of each plan it is used for.
+        if !use_plan_cache || self.plan_cache.is_none() {
+            return self.optimize_in_memo(root_rel);
+        }
+        let plan_cache = self.plan_cache.as_mut().unwrap();
+        plan_cache.check_catalog_version(self.catalog.version());
+        let (fingerprint, root_rel) = plan_cache::fingerprint(&root_rel);
+        if let Some(plan) = plan_cache.get(&fingerprint) {
+            return Ok(plan);
+        }
+        let (group_id, optimized_rel) = self.optimize_in_memo(root_rel)?;
+        let plan_cache = self.plan_cache.as_mut().unwrap();
+        Ok((
+            group_id,
+            plan_cache.insert(fingerprint, group_id, optimized_rel),
+        ))
+    }
+
+    /// Resolves the column ids of a plan and simplifies it in the heuristic stage.
+    fn simplify(&mut self, root_rel: OptRelNodeRef) -> Result<OptRelNodeRef> {
+        let root_rel = if column_ids::has_column_ids(&root_rel) {
+            let builder = SchemaPropertyBuilder::new(self.catalog.clone());
+            column_ids::resolve_column_ids(&builder, &root_rel)?.0
+        } else {
+            root_rel
+        };
+        self.heuristic_optimizer.optimize(root_rel)
+    }
+
+    /// Checks whether binding `values` to the parameters of a plan lets the plan be simplified
+    /// further than with the parameters unknown, e.g., when a predicate becomes false. The plan
+    /// optimized once for all values may then be far from the best plan for these values, and is
+    /// worth optimizing again with the values bound. See [`bind_parameters`].
+    pub fn is_parameter_sensitive(
+        &mut self,
+        root_rel: OptRelNodeRef,
+        values: &[Value],
+    ) -> Result<bool> {
+        let bound = self.simplify(bind_parameters(&root_rel, values)?)?;
+        let prepared = self.simplify(root_rel)?;
+        Ok(!parameters::is_bound_plan(&prepared, &bound))
+    }
+
+    /// Optimizes a simplified plan in the memo.
+    fn optimize_in_memo(&mut self, root_rel: OptRelNodeRef) -> Result<(GroupId, OptRelNodeRef)> {
+        let options = JoinOrderOptions {
+            dphyp: self.enable_join_enumeration,
+            greedy_threshold: self.greedy_join_threshold,
+            row_cnts: self
+                .runtime_statistics
+                .lock()
+                .unwrap()
+                .history
+                .iter()
+                .map(|(group_id, (row_cnt, _))| (*group_id, *row_cnt))
+                .collect(),
+        };
+        join_enumeration::enumerate_join_orders(&mut self.optimizer, root_rel.clone(), &options);
         let group_id = self.optimizer.step_optimize_rel(root_rel)?;
 
+        // A group referenced by several parents, e.g., the plan of a common table expression
+        // used several times, is executed once per reference unless its output is spooled.
+        let ref_counts = self.optimizer.step_get_group_ref_counts(group_id)?;
+        let spooled_groups = spool::groups_to_spool(&self.optimizer, &ref_counts);
+
         let optimized_rel =
             self.optimizer
-                .step_get_optimize_rel(group_id, |rel_node, group_id| {
+                .step_get_optimize_rel(group_id, |mut rel_node, group_id| {
                     if rel_node.typ.is_plan_node() && self.enable_adaptive {
-                        return PhysicalCollector::new(
+                        rel_node = PhysicalCollector::new(
+                            PlanNode::from_rel_node(rel_node).unwrap(),
+                            group_id,
+                        )
+                        .into_rel_node();
+                    }
+                    if rel_node.typ.is_plan_node() && spooled_groups.contains(&group_id) {
+                        rel_node = PhysicalSpool::new(
                             PlanNode::from_rel_node(rel_node).unwrap(),
                             group_id,
                         )
@@ -109,7 +421,86 @@ impl DatafusionOptimizer {
         Ok((group_id, optimized_rel))
     }
 
+    /// Drops the groups of the memo that are no longer needed in adaptive mode, where the memo is
+    /// kept across optimizations, together with the row counts observed for them.
+    fn collect_memo_garbage(&mut self) {
+        if self.optimizer.step_gc() > 0 {
+            let optimizer = &self.optimizer;
+            self.runtime_statistics
+                .lock()
+                .unwrap()
+                .history
+                .retain(|group_id, _| optimizer.has_group(*group_id));
+        }
+    }
+
+    /// Checks whether the memo still holds the groups the collectors of an optimized plan report
+    /// row counts for. A plan kept across optimizations, e.g., of a prepared statement, needs to be
+    /// optimized again once the garbage collection of the memo drops its groups.
+    pub fn has_collector_groups(&self, rel_node: &OptRelNodeRef) -> bool {
+        if let OptRelNodeTyp::PhysicalCollector(group_id) = rel_node.typ {
+            if !self.optimizer.has_group(group_id) {
+                return false;
+            }
+        }
+        rel_node
+            .children
+            .iter()
+            .all(|child| self.has_collector_groups(child))
+    }
+
+    /// Explains a plan with its column references printed as the names of the columns.
+    pub fn explain_with_names(&self, rel_node: OptRelNodeRef) -> String {
+        let builder = SchemaPropertyBuilder::new(self.catalog.clone());
+        PlanNode::from_rel_node(rel_node)
+            .unwrap()
+            .explain_with_names_to_string(&builder)
+    }
+
     pub fn dump(&self, group_id: Option<GroupId>) {
         self.optimizer.dump(group_id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{scan, TestCatalog};
+
+    #[test]
+    fn collector_groups_are_dropped_with_their_plans() {
+        let mut optimizer = DatafusionOptimizer::new_physical(Box::new(TestCatalog));
+        optimizer.set_memo_capacity(Some(1), None);
+        let (_, first) = optimizer.optimize(scan("t1", 3).into_rel_node()).unwrap();
+        assert!(optimizer.has_collector_groups(&first));
+        let (_, second) = optimizer.optimize(scan("t2", 3).into_rel_node()).unwrap();
+        assert!(optimizer.has_collector_groups(&second));
+        assert!(!optimizer.has_collector_groups(&first));
+    }
+
+    #[test]
+    fn plans_are_cached_outside_adaptive_mode() {
+        let mut optimizer = DatafusionOptimizer::new_physical(Box::new(TestCatalog));
+        optimizer.enable_adaptive(false);
+        optimizer.enable_plan_cache(1);
+        optimizer.optimize(scan("t1", 3).into_rel_node()).unwrap();
+        optimizer.optimize(scan("t1", 3).into_rel_node()).unwrap();
+        let stats = optimizer.plan_cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+    }
+
+    #[test]
+    fn adaptive_mode_bypasses_the_plan_cache() {
+        let mut optimizer = DatafusionOptimizer::new_physical(Box::new(TestCatalog));
+        optimizer.enable_plan_cache(1);
+        let (_, first) = optimizer.optimize(scan("t1", 3).into_rel_node()).unwrap();
+        let (_, second) = optimizer.optimize(scan("t1", 3).into_rel_node()).unwrap();
+        assert_eq!(
+            optimizer.plan_cache_stats(),
+            Some(PlanCacheStats::default())
+        );
+        // Both plans are collected, so that the row counts of each run reach the optimizer.
+        assert!(optimizer.has_collector_groups(&first));
+        assert!(optimizer.has_collector_groups(&second));
+    }
+}
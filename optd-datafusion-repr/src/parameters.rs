@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use optd_core::rel_node::{RelNode, Value};
+
+use crate::plan_nodes::{OptRelNodeRef, OptRelNodeTyp};
+
+/// Replaces the parameters of a plan by constants, where `values[i]` is the value of parameter
+/// `i + 1` in the representation of the type of the parameter.
+pub fn bind_parameters(rel_node: &OptRelNodeRef, values: &[Value]) -> Result<OptRelNodeRef> {
+    if let OptRelNodeTyp::Parameter(typ) = &rel_node.typ {
+        let index = rel_node.data.as_ref().unwrap().as_u64() as usize;
+        let Some(value) = index.checked_sub(1).and_then(|idx| values.get(idx)) else {
+            bail!("parameter ${} is not bound", index)
+        };
+        return Ok(Arc::new(RelNode {
+            typ: OptRelNodeTyp::Constant(typ.clone()),
+            children: vec![],
+            data: Some(value.clone()),
+        }));
+    }
+    if rel_node.children.is_empty() {
+        return Ok(rel_node.clone());
+    }
+    Ok(Arc::new(RelNode {
+        typ: rel_node.typ.clone(),
+        children: rel_node
+            .children
+            .iter()
+            .map(|child| bind_parameters(child, values))
+            .collect::<Result<_>>()?,
+        data: rel_node.data.clone(),
+    }))
+}
+
+/// Checks whether `bound` is `prepared` with constants in place of its parameters, i.e., the
+/// bound values did not let the plan be simplified any further.
+pub fn is_bound_plan(prepared: &OptRelNodeRef, bound: &OptRelNodeRef) -> bool {
+    if let OptRelNodeTyp::Parameter(typ) = &prepared.typ {
+        return bound.typ == OptRelNodeTyp::Constant(typ.clone());
+    }
+    prepared.typ == bound.typ
+        && prepared.data == bound.data
+        && prepared.children.len() == bound.children.len()
+        && prepared
+            .children
+            .iter()
+            .zip(&bound.children)
+            .all(|(prepared, bound)| is_bound_plan(prepared, bound))
+}
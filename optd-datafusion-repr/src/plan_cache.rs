@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use optd_core::cascades::GroupId;
+use optd_core::rel_node::{RelNode, Value};
+
+use crate::plan_nodes::{
+    ConstantExpr, ConstantType, OptRelNode, OptRelNodeRef, OptRelNodeTyp, ParameterExpr,
+};
+
+/// The counters of a plan cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlanCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// The plans dropped to make room for new plans.
+    pub evictions: usize,
+    /// The times the whole cache was dropped, e.g., because the catalog changed.
+    pub invalidations: usize,
+}
+
+/// A constant of a plan that is replaced by a parameter in its fingerprint.
+type Parameter = (ConstantType, Value);
+
+/// A plan with its constants replaced by parameters, together with the values of the parameters.
+pub struct Fingerprint {
+    shape: String,
+    parameters: Vec<Parameter>,
+    /// The highest index of the parameters the plan already has, e.g., as a prepared statement.
+    /// The parameters of the fingerprint are numbered after them.
+    offset: usize,
+}
+
+/// Fingerprints a plan, and replaces the constants that are parameters of the fingerprint by
+/// [`crate::plan_nodes::ParameterExpr`]s in the plan. The plan is optimized with the parameters,
+/// so that the optimized plan keeps track of where each of them is bound.
+///
+/// Constants other than nulls, booleans and unsigned integers become parameters: nulls and
+/// booleans are folded away by expression simplification, and unsigned integers are the row
+/// counts of limits, which rules and the cost model read.
+pub fn fingerprint(rel_node: &OptRelNodeRef) -> (Fingerprint, OptRelNodeRef) {
+    let mut fingerprint = Fingerprint {
+        shape: String::new(),
+        parameters: vec![],
+        offset: max_parameter_index(rel_node),
+    };
+    let rel_node = fingerprint.add(rel_node);
+    (fingerprint, rel_node)
+}
+
+fn max_parameter_index(rel_node: &OptRelNodeRef) -> usize {
+    match (&rel_node.typ, &rel_node.data) {
+        (OptRelNodeTyp::Parameter(_), Some(index)) => index.as_u64() as usize,
+        _ => rel_node
+            .children
+            .iter()
+            .map(max_parameter_index)
+            .max()
+            .unwrap_or(0),
+    }
+}
+
+/// Gets the constant of `rel_node` that is a parameter in fingerprints.
+fn parameter(rel_node: &RelNode<OptRelNodeTyp>) -> Option<Parameter> {
+    match (&rel_node.typ, &rel_node.data) {
+        (OptRelNodeTyp::Constant(ConstantType::Bool), _) => None,
+        (_, Some(Value::Null | Value::UInt(_))) => None,
+        (OptRelNodeTyp::Constant(typ), Some(value)) => Some((typ.clone(), value.clone())),
+        _ => None,
+    }
+}
+
+impl Fingerprint {
+    fn add(&mut self, rel_node: &OptRelNodeRef) -> OptRelNodeRef {
+        if let Some(parameter) = parameter(rel_node) {
+            write!(self.shape, "({:?} ?)", parameter.0).unwrap();
+            let index = self.offset + self.parameters.len() + 1;
+            let typ = parameter.0.clone();
+            self.parameters.push(parameter);
+            return ParameterExpr::new(index, typ).into_rel_node();
+        }
+        write!(self.shape, "({:?} {:?}", rel_node.typ, rel_node.data).unwrap();
+        let mut children = Vec::with_capacity(rel_node.children.len());
+        for child in &rel_node.children {
+            self.shape.push(' ');
+            children.push(self.add(child));
+        }
+        self.shape.push(')');
+        if children.is_empty() {
+            return rel_node.clone();
+        }
+        Arc::new(RelNode {
+            typ: rel_node.typ.clone(),
+            children,
+            data: rel_node.data.clone(),
+        })
+    }
+
+    /// Replaces the parameters of the fingerprint in `rel_node` by their values.
+    fn bind(&self, rel_node: &OptRelNodeRef) -> OptRelNodeRef {
+        if let OptRelNodeTyp::Parameter(typ) = &rel_node.typ {
+            let index = rel_node.data.as_ref().unwrap().as_u64() as usize;
+            if index > self.offset {
+                return ConstantExpr::new_with_type(
+                    self.parameters[index - self.offset - 1].1.clone(),
+                    typ.clone(),
+                )
+                .into_rel_node();
+            }
+        }
+        if rel_node.children.is_empty() {
+            return rel_node.clone();
+        }
+        Arc::new(RelNode {
+            typ: rel_node.typ.clone(),
+            children: rel_node
+                .children
+                .iter()
+                .map(|child| self.bind(child))
+                .collect(),
+            data: rel_node.data.clone(),
+        })
+    }
+}
+
+struct CachedPlan {
+    group_id: GroupId,
+    /// The optimized plan with the parameters of its fingerprint unbound.
+    optimized_rel: OptRelNodeRef,
+    last_used: usize,
+}
+
+/// Caches optimized plans by the fingerprints of the plans they were optimized from, so that a
+/// plan differing from a cached one only by its constants is not optimized again. The least
+/// recently used plan is evicted once the cache is full.
+pub struct PlanCache {
+    plans: HashMap<String, CachedPlan>,
+    capacity: usize,
+    /// The version of the catalog the cached plans were optimized with.
+    catalog_version: u64,
+    clock: usize,
+    stats: PlanCacheStats,
+}
+
+impl PlanCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            plans: HashMap::new(),
+            capacity,
+            catalog_version: 0,
+            clock: 0,
+            stats: PlanCacheStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> PlanCacheStats {
+        self.stats
+    }
+
+    /// Drops all cached plans.
+    pub fn invalidate(&mut self) {
+        if !self.plans.is_empty() {
+            self.plans.clear();
+            self.stats.invalidations += 1;
+        }
+    }
+
+    /// Drops all cached plans if they were optimized with another version of the catalog.
+    pub fn check_catalog_version(&mut self, catalog_version: u64) {
+        if self.catalog_version != catalog_version {
+            self.invalidate();
+            self.catalog_version = catalog_version;
+        }
+    }
+
+    /// Looks up the plan of `fingerprint`, with the parameters bound to the values of
+    /// `fingerprint`.
+    pub fn get(&mut self, fingerprint: &Fingerprint) -> Option<(GroupId, OptRelNodeRef)> {
+        self.clock += 1;
+        let Some(plan) = self.plans.get_mut(&fingerprint.shape) else {
+            self.stats.misses += 1;
+            return None;
+        };
+        plan.last_used = self.clock;
+        self.stats.hits += 1;
+        Some((plan.group_id, fingerprint.bind(&plan.optimized_rel)))
+    }
+
+    /// Caches `optimized_rel`, which is optimized from the plan `fingerprint` parameterized, and
+    /// returns it with the parameters bound.
+    pub fn insert(
+        &mut self,
+        fingerprint: Fingerprint,
+        group_id: GroupId,
+        optimized_rel: OptRelNodeRef,
+    ) -> OptRelNodeRef {
+        let bound = fingerprint.bind(&optimized_rel);
+        if self.capacity == 0 {
+            return bound;
+        }
+        if self.plans.len() >= self.capacity && !self.plans.contains_key(&fingerprint.shape) {
+            let lru = self
+                .plans
+                .iter()
+                .min_by_key(|(_, plan)| plan.last_used)
+                .map(|(shape, _)| shape.clone())
+                .unwrap();
+            self.plans.remove(&lru);
+            self.stats.evictions += 1;
+        }
+        self.plans.insert(
+            fingerprint.shape,
+            CachedPlan {
+                group_id,
+                optimized_rel,
+                last_used: self.clock,
+            },
+        );
+        bound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan_nodes::{Expr, ExprList, LogOpExpr, LogOpType, LogicalFilter, PlanNode};
+    use crate::testing::{col, eq, scan};
+
+    fn filter(cond: Expr) -> OptRelNodeRef {
+        LogicalFilter::new(scan("t", 3), cond).into_rel_node()
+    }
+
+    fn and(left: Expr, right: Expr) -> Expr {
+        LogOpExpr::new(LogOpType::And, ExprList::new(vec![left, right])).into_expr()
+    }
+
+    fn int(value: i64) -> Expr {
+        ConstantExpr::int(value).into_expr()
+    }
+
+    fn param(index: usize) -> Expr {
+        ParameterExpr::new(index, ConstantType::Int64).into_expr()
+    }
+
+    #[test]
+    fn constants_become_parameters() {
+        let (cached, parameterized) =
+            fingerprint(&filter(and(eq(col(0), int(5)), eq(col(1), int(7)))));
+        assert_eq!(
+            parameterized.to_string(),
+            filter(and(eq(col(0), param(1)), eq(col(1), param(2)))).to_string()
+        );
+        let (other, _) = fingerprint(&filter(and(eq(col(0), int(6)), eq(col(1), int(6)))));
+        assert_eq!(cached.shape, other.shape);
+        // Unsigned integers are kept in the shape.
+        let uint = |value| ConstantExpr::uint(value).into_expr();
+        let (limit, _) = fingerprint(&filter(eq(col(0), uint(10))));
+        let (other, _) = fingerprint(&filter(eq(col(0), uint(20))));
+        assert_ne!(limit.shape, other.shape);
+    }
+
+    #[test]
+    fn parameters_are_bound_by_position() {
+        let mut cache = PlanCache::new(1);
+        let (cached, parameterized) =
+            fingerprint(&filter(and(eq(col(0), int(5)), eq(col(1), int(7)))));
+        // The optimizer introduces another constant equal to the first parameter, which is not a
+        // parameter of the optimized plan.
+        let optimized = LogicalFilter::new(
+            PlanNode::from_rel_node(parameterized).unwrap(),
+            eq(col(2), int(5)),
+        )
+        .into_rel_node();
+        let bound = cache.insert(cached, GroupId::default(), optimized);
+        let expected = |first, second| {
+            LogicalFilter::new(
+                PlanNode::from_rel_node(filter(and(
+                    eq(col(0), int(first)),
+                    eq(col(1), int(second)),
+                )))
+                .unwrap(),
+                eq(col(2), int(5)),
+            )
+            .into_rel_node()
+            .to_string()
+        };
+        assert_eq!(bound.to_string(), expected(5, 7));
+
+        let (other, _) = fingerprint(&filter(and(eq(col(0), int(6)), eq(col(1), int(6)))));
+        let (_, bound) = cache.get(&other).unwrap();
+        assert_eq!(bound.to_string(), expected(6, 6));
+        assert_eq!(cache.stats().hits, 1);
+    }
+
+    #[test]
+    fn parameters_of_the_plan_are_kept() {
+        let mut cache = PlanCache::new(1);
+        let (cached, parameterized) =
+            fingerprint(&filter(and(eq(col(0), param(1)), eq(col(1), int(7)))));
+        assert_eq!(
+            parameterized.to_string(),
+            filter(and(eq(col(0), param(1)), eq(col(1), param(2)))).to_string()
+        );
+        let bound = cache.insert(cached, GroupId::default(), parameterized);
+        assert_eq!(
+            bound.to_string(),
+            filter(and(eq(col(0), param(1)), eq(col(1), int(7)))).to_string()
+        );
+    }
+
+    #[test]
+    fn catalog_changes_invalidate_plans() {
+        let mut cache = PlanCache::new(1);
+        let plan = filter(eq(col(0), int(5)));
+        cache.check_catalog_version(1);
+        let (cached, parameterized) = fingerprint(&plan);
+        cache.insert(cached, GroupId::default(), parameterized);
+        cache.check_catalog_version(1);
+        assert!(cache.get(&fingerprint_of(&plan)).is_some());
+        cache.check_catalog_version(2);
+        assert!(cache.get(&fingerprint_of(&plan)).is_none());
+        assert_eq!(cache.stats().invalidations, 1);
+    }
+
+    fn fingerprint_of(plan: &OptRelNodeRef) -> Fingerprint {
+        fingerprint(plan).0
+    }
+}
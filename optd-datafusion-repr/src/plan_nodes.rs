@@ -2,6 +2,8 @@
 
 mod agg;
 mod apply;
+mod empty;
+mod exchange;
 mod expr;
 mod filter;
 mod join;
@@ -9,30 +11,42 @@ pub(super) mod macros;
 mod projection;
 mod scan;
 mod sort;
+mod union;
+mod values;
 
-use std::sync::Arc;
+use std::{collections::BTreeSet, sync::Arc};
 
 use optd_core::{
     cascades::{CascadesOptimizer, GroupId},
-    rel_node::{RelNode, RelNodeRef, RelNodeTyp},
+    rel_node::{RelNode, RelNodeRef, RelNodeTyp, Value},
 };
 
-pub use agg::{LogicalAgg, PhysicalAgg};
+pub use agg::{LogicalAgg, PhysicalAgg, PhysicalStreamAgg, PhysicalTwoPhaseAgg};
 pub use apply::{ApplyType, LogicalApply};
+pub use empty::{LogicalEmpty, PhysicalEmpty};
+pub use exchange::{ExchangeType, PhysicalExchange};
+pub(crate) use expr::list_column_ids;
 pub use expr::{
-    BinOpExpr, BinOpType, ColumnRefExpr, ConstantExpr, ConstantType, ExprList, FuncExpr, FuncType,
-    LogOpExpr, LogOpType, SortOrderExpr, SortOrderType, UnOpExpr, UnOpType,
+    BinOpExpr, BinOpType, ColumnId, ColumnIdExpr, ColumnRefExpr, ConstantExpr, ConstantType,
+    ExprList, FuncExpr, FuncType, IntervalUnit, LogOpExpr, LogOpType, ParameterExpr, SortOrderExpr,
+    SortOrderType, TimeUnit, UnOpExpr, UnOpType,
+};
+pub use filter::{LogicalFilter, PhysicalFilter, PhysicalRuntimeFilter};
+pub use join::{
+    join_mapping, join_mapping_data, JoinType, LogicalJoin, PhysicalHashJoin,
+    PhysicalNestedLoopJoin,
 };
-pub use filter::{LogicalFilter, PhysicalFilter};
-pub use join::{JoinType, LogicalJoin, PhysicalHashJoin, PhysicalNestedLoopJoin};
 use pretty_xmlish::{Pretty, PrettyConfig};
 pub use projection::{LogicalProjection, PhysicalProjection};
 pub use scan::{LogicalScan, PhysicalScan};
 pub use sort::{LogicalSort, PhysicalSort};
+pub use union::{LogicalUnion, PhysicalUnion};
+pub use values::{LogicalValues, PhysicalValues};
 
 use crate::{
     adaptive::PhysicalCollector,
     properties::schema::{Schema, SchemaPropertyBuilder},
+    spool::PhysicalSpool,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -48,18 +62,31 @@ pub enum OptRelNodeTyp {
     Sort,
     Agg,
     Apply(ApplyType),
+    Empty,
+    Values,
+    Union,
     // Physical plan nodes
     PhysicalProjection,
     PhysicalFilter,
+    PhysicalRuntimeFilter,
     PhysicalScan,
+    PhysicalEmpty,
+    PhysicalValues,
+    PhysicalUnion,
     PhysicalSort,
     PhysicalAgg,
+    PhysicalStreamAgg,
+    PhysicalTwoPhaseAgg,
     PhysicalHashJoin(JoinType),
     PhysicalNestedLoopJoin(JoinType),
+    PhysicalExchange(ExchangeType),
     PhysicalCollector(GroupId), // only produced after optimization is done
+    PhysicalSpool(GroupId),     // only produced after optimization is done
     // Expressions
     Constant(ConstantType),
     ColumnRef,
+    ColumnId,
+    Parameter(ConstantType),
     UnOp(UnOpType),
     BinOp(BinOpType),
     LogOp(LogOpType),
@@ -78,14 +105,25 @@ impl OptRelNodeTyp {
                 | Self::Apply(_)
                 | Self::Sort
                 | Self::Agg
+                | Self::Empty
+                | Self::Values
+                | Self::Union
                 | Self::PhysicalProjection
                 | Self::PhysicalFilter
+                | Self::PhysicalRuntimeFilter
                 | Self::PhysicalNestedLoopJoin(_)
                 | Self::PhysicalScan
+                | Self::PhysicalEmpty
+                | Self::PhysicalValues
+                | Self::PhysicalUnion
                 | Self::PhysicalSort
                 | Self::PhysicalAgg
+                | Self::PhysicalStreamAgg
+                | Self::PhysicalTwoPhaseAgg
                 | Self::PhysicalHashJoin(_)
+                | Self::PhysicalExchange(_)
                 | Self::PhysicalCollector(_)
+                | Self::PhysicalSpool(_)
         )
     }
 
@@ -94,6 +132,8 @@ impl OptRelNodeTyp {
             self,
             Self::Constant(_)
                 | Self::ColumnRef
+                | Self::ColumnId
+                | Self::Parameter(_)
                 | Self::UnOp(_)
                 | Self::BinOp(_)
                 | Self::Func(_)
@@ -120,6 +160,9 @@ impl RelNodeTyp for OptRelNodeTyp {
                 | Self::Apply(_)
                 | Self::Sort
                 | Self::Agg
+                | Self::Empty
+                | Self::Values
+                | Self::Union
         )
     }
 
@@ -151,20 +194,35 @@ pub trait OptRelNode: 'static + Clone {
 
     fn dispatch_explain(&self) -> Pretty<'static>;
 
+    /// Explains the node like `dispatch_explain`, with the column references in the expressions of
+    /// plan nodes printed as the names of the columns they point to. Nodes without expressions or
+    /// children fall back to `dispatch_explain`.
+    fn dispatch_explain_with_names(&self, _builder: &SchemaPropertyBuilder) -> Pretty<'static> {
+        self.dispatch_explain()
+    }
+
     fn explain(&self) -> Pretty<'static> {
         explain(self.clone().into_rel_node())
     }
 
+    /// Explains the node, printing column references as names resolved against `schema`. Only
+    /// expressions are resolved; plan nodes are explained as usual.
+    fn explain_with_schema(&self, schema: &Schema) -> Pretty<'static> {
+        explain_with_schema(self.clone().into_rel_node(), schema)
+    }
+
+    /// Explains the plan, printing the column references of each plan node as names resolved
+    /// against the schemas of its inputs, which `builder` derives.
+    fn explain_with_names(&self, builder: &SchemaPropertyBuilder) -> Pretty<'static> {
+        explain_with_names(self.clone().into_rel_node(), builder)
+    }
+
     fn explain_to_string(&self) -> String {
-        let mut config = PrettyConfig {
-            need_boundaries: false,
-            reduced_spaces: false,
-            width: 300,
-            ..Default::default()
-        };
-        let mut out = String::new();
-        config.unicode(&mut out, &self.explain());
-        out
+        pretty_to_string(&self.explain())
+    }
+
+    fn explain_with_names_to_string(&self, builder: &SchemaPropertyBuilder) -> String {
+        pretty_to_string(&self.explain_with_names(builder))
     }
 
     fn into_plan_node(self) -> PlanNode {
@@ -243,6 +301,92 @@ impl Expr {
     pub fn child(&self, idx: usize) -> OptRelNodeRef {
         self.0.child(idx)
     }
+
+    /// Rewrites every column reference in the expression with `rewrite`, which maps an old column
+    /// index to a new one. Returns `None` if `rewrite` returns `None` for any column.
+    pub fn rewrite_column_refs(&self, rewrite: &impl Fn(usize) -> Option<usize>) -> Option<Self> {
+        Some(Self(rewrite_column_refs(self.0.clone(), rewrite)?))
+    }
+
+    /// Collects the indices of all columns referenced by the expression.
+    pub fn collect_column_refs(&self, refs: &mut BTreeSet<usize>) {
+        collect_column_refs(&self.0, refs)
+    }
+
+    /// Checks whether the predicate is never true when the columns `is_null` returns true for are
+    /// all null, e.g., the columns padded with nulls by an outer join. Rows in which those columns
+    /// are null are then filtered out by the predicate.
+    pub fn rejects_nulls(&self, is_null: &impl Fn(usize) -> bool) -> bool {
+        rejects_nulls(&self.0, is_null)
+    }
+}
+
+fn rejects_nulls(rel_node: &OptRelNodeRef, is_null: &impl Fn(usize) -> bool) -> bool {
+    match &rel_node.typ {
+        OptRelNodeTyp::LogOp(LogOpType::And) => rel_node
+            .child(0)
+            .children
+            .iter()
+            .any(|child| rejects_nulls(child, is_null)),
+        OptRelNodeTyp::LogOp(LogOpType::Or) => rel_node
+            .child(0)
+            .children
+            .iter()
+            .all(|child| rejects_nulls(child, is_null)),
+        OptRelNodeTyp::BinOp(BinOpType::And) => {
+            rejects_nulls(&rel_node.child(0), is_null) || rejects_nulls(&rel_node.child(1), is_null)
+        }
+        OptRelNodeTyp::BinOp(BinOpType::Or) => {
+            rejects_nulls(&rel_node.child(0), is_null) && rejects_nulls(&rel_node.child(1), is_null)
+        }
+        OptRelNodeTyp::Constant(_) if matches!(rel_node.data, Some(Value::Bool(false))) => true,
+        _ => is_null_if(rel_node, is_null),
+    }
+}
+
+/// Checks whether the expression evaluates to null when the columns `is_null` returns true for are
+/// all null. Only operators returning null on a null input are followed.
+fn is_null_if(rel_node: &OptRelNodeRef, is_null: &impl Fn(usize) -> bool) -> bool {
+    match &rel_node.typ {
+        OptRelNodeTyp::ColumnRef => is_null(rel_node.data.as_ref().unwrap().as_i64() as usize),
+        OptRelNodeTyp::Constant(_) => rel_node.data.as_ref().is_none_or(Value::is_null),
+        OptRelNodeTyp::UnOp(_) => is_null_if(&rel_node.child(0), is_null),
+        OptRelNodeTyp::BinOp(BinOpType::And | BinOpType::Or) => false,
+        OptRelNodeTyp::BinOp(_) => {
+            is_null_if(&rel_node.child(0), is_null) || is_null_if(&rel_node.child(1), is_null)
+        }
+        _ => false,
+    }
+}
+
+pub(crate) fn rewrite_column_refs(
+    rel_node: OptRelNodeRef,
+    rewrite: &impl Fn(usize) -> Option<usize>,
+) -> Option<OptRelNodeRef> {
+    if rel_node.typ == OptRelNodeTyp::ColumnRef {
+        let idx = rel_node.data.as_ref().unwrap().as_i64() as usize;
+        return Some(ColumnRefExpr::new(rewrite(idx)?).into_rel_node());
+    }
+    let children = rel_node
+        .children
+        .iter()
+        .map(|child| rewrite_column_refs(child.clone(), rewrite))
+        .collect::<Option<Vec<_>>>()?;
+    Some(Arc::new(RelNode {
+        typ: rel_node.typ.clone(),
+        children,
+        data: rel_node.data.clone(),
+    }))
+}
+
+pub(crate) fn collect_column_refs(rel_node: &OptRelNodeRef, refs: &mut BTreeSet<usize>) {
+    if rel_node.typ == OptRelNodeTyp::ColumnRef {
+        refs.insert(rel_node.data.as_ref().unwrap().as_i64() as usize);
+        return;
+    }
+    for child in &rel_node.children {
+        collect_column_refs(child, refs);
+    }
 }
 
 impl OptRelNode for Expr {
@@ -272,82 +416,194 @@ impl OptRelNode for Expr {
 }
 
 pub fn explain(rel_node: OptRelNodeRef) -> Pretty<'static> {
+    explain_inner(rel_node, None)
+}
+
+/// Explains a plan, printing the column references of each plan node as names resolved against
+/// the schemas of its inputs, which `builder` derives.
+pub fn explain_with_names(
+    rel_node: OptRelNodeRef,
+    builder: &SchemaPropertyBuilder,
+) -> Pretty<'static> {
+    explain_inner(rel_node, Some(builder))
+}
+
+fn explain_inner(
+    rel_node: OptRelNodeRef,
+    builder: Option<&SchemaPropertyBuilder>,
+) -> Pretty<'static> {
     match rel_node.typ {
-        OptRelNodeTyp::ColumnRef => ColumnRefExpr::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::Constant(_) => ConstantExpr::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::UnOp(_) => UnOpExpr::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::BinOp(_) => BinOpExpr::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::Func(_) => FuncExpr::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::Join(_) => LogicalJoin::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::Scan => LogicalScan::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::Filter => LogicalFilter::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::Apply(_) => LogicalApply::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::PhysicalFilter => PhysicalFilter::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::PhysicalScan => PhysicalScan::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::PhysicalNestedLoopJoin(_) => PhysicalNestedLoopJoin::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
+        OptRelNodeTyp::ColumnRef => {
+            dispatch(ColumnRefExpr::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::ColumnId => {
+            dispatch(ColumnIdExpr::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::Parameter(_) => {
+            dispatch(ParameterExpr::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::Constant(_) => {
+            dispatch(ConstantExpr::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::UnOp(_) => dispatch(UnOpExpr::from_rel_node(rel_node).unwrap(), builder),
+        OptRelNodeTyp::BinOp(_) => dispatch(BinOpExpr::from_rel_node(rel_node).unwrap(), builder),
+        OptRelNodeTyp::Func(_) => dispatch(FuncExpr::from_rel_node(rel_node).unwrap(), builder),
+        OptRelNodeTyp::Join(_) => dispatch(LogicalJoin::from_rel_node(rel_node).unwrap(), builder),
+        OptRelNodeTyp::Scan => dispatch(LogicalScan::from_rel_node(rel_node).unwrap(), builder),
+        OptRelNodeTyp::Filter => dispatch(LogicalFilter::from_rel_node(rel_node).unwrap(), builder),
+        OptRelNodeTyp::Apply(_) => {
+            dispatch(LogicalApply::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::PhysicalFilter => {
+            dispatch(PhysicalFilter::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::PhysicalRuntimeFilter => dispatch(
+            PhysicalRuntimeFilter::from_rel_node(rel_node).unwrap(),
+            builder,
+        ),
+        OptRelNodeTyp::PhysicalScan => {
+            dispatch(PhysicalScan::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::Empty => dispatch(LogicalEmpty::from_rel_node(rel_node).unwrap(), builder),
+        OptRelNodeTyp::PhysicalEmpty => {
+            dispatch(PhysicalEmpty::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::Values => dispatch(LogicalValues::from_rel_node(rel_node).unwrap(), builder),
+        OptRelNodeTyp::PhysicalValues => {
+            dispatch(PhysicalValues::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::Union => dispatch(LogicalUnion::from_rel_node(rel_node).unwrap(), builder),
+        OptRelNodeTyp::PhysicalUnion => {
+            dispatch(PhysicalUnion::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::PhysicalNestedLoopJoin(_) => dispatch(
+            PhysicalNestedLoopJoin::from_rel_node(rel_node).unwrap(),
+            builder,
+        ),
         OptRelNodeTyp::Placeholder(_) => unreachable!("should not explain a placeholder"),
         OptRelNodeTyp::List => {
-            ExprList::from_rel_node(rel_node) // ExprList is the only place that we will have list in the datafusion repr
-                .unwrap()
-                .dispatch_explain()
+            // ExprList is the only place that we will have list in the datafusion repr
+            dispatch(ExprList::from_rel_node(rel_node).unwrap(), builder)
         }
-        OptRelNodeTyp::Agg => LogicalAgg::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::Sort => LogicalSort::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::Projection => LogicalProjection::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::PhysicalProjection => PhysicalProjection::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::PhysicalAgg => PhysicalAgg::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::PhysicalSort => PhysicalSort::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::PhysicalHashJoin(_) => PhysicalHashJoin::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::SortOrder(_) => SortOrderExpr::from_rel_node(rel_node)
-            .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::LogOp(_) => LogOpExpr::from_rel_node(rel_node)
+        OptRelNodeTyp::Agg => dispatch(LogicalAgg::from_rel_node(rel_node).unwrap(), builder),
+        OptRelNodeTyp::Sort => dispatch(LogicalSort::from_rel_node(rel_node).unwrap(), builder),
+        OptRelNodeTyp::Projection => {
+            dispatch(LogicalProjection::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::PhysicalProjection => dispatch(
+            PhysicalProjection::from_rel_node(rel_node).unwrap(),
+            builder,
+        ),
+        OptRelNodeTyp::PhysicalAgg => {
+            dispatch(PhysicalAgg::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::PhysicalStreamAgg => {
+            dispatch(PhysicalStreamAgg::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::PhysicalTwoPhaseAgg => dispatch(
+            PhysicalTwoPhaseAgg::from_rel_node(rel_node).unwrap(),
+            builder,
+        ),
+        OptRelNodeTyp::PhysicalSort => {
+            dispatch(PhysicalSort::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::PhysicalHashJoin(_) => {
+            dispatch(PhysicalHashJoin::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::PhysicalExchange(_) => {
+            dispatch(PhysicalExchange::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::SortOrder(_) => {
+            dispatch(SortOrderExpr::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::LogOp(_) => dispatch(LogOpExpr::from_rel_node(rel_node).unwrap(), builder),
+        OptRelNodeTyp::PhysicalCollector(group_id) => {
+            dispatch(PhysicalCollector::from_rel_node(rel_node).unwrap(), builder)
+        }
+        OptRelNodeTyp::PhysicalSpool(_) => {
+            dispatch(PhysicalSpool::from_rel_node(rel_node).unwrap(), builder)
+        }
+    }
+}
+
+/// Explains an expression, printing column references as names resolved against `schema`.
+pub fn explain_with_schema(rel_node: OptRelNodeRef, schema: &Schema) -> Pretty<'static> {
+    let explain_children = |rel_node: &OptRelNodeRef| {
+        rel_node
+            .children
+            .iter()
+            .map(|child| explain_with_schema(child.clone(), schema))
+            .collect()
+    };
+    match &rel_node.typ {
+        OptRelNodeTyp::ColumnRef => ColumnRefExpr::from_rel_node(rel_node.clone())
             .unwrap()
-            .dispatch_explain(),
-        OptRelNodeTyp::PhysicalCollector(group_id) => PhysicalCollector::from_rel_node(rel_node)
+            .explain_resolved(schema),
+        OptRelNodeTyp::ColumnId => ColumnIdExpr::from_rel_node(rel_node.clone())
             .unwrap()
-            .dispatch_explain(),
+            .explain_resolved(schema),
+        OptRelNodeTyp::UnOp(op_type) => {
+            Pretty::simple_record(op_type.to_string(), vec![], explain_children(&rel_node))
+        }
+        OptRelNodeTyp::BinOp(op_type) => {
+            Pretty::simple_record(op_type.to_string(), vec![], explain_children(&rel_node))
+        }
+        OptRelNodeTyp::LogOp(op_type) => {
+            Pretty::simple_record(op_type.to_string(), vec![], explain_children(&rel_node))
+        }
+        OptRelNodeTyp::Func(func_type) => {
+            Pretty::simple_record(func_type.to_string(), vec![], explain_children(&rel_node))
+        }
+        OptRelNodeTyp::SortOrder(order) => Pretty::simple_record(
+            "SortOrder",
+            vec![("order", order.to_string().into())],
+            explain_children(&rel_node),
+        ),
+        OptRelNodeTyp::List => Pretty::Array(explain_children(&rel_node)),
+        _ => explain(rel_node.clone()),
     }
 }
 
+/// Gets the schema the expression child `idx` of a plan node is evaluated over. The keys of a
+/// hash join are evaluated over the input they come from, and the expressions of other plan nodes
+/// over their plan node children, whose columns are concatenated.
+pub(crate) fn expr_input_schema(
+    builder: &SchemaPropertyBuilder,
+    rel_node: &OptRelNodeRef,
+    idx: usize,
+) -> Schema {
+    match (&rel_node.typ, idx) {
+        (OptRelNodeTyp::PhysicalHashJoin(_), 2) => builder.derive_tree(&rel_node.child(0)),
+        (OptRelNodeTyp::PhysicalHashJoin(_), 3) => builder.derive_tree(&rel_node.child(1)),
+        _ => rel_node
+            .children
+            .iter()
+            .filter(|child| child.typ.is_plan_node())
+            .fold(Schema::new(vec![]), |input, child| {
+                input.concat(&builder.derive_tree(child))
+            }),
+    }
+}
+
+fn dispatch(node: impl OptRelNode, builder: Option<&SchemaPropertyBuilder>) -> Pretty<'static> {
+    match builder {
+        Some(builder) => node.dispatch_explain_with_names(builder),
+        None => node.dispatch_explain(),
+    }
+}
+
+fn pretty_to_string(pretty: &Pretty<'_>) -> String {
+    let mut config = PrettyConfig {
+        need_boundaries: false,
+        reduced_spaces: false,
+        width: 300,
+        ..Default::default()
+    };
+    let mut out = String::new();
+    config.unicode(&mut out, pretty);
+    out
+}
+
 fn replace_typ(node: OptRelNodeRef, target_type: OptRelNodeTyp) -> OptRelNodeRef {
     Arc::new(RelNode {
         typ: target_type,
@@ -355,3 +611,96 @@ fn replace_typ(node: OptRelNodeRef, target_type: OptRelNodeTyp) -> OptRelNodeRef
         data: node.data.clone(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{col, eq, scan, TestCatalog};
+
+    /// Columns 0 and 1 are padded with nulls, column 2 is not.
+    fn is_null(index: usize) -> bool {
+        index < 2
+    }
+
+    fn or(exprs: Vec<Expr>) -> Expr {
+        LogOpExpr::new(LogOpType::Or, ExprList::new(exprs)).into_expr()
+    }
+
+    fn and(exprs: Vec<Expr>) -> Expr {
+        LogOpExpr::new(LogOpType::And, ExprList::new(exprs)).into_expr()
+    }
+
+    fn int(value: i64) -> Expr {
+        ConstantExpr::int(value).into_expr()
+    }
+
+    #[test]
+    fn comparison_on_null_column_rejects_nulls() {
+        assert!(eq(col(0), col(2)).rejects_nulls(&is_null));
+        assert!(
+            eq(col(0), ConstantExpr::null(ConstantType::Int32).into_expr()).rejects_nulls(&is_null)
+        );
+        let sum = BinOpExpr::new(col(1), int(1), BinOpType::Add).into_expr();
+        assert!(BinOpExpr::new(sum, int(2), BinOpType::Gt)
+            .into_expr()
+            .rejects_nulls(&is_null));
+        let not = UnOpExpr::new(eq(col(0), int(1)), UnOpType::Not).into_expr();
+        assert!(not.rejects_nulls(&is_null));
+    }
+
+    #[test]
+    fn comparison_on_other_columns_keeps_nulls() {
+        assert!(!eq(col(2), int(1)).rejects_nulls(&is_null));
+        assert!(!ConstantExpr::bool(true).into_expr().rejects_nulls(&is_null));
+        assert!(ConstantExpr::bool(false)
+            .into_expr()
+            .rejects_nulls(&is_null));
+    }
+
+    #[test]
+    fn conjunction_rejects_nulls_if_any_conjunct_does() {
+        let pred = and(vec![eq(col(2), int(1)), eq(col(0), int(1))]);
+        assert!(pred.rejects_nulls(&is_null));
+        let pred = and(vec![eq(col(2), int(1)), eq(col(2), int(2))]);
+        assert!(!pred.rejects_nulls(&is_null));
+    }
+
+    #[test]
+    fn disjunction_rejects_nulls_if_all_disjuncts_do() {
+        let pred = or(vec![eq(col(0), int(1)), eq(col(1), int(1))]);
+        assert!(pred.rejects_nulls(&is_null));
+        let pred = or(vec![eq(col(0), int(1)), eq(col(2), int(1))]);
+        assert!(!pred.rejects_nulls(&is_null));
+        // Disjunctions are not followed below other operators.
+        let pred = BinOpExpr::new(pred, int(1), BinOpType::Eq).into_expr();
+        assert!(!pred.rejects_nulls(&is_null));
+    }
+
+    #[test]
+    fn explain_with_names_resolves_columns_against_the_inputs() {
+        let builder = SchemaPropertyBuilder::new(Arc::new(TestCatalog));
+        let join = LogicalJoin::new(
+            scan("t1", 2),
+            scan("t2", 3),
+            eq(col(1), col(4)),
+            JoinType::Inner,
+        );
+        let filter = LogicalFilter::new(join.into_plan_node(), eq(col(0), col(2)));
+        let explained = filter.explain_with_names_to_string(&builder);
+        assert!(!explained.contains('#'), "{}", explained);
+        assert!(explained.contains("t1.b"), "{}", explained);
+        assert!(explained.contains("t2.c"), "{}", explained);
+        // The keys of a hash join are evaluated over the input they come from.
+        let join = PhysicalHashJoin::new(
+            scan("t1", 2),
+            scan("t2", 3),
+            ExprList::new(vec![col(1)]),
+            ExprList::new(vec![col(2)]),
+            JoinType::Inner,
+        );
+        let explained = join.explain_with_names_to_string(&builder);
+        assert!(explained.contains("left_keys: [ t1.b ]"), "{}", explained);
+        assert!(explained.contains("right_keys: [ t2.c ]"), "{}", explained);
+        assert!(filter.explain_to_string().contains("#4"));
+    }
+}
@@ -16,6 +16,7 @@ define_plan_node!(
     ]
 );
 
+/// Hash aggregation.
 #[derive(Clone, Debug)]
 pub struct PhysicalAgg(pub PlanNode);
 
@@ -28,3 +29,33 @@ define_plan_node!(
         { 2, groups: ExprList }
     ]
 );
+
+/// Aggregation over an input sorted on the group by columns, which emits each group as soon as it
+/// ends without building a hash table.
+#[derive(Clone, Debug)]
+pub struct PhysicalStreamAgg(pub PlanNode);
+
+define_plan_node!(
+    PhysicalStreamAgg : PlanNode,
+    PhysicalStreamAgg, [
+        { 0, child: PlanNode }
+    ], [
+        { 1, aggrs: ExprList },
+        { 2, groups: ExprList }
+    ]
+);
+
+/// Hash aggregation split into a partial aggregation on each input partition and a final
+/// aggregation after repartitioning the partial results on the group by columns.
+#[derive(Clone, Debug)]
+pub struct PhysicalTwoPhaseAgg(pub PlanNode);
+
+define_plan_node!(
+    PhysicalTwoPhaseAgg : PlanNode,
+    PhysicalTwoPhaseAgg, [
+        { 0, child: PlanNode }
+    ], [
+        { 1, aggrs: ExprList },
+        { 2, groups: ExprList }
+    ]
+);
@@ -5,7 +5,10 @@ use pretty_xmlish::Pretty;
 
 use optd_core::rel_node::RelNode;
 
-use super::{Expr, JoinType, OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode};
+use super::{
+    expr_input_schema, Expr, JoinType, OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode,
+};
+use crate::properties::schema::SchemaPropertyBuilder;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum ApplyType {
@@ -58,6 +61,21 @@ impl OptRelNode for LogicalApply {
             vec![self.left_child().explain(), self.right_child().explain()],
         )
     }
+
+    fn dispatch_explain_with_names(&self, builder: &SchemaPropertyBuilder) -> Pretty<'static> {
+        let input = expr_input_schema(builder, &self.clone().into_rel_node(), 2);
+        Pretty::simple_record(
+            "LogicalApply",
+            vec![
+                ("typ", self.apply_type().to_string().into()),
+                ("cond", self.cond().explain_with_schema(&input)),
+            ],
+            vec![
+                self.left_child().explain_with_names(builder),
+                self.right_child().explain_with_names(builder),
+            ],
+        )
+    }
 }
 
 impl LogicalApply {
@@ -0,0 +1,87 @@
+use pretty_xmlish::Pretty;
+
+use optd_core::rel_node::RelNode;
+
+use super::{replace_typ, OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode};
+use crate::properties::schema::Schema;
+
+/// A relation without rows. As it has no input, its schema is kept in the data of the node.
+#[derive(Clone, Debug)]
+pub struct LogicalEmpty(pub PlanNode);
+
+impl OptRelNode for LogicalEmpty {
+    fn into_rel_node(self) -> OptRelNodeRef {
+        self.0.into_rel_node()
+    }
+
+    fn from_rel_node(rel_node: OptRelNodeRef) -> Option<Self> {
+        if rel_node.typ != OptRelNodeTyp::Empty {
+            return None;
+        }
+        PlanNode::from_rel_node(rel_node).map(Self)
+    }
+
+    fn dispatch_explain(&self) -> Pretty<'static> {
+        Pretty::childless_record("LogicalEmpty", explain_fields(&self.schema()))
+    }
+}
+
+impl LogicalEmpty {
+    pub fn new(schema: &Schema) -> LogicalEmpty {
+        LogicalEmpty(PlanNode(
+            RelNode {
+                typ: OptRelNodeTyp::Empty,
+                children: vec![],
+                data: Some(schema.to_value()),
+            }
+            .into(),
+        ))
+    }
+
+    pub fn schema(&self) -> Schema {
+        Schema::from_value(self.clone().into_rel_node().data.as_ref().unwrap())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PhysicalEmpty(pub PlanNode);
+
+impl OptRelNode for PhysicalEmpty {
+    fn into_rel_node(self) -> OptRelNodeRef {
+        replace_typ(self.0.into_rel_node(), OptRelNodeTyp::PhysicalEmpty)
+    }
+
+    fn from_rel_node(rel_node: OptRelNodeRef) -> Option<Self> {
+        if rel_node.typ != OptRelNodeTyp::PhysicalEmpty {
+            return None;
+        }
+        PlanNode::from_rel_node(rel_node).map(Self)
+    }
+
+    fn dispatch_explain(&self) -> Pretty<'static> {
+        Pretty::childless_record("PhysicalEmpty", explain_fields(&self.schema()))
+    }
+}
+
+impl PhysicalEmpty {
+    pub fn new(node: PlanNode) -> PhysicalEmpty {
+        Self(node)
+    }
+
+    pub fn schema(&self) -> Schema {
+        Schema::from_value(self.clone().into_rel_node().data.as_ref().unwrap())
+    }
+}
+
+fn explain_fields(schema: &Schema) -> Vec<(&'static str, Pretty<'static>)> {
+    vec![(
+        "columns",
+        Pretty::Array(
+            schema
+                .fields
+                .iter()
+                .map(|field| field.name.clone().into())
+                .collect(),
+        ),
+    )]
+}
@@ -0,0 +1,39 @@
+use core::fmt;
+use std::fmt::Display;
+
+use super::macros::define_plan_node;
+use super::{ExprList, OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode};
+
+/// How an exchange redistributes the rows of its input across partitions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ExchangeType {
+    /// Gathers all rows into a single partition.
+    Single,
+    /// Partitions rows by the hash of the exchange keys.
+    Hash,
+    /// Spreads rows evenly over the partitions.
+    RoundRobin,
+    /// Makes all rows available to every partition.
+    Broadcast,
+}
+
+impl Display for ExchangeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Redistributes its input across partitions. Exchanges are added by implementation rules to
+/// satisfy the distribution required by partitioned operators; the keys are only used by hash
+/// exchanges and are empty otherwise.
+#[derive(Clone, Debug)]
+pub struct PhysicalExchange(pub PlanNode);
+
+define_plan_node!(
+    PhysicalExchange : PlanNode,
+    PhysicalExchange, [
+        { 0, child: PlanNode }
+    ], [
+        { 1, keys: ExprList }
+    ], { exchange_type: ExchangeType }
+);
@@ -1,11 +1,14 @@
-use std::fmt::Display;
+use std::{collections::BTreeSet, fmt::Display, sync::Arc};
 
 use itertools::Itertools;
 use pretty_xmlish::Pretty;
 
 use optd_core::rel_node::{RelNode, Value};
 
-use super::{Expr, OptRelNode, OptRelNodeRef, OptRelNodeTyp};
+use super::{
+    collect_column_refs, rewrite_column_refs, Expr, OptRelNode, OptRelNodeRef, OptRelNodeTyp,
+};
+use crate::properties::schema::Schema;
 
 #[derive(Clone, Debug)]
 pub struct ExprList(OptRelNodeRef);
@@ -37,6 +40,53 @@ impl ExprList {
             .map(|x| Expr::from_rel_node(x.clone()).unwrap())
             .collect_vec()
     }
+
+    /// Rewrites every column reference in the list with `rewrite`. See
+    /// [`Expr::rewrite_column_refs`].
+    pub fn rewrite_column_refs(&self, rewrite: &impl Fn(usize) -> Option<usize>) -> Option<Self> {
+        Some(ExprList(rewrite_column_refs(self.0.clone(), rewrite)?))
+    }
+
+    /// Collects the indices of all columns referenced by the list.
+    pub fn collect_column_refs(&self, refs: &mut BTreeSet<usize>) {
+        collect_column_refs(&self.0, refs)
+    }
+
+    /// Assigns ids to the columns the expressions of the list produce when the list is the output
+    /// of a plan node.
+    pub fn with_column_ids(self, ids: &[ColumnId]) -> Self {
+        assert_eq!(ids.len(), self.len());
+        let bytes = ids
+            .iter()
+            .flat_map(|id| id.to_le_bytes())
+            .collect::<Vec<_>>();
+        ExprList(
+            RelNode {
+                typ: OptRelNodeTyp::List,
+                children: self.0.children.clone(),
+                data: Some(Value::Serialized(bytes.into())),
+            }
+            .into(),
+        )
+    }
+
+    /// Gets the ids of the columns the expressions of the list produce, if they are assigned.
+    pub fn column_ids(&self) -> Option<Vec<ColumnId>> {
+        list_column_ids(&self.0.data)
+    }
+}
+
+/// Decodes the column ids from the data of a list.
+pub(crate) fn list_column_ids(data: &Option<Value>) -> Option<Vec<ColumnId>> {
+    match data {
+        Some(Value::Serialized(bytes)) => Some(
+            bytes
+                .chunks_exact(8)
+                .map(|id| ColumnId::from_le_bytes(id.try_into().unwrap()))
+                .collect(),
+        ),
+        _ => None,
+    }
 }
 
 impl OptRelNode for ExprList {
@@ -61,13 +111,88 @@ impl OptRelNode for ExprList {
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum TimeUnit {
+    Second,
+    Millisecond,
+    Microsecond,
+    Nanosecond,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum IntervalUnit {
+    /// Stored as `Value::Int`, number of months.
+    YearMonth,
+    /// Stored as `Value::Int`, days in the high 32 bits and milliseconds in the low 32 bits.
+    DayTime,
+    /// Stored as `Value::Int128`, in the Arrow month-day-nano layout.
+    MonthDayNano,
+}
+
+/// The type of a constant or a column. Mirrors the Arrow types we support.
+///
+/// Signed integers, dates, timestamps and the narrow intervals are stored as `Value::Int`,
+/// unsigned integers as `Value::UInt`, floats as `Value::Float`, decimals as the unscaled
+/// `Value::Int128` and binaries as `Value::Serialized`. A null of any type is `Value::Null`.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum ConstantType {
+    Any,
+    Null,
     Bool,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Float32,
+    Float64,
+    /// Decimal with precision and scale.
+    Decimal128(u8, i8),
+    /// Days since epoch.
+    Date32,
+    /// Milliseconds since epoch.
+    Date64,
+    Timestamp(TimeUnit, Option<Arc<str>>),
+    Interval(IntervalUnit),
     Utf8String,
-    Int,
-    Date,
-    Decimal,
-    Any,
+    Binary,
+}
+
+impl ConstantType {
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Self::Int8
+                | Self::Int16
+                | Self::Int32
+                | Self::Int64
+                | Self::UInt8
+                | Self::UInt16
+                | Self::UInt32
+                | Self::UInt64
+                | Self::Float32
+                | Self::Float64
+                | Self::Decimal128(_, _)
+        )
+    }
+
+    /// Estimated width of a value of this type in bytes. Variable-length types use a fixed guess.
+    pub fn byte_width(&self) -> usize {
+        match self {
+            Self::Null => 0,
+            Self::Bool | Self::Int8 | Self::UInt8 => 1,
+            Self::Int16 | Self::UInt16 => 2,
+            Self::Int32 | Self::UInt32 | Self::Float32 | Self::Date32 => 4,
+            Self::Interval(IntervalUnit::YearMonth) => 4,
+            Self::Int64 | Self::UInt64 | Self::Float64 | Self::Date64 | Self::Timestamp(_, _) => 8,
+            Self::Interval(IntervalUnit::DayTime) => 8,
+            Self::Decimal128(_, _) | Self::Interval(IntervalUnit::MonthDayNano) => 16,
+            Self::Utf8String | Self::Binary => 16,
+            Self::Any => 8,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -76,11 +201,14 @@ pub struct ConstantExpr(pub Expr);
 impl ConstantExpr {
     pub fn new(value: Value) -> Self {
         let typ = match &value {
+            Value::Null => ConstantType::Null,
             Value::Bool(_) => ConstantType::Bool,
             Value::String(_) => ConstantType::Utf8String,
-            Value::Int(_) => ConstantType::Int,
-            Value::Float(_) => ConstantType::Decimal,
-            _ => unimplemented!(),
+            Value::Int(_) => ConstantType::Int64,
+            Value::UInt(_) => ConstantType::UInt64,
+            Value::Int128(_) => ConstantType::Decimal128(38, 0),
+            Value::Float(_) => ConstantType::Float64,
+            Value::Serialized(_) => ConstantType::Binary,
         };
         Self::new_with_type(value, typ)
     }
@@ -96,6 +224,11 @@ impl ConstantExpr {
         ))
     }
 
+    /// Creates a null constant of the given type.
+    pub fn null(typ: ConstantType) -> Self {
+        Self::new_with_type(Value::Null, typ)
+    }
+
     pub fn bool(value: bool) -> Self {
         Self::new_with_type(Value::Bool(value), ConstantType::Bool)
     }
@@ -108,15 +241,46 @@ impl ConstantExpr {
     }
 
     pub fn int(value: i64) -> Self {
-        Self::new_with_type(Value::Int(value), ConstantType::Int)
+        Self::new_with_type(Value::Int(value), ConstantType::Int64)
+    }
+
+    pub fn uint(value: u64) -> Self {
+        Self::new_with_type(Value::UInt(value), ConstantType::UInt64)
+    }
+
+    pub fn float(value: f64) -> Self {
+        Self::new_with_type(Value::Float(value.into()), ConstantType::Float64)
     }
 
     pub fn date(value: i64) -> Self {
-        Self::new_with_type(Value::Int(value), ConstantType::Date)
+        Self::new_with_type(Value::Int(value), ConstantType::Date32)
+    }
+
+    pub fn decimal(value: i128, precision: u8, scale: i8) -> Self {
+        Self::new_with_type(
+            Value::Int128(value),
+            ConstantType::Decimal128(precision, scale),
+        )
+    }
+
+    pub fn binary(value: impl AsRef<[u8]>) -> Self {
+        Self::new_with_type(
+            Value::Serialized(value.as_ref().into()),
+            ConstantType::Binary,
+        )
     }
 
-    pub fn decimal(value: f64) -> Self {
-        Self::new_with_type(Value::Float(value.into()), ConstantType::Decimal)
+    /// Gets the type of the constant.
+    pub fn constant_type(&self) -> ConstantType {
+        if let OptRelNodeTyp::Constant(typ) = &self.0 .0.typ {
+            typ.clone()
+        } else {
+            unreachable!()
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.value().is_null()
     }
 
     /// Gets the constant value.
@@ -138,7 +302,16 @@ impl OptRelNode for ConstantExpr {
     }
 
     fn dispatch_explain(&self) -> Pretty<'static> {
-        Pretty::display(&self.value())
+        match (self.constant_type(), self.value()) {
+            (ConstantType::Decimal128(_, scale), Value::Int128(x)) if scale > 0 => {
+                let scale = scale as usize;
+                let sign = if x < 0 { "-" } else { "" };
+                let digits = format!("{:0>width$}", x.unsigned_abs(), width = scale + 1);
+                let (int_part, frac_part) = digits.split_at(digits.len() - scale);
+                Pretty::display(&format!("{sign}{int_part}.{frac_part}"))
+            }
+            (_, value) => Pretty::display(&value),
+        }
     }
 }
 
@@ -166,6 +339,15 @@ impl ColumnRefExpr {
     pub fn index(&self) -> usize {
         self.get_data_usize()
     }
+
+    /// Explains the column reference as the qualified name of the column it points to in
+    /// `schema`, falling back to the index if it is out of range.
+    pub fn explain_resolved(&self, schema: &Schema) -> Pretty<'static> {
+        match schema.fields.get(self.index()) {
+            Some(field) => Pretty::display(&field.qualified_name()),
+            None => self.dispatch_explain(),
+        }
+    }
 }
 
 impl OptRelNode for ColumnRefExpr {
@@ -185,6 +367,106 @@ impl OptRelNode for ColumnRefExpr {
     }
 }
 
+/// A globally unique identifier of a column, which stays the same wherever the column is moved in
+/// the plan.
+pub type ColumnId = u64;
+
+/// A reference to a column by its id instead of its position in the input. Column ids are
+/// resolved to positions before optimization, so rules only see `ColumnRefExpr`s.
+#[derive(Clone, Debug)]
+pub struct ColumnIdExpr(pub Expr);
+
+impl ColumnIdExpr {
+    pub fn new(id: ColumnId) -> ColumnIdExpr {
+        ColumnIdExpr(Expr(
+            RelNode {
+                typ: OptRelNodeTyp::ColumnId,
+                children: vec![],
+                data: Some(Value::UInt(id)),
+            }
+            .into(),
+        ))
+    }
+
+    pub fn id(&self) -> ColumnId {
+        self.0 .0.data.as_ref().unwrap().as_u64()
+    }
+
+    /// Explains the column reference as the qualified name of the column with the id in `schema`,
+    /// falling back to the id if there is no such column.
+    pub fn explain_resolved(&self, schema: &Schema) -> Pretty<'static> {
+        match schema.index_of_id(self.id()) {
+            Some(idx) => Pretty::display(&schema.field(idx).qualified_name()),
+            None => self.dispatch_explain(),
+        }
+    }
+}
+
+impl OptRelNode for ColumnIdExpr {
+    fn into_rel_node(self) -> OptRelNodeRef {
+        self.0.into_rel_node()
+    }
+
+    fn from_rel_node(rel_node: OptRelNodeRef) -> Option<Self> {
+        if rel_node.typ != OptRelNodeTyp::ColumnId {
+            return None;
+        }
+        Expr::from_rel_node(rel_node).map(Self)
+    }
+
+    fn dispatch_explain(&self) -> Pretty<'static> {
+        Pretty::display(&format!("${}", self.id()))
+    }
+}
+
+/// A parameter of a prepared statement, e.g., `$1`, which is bound to a constant when the plan is
+/// executed. Parameters are numbered from 1.
+#[derive(Clone, Debug)]
+pub struct ParameterExpr(pub Expr);
+
+impl ParameterExpr {
+    pub fn new(index: usize, typ: ConstantType) -> ParameterExpr {
+        ParameterExpr(Expr(
+            RelNode {
+                typ: OptRelNodeTyp::Parameter(typ),
+                children: vec![],
+                data: Some(Value::UInt(index as u64)),
+            }
+            .into(),
+        ))
+    }
+
+    pub fn index(&self) -> usize {
+        self.0 .0.data.as_ref().unwrap().as_u64() as usize
+    }
+
+    /// Gets the type of the values the parameter is bound to.
+    pub fn parameter_type(&self) -> ConstantType {
+        if let OptRelNodeTyp::Parameter(typ) = &self.0 .0.typ {
+            typ.clone()
+        } else {
+            unreachable!()
+        }
+    }
+}
+
+impl OptRelNode for ParameterExpr {
+    fn into_rel_node(self) -> OptRelNodeRef {
+        self.0.into_rel_node()
+    }
+
+    fn from_rel_node(rel_node: OptRelNodeRef) -> Option<Self> {
+        if let OptRelNodeTyp::Parameter(_) = rel_node.typ {
+            return Expr::from_rel_node(rel_node).map(Self);
+        }
+        None
+    }
+
+    fn dispatch_explain(&self) -> Pretty<'static> {
+        Pretty::display(&format!("${}", self.index()))
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum UnOpType {
     Neg = 1,
@@ -264,6 +546,26 @@ pub enum BinOpType {
     Xor,
 }
 
+impl BinOpType {
+    pub fn is_numerical(&self) -> bool {
+        matches!(
+            self,
+            Self::Add | Self::Sub | Self::Mul | Self::Div | Self::Mod
+        )
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Self::Eq | Self::Neq | Self::Gt | Self::Lt | Self::Geq | Self::Leq
+        )
+    }
+
+    pub fn is_logical(&self) -> bool {
+        matches!(self, Self::And | Self::Or | Self::Xor)
+    }
+}
+
 impl Display for BinOpType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self)
@@ -1,6 +1,6 @@
 use super::macros::define_plan_node;
 
-use super::{Expr, OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode};
+use super::{Expr, ExprList, OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode};
 
 #[derive(Clone, Debug)]
 pub struct LogicalFilter(pub PlanNode);
@@ -25,3 +25,19 @@ define_plan_node!(
         { 1, cond: Expr }
     ]
 );
+
+/// Drops the rows of the probe side of a hash join whose keys cannot match any row of the build
+/// side. The filter is a bloom filter and a key range collected while the hash table is built, so
+/// it must sit directly below the probe side of a `PhysicalHashJoin`, and its keys are the probe
+/// keys of the join.
+#[derive(Clone, Debug)]
+pub struct PhysicalRuntimeFilter(pub PlanNode);
+
+define_plan_node!(
+    PhysicalRuntimeFilter : PlanNode,
+    PhysicalRuntimeFilter, [
+        { 0, child: PlanNode }
+    ], [
+        { 1, keys: ExprList }
+    ]
+);
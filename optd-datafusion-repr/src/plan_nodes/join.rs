@@ -1,6 +1,8 @@
 use core::fmt;
 use std::fmt::Display;
 
+use optd_core::rel_node::{RelNode, Value};
+
 use super::macros::define_plan_node;
 use super::{Expr, ExprList, OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode};
 
@@ -36,6 +38,55 @@ define_plan_node!(
     ], { join_type: JoinType }
 );
 
+impl LogicalJoin {
+    /// Reorders the output columns of the join, where output column `i` is column `mapping[i]` of
+    /// the left input followed by the right input. The join condition still refers to the columns
+    /// of the inputs in that order. This lets a join swap its inputs without a projection on top
+    /// to restore the column order.
+    pub fn with_mapping(self, mapping: &[usize]) -> Self {
+        let node = self.into_rel_node();
+        Self(PlanNode(
+            RelNode {
+                typ: node.typ.clone(),
+                children: node.children.clone(),
+                data: join_mapping_data(mapping),
+            }
+            .into(),
+        ))
+    }
+
+    /// Gets the output column mapping of the join, if its columns are reordered.
+    pub fn mapping(&self) -> Option<Vec<usize>> {
+        join_mapping(&self.0 .0.data)
+    }
+}
+
+/// Encodes an output column mapping into the data of a logical join. The identity mapping is not
+/// stored, so that a join commuted back and forth is the same expression as the original one.
+pub fn join_mapping_data(mapping: &[usize]) -> Option<Value> {
+    if mapping.iter().enumerate().all(|(i, col)| i == *col) {
+        return None;
+    }
+    let bytes = mapping
+        .iter()
+        .flat_map(|col| (*col as u32).to_le_bytes())
+        .collect::<Vec<_>>();
+    Some(Value::Serialized(bytes.into()))
+}
+
+/// Decodes the output column mapping from the data of a logical join.
+pub fn join_mapping(data: &Option<Value>) -> Option<Vec<usize>> {
+    match data {
+        Some(Value::Serialized(bytes)) => Some(
+            bytes
+                .chunks_exact(4)
+                .map(|col| u32::from_le_bytes(col.try_into().unwrap()) as usize)
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct PhysicalNestedLoopJoin(pub PlanNode);
 
@@ -62,3 +113,25 @@ define_plan_node!(
         { 3, right_keys: ExprList }
     ], { join_type: JoinType }
 );
+
+impl PhysicalHashJoin {
+    /// Builds the hash table on the right input and probes it with the left one. The output
+    /// columns stay in the order of the left input followed by the right input, so no projection
+    /// is needed to swap the sides.
+    pub fn with_build_right(self, build_right: bool) -> Self {
+        let node = self.into_rel_node();
+        Self(PlanNode(
+            RelNode {
+                typ: node.typ.clone(),
+                children: node.children.clone(),
+                data: build_right.then_some(Value::Bool(true)),
+            }
+            .into(),
+        ))
+    }
+
+    /// Checks whether the hash table is built on the right input.
+    pub fn build_right(&self) -> bool {
+        self.0 .0.data.as_ref().is_some_and(|data| data.as_bool())
+    }
+}
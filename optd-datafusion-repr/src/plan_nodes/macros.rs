@@ -32,6 +32,29 @@ macro_rules! define_plan_node {
                     ],
                 )
             }
+
+            fn dispatch_explain_with_names(
+                &self,
+                builder: &$crate::properties::schema::SchemaPropertyBuilder,
+            ) -> pretty_xmlish::Pretty<'static> {
+                #[allow(unused_variables)]
+                let rel_node = self.clone().into_rel_node();
+                pretty_xmlish::Pretty::simple_record(
+                    stringify!($struct_name),
+                    vec![
+                        $( (stringify!($inner_name), self.$inner_name().to_string().into() ) , )?
+                        $( (
+                            stringify!($attr_name),
+                            self.$attr_name().explain_with_schema(
+                                &$crate::plan_nodes::expr_input_schema(builder, &rel_node, $attr_id),
+                            ),
+                        ) ),*
+                    ],
+                    vec![
+                        $( self.$child_name().explain_with_names(builder) ),*
+                    ],
+                )
+            }
         }
 
         impl $struct_name {
@@ -67,7 +90,7 @@ macro_rules! define_plan_node {
             )*
 
             $(
-                pub fn $inner_name(&self) -> JoinType {
+                pub fn $inner_name(&self) -> $inner_typ {
                     if let OptRelNodeTyp :: $variant ($inner_name) = self.0 .0.typ {
                         return $inner_name;
                     } else {
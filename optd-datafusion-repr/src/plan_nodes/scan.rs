@@ -4,7 +4,11 @@ use pretty_xmlish::Pretty;
 
 use optd_core::rel_node::{RelNode, Value};
 
-use super::{replace_typ, OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode};
+use super::{
+    explain, explain_with_schema, replace_typ, ConstantExpr, ConstantType, ExprList, OptRelNode,
+    OptRelNodeRef, OptRelNodeTyp, PlanNode,
+};
+use crate::properties::schema::{Schema, SchemaPropertyBuilder};
 
 #[derive(Clone, Debug)]
 pub struct LogicalScan(pub PlanNode);
@@ -24,17 +28,57 @@ impl OptRelNode for LogicalScan {
     fn dispatch_explain(&self) -> Pretty<'static> {
         Pretty::childless_record(
             "LogicalScan",
-            vec![("table", self.table().to_string().into())],
+            explain_fields(self.clone().into_rel_node(), None),
+        )
+    }
+
+    fn dispatch_explain_with_names(&self, builder: &SchemaPropertyBuilder) -> Pretty<'static> {
+        let table = builder.table_schema(&self.table());
+        Pretty::childless_record(
+            "LogicalScan",
+            explain_fields(self.clone().into_rel_node(), Some(&table)),
         )
     }
 }
 
 impl LogicalScan {
-    pub fn new(table: String) -> LogicalScan {
+    /// Creates a scan of `table` that reads the columns in `projection`, which is a list of
+    /// column references into the table schema.
+    pub fn new(table: String, projection: ExprList) -> LogicalScan {
+        Self::new_with_pushdown(
+            table,
+            projection,
+            ExprList::new(vec![]),
+            ExprList::new(vec![]),
+            None,
+        )
+    }
+
+    /// Creates a scan with predicates and a row limit pushed into it. The table applies the
+    /// `filters` exactly, while it may let rows failing the `inexact_filters` through, so these
+    /// are also evaluated above the scan. Both refer to the columns of the table schema rather
+    /// than the projected columns, and rows are only counted towards `fetch` after all filters are
+    /// applied.
+    pub fn new_with_pushdown(
+        table: String,
+        projection: ExprList,
+        filters: ExprList,
+        inexact_filters: ExprList,
+        fetch: Option<usize>,
+    ) -> LogicalScan {
+        let fetch = match fetch {
+            Some(fetch) => ConstantExpr::uint(fetch as u64),
+            None => ConstantExpr::null(ConstantType::UInt64),
+        };
         LogicalScan(PlanNode(
             RelNode {
                 typ: OptRelNodeTyp::Scan,
-                children: vec![],
+                children: vec![
+                    projection.into_rel_node(),
+                    filters.into_rel_node(),
+                    fetch.into_rel_node(),
+                    inexact_filters.into_rel_node(),
+                ],
                 data: Some(Value::String(table.into())),
             }
             .into(),
@@ -44,6 +88,25 @@ impl LogicalScan {
     pub fn table(&self) -> Arc<str> {
         self.clone().into_rel_node().data.as_ref().unwrap().as_str()
     }
+
+    pub fn projection(&self) -> ExprList {
+        ExprList::from_rel_node(self.clone().into_rel_node().child(0)).unwrap()
+    }
+
+    /// Gets the predicates the scan applies exactly, which refer to the columns of the table.
+    pub fn filters(&self) -> ExprList {
+        ExprList::from_rel_node(self.clone().into_rel_node().child(1)).unwrap()
+    }
+
+    /// Gets the predicates the scan may apply partially, which refer to the columns of the table.
+    pub fn inexact_filters(&self) -> ExprList {
+        ExprList::from_rel_node(self.clone().into_rel_node().child(3)).unwrap()
+    }
+
+    /// Gets the maximum number of rows the scan produces, if limited.
+    pub fn fetch(&self) -> Option<usize> {
+        scan_fetch(self.clone().into_rel_node())
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -64,7 +127,15 @@ impl OptRelNode for PhysicalScan {
     fn dispatch_explain(&self) -> Pretty<'static> {
         Pretty::childless_record(
             "PhysicalScan",
-            vec![("table", self.table().to_string().into())],
+            explain_fields(self.clone().into_rel_node(), None),
+        )
+    }
+
+    fn dispatch_explain_with_names(&self, builder: &SchemaPropertyBuilder) -> Pretty<'static> {
+        let table = builder.table_schema(&self.table());
+        Pretty::childless_record(
+            "PhysicalScan",
+            explain_fields(self.clone().into_rel_node(), Some(&table)),
         )
     }
 }
@@ -77,4 +148,63 @@ impl PhysicalScan {
     pub fn table(&self) -> Arc<str> {
         self.clone().into_rel_node().data.as_ref().unwrap().as_str()
     }
+
+    pub fn projection(&self) -> ExprList {
+        ExprList::from_rel_node(self.clone().into_rel_node().child(0)).unwrap()
+    }
+
+    /// Gets the predicates the scan applies exactly, which refer to the columns of the table.
+    pub fn filters(&self) -> ExprList {
+        ExprList::from_rel_node(self.clone().into_rel_node().child(1)).unwrap()
+    }
+
+    /// Gets the predicates the scan may apply partially, which refer to the columns of the table.
+    pub fn inexact_filters(&self) -> ExprList {
+        ExprList::from_rel_node(self.clone().into_rel_node().child(3)).unwrap()
+    }
+
+    /// Gets the maximum number of rows the scan produces, if limited.
+    pub fn fetch(&self) -> Option<usize> {
+        scan_fetch(self.clone().into_rel_node())
+    }
+}
+
+fn scan_fetch(rel_node: OptRelNodeRef) -> Option<usize> {
+    let fetch = ConstantExpr::from_rel_node(rel_node.child(2)).unwrap();
+    if fetch.is_null() {
+        None
+    } else {
+        Some(fetch.value().as_u64() as usize)
+    }
+}
+
+/// Explains the fields of a scan. The projection and the filters refer to the columns of the
+/// table, which are printed as names if the schema of the table is given.
+fn explain_fields(
+    rel_node: OptRelNodeRef,
+    table: Option<&Schema>,
+) -> Vec<(&'static str, Pretty<'static>)> {
+    let explain_list = |list: &OptRelNodeRef| match table {
+        Some(table) => explain_with_schema(list.clone(), table),
+        None => explain(list.clone()),
+    };
+    let mut fields = vec![
+        (
+            "table",
+            rel_node.data.as_ref().unwrap().as_str().to_string().into(),
+        ),
+        ("projection", explain_list(&rel_node.child(0))),
+    ];
+    let filters = ExprList::from_rel_node(rel_node.child(1)).unwrap();
+    if !filters.is_empty() {
+        fields.push(("filters", explain_list(&rel_node.child(1))));
+    }
+    let inexact_filters = ExprList::from_rel_node(rel_node.child(3)).unwrap();
+    if !inexact_filters.is_empty() {
+        fields.push(("inexact_filters", explain_list(&rel_node.child(3))));
+    }
+    if let Some(fetch) = scan_fetch(rel_node) {
+        fields.push(("fetch", fetch.to_string().into()));
+    }
+    fields
 }
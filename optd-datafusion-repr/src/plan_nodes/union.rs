@@ -0,0 +1,29 @@
+use super::macros::define_plan_node;
+
+use super::{OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode};
+
+/// The rows of both inputs without removing duplicates, i.e., `UNION ALL`. The columns are matched
+/// by position, and are named after the columns of the left input.
+#[derive(Clone, Debug)]
+pub struct LogicalUnion(pub PlanNode);
+
+define_plan_node!(
+    LogicalUnion : PlanNode,
+    Union, [
+        { 0, left: PlanNode },
+        { 1, right: PlanNode }
+    ], [
+    ]
+);
+
+#[derive(Clone, Debug)]
+pub struct PhysicalUnion(pub PlanNode);
+
+define_plan_node!(
+    PhysicalUnion : PlanNode,
+    PhysicalUnion, [
+        { 0, left: PlanNode },
+        { 1, right: PlanNode }
+    ], [
+    ]
+);
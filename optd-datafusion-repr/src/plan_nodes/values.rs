@@ -0,0 +1,125 @@
+use pretty_xmlish::Pretty;
+
+use optd_core::rel_node::RelNode;
+
+use super::{replace_typ, ExprList, OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode};
+use crate::properties::schema::Schema;
+
+/// An inline relation, whose rows are lists of expressions without column references. As it has no
+/// input, its schema is kept in the data of the node.
+#[derive(Clone, Debug)]
+pub struct LogicalValues(pub PlanNode);
+
+impl OptRelNode for LogicalValues {
+    fn into_rel_node(self) -> OptRelNodeRef {
+        self.0.into_rel_node()
+    }
+
+    fn from_rel_node(rel_node: OptRelNodeRef) -> Option<Self> {
+        if rel_node.typ != OptRelNodeTyp::Values {
+            return None;
+        }
+        PlanNode::from_rel_node(rel_node).map(Self)
+    }
+
+    fn dispatch_explain(&self) -> Pretty<'static> {
+        Pretty::childless_record(
+            "LogicalValues",
+            explain_fields(self.clone().into_rel_node()),
+        )
+    }
+}
+
+impl LogicalValues {
+    pub fn new(schema: &Schema, rows: Vec<ExprList>) -> LogicalValues {
+        assert!(rows.iter().all(|row| row.len() == schema.len()));
+        let rows = RelNode::new_list(rows.into_iter().map(|row| row.into_rel_node()).collect());
+        LogicalValues(PlanNode(
+            RelNode {
+                typ: OptRelNodeTyp::Values,
+                children: vec![rows.into()],
+                data: Some(schema.to_value()),
+            }
+            .into(),
+        ))
+    }
+
+    pub fn schema(&self) -> Schema {
+        Schema::from_value(self.clone().into_rel_node().data.as_ref().unwrap())
+    }
+
+    pub fn rows(&self) -> Vec<ExprList> {
+        values_rows(self.clone().into_rel_node())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PhysicalValues(pub PlanNode);
+
+impl OptRelNode for PhysicalValues {
+    fn into_rel_node(self) -> OptRelNodeRef {
+        replace_typ(self.0.into_rel_node(), OptRelNodeTyp::PhysicalValues)
+    }
+
+    fn from_rel_node(rel_node: OptRelNodeRef) -> Option<Self> {
+        if rel_node.typ != OptRelNodeTyp::PhysicalValues {
+            return None;
+        }
+        PlanNode::from_rel_node(rel_node).map(Self)
+    }
+
+    fn dispatch_explain(&self) -> Pretty<'static> {
+        Pretty::childless_record(
+            "PhysicalValues",
+            explain_fields(self.clone().into_rel_node()),
+        )
+    }
+}
+
+impl PhysicalValues {
+    pub fn new(node: PlanNode) -> PhysicalValues {
+        Self(node)
+    }
+
+    pub fn schema(&self) -> Schema {
+        Schema::from_value(self.clone().into_rel_node().data.as_ref().unwrap())
+    }
+
+    pub fn rows(&self) -> Vec<ExprList> {
+        values_rows(self.clone().into_rel_node())
+    }
+}
+
+fn values_rows(rel_node: OptRelNodeRef) -> Vec<ExprList> {
+    rel_node
+        .child(0)
+        .children
+        .iter()
+        .map(|row| ExprList::from_rel_node(row.clone()).unwrap())
+        .collect()
+}
+
+fn explain_fields(rel_node: OptRelNodeRef) -> Vec<(&'static str, Pretty<'static>)> {
+    let schema = Schema::from_value(rel_node.data.as_ref().unwrap());
+    vec![
+        (
+            "columns",
+            Pretty::Array(
+                schema
+                    .fields
+                    .iter()
+                    .map(|field| field.name.clone().into())
+                    .collect(),
+            ),
+        ),
+        (
+            "rows",
+            Pretty::Array(
+                values_rows(rel_node)
+                    .iter()
+                    .map(|row| row.explain())
+                    .collect(),
+            ),
+        ),
+    ]
+}
@@ -1 +1,5 @@
+pub mod distribution;
+pub mod equivalence;
+pub mod keys;
+pub mod ordering;
 pub mod schema;
@@ -0,0 +1,143 @@
+use optd_core::{property::PropertyBuilder, rel_node::Value};
+
+use crate::plan_nodes::{ExchangeType, OptRelNodeRef, OptRelNodeTyp};
+
+/// How the output rows of a plan node are spread across partitions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Distribution {
+    /// Nothing is known about the distribution.
+    Any,
+    /// All rows are in a single partition.
+    Single,
+    /// Rows are partitioned by the hash of the given output columns.
+    Hash(Vec<usize>),
+    /// Rows are spread evenly over the partitions.
+    RoundRobin,
+    /// Every partition holds all rows.
+    Broadcast,
+    /// The column referenced by an expression, or the columns of a list of column references.
+    /// Only used while deriving the distribution of plan nodes.
+    Columns(Vec<Option<usize>>),
+}
+
+impl Distribution {
+    /// Checks whether rows with equal values on `columns` are guaranteed to be in the same
+    /// partition.
+    pub fn is_colocated_on(&self, columns: &[usize]) -> bool {
+        match self {
+            Self::Single => true,
+            Self::Hash(keys) => !keys.is_empty() && keys.iter().all(|key| columns.contains(key)),
+            _ => false,
+        }
+    }
+
+    /// Checks whether rows distributed like `self` meet the `required` distribution of a parent,
+    /// which an exchange enforces otherwise. Hash distributions must be on the same columns in the
+    /// same order, so that both inputs of a partitioned join hash their keys alike.
+    pub fn satisfies(&self, required: &Distribution) -> bool {
+        match required {
+            Self::Any => true,
+            required => self == required,
+        }
+    }
+}
+
+pub struct DistributionPropertyBuilder;
+
+impl DistributionPropertyBuilder {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Derives the distribution property of a plan or expression tree outside of the memo.
+    pub fn derive_tree(&self, node: &OptRelNodeRef) -> Distribution {
+        let children = node
+            .children
+            .iter()
+            .map(|child| self.derive_tree(child))
+            .collect::<Vec<_>>();
+        self.derive(
+            node.typ.clone(),
+            node.data.clone(),
+            &children.iter().collect::<Vec<_>>(),
+        )
+    }
+}
+
+impl PropertyBuilder<OptRelNodeTyp> for DistributionPropertyBuilder {
+    type Prop = Distribution;
+
+    fn derive(
+        &self,
+        typ: OptRelNodeTyp,
+        data: Option<Value>,
+        children: &[&Self::Prop],
+    ) -> Self::Prop {
+        match typ {
+            OptRelNodeTyp::PhysicalExchange(exchange_type) => match exchange_type {
+                ExchangeType::Single => Distribution::Single,
+                ExchangeType::RoundRobin => Distribution::RoundRobin,
+                ExchangeType::Broadcast => Distribution::Broadcast,
+                ExchangeType::Hash => match children[1] {
+                    Distribution::Columns(columns) => columns
+                        .iter()
+                        .copied()
+                        .collect::<Option<Vec<_>>>()
+                        .map(Distribution::Hash)
+                        .unwrap_or(Distribution::Any),
+                    _ => Distribution::Any,
+                },
+            },
+            OptRelNodeTyp::Filter
+            | OptRelNodeTyp::PhysicalFilter
+            | OptRelNodeTyp::PhysicalRuntimeFilter
+            | OptRelNodeTyp::PhysicalCollector(_)
+            | OptRelNodeTyp::PhysicalSpool(_) => children[0].clone(),
+            OptRelNodeTyp::Projection | OptRelNodeTyp::PhysicalProjection => {
+                match (children[0], children[1]) {
+                    (Distribution::Hash(keys), Distribution::Columns(columns)) => keys
+                        .iter()
+                        .map(|key| columns.iter().position(|col| *col == Some(*key)))
+                        .collect::<Option<Vec<_>>>()
+                        .map(Distribution::Hash)
+                        .unwrap_or(Distribution::Any),
+                    (Distribution::Hash(_), _) => Distribution::Any,
+                    (distribution, _) => distribution.clone(),
+                }
+            }
+            // Sorts and aggregations without group by columns gather their output into one
+            // partition.
+            OptRelNodeTyp::Sort | OptRelNodeTyp::PhysicalSort => Distribution::Single,
+            OptRelNodeTyp::Empty
+            | OptRelNodeTyp::PhysicalEmpty
+            | OptRelNodeTyp::Values
+            | OptRelNodeTyp::PhysicalValues => Distribution::Single,
+            OptRelNodeTyp::Agg
+            | OptRelNodeTyp::PhysicalAgg
+            | OptRelNodeTyp::PhysicalStreamAgg
+            | OptRelNodeTyp::PhysicalTwoPhaseAgg
+                if matches!(children[2], Distribution::Columns(columns) if columns.is_empty()) =>
+            {
+                Distribution::Single
+            }
+            OptRelNodeTyp::ColumnRef => {
+                Distribution::Columns(vec![Some(data.unwrap().as_i64() as usize)])
+            }
+            OptRelNodeTyp::List => Distribution::Columns(
+                children
+                    .iter()
+                    .map(|child| match child {
+                        Distribution::Columns(columns) if columns.len() == 1 => columns[0],
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            typ if typ.is_expression() => Distribution::Columns(vec![None]),
+            _ => Distribution::Any,
+        }
+    }
+
+    fn property_name(&self) -> &'static str {
+        "distribution"
+    }
+}
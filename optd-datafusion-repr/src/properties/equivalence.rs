@@ -0,0 +1,283 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use optd_core::{
+    property::PropertyBuilder,
+    rel_node::{RelNode, Value},
+};
+
+use super::schema::Schema;
+use crate::plan_nodes::{
+    join_mapping, BinOpType, JoinType, LogOpType, OptRelNodeRef, OptRelNodeTyp,
+};
+
+/// The sets of output columns of a plan node that are known to hold equal, non-null values in
+/// every row, as established by the equality predicates of filters and joins.
+#[derive(Clone)]
+pub struct EquivalenceClasses {
+    /// Disjoint sets of at least two columns.
+    classes: Vec<BTreeSet<usize>>,
+    /// The number of output columns.
+    pub width: usize,
+    /// For expressions and lists, the expression tree rooted at this node.
+    expr: Option<OptRelNodeRef>,
+}
+
+impl std::fmt::Debug for EquivalenceClasses {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.classes.iter()).finish()
+    }
+}
+
+impl EquivalenceClasses {
+    pub fn new(width: usize) -> Self {
+        Self {
+            classes: vec![],
+            width,
+            expr: None,
+        }
+    }
+
+    pub fn classes(&self) -> &[BTreeSet<usize>] {
+        &self.classes
+    }
+
+    /// Gets the class of `col`, if it is known to equal any other column.
+    pub fn class_of(&self, col: usize) -> Option<&BTreeSet<usize>> {
+        self.classes.iter().find(|class| class.contains(&col))
+    }
+
+    pub fn is_equal(&self, a: usize, b: usize) -> bool {
+        a == b || self.class_of(a).is_some_and(|class| class.contains(&b))
+    }
+
+    /// Records that columns `a` and `b` are equal, merging their classes.
+    pub fn add_equality(&mut self, a: usize, b: usize) {
+        if self.is_equal(a, b) {
+            return;
+        }
+        let mut class = BTreeSet::from([a, b]);
+        self.classes.retain(|other| {
+            if other.contains(&a) || other.contains(&b) {
+                class.extend(other);
+                false
+            } else {
+                true
+            }
+        });
+        self.classes.push(class);
+    }
+
+    /// Records the column equalities among the conjuncts of `cond`.
+    pub fn add_predicate(&mut self, cond: &OptRelNodeRef) {
+        match &cond.typ {
+            OptRelNodeTyp::LogOp(LogOpType::And) => {
+                for child in &cond.child(0).children {
+                    self.add_predicate(child);
+                }
+            }
+            OptRelNodeTyp::BinOp(BinOpType::And) => {
+                self.add_predicate(&cond.child(0));
+                self.add_predicate(&cond.child(1));
+            }
+            OptRelNodeTyp::BinOp(BinOpType::Eq) => {
+                if let (Some(a), Some(b)) = (column_ref(&cond.child(0)), column_ref(&cond.child(1)))
+                {
+                    self.add_equality(a, b);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Appends the classes of `other`, whose columns come after the columns of `self`.
+    pub fn concat(&self, other: &Self) -> Self {
+        let mut classes = self.classes.clone();
+        classes.extend(
+            other
+                .classes
+                .iter()
+                .map(|class| class.iter().map(|col| col + self.width).collect()),
+        );
+        Self {
+            classes,
+            width: self.width + other.width,
+            expr: None,
+        }
+    }
+
+    /// Moves the columns of the classes to their positions in the output of a join with an output
+    /// column mapping.
+    fn map_columns(&self, mapping: &[usize]) -> Self {
+        let mut classes = Self::new(mapping.len());
+        classes.classes = self
+            .classes
+            .iter()
+            .map(|class| {
+                class
+                    .iter()
+                    .map(|col| mapping.iter().position(|x| x == col).unwrap())
+                    .collect()
+            })
+            .collect();
+        classes
+    }
+
+    /// Maps the classes to the output of a list of expressions over these columns, where an
+    /// output column takes the class of the column it references.
+    fn project(&self, exprs: &OptRelNodeRef) -> Self {
+        let mut classes = Self::new(exprs.children.len());
+        let columns = exprs.children.iter().map(column_ref).collect::<Vec<_>>();
+        for (i, a) in columns.iter().enumerate() {
+            for (j, b) in columns.iter().enumerate().skip(i + 1) {
+                if let (Some(a), Some(b)) = (a, b) {
+                    if self.is_equal(*a, *b) {
+                        classes.add_equality(i, j);
+                    }
+                }
+            }
+        }
+        classes
+    }
+}
+
+fn column_ref(expr: &OptRelNodeRef) -> Option<usize> {
+    (expr.typ == OptRelNodeTyp::ColumnRef).then(|| expr.data.as_ref().unwrap().as_i64() as usize)
+}
+
+pub struct EquivalencePropertyBuilder;
+
+impl EquivalencePropertyBuilder {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+fn join_classes(
+    join_type: JoinType,
+    left: &EquivalenceClasses,
+    right: &EquivalenceClasses,
+    cond: impl FnOnce(&mut EquivalenceClasses),
+) -> EquivalenceClasses {
+    // Columns of a side padded with nulls are no longer equal to anything.
+    let empty_left = EquivalenceClasses::new(left.width);
+    let empty_right = EquivalenceClasses::new(right.width);
+    match join_type {
+        JoinType::Inner | JoinType::Cross => {
+            let mut classes = left.concat(right);
+            cond(&mut classes);
+            classes
+        }
+        JoinType::LeftOuter => left.concat(&empty_right),
+        JoinType::RightOuter => empty_left.concat(right),
+        JoinType::FullOuter => empty_left.concat(&empty_right),
+        JoinType::LeftSemi | JoinType::LeftAnti => left.clone(),
+        JoinType::RightSemi | JoinType::RightAnti => right.clone(),
+    }
+}
+
+impl PropertyBuilder<OptRelNodeTyp> for EquivalencePropertyBuilder {
+    type Prop = EquivalenceClasses;
+
+    fn derive(
+        &self,
+        typ: OptRelNodeTyp,
+        data: Option<Value>,
+        children: &[&Self::Prop],
+    ) -> Self::Prop {
+        match typ {
+            OptRelNodeTyp::Scan | OptRelNodeTyp::PhysicalScan => {
+                // The pushed-down filters refer to the columns of the table.
+                let projection = children[0].expr.as_ref().unwrap();
+                let mut table = EquivalenceClasses::new(usize::MAX);
+                for filter in &children[1].expr.as_ref().unwrap().children {
+                    table.add_predicate(filter);
+                }
+                table.project(projection)
+            }
+            OptRelNodeTyp::Filter | OptRelNodeTyp::PhysicalFilter => {
+                let mut classes = children[0].clone();
+                classes.add_predicate(children[1].expr.as_ref().unwrap());
+                classes
+            }
+            OptRelNodeTyp::Projection | OptRelNodeTyp::PhysicalProjection => {
+                children[0].project(children[1].expr.as_ref().unwrap())
+            }
+            OptRelNodeTyp::PhysicalRuntimeFilter
+            | OptRelNodeTyp::Sort
+            | OptRelNodeTyp::PhysicalSort
+            | OptRelNodeTyp::PhysicalExchange(_)
+            | OptRelNodeTyp::PhysicalCollector(_)
+            | OptRelNodeTyp::PhysicalSpool(_) => children[0].clone(),
+            OptRelNodeTyp::Agg
+            | OptRelNodeTyp::PhysicalAgg
+            | OptRelNodeTyp::PhysicalStreamAgg
+            | OptRelNodeTyp::PhysicalTwoPhaseAgg => {
+                // The group by columns come first, followed by the aggregates.
+                let mut classes = children[0].project(children[2].expr.as_ref().unwrap());
+                classes.width += children[1].width;
+                classes
+            }
+            OptRelNodeTyp::Join(join_type) => {
+                let classes = join_classes(join_type, children[0], children[1], |classes| {
+                    classes.add_predicate(children[2].expr.as_ref().unwrap())
+                });
+                match join_mapping(&data) {
+                    Some(mapping) => classes.map_columns(&mapping),
+                    None => classes,
+                }
+            }
+            OptRelNodeTyp::PhysicalNestedLoopJoin(join_type) => {
+                join_classes(join_type, children[0], children[1], |classes| {
+                    classes.add_predicate(children[2].expr.as_ref().unwrap())
+                })
+            }
+            OptRelNodeTyp::PhysicalHashJoin(join_type) => {
+                join_classes(join_type, children[0], children[1], |classes| {
+                    let left_keys = &children[2].expr.as_ref().unwrap().children;
+                    let right_keys = &children[3].expr.as_ref().unwrap().children;
+                    for (left_key, right_key) in left_keys.iter().zip(right_keys) {
+                        if let (Some(a), Some(b)) = (column_ref(left_key), column_ref(right_key)) {
+                            classes.add_equality(a, b + children[0].width);
+                        }
+                    }
+                })
+            }
+            OptRelNodeTyp::Apply(apply_type) => join_classes(
+                apply_type.to_join_type(),
+                children[0],
+                children[1],
+                |classes| classes.add_predicate(children[2].expr.as_ref().unwrap()),
+            ),
+            typ if typ.is_expression() || typ == OptRelNodeTyp::List => {
+                let expr = Arc::new(RelNode {
+                    typ,
+                    children: children
+                        .iter()
+                        .map(|child| child.expr.clone().unwrap())
+                        .collect(),
+                    data,
+                });
+                EquivalenceClasses {
+                    classes: vec![],
+                    width: expr.children.len(),
+                    expr: Some(expr),
+                }
+            }
+            OptRelNodeTyp::Union | OptRelNodeTyp::PhysicalUnion => {
+                EquivalenceClasses::new(children[0].width)
+            }
+            OptRelNodeTyp::Empty
+            | OptRelNodeTyp::PhysicalEmpty
+            | OptRelNodeTyp::Values
+            | OptRelNodeTyp::PhysicalValues => {
+                EquivalenceClasses::new(Schema::from_value(data.as_ref().unwrap()).len())
+            }
+            _ => EquivalenceClasses::new(0),
+        }
+    }
+
+    fn property_name(&self) -> &'static str {
+        "equivalence"
+    }
+}
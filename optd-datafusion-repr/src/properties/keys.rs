@@ -0,0 +1,413 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use optd_core::{
+    property::PropertyBuilder,
+    rel_node::{RelNode, Value},
+};
+
+use super::schema::{Catalog, ForeignKey, Schema};
+use crate::plan_nodes::{
+    join_mapping, BinOpType, JoinType, LogOpType, OptRelNodeRef, OptRelNodeTyp,
+};
+
+/// The sets of output columns that uniquely identify a row of a plan node. An empty set means the
+/// node produces at most one row.
+#[derive(Clone)]
+pub struct Keys {
+    pub keys: Vec<BTreeSet<usize>>,
+    /// Functional dependencies among the output columns besides the keys, where the columns of the
+    /// first set determine the columns of the second set.
+    pub dependencies: Vec<(BTreeSet<usize>, BTreeSet<usize>)>,
+    /// The foreign keys among the output columns, which are never null and always reference an
+    /// existing row. The referenced columns are the columns of the referenced table.
+    pub foreign_keys: Vec<ForeignKey>,
+    /// The table and the table column of each output column, if the node produces every row of
+    /// the table exactly once.
+    pub table: Option<(String, Vec<usize>)>,
+    /// The number of output columns.
+    pub width: usize,
+    /// For expressions and lists, the expression tree rooted at this node.
+    expr: Option<OptRelNodeRef>,
+}
+
+impl std::fmt::Debug for Keys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.keys.iter()).finish()
+    }
+}
+
+impl Keys {
+    pub fn new(keys: Vec<BTreeSet<usize>>, width: usize) -> Self {
+        Self {
+            keys,
+            dependencies: vec![],
+            foreign_keys: vec![],
+            table: None,
+            width,
+            expr: None,
+        }
+    }
+
+    /// Gets the columns determined by `columns`, including themselves.
+    pub fn closure(&self, columns: &BTreeSet<usize>) -> BTreeSet<usize> {
+        let mut closure = columns.clone();
+        loop {
+            let len = closure.len();
+            for key in &self.keys {
+                if key.is_subset(&closure) {
+                    closure.extend(0..self.width);
+                }
+            }
+            for (from, to) in &self.dependencies {
+                if from.is_subset(&closure) {
+                    closure.extend(to);
+                }
+            }
+            if closure.len() == len {
+                return closure;
+            }
+        }
+    }
+
+    /// Checks whether the rows are unique on `columns`, i.e., they determine some key.
+    pub fn is_unique(&self, columns: &BTreeSet<usize>) -> bool {
+        let closure = self.closure(columns);
+        self.keys.iter().any(|key| key.is_subset(&closure))
+    }
+
+    /// Records the column equalities among the conjuncts of `cond`, each column of which
+    /// determines the other.
+    fn add_predicate(&mut self, cond: &OptRelNodeRef) {
+        match &cond.typ {
+            OptRelNodeTyp::LogOp(LogOpType::And) => {
+                for child in &cond.child(0).children {
+                    self.add_predicate(child);
+                }
+            }
+            OptRelNodeTyp::BinOp(BinOpType::And) => {
+                self.add_predicate(&cond.child(0));
+                self.add_predicate(&cond.child(1));
+            }
+            OptRelNodeTyp::BinOp(BinOpType::Eq) => {
+                if let (Some(a), Some(b)) = (column_ref(&cond.child(0)), column_ref(&cond.child(1)))
+                {
+                    self.add_equality(a, b);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn add_equality(&mut self, a: usize, b: usize) {
+        self.dependencies
+            .push((BTreeSet::from([a]), BTreeSet::from([b])));
+        self.dependencies
+            .push((BTreeSet::from([b]), BTreeSet::from([a])));
+    }
+
+    /// Moves the columns to `positions`, dropping everything that refers to a column without a
+    /// position.
+    fn remap(&self, positions: &HashMap<usize, usize>, width: usize) -> Self {
+        let remap_set = |set: &BTreeSet<usize>| {
+            set.iter()
+                .map(|col| positions.get(col).copied())
+                .collect::<Option<BTreeSet<_>>>()
+        };
+        let dependencies = self
+            .dependencies
+            .iter()
+            .filter_map(|(from, to)| {
+                let to = to
+                    .iter()
+                    .filter_map(|col| positions.get(col).copied())
+                    .collect();
+                Some((remap_set(from)?, to))
+            })
+            .collect();
+        let foreign_keys = self
+            .foreign_keys
+            .iter()
+            .filter_map(|foreign_key| {
+                Some(ForeignKey {
+                    columns: foreign_key
+                        .columns
+                        .iter()
+                        .map(|col| positions.get(col).copied())
+                        .collect::<Option<_>>()?,
+                    ..foreign_key.clone()
+                })
+            })
+            .collect();
+        Self {
+            keys: self.keys.iter().filter_map(remap_set).collect(),
+            dependencies,
+            foreign_keys,
+            table: None,
+            width,
+            expr: None,
+        }
+    }
+
+    /// Moves the columns to their positions in the output of a join with an output column
+    /// mapping.
+    fn map_columns(&self, mapping: &[usize]) -> Self {
+        let positions = mapping
+            .iter()
+            .enumerate()
+            .map(|(idx, col)| (*col, idx))
+            .collect::<HashMap<_, _>>();
+        self.remap(&positions, mapping.len())
+    }
+
+    fn shift(&self, offset: usize) -> Self {
+        let positions = (0..self.width).map(|col| (col, col + offset)).collect();
+        self.remap(&positions, self.width + offset)
+    }
+
+    /// Maps the columns to the output of a list of expressions over them, where the columns
+    /// projected as plain column references keep their keys and dependencies.
+    fn project(&self, exprs: &OptRelNodeRef) -> Self {
+        let mut positions = HashMap::new();
+        let columns = exprs.children.iter().map(column_ref).collect::<Vec<_>>();
+        for (idx, col) in columns.iter().enumerate() {
+            if let Some(col) = col {
+                positions.entry(*col).or_insert(idx);
+            }
+        }
+        let mut keys = self.remap(&positions, exprs.children.len());
+        keys.table = self.table.as_ref().and_then(|(table, table_columns)| {
+            let table_columns = columns
+                .iter()
+                .map(|col| Some(table_columns[(*col)?]))
+                .collect::<Option<_>>()?;
+            Some((table.clone(), table_columns))
+        });
+        keys
+    }
+}
+
+fn column_ref(expr: &OptRelNodeRef) -> Option<usize> {
+    (expr.typ == OptRelNodeTyp::ColumnRef).then(|| expr.data.as_ref().unwrap().as_i64() as usize)
+}
+
+pub struct KeyPropertyBuilder {
+    catalog: Arc<dyn Catalog>,
+}
+
+impl KeyPropertyBuilder {
+    pub fn new(catalog: Arc<dyn Catalog>) -> Self {
+        Self { catalog }
+    }
+
+    /// Gets the keys of every row of `table`. Unique keys with nullable columns are skipped, as
+    /// they may hold duplicate nulls, and so are foreign keys with nullable columns.
+    fn table_keys(&self, table: &str) -> Keys {
+        let schema = self.catalog.get(table);
+        let non_null = |col: &usize| !schema.field(*col).nullable;
+        let mut keys = Keys::new(
+            self.catalog
+                .unique_keys(table)
+                .into_iter()
+                .filter(|key| key.iter().all(non_null))
+                .collect(),
+            schema.len(),
+        );
+        keys.foreign_keys = self
+            .catalog
+            .foreign_keys(table)
+            .into_iter()
+            .filter(|foreign_key| foreign_key.columns.iter().all(non_null))
+            .collect();
+        keys.table = Some((table.to_string(), (0..schema.len()).collect()));
+        keys
+    }
+}
+
+fn join_keys(join_type: JoinType, left: &Keys, right: &Keys, cond: impl FnOnce(&mut Keys)) -> Keys {
+    match join_type {
+        JoinType::LeftSemi | JoinType::LeftAnti => {
+            return Keys {
+                table: None,
+                ..left.clone()
+            }
+        }
+        JoinType::RightSemi | JoinType::RightAnti => {
+            return Keys {
+                table: None,
+                ..right.clone()
+            }
+        }
+        _ => {}
+    }
+    // Every output row pairs one left row with one right row (or nulls), so a left key together
+    // with a right key is a key of the join.
+    let width = left.width + right.width;
+    let right = right.shift(left.width);
+    let mut keys = Keys::new(vec![], width);
+    for left_key in &left.keys {
+        for right_key in &right.keys {
+            keys.keys.push(left_key.union(right_key).copied().collect());
+        }
+    }
+    // A key of each side still determines the columns of its side.
+    keys.dependencies = [left.dependencies.clone(), right.dependencies.clone()].concat();
+    keys.dependencies.extend(
+        left.keys
+            .iter()
+            .map(|key| (key.clone(), (0..left.width).collect())),
+    );
+    keys.dependencies.extend(
+        right
+            .keys
+            .iter()
+            .map(|key| (key.clone(), (left.width..width).collect())),
+    );
+    // The foreign keys of a side padded with nulls may be null.
+    keys.foreign_keys = match join_type {
+        JoinType::Inner | JoinType::Cross => {
+            [left.foreign_keys.clone(), right.foreign_keys.clone()].concat()
+        }
+        JoinType::LeftOuter => left.foreign_keys.clone(),
+        JoinType::RightOuter => right.foreign_keys.clone(),
+        _ => vec![],
+    };
+    if matches!(join_type, JoinType::Inner | JoinType::Cross) {
+        cond(&mut keys);
+    }
+    // A key of one side remains a key if each of its rows joins at most one row of the other
+    // side, e.g., when the join condition equates a key of the other side with its columns.
+    for key in left.keys.iter().chain(&right.keys) {
+        if !keys.keys.contains(key) && keys.is_unique(key) {
+            keys.keys.push(key.clone());
+        }
+    }
+    keys
+}
+
+impl PropertyBuilder<OptRelNodeTyp> for KeyPropertyBuilder {
+    type Prop = Keys;
+
+    fn derive(
+        &self,
+        typ: OptRelNodeTyp,
+        data: Option<Value>,
+        children: &[&Self::Prop],
+    ) -> Self::Prop {
+        match typ {
+            OptRelNodeTyp::Scan | OptRelNodeTyp::PhysicalScan => {
+                let table = data.as_ref().unwrap().as_str();
+                let mut keys = self
+                    .table_keys(&table)
+                    .project(children[0].expr.as_ref().unwrap());
+                // Pushed-down filters and limits drop rows, which keeps the foreign keys valid but
+                // no longer produces every row of the table.
+                let fetch = children[2].expr.as_ref().unwrap();
+                if children[1].width > 0 || !fetch.data.as_ref().unwrap().is_null() {
+                    keys.table = None;
+                }
+                keys
+            }
+            OptRelNodeTyp::Projection | OptRelNodeTyp::PhysicalProjection => {
+                // A key survives if all of its columns are projected as plain column references.
+                children[0].project(children[1].expr.as_ref().unwrap())
+            }
+            OptRelNodeTyp::Filter | OptRelNodeTyp::PhysicalFilter => {
+                let mut keys = children[0].clone();
+                keys.table = None;
+                keys.add_predicate(children[1].expr.as_ref().unwrap());
+                keys
+            }
+            OptRelNodeTyp::PhysicalRuntimeFilter => Keys {
+                table: None,
+                ..children[0].clone()
+            },
+            OptRelNodeTyp::Sort
+            | OptRelNodeTyp::PhysicalSort
+            | OptRelNodeTyp::PhysicalExchange(_)
+            | OptRelNodeTyp::PhysicalCollector(_)
+            | OptRelNodeTyp::PhysicalSpool(_) => children[0].clone(),
+            OptRelNodeTyp::Agg
+            | OptRelNodeTyp::PhysicalAgg
+            | OptRelNodeTyp::PhysicalStreamAgg
+            | OptRelNodeTyp::PhysicalTwoPhaseAgg => {
+                // The group by columns come first and are unique after aggregation.
+                let group_cnt = children[2].width;
+                Keys::new(
+                    vec![(0..group_cnt).collect()],
+                    group_cnt + children[1].width,
+                )
+            }
+            OptRelNodeTyp::Join(join_type) => {
+                let keys = join_keys(join_type, children[0], children[1], |keys| {
+                    keys.add_predicate(children[2].expr.as_ref().unwrap())
+                });
+                match join_mapping(&data) {
+                    Some(mapping) => keys.map_columns(&mapping),
+                    None => keys,
+                }
+            }
+            OptRelNodeTyp::PhysicalNestedLoopJoin(join_type) => {
+                join_keys(join_type, children[0], children[1], |keys| {
+                    keys.add_predicate(children[2].expr.as_ref().unwrap())
+                })
+            }
+            OptRelNodeTyp::PhysicalHashJoin(join_type) => {
+                join_keys(join_type, children[0], children[1], |keys| {
+                    let left_keys = &children[2].expr.as_ref().unwrap().children;
+                    let right_keys = &children[3].expr.as_ref().unwrap().children;
+                    for (left_key, right_key) in left_keys.iter().zip(right_keys) {
+                        if let (Some(a), Some(b)) = (column_ref(left_key), column_ref(right_key)) {
+                            keys.add_equality(a, b + children[0].width);
+                        }
+                    }
+                })
+            }
+            OptRelNodeTyp::Apply(apply_type) => join_keys(
+                apply_type.to_join_type(),
+                children[0],
+                children[1],
+                |keys| keys.add_predicate(children[2].expr.as_ref().unwrap()),
+            ),
+            typ if typ.is_expression() || typ == OptRelNodeTyp::List => {
+                let expr = Arc::new(RelNode {
+                    typ,
+                    children: children
+                        .iter()
+                        .map(|child| child.expr.clone().unwrap())
+                        .collect(),
+                    data,
+                });
+                Keys {
+                    width: expr.children.len(),
+                    expr: Some(expr),
+                    ..Keys::new(vec![], 0)
+                }
+            }
+            OptRelNodeTyp::Empty | OptRelNodeTyp::PhysicalEmpty => {
+                // Any set of columns is unique in a relation without rows.
+                let width = Schema::from_value(data.as_ref().unwrap()).len();
+                Keys::new(vec![BTreeSet::new()], width)
+            }
+            // The same row may come from both inputs.
+            OptRelNodeTyp::Union | OptRelNodeTyp::PhysicalUnion => {
+                Keys::new(vec![], children[0].width)
+            }
+            OptRelNodeTyp::Values | OptRelNodeTyp::PhysicalValues => {
+                // Any set of columns is unique if there is at most one row, and the width of the
+                // list of rows is the number of rows.
+                let width = Schema::from_value(data.as_ref().unwrap()).len();
+                if children[0].width <= 1 {
+                    Keys::new(vec![BTreeSet::new()], width)
+                } else {
+                    Keys::new(vec![], width)
+                }
+            }
+            _ => Keys::new(vec![], 0),
+        }
+    }
+
+    fn property_name(&self) -> &'static str {
+        "keys"
+    }
+}
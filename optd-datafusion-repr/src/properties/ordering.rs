@@ -0,0 +1,97 @@
+use optd_core::{property::PropertyBuilder, rel_node::Value};
+
+use crate::plan_nodes::OptRelNodeTyp;
+
+/// The order of the output rows of a plan node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Ordering {
+    /// Rows are sorted on the given output columns, from the most significant one, so that rows
+    /// with equal values on any prefix of the columns are adjacent. Rows not known to be sorted
+    /// have no columns.
+    Sorted(Vec<usize>),
+    /// The column referenced by an expression, or the columns of a list of column references or
+    /// sort orders. Only used while deriving the ordering of plan nodes.
+    Columns(Vec<Option<usize>>),
+}
+
+impl Ordering {
+    /// Checks whether rows with equal values on `columns` are adjacent, i.e., `columns` are the
+    /// leading sort columns in some order.
+    pub fn is_grouped_on(&self, columns: &[usize]) -> bool {
+        match self {
+            Self::Sorted(sorted) => {
+                !columns.is_empty()
+                    && sorted.len() >= columns.len()
+                    && sorted[..columns.len()]
+                        .iter()
+                        .all(|col| columns.contains(col))
+            }
+            Self::Columns(_) => false,
+        }
+    }
+}
+
+pub struct OrderingPropertyBuilder;
+
+impl OrderingPropertyBuilder {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl PropertyBuilder<OptRelNodeTyp> for OrderingPropertyBuilder {
+    type Prop = Ordering;
+
+    fn derive(
+        &self,
+        typ: OptRelNodeTyp,
+        data: Option<Value>,
+        children: &[&Self::Prop],
+    ) -> Self::Prop {
+        match typ {
+            // The rows are sorted on the leading sort orders that are column references.
+            OptRelNodeTyp::Sort | OptRelNodeTyp::PhysicalSort => match children[1] {
+                Ordering::Columns(columns) => {
+                    Ordering::Sorted(columns.iter().map_while(|col| *col).collect())
+                }
+                _ => Ordering::Sorted(vec![]),
+            },
+            OptRelNodeTyp::Filter
+            | OptRelNodeTyp::PhysicalFilter
+            | OptRelNodeTyp::PhysicalRuntimeFilter
+            | OptRelNodeTyp::PhysicalCollector(_)
+            | OptRelNodeTyp::PhysicalSpool(_) => children[0].clone(),
+            OptRelNodeTyp::Projection | OptRelNodeTyp::PhysicalProjection => {
+                match (children[0], children[1]) {
+                    (Ordering::Sorted(sorted), Ordering::Columns(columns)) => Ordering::Sorted(
+                        sorted
+                            .iter()
+                            .map_while(|key| columns.iter().position(|col| *col == Some(*key)))
+                            .collect(),
+                    ),
+                    _ => Ordering::Sorted(vec![]),
+                }
+            }
+            OptRelNodeTyp::ColumnRef => {
+                Ordering::Columns(vec![Some(data.unwrap().as_i64() as usize)])
+            }
+            OptRelNodeTyp::SortOrder(_) => children[0].clone(),
+            OptRelNodeTyp::List => Ordering::Columns(
+                children
+                    .iter()
+                    .map(|child| match child {
+                        Ordering::Columns(columns) if columns.len() == 1 => columns[0],
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            typ if typ.is_expression() => Ordering::Columns(vec![None]),
+            // Exchanges, joins and hash aggregations do not keep the order of their inputs.
+            _ => Ordering::Sorted(vec![]),
+        }
+    }
+
+    fn property_name(&self) -> &'static str {
+        "ordering"
+    }
+}
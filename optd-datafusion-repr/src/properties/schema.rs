@@ -1,33 +1,485 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use itertools::Itertools;
+use optd_core::{
+    property::PropertyBuilder,
+    rel_node::{RelNode, Value},
+};
+
+use crate::{
+    plan_nodes::{
+        join_mapping, list_column_ids, ColumnId, ConstantType, Expr, FuncType, IntervalUnit,
+        JoinType, OptRelNodeRef, OptRelNodeTyp, TimeUnit, UnOpType,
+    },
+    typing::expr_type,
 };
 
-use optd_core::property::PropertyBuilder;
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Field {
+    pub name: String,
+    /// The table (or alias) the column comes from, if any.
+    pub qualifier: Option<String>,
+    pub typ: ConstantType,
+    pub nullable: bool,
+    /// The estimated number of distinct values in the column, if known.
+    pub ndv: Option<usize>,
+    /// The id of the column, if column ids are assigned.
+    pub id: Option<ColumnId>,
+}
+
+impl Field {
+    pub fn new(name: impl Into<String>, typ: ConstantType, nullable: bool) -> Self {
+        Self {
+            name: name.into(),
+            qualifier: None,
+            typ,
+            nullable,
+            ndv: None,
+            id: None,
+        }
+    }
+
+    pub fn with_qualifier(mut self, qualifier: impl Into<String>) -> Self {
+        self.qualifier = Some(qualifier.into());
+        self
+    }
+
+    pub fn with_nullable(mut self, nullable: bool) -> Self {
+        self.nullable = nullable;
+        self
+    }
+
+    pub fn with_id(mut self, id: Option<ColumnId>) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn with_ndv(mut self, ndv: Option<usize>) -> Self {
+        self.ndv = ndv;
+        self
+    }
 
-use crate::plan_nodes::{ConstantType, OptRelNodeTyp};
+    /// Gets the name of the field prefixed with its qualifier, e.g., `t1.a`.
+    pub fn qualified_name(&self) -> String {
+        match &self.qualifier {
+            Some(qualifier) => format!("{}.{}", qualifier, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
 
-#[derive(Clone, Debug)]
-pub struct Schema(pub Vec<ConstantType>);
+#[derive(Clone)]
+pub struct Schema {
+    pub fields: Vec<Field>,
+    /// For expressions and lists, the expression tree rooted at this node, so that the plan node
+    /// owning the expression can type it against its input schema.
+    pub(crate) expr: Option<OptRelNodeRef>,
+}
+
+impl std::fmt::Debug for Schema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.fields.iter()).finish()
+    }
+}
 
 impl Schema {
+    pub fn new(fields: Vec<Field>) -> Self {
+        Self { fields, expr: None }
+    }
+
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn field(&self, idx: usize) -> &Field {
+        &self.fields[idx]
+    }
+
+    /// Finds the index of a column by name, optionally restricted to a qualifier.
+    pub fn index_of(&self, qualifier: Option<&str>, name: &str) -> Option<usize> {
+        self.fields.iter().position(|field| {
+            field.name == name && (qualifier.is_none() || field.qualifier.as_deref() == qualifier)
+        })
+    }
+
+    /// Finds the index of a column by its id.
+    pub fn index_of_id(&self, id: ColumnId) -> Option<usize> {
+        self.fields.iter().position(|field| field.id == Some(id))
+    }
+
+    pub(crate) fn concat(&self, other: &Schema) -> Schema {
+        Schema::new(
+            self.fields
+                .iter()
+                .chain(other.fields.iter())
+                .cloned()
+                .collect(),
+        )
+    }
+
+    fn with_all_nullable(&self) -> Schema {
+        Schema::new(
+            self.fields
+                .iter()
+                .map(|field| field.clone().with_nullable(true))
+                .collect(),
+        )
+    }
+
+    /// Creates the schema property of an expression, which has no fields.
+    pub(crate) fn from_expr(expr: OptRelNodeRef) -> Schema {
+        Schema {
+            fields: vec![],
+            expr: Some(expr),
+        }
+    }
+}
+
+/// Encodes schemas into the data of plan nodes that have no input to derive them from, e.g., an
+/// empty relation. The fields are written in order as their name, qualifier, type, nullability and
+/// id, where strings are prefixed by their length and optional values by a presence byte.
+impl Schema {
+    pub fn to_value(&self) -> Value {
+        fn put_str(bytes: &mut Vec<u8>, s: &str) {
+            bytes.extend((s.len() as u32).to_le_bytes());
+            bytes.extend(s.as_bytes());
+        }
+        fn put_type(bytes: &mut Vec<u8>, typ: &ConstantType) {
+            let (tag, params): (u8, Vec<u8>) = match typ {
+                ConstantType::Any => (0, vec![]),
+                ConstantType::Null => (1, vec![]),
+                ConstantType::Bool => (2, vec![]),
+                ConstantType::Int8 => (3, vec![]),
+                ConstantType::Int16 => (4, vec![]),
+                ConstantType::Int32 => (5, vec![]),
+                ConstantType::Int64 => (6, vec![]),
+                ConstantType::UInt8 => (7, vec![]),
+                ConstantType::UInt16 => (8, vec![]),
+                ConstantType::UInt32 => (9, vec![]),
+                ConstantType::UInt64 => (10, vec![]),
+                ConstantType::Float32 => (11, vec![]),
+                ConstantType::Float64 => (12, vec![]),
+                ConstantType::Decimal128(precision, scale) => (13, vec![*precision, *scale as u8]),
+                ConstantType::Date32 => (14, vec![]),
+                ConstantType::Date64 => (15, vec![]),
+                ConstantType::Timestamp(unit, tz) => {
+                    let mut params = vec![*unit as u8, tz.is_some() as u8];
+                    if let Some(tz) = tz {
+                        put_str(&mut params, tz);
+                    }
+                    (16, params)
+                }
+                ConstantType::Interval(unit) => (17, vec![*unit as u8]),
+                ConstantType::Utf8String => (18, vec![]),
+                ConstantType::Binary => (19, vec![]),
+            };
+            bytes.push(tag);
+            bytes.extend(params);
+        }
+
+        let mut bytes = vec![];
+        bytes.extend((self.fields.len() as u32).to_le_bytes());
+        for field in &self.fields {
+            put_str(&mut bytes, &field.name);
+            bytes.push(field.qualifier.is_some() as u8);
+            if let Some(qualifier) = &field.qualifier {
+                put_str(&mut bytes, qualifier);
+            }
+            put_type(&mut bytes, &field.typ);
+            bytes.push(field.nullable as u8);
+            bytes.push(field.id.is_some() as u8);
+            if let Some(id) = field.id {
+                bytes.extend(id.to_le_bytes());
+            }
+        }
+        Value::Serialized(bytes.into())
+    }
+
+    pub fn from_value(value: &Value) -> Schema {
+        struct Reader<'a>(&'a [u8]);
+        impl Reader<'_> {
+            fn take(&mut self, len: usize) -> &[u8] {
+                let (head, tail) = self.0.split_at(len);
+                self.0 = tail;
+                head
+            }
+            fn u8(&mut self) -> u8 {
+                self.take(1)[0]
+            }
+            fn u32(&mut self) -> u32 {
+                u32::from_le_bytes(self.take(4).try_into().unwrap())
+            }
+            fn str(&mut self) -> String {
+                let len = self.u32() as usize;
+                String::from_utf8(self.take(len).to_vec()).unwrap()
+            }
+            fn typ(&mut self) -> ConstantType {
+                match self.u8() {
+                    0 => ConstantType::Any,
+                    1 => ConstantType::Null,
+                    2 => ConstantType::Bool,
+                    3 => ConstantType::Int8,
+                    4 => ConstantType::Int16,
+                    5 => ConstantType::Int32,
+                    6 => ConstantType::Int64,
+                    7 => ConstantType::UInt8,
+                    8 => ConstantType::UInt16,
+                    9 => ConstantType::UInt32,
+                    10 => ConstantType::UInt64,
+                    11 => ConstantType::Float32,
+                    12 => ConstantType::Float64,
+                    13 => ConstantType::Decimal128(self.u8(), self.u8() as i8),
+                    14 => ConstantType::Date32,
+                    15 => ConstantType::Date64,
+                    16 => {
+                        let unit = match self.u8() {
+                            0 => TimeUnit::Second,
+                            1 => TimeUnit::Millisecond,
+                            2 => TimeUnit::Microsecond,
+                            _ => TimeUnit::Nanosecond,
+                        };
+                        let tz = (self.u8() != 0).then(|| self.str().into());
+                        ConstantType::Timestamp(unit, tz)
+                    }
+                    17 => ConstantType::Interval(match self.u8() {
+                        0 => IntervalUnit::YearMonth,
+                        1 => IntervalUnit::DayTime,
+                        _ => IntervalUnit::MonthDayNano,
+                    }),
+                    18 => ConstantType::Utf8String,
+                    _ => ConstantType::Binary,
+                }
+            }
+        }
+
+        let bytes = value.as_bytes();
+        let mut reader = Reader(&bytes);
+        let len = reader.u32();
+        let fields = (0..len)
+            .map(|_| {
+                let name = reader.str();
+                let qualifier = (reader.u8() != 0).then(|| reader.str());
+                let typ = reader.typ();
+                let nullable = reader.u8() != 0;
+                let id = (reader.u8() != 0)
+                    .then(|| ColumnId::from_le_bytes(reader.take(8).try_into().unwrap()));
+                Field {
+                    qualifier,
+                    id,
+                    ..Field::new(name, typ, nullable)
+                }
+            })
+            .collect();
+        Schema::new(fields)
     }
 }
 
+/// How a predicate can be evaluated by the scan of a table.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FilterPushDown {
+    /// The scan cannot use the predicate.
+    Unsupported,
+    /// The scan uses the predicate to skip data, but may still return rows that do not satisfy
+    /// it, so the predicate must also be evaluated above the scan.
+    Inexact,
+    /// The scan only returns rows that satisfy the predicate.
+    Exact,
+}
+
+/// A foreign key of a table, whose values in `columns` are either null or the values of the unique
+/// key `referenced_columns` of a row in `referenced_table`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ForeignKey {
+    pub columns: Vec<usize>,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<usize>,
+}
+
 pub trait Catalog: Send + Sync + 'static {
     fn get(&self, name: &str) -> Schema;
+
+    /// The primary and unique keys of `table`, as sets of column indices.
+    fn unique_keys(&self, _table: &str) -> Vec<BTreeSet<usize>> {
+        vec![]
+    }
+
+    /// The foreign keys of `table`.
+    fn foreign_keys(&self, _table: &str) -> Vec<ForeignKey> {
+        vec![]
+    }
+
+    /// Decides whether `filter`, which refers to the columns of the table schema, can be pushed
+    /// into the scan of `table`.
+    fn filter_pushdown(&self, _table: &str, _filter: &Expr) -> FilterPushDown {
+        FilterPushDown::Unsupported
+    }
+
+    /// A number that changes whenever the tables, their constraints or their statistics change.
+    /// Plans optimized with another version are no longer cached.
+    fn version(&self) -> u64 {
+        0
+    }
 }
 
 pub struct SchemaPropertyBuilder {
-    catalog: Box<dyn Catalog>,
+    catalog: Arc<dyn Catalog>,
 }
 
 impl SchemaPropertyBuilder {
-    pub fn new(catalog: Box<dyn Catalog>) -> Self {
+    pub fn new(catalog: Arc<dyn Catalog>) -> Self {
         Self { catalog }
     }
+
+    /// Gets the schema of `table`, with the columns qualified by the table name.
+    pub(crate) fn table_schema(&self, table: &str) -> Schema {
+        let schema = self.catalog.get(table);
+        Schema::new(
+            schema
+                .fields
+                .into_iter()
+                .map(|field| {
+                    if field.qualifier.is_none() {
+                        field.with_qualifier(table)
+                    } else {
+                        field
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Derives the schema property of a plan or expression tree outside of the memo.
+    pub fn derive_tree(&self, node: &OptRelNodeRef) -> Schema {
+        let children = node
+            .children
+            .iter()
+            .map(|child| self.derive_tree(child))
+            .collect::<Vec<_>>();
+        self.derive(
+            node.typ.clone(),
+            node.data.clone(),
+            &children.iter().collect::<Vec<_>>(),
+        )
+    }
+}
+
+/// Computes the output schema of a join (or apply) of the given type.
+pub fn join_schema(join_type: JoinType, left: &Schema, right: &Schema) -> Schema {
+    match join_type {
+        JoinType::Inner | JoinType::Cross => left.concat(right),
+        JoinType::LeftOuter => left.concat(&right.with_all_nullable()),
+        JoinType::RightOuter => left.with_all_nullable().concat(right),
+        JoinType::FullOuter => left.with_all_nullable().concat(&right.with_all_nullable()),
+        JoinType::LeftSemi | JoinType::LeftAnti => left.clone(),
+        JoinType::RightSemi | JoinType::RightAnti => right.clone(),
+    }
+}
+
+/// Computes the output fields of a list of expressions evaluated over `input`. A column reference
+/// keeps the id of the column, unless the list assigns ids to its columns.
+pub fn expr_list_schema(exprs: &OptRelNodeRef, input: &Schema) -> Schema {
+    assert_eq!(exprs.typ, OptRelNodeTyp::List);
+    let fields = exprs.children.iter().map(|expr| expr_field(expr, input));
+    match list_column_ids(&exprs.data) {
+        Some(ids) => Schema::new(
+            fields
+                .zip(ids)
+                .map(|(field, id)| field.with_id(Some(id)))
+                .collect(),
+        ),
+        None => Schema::new(fields.collect()),
+    }
+}
+
+/// Computes the output field of an expression evaluated over `input`.
+pub fn expr_field(expr: &OptRelNodeRef, input: &Schema) -> Field {
+    if let OptRelNodeTyp::ColumnRef = expr.typ {
+        let idx = expr.data.as_ref().unwrap().as_i64() as usize;
+        return input.field(idx).clone();
+    }
+    if let Some(idx) = column_id_index(expr, input) {
+        return input.field(idx).clone();
+    }
+    Field::new(
+        expr_name(expr, input),
+        expr_type(expr, input),
+        expr_nullable(expr, input),
+    )
+}
+
+/// Resolves a column id reference to the index of the column in `input`.
+fn column_id_index(expr: &OptRelNodeRef, input: &Schema) -> Option<usize> {
+    if expr.typ != OptRelNodeTyp::ColumnId {
+        return None;
+    }
+    input.index_of_id(expr.data.as_ref().unwrap().as_u64())
+}
+
+/// Generates a display name for an expression, resolving column references against `input`.
+pub fn expr_name(expr: &OptRelNodeRef, input: &Schema) -> String {
+    let children_names = || {
+        expr.children
+            .iter()
+            .map(|child| expr_name(child, input))
+            .join(", ")
+    };
+    match &expr.typ {
+        OptRelNodeTyp::ColumnRef => {
+            let idx = expr.data.as_ref().unwrap().as_i64() as usize;
+            match input.fields.get(idx) {
+                Some(field) => field.name.clone(),
+                None => format!("#{}", idx),
+            }
+        }
+        OptRelNodeTyp::ColumnId => match column_id_index(expr, input) {
+            Some(idx) => input.field(idx).name.clone(),
+            None => format!("${}", expr.data.as_ref().unwrap()),
+        },
+        OptRelNodeTyp::Constant(_) => expr.data.as_ref().unwrap().to_string(),
+        OptRelNodeTyp::Parameter(_) => format!("${}", expr.data.as_ref().unwrap()),
+        OptRelNodeTyp::UnOp(UnOpType::Neg) => format!("(- {})", children_names()),
+        OptRelNodeTyp::UnOp(UnOpType::Not) => format!("NOT {}", children_names()),
+        OptRelNodeTyp::BinOp(op) => format!("{}({})", op, children_names()),
+        OptRelNodeTyp::Func(FuncType::Scalar(func)) => {
+            format!("{}({})", func, expr_name(&expr.child(0), input))
+        }
+        OptRelNodeTyp::Func(FuncType::Agg(func)) => {
+            format!("{}({})", func, expr_name(&expr.child(0), input))
+        }
+        OptRelNodeTyp::Func(FuncType::Case) => {
+            format!("CASE({})", expr_name(&expr.child(0), input))
+        }
+        OptRelNodeTyp::LogOp(op) => format!("{}({})", op, expr_name(&expr.child(0), input)),
+        OptRelNodeTyp::SortOrder(_) => expr_name(&expr.child(0), input),
+        OptRelNodeTyp::List => children_names(),
+        typ => unreachable!("{} is not an expression", typ),
+    }
+}
+
+/// Infers whether an expression evaluated over `input` may produce nulls.
+pub fn expr_nullable(expr: &OptRelNodeRef, input: &Schema) -> bool {
+    match &expr.typ {
+        OptRelNodeTyp::ColumnRef => {
+            let idx = expr.data.as_ref().unwrap().as_i64() as usize;
+            input.field(idx).nullable
+        }
+        OptRelNodeTyp::ColumnId => match column_id_index(expr, input) {
+            Some(idx) => input.field(idx).nullable,
+            None => true,
+        },
+        OptRelNodeTyp::Constant(_) => expr.data.as_ref().unwrap().is_null(),
+        // A parameter may be bound to null.
+        OptRelNodeTyp::Parameter(_) => true,
+        OptRelNodeTyp::Func(FuncType::Agg(datafusion_expr::AggregateFunction::Count)) => false,
+        // Aggregates without group by return null on empty input.
+        OptRelNodeTyp::Func(FuncType::Agg(_)) => true,
+        _ => expr.children.iter().any(|x| expr_nullable(x, input)),
+    }
 }
 
 impl PropertyBuilder<OptRelNodeTyp> for SchemaPropertyBuilder {
@@ -36,23 +488,84 @@ impl PropertyBuilder<OptRelNodeTyp> for SchemaPropertyBuilder {
     fn derive(
         &self,
         typ: OptRelNodeTyp,
-        data: Option<optd_core::rel_node::Value>,
+        data: Option<Value>,
         children: &[&Self::Prop],
     ) -> Self::Prop {
         match typ {
-            OptRelNodeTyp::Scan => {
+            OptRelNodeTyp::Scan | OptRelNodeTyp::PhysicalScan => {
                 let name = data.unwrap().as_str().to_string();
-                self.catalog.get(&name)
+                expr_list_schema(
+                    children[0].expr.as_ref().unwrap(),
+                    &self.table_schema(&name),
+                )
+            }
+            OptRelNodeTyp::Projection | OptRelNodeTyp::PhysicalProjection => {
+                expr_list_schema(children[1].expr.as_ref().unwrap(), children[0])
+            }
+            OptRelNodeTyp::Empty
+            | OptRelNodeTyp::PhysicalEmpty
+            | OptRelNodeTyp::Values
+            | OptRelNodeTyp::PhysicalValues => Schema::from_value(data.as_ref().unwrap()),
+            OptRelNodeTyp::Union | OptRelNodeTyp::PhysicalUnion => Schema::new(
+                children[0]
+                    .fields
+                    .iter()
+                    .zip(&children[1].fields)
+                    .map(|(left, right)| Field {
+                        nullable: left.nullable || right.nullable,
+                        // A value may come from either input.
+                        ndv: left.ndv.zip(right.ndv).map(|(left, right)| left + right),
+                        ..left.clone()
+                    })
+                    .collect(),
+            ),
+            OptRelNodeTyp::Filter
+            | OptRelNodeTyp::PhysicalFilter
+            | OptRelNodeTyp::PhysicalRuntimeFilter
+            | OptRelNodeTyp::Sort
+            | OptRelNodeTyp::PhysicalSort
+            | OptRelNodeTyp::PhysicalExchange(_)
+            | OptRelNodeTyp::PhysicalCollector(_)
+            | OptRelNodeTyp::PhysicalSpool(_) => children[0].clone(),
+            OptRelNodeTyp::Agg
+            | OptRelNodeTyp::PhysicalAgg
+            | OptRelNodeTyp::PhysicalStreamAgg
+            | OptRelNodeTyp::PhysicalTwoPhaseAgg => {
+                // The output of an aggregation is the group by columns followed by the aggregates.
+                let aggrs = expr_list_schema(children[1].expr.as_ref().unwrap(), children[0]);
+                let groups = expr_list_schema(children[2].expr.as_ref().unwrap(), children[0]);
+                groups.concat(&aggrs)
+            }
+            OptRelNodeTyp::Join(join_type) => {
+                let schema = join_schema(join_type, children[0], children[1]);
+                match join_mapping(&data) {
+                    Some(mapping) => Schema::new(
+                        mapping
+                            .iter()
+                            .map(|col| schema.field(*col).clone())
+                            .collect(),
+                    ),
+                    None => schema,
+                }
+            }
+            OptRelNodeTyp::PhysicalHashJoin(join_type)
+            | OptRelNodeTyp::PhysicalNestedLoopJoin(join_type) => {
+                join_schema(join_type, children[0], children[1])
+            }
+            OptRelNodeTyp::Apply(apply_type) => {
+                join_schema(apply_type.to_join_type(), children[0], children[1])
             }
-            OptRelNodeTyp::Projection => children[1].clone(),
-            OptRelNodeTyp::Filter => children[0].clone(),
-            OptRelNodeTyp::Join(_) => {
-                let mut schema = children[0].clone();
-                schema.0.extend(children[1].clone().0);
-                schema
+            typ if typ.is_expression() || typ == OptRelNodeTyp::List => {
+                Schema::from_expr(Arc::new(RelNode {
+                    typ,
+                    children: children
+                        .iter()
+                        .map(|child| child.expr.clone().unwrap())
+                        .collect(),
+                    data,
+                }))
             }
-            OptRelNodeTyp::List => Schema(vec![ConstantType::Any; children.len()]),
-            _ => Schema(vec![]),
+            _ => Schema::new(vec![]),
         }
     }
 
@@ -1,8 +1,36 @@
+mod agg;
+mod constraints;
+mod distinct;
+mod eager_agg;
+mod empty;
+mod exchange;
+mod filter;
 // mod filter_join;
 mod joins;
 mod macros;
+mod outer_join;
 mod physical;
+mod projection;
+mod simplify;
 
+pub use agg::{HashAggRule, PartitionedAggRule, StreamAggRule, TwoPhaseAggRule};
+pub use constraints::{AggOnKeyRemovalRule, JoinEliminationRule};
+pub use distinct::{DistinctUnionRule, SemiJoinDistinctRule};
+pub use eager_agg::{EagerAggregationRule, LazyAggregationRule};
+pub use empty::EmptyPropagationRule;
+pub use filter::FilterPushDownScan;
+pub(crate) use filter::{and_conjuncts, split_conjuncts};
 // pub use filter_join::FilterJoinPullUpRule;
-pub use joins::{HashJoinRule, JoinAssocRule, JoinCommuteRule, ProjectionPullUpJoin};
+pub use joins::{
+    HashJoinRule, JoinAssocReverseRule, JoinAssocRule, JoinCommuteRule, PartitionedHashJoinRule,
+    ProjectionPullUpJoin, RuntimeFilterHashJoinRule,
+};
+pub use outer_join::{
+    OuterJoinAssocReverseRule, OuterJoinAssocRule, OuterJoinCommuteRule, OuterJoinSimplifyRule,
+};
 pub use physical::PhysicalConversionRule;
+pub use projection::{
+    ProjectionMergeRule, ProjectionPushDownAgg, ProjectionPushDownFilter, ProjectionPushDownJoin,
+    ProjectionPushDownScan,
+};
+pub use simplify::{SimplifyFilterRule, SimplifyJoinRule};
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use optd_core::optimizer::Optimizer;
+use optd_core::rel_node::RelNode;
+use optd_core::rules::{Rule, RuleMatcher};
+
+use super::exchange::{distribution, enforce, key_columns};
+use super::macros::define_impl_rule;
+use crate::plan_nodes::{
+    ExprList, OptRelNode, OptRelNodeTyp, PhysicalAgg, PhysicalStreamAgg, PhysicalTwoPhaseAgg,
+    PlanNode,
+};
+use crate::properties::distribution::Distribution;
+use crate::properties::ordering::{Ordering, OrderingPropertyBuilder};
+
+fn ordering(optimizer: &impl Optimizer<OptRelNodeTyp>, node: &RelNode<OptRelNodeTyp>) -> Ordering {
+    optimizer.get_property::<OrderingPropertyBuilder>(Arc::new(node.clone()), 4)
+}
+
+define_impl_rule!(HashAggRule, apply_hash_agg, (Agg, child, [exprs], [groups]));
+
+fn apply_hash_agg(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    HashAggRulePicks {
+        child,
+        exprs,
+        groups,
+    }: HashAggRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let exprs = ExprList::from_rel_node(exprs.into()).unwrap();
+    let groups = ExprList::from_rel_node(groups.into()).unwrap();
+    // Each partition is aggregated on its own, so the rows of a group must not be spread over
+    // several partitions: the input is gathered into one unless it is colocated on the groups.
+    let colocated = key_columns(&groups)
+        .is_some_and(|cols| distribution(optimizer, &child).is_colocated_on(&cols));
+    let required = if colocated {
+        Distribution::Any
+    } else {
+        Distribution::Single
+    };
+    let node = PhysicalAgg::new(enforce(optimizer, child, &required), exprs, groups);
+    vec![node.into_rel_node().as_ref().clone()]
+}
+
+// Agg A exprs groups -> StreamAgg A exprs groups, if the rows of A with equal group by columns are
+// adjacent, e.g., A is sorted on them.
+define_impl_rule!(
+    StreamAggRule,
+    apply_stream_agg,
+    (Agg, child, [exprs], [groups])
+);
+
+fn apply_stream_agg(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    StreamAggRulePicks {
+        child,
+        exprs,
+        groups,
+    }: StreamAggRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let exprs = ExprList::from_rel_node(exprs.into()).unwrap();
+    let groups = ExprList::from_rel_node(groups.into()).unwrap();
+    let grouped =
+        key_columns(&groups).is_some_and(|cols| ordering(optimizer, &child).is_grouped_on(&cols));
+    if !grouped {
+        return vec![];
+    }
+    // Only a single partition keeps the order of its rows.
+    let node = PhysicalStreamAgg::new(
+        enforce(optimizer, child, &Distribution::Single),
+        exprs,
+        groups,
+    );
+    vec![node.into_rel_node().as_ref().clone()]
+}
+
+define_impl_rule!(
+    TwoPhaseAggRule,
+    apply_two_phase_agg,
+    (Agg, child, [exprs], [groups])
+);
+
+fn apply_two_phase_agg(
+    _optimizer: &impl Optimizer<OptRelNodeTyp>,
+    TwoPhaseAggRulePicks {
+        child,
+        exprs,
+        groups,
+    }: TwoPhaseAggRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let exprs = ExprList::from_rel_node(exprs.into()).unwrap();
+    let groups = ExprList::from_rel_node(groups.into()).unwrap();
+    let node = PhysicalTwoPhaseAgg::new(PlanNode::from_group(child.into()), exprs, groups);
+    vec![node.into_rel_node().as_ref().clone()]
+}
+
+// Agg A exprs groups -> Agg (Exchange A groups) exprs groups, where each partition aggregates the
+// groups hashed to it.
+define_impl_rule!(
+    PartitionedAggRule,
+    apply_partitioned_agg,
+    (Agg, child, [exprs], [groups])
+);
+
+fn apply_partitioned_agg(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    PartitionedAggRulePicks {
+        child,
+        exprs,
+        groups,
+    }: PartitionedAggRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let exprs = ExprList::from_rel_node(exprs.into()).unwrap();
+    let groups = ExprList::from_rel_node(groups.into()).unwrap();
+    // Aggregations grouped by expressions cannot require their input to be partitioned on them.
+    let Some(cols) = key_columns(&groups).filter(|cols| !cols.is_empty()) else {
+        return vec![];
+    };
+    // The hash aggregation covers inputs whose groups are already colocated.
+    let child_distribution = distribution(optimizer, &child);
+    if child_distribution == Distribution::Single || child_distribution.is_colocated_on(&cols) {
+        return vec![];
+    }
+    let child = enforce(optimizer, child, &Distribution::Hash(cols));
+    let node = PhysicalAgg::new(child, exprs, groups);
+    vec![node.into_rel_node().as_ref().clone()]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use datafusion_expr::AggregateFunction;
+    use optd_core::optimizer::Optimizer;
+
+    use super::*;
+    use crate::plan_nodes::{
+        ExchangeType, FuncExpr, FuncType, LogicalAgg, LogicalSort, OptRelNodeRef, SortOrderExpr,
+        SortOrderType,
+    };
+    use crate::rules::PhysicalConversionRule;
+    use crate::testing::{col, find_nodes, new_optimizer_with_rules, scan};
+
+    /// Optimizes an aggregation with the aggregation rules, over tables of a million rows.
+    fn optimize_agg(child: PlanNode, groups: Vec<usize>) -> OptRelNodeRef {
+        let mut rules = PhysicalConversionRule::all_conversions();
+        rules.push(Arc::new(HashAggRule::new()));
+        rules.push(Arc::new(StreamAggRule::new()));
+        rules.push(Arc::new(TwoPhaseAggRule::new()));
+        rules.push(Arc::new(PartitionedAggRule::new()));
+        let mut optimizer =
+            new_optimizer_with_rules(rules, HashMap::from([("t".to_string(), 1_000_000)]));
+        let count = FuncExpr::new(
+            FuncType::new_agg(AggregateFunction::Count),
+            ExprList::new(vec![col(1)]),
+        );
+        let plan = LogicalAgg::new(
+            child,
+            ExprList::new(vec![count.into_expr()]),
+            ExprList::new(groups.into_iter().map(col).collect()),
+        );
+        optimizer.optimize(plan.into_rel_node()).unwrap()
+    }
+
+    fn sorted_scan(column: usize) -> PlanNode {
+        let order = SortOrderExpr::new(SortOrderType::Asc, col(column));
+        LogicalSort::new(scan("t", 3), ExprList::new(vec![order.into_expr()])).into_plan_node()
+    }
+
+    #[test]
+    fn grouped_aggregations_are_hash_partitioned() {
+        // select count(b) from t group by a
+        let plan = optimize_agg(scan("t", 3), vec![0]);
+        assert_eq!(plan.typ, OptRelNodeTyp::PhysicalAgg);
+        assert_eq!(
+            plan.child(0).typ,
+            OptRelNodeTyp::PhysicalExchange(ExchangeType::Hash)
+        );
+    }
+
+    #[test]
+    fn aggregations_over_unrelated_orders_are_hashed() {
+        // select count(b) from (select * from t order by b) group by a
+        let plan = optimize_agg(sorted_scan(1), vec![0]);
+        assert_eq!(plan.typ, OptRelNodeTyp::PhysicalAgg);
+        assert_eq!(plan.child(0).typ, OptRelNodeTyp::PhysicalSort);
+    }
+
+    #[test]
+    fn aggregations_over_sorted_groups_are_streamed() {
+        // select count(b) from (select * from t order by a) group by a
+        let plan = optimize_agg(sorted_scan(0), vec![0]);
+        assert_eq!(plan.typ, OptRelNodeTyp::PhysicalStreamAgg);
+        assert_eq!(plan.child(0).typ, OptRelNodeTyp::PhysicalSort);
+    }
+
+    #[test]
+    fn aggregations_without_groups_take_two_phases() {
+        // select count(b) from t
+        let plan = optimize_agg(scan("t", 3), vec![]);
+        assert_eq!(plan.typ, OptRelNodeTyp::PhysicalTwoPhaseAgg);
+        assert!(find_nodes(&plan, &|typ| matches!(
+            typ,
+            OptRelNodeTyp::PhysicalExchange(_)
+        ))
+        .is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use optd_core::optimizer::Optimizer;
+use optd_core::rel_node::RelNode;
+use optd_core::rules::{Rule, RuleMatcher};
+
+use super::filter::split_conjuncts;
+use super::joins::{schema_len, unmap_join_output};
+use super::macros::define_rule;
+use crate::plan_nodes::{
+    BinOpType, ColumnRefExpr, Expr, ExprList, JoinType, LogicalProjection, OptRelNode,
+    OptRelNodeTyp, PlanNode,
+};
+use crate::properties::keys::KeyPropertyBuilder;
+
+/// Gets the columns equated by `cond` if it is an equality of two columns, the smaller one first.
+pub(super) fn equated_columns(cond: &Expr) -> Option<(usize, usize)> {
+    if cond.typ() != OptRelNodeTyp::BinOp(BinOpType::Eq) {
+        return None;
+    }
+    let a = ColumnRefExpr::from_rel_node(cond.child(0))?.index();
+    let b = ColumnRefExpr::from_rel_node(cond.child(1))?.index();
+    Some((a.min(b), a.max(b)))
+}
+
+// Projection (A join B) exprs -> Projection A exprs, where the join equates a foreign key of A with
+// the key it references in B, B produces every row of the referenced table, and `exprs` only refer
+// to A. Every row of A then joins exactly one row of B.
+define_rule!(
+    JoinEliminationRule,
+    apply_join_elimination,
+    (
+        Projection,
+        (Join(JoinType::Inner) => join, left, right, [cond]),
+        [exprs]
+    )
+);
+
+fn apply_join_elimination(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    JoinEliminationRulePicks {
+        join,
+        left,
+        right,
+        cond,
+        exprs,
+    }: JoinEliminationRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let left_len = schema_len(optimizer, &left);
+    let exprs = unmap_join_output(&join, ExprList::from_rel_node(exprs.into()).unwrap());
+    let mut cols = BTreeSet::new();
+    exprs.collect_column_refs(&mut cols);
+    if cols.iter().any(|col| *col >= left_len) {
+        return vec![];
+    }
+    let right_keys = optimizer.get_property::<KeyPropertyBuilder>(Arc::new(right), 1);
+    let Some((table, table_columns)) = &right_keys.table else {
+        return vec![];
+    };
+
+    // Pairs each column of A with the column of the table it is equated with.
+    let mut conds = vec![];
+    split_conjuncts(Expr::from_rel_node(cond.into()).unwrap(), &mut conds);
+    let mut pairs = BTreeSet::new();
+    for cond in &conds {
+        let Some((left_col, right_col)) = equated_columns(cond) else {
+            return vec![];
+        };
+        if left_col >= left_len || right_col < left_len {
+            return vec![];
+        }
+        pairs.insert((left_col, table_columns[right_col - left_len]));
+    }
+    let left_keys = optimizer.get_property::<KeyPropertyBuilder>(Arc::new(left.clone()), 1);
+    let is_foreign_key = left_keys.foreign_keys.iter().any(|foreign_key| {
+        foreign_key.referenced_table == *table
+            && foreign_key
+                .columns
+                .iter()
+                .copied()
+                .zip(foreign_key.referenced_columns.iter().copied())
+                .collect::<BTreeSet<_>>()
+                == pairs
+    });
+    if pairs.is_empty() || !is_foreign_key {
+        return vec![];
+    }
+
+    let node = LogicalProjection::new(PlanNode::from_group(left.into()), exprs);
+    vec![node.into_rel_node().as_ref().clone()]
+}
+
+// Agg A [] groups -> Projection A groups, where A is already unique on the group by columns, e.g.,
+// a DISTINCT on a key.
+define_rule!(
+    AggOnKeyRemovalRule,
+    apply_agg_on_key_removal,
+    (Agg, child, [exprs], [groups])
+);
+
+fn apply_agg_on_key_removal(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    AggOnKeyRemovalRulePicks {
+        child,
+        exprs,
+        groups,
+    }: AggOnKeyRemovalRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let exprs = ExprList::from_rel_node(exprs.into()).unwrap();
+    let groups = ExprList::from_rel_node(groups.into()).unwrap();
+    // An aggregation without group by columns produces a row even if A is empty.
+    if !exprs.is_empty() || groups.is_empty() {
+        return vec![];
+    }
+    let Some(columns) = groups
+        .to_vec()
+        .into_iter()
+        .map(|expr| ColumnRefExpr::from_rel_node(expr.into_rel_node()).map(|col| col.index()))
+        .collect::<Option<BTreeSet<_>>>()
+    else {
+        return vec![];
+    };
+    let keys = optimizer.get_property::<KeyPropertyBuilder>(Arc::new(child.clone()), 1);
+    if !keys.is_unique(&columns) {
+        return vec![];
+    }
+    let node = LogicalProjection::new(PlanNode::from_group(child.into()), groups);
+    vec![node.into_rel_node().as_ref().clone()]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::plan_nodes::{
+        ConstantType, ExprList, JoinType, LogicalJoin, LogicalProjection, OptRelNode,
+        OptRelNodeRef, OptRelNodeTyp,
+    };
+    use crate::properties::schema::{Catalog, Field, ForeignKey, Schema};
+    use crate::testing::{col, eq, scan};
+    use crate::DatafusionOptimizer;
+
+    /// `orders(o_id, o_custkey)`, where `o_custkey` references the key `c_custkey` of
+    /// `customer(c_custkey, c_name)`.
+    struct ForeignKeyCatalog;
+
+    impl Catalog for ForeignKeyCatalog {
+        fn get(&self, name: &str) -> Schema {
+            let fields = match name {
+                "orders" => ["o_id", "o_custkey"],
+                "customer" => ["c_custkey", "c_name"],
+                _ => unreachable!(),
+            };
+            Schema::new(
+                fields
+                    .into_iter()
+                    .map(|name| Field::new(name, ConstantType::Int32, false))
+                    .collect(),
+            )
+        }
+
+        fn unique_keys(&self, _table: &str) -> Vec<BTreeSet<usize>> {
+            vec![BTreeSet::from([0])]
+        }
+
+        fn foreign_keys(&self, table: &str) -> Vec<ForeignKey> {
+            match table {
+                "orders" => vec![ForeignKey {
+                    columns: vec![1],
+                    referenced_table: "customer".to_string(),
+                    referenced_columns: vec![0],
+                }],
+                _ => vec![],
+            }
+        }
+    }
+
+    fn has_join(rel_node: &OptRelNodeRef) -> bool {
+        matches!(
+            rel_node.typ,
+            OptRelNodeTyp::PhysicalHashJoin(_) | OptRelNodeTyp::PhysicalNestedLoopJoin(_)
+        ) || rel_node.children.iter().any(has_join)
+    }
+
+    /// Optimizes `select o_id from orders join customer on <left> = <right>`.
+    fn optimize_join(left: usize, right: usize) -> OptRelNodeRef {
+        let mut optimizer = DatafusionOptimizer::new_physical(Box::new(ForeignKeyCatalog));
+        let join = LogicalJoin::new(
+            scan("orders", 2),
+            scan("customer", 2),
+            eq(col(left), col(right)),
+            JoinType::Inner,
+        );
+        let plan = LogicalProjection::new(join.into_plan_node(), ExprList::new(vec![col(0)]));
+        optimizer.optimize(plan.into_rel_node()).unwrap().1
+    }
+
+    #[test]
+    fn join_on_foreign_key_is_eliminated() {
+        assert!(!has_join(&optimize_join(1, 2)));
+    }
+
+    #[test]
+    fn join_on_other_columns_is_kept() {
+        assert!(has_join(&optimize_join(0, 2)));
+        assert!(has_join(&optimize_join(1, 3)));
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use itertools::Itertools;
+use optd_core::optimizer::Optimizer;
+use optd_core::rel_node::RelNode;
+use optd_core::rules::{Rule, RuleMatcher};
+
+use super::constraints::equated_columns;
+use super::filter::{and_conjuncts, split_conjuncts};
+use super::joins::{logical_join, schema_len};
+use super::macros::define_rule;
+use crate::plan_nodes::{
+    join_mapping, ColumnRefExpr, Expr, ExprList, JoinType, LogicalAgg, LogicalProjection,
+    LogicalUnion, OptRelNode, OptRelNodeTyp, PlanNode,
+};
+use crate::properties::keys::KeyPropertyBuilder;
+
+/// Removes the duplicate rows of `node`, which has `width` columns.
+fn distinct(node: RelNode<OptRelNodeTyp>, width: usize) -> PlanNode {
+    let groups = (0..width)
+        .map(|col| ColumnRefExpr::new(col).into_expr())
+        .collect();
+    LogicalAgg::new(
+        PlanNode::from_group(node.into()),
+        ExprList::new(vec![]),
+        ExprList::new(groups),
+    )
+    .into_plan_node()
+}
+
+// Agg (A union B) [] groups -> Agg (Agg A [] all union Agg B [] all) [] groups, where `groups`
+// are all columns of the union, i.e., a DISTINCT over the union. Removing the duplicates of each
+// input first makes the union smaller. Inputs that are already unique are kept as they are.
+define_rule!(
+    DistinctUnionRule,
+    apply_distinct_union,
+    (Agg, (Union, left, right), [exprs], [groups])
+);
+
+fn apply_distinct_union(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    DistinctUnionRulePicks {
+        left,
+        right,
+        exprs,
+        groups,
+    }: DistinctUnionRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let exprs = ExprList::from_rel_node(exprs.into()).unwrap();
+    let groups = ExprList::from_rel_node(groups.into()).unwrap();
+    let width = schema_len(optimizer, &left);
+    let Some(columns) = groups
+        .to_vec()
+        .into_iter()
+        .map(|expr| ColumnRefExpr::from_rel_node(expr.into_rel_node()).map(|col| col.index()))
+        .collect::<Option<BTreeSet<_>>>()
+    else {
+        return vec![];
+    };
+    if !exprs.is_empty() || columns.len() != width {
+        return vec![];
+    }
+
+    let mut changed = false;
+    let mut distinct_input = |input: RelNode<OptRelNodeTyp>| {
+        let keys = optimizer.get_property::<KeyPropertyBuilder>(Arc::new(input.clone()), 1);
+        if keys.is_unique(&columns) {
+            PlanNode::from_group(input.into())
+        } else {
+            changed = true;
+            distinct(input, width)
+        }
+    };
+    let union = LogicalUnion::new(distinct_input(left), distinct_input(right));
+    if !changed {
+        return vec![];
+    }
+    let node = LogicalAgg::new(union.into_plan_node(), exprs, groups);
+    vec![node.into_rel_node().as_ref().clone()]
+}
+
+// A semi join B on cond -> A join (Agg B [] cols) on cond, where `cond` equates each column of B
+// in `cols` with a column of A, as for `a IN (SELECT b FROM B)`. Each row of A then matches at
+// most one row of the DISTINCT, and the inner join can be reordered with other joins.
+define_rule!(
+    SemiJoinDistinctRule,
+    apply_semi_join_distinct,
+    (Join(JoinType::LeftSemi) => join, left, right, [cond])
+);
+
+fn apply_semi_join_distinct(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    SemiJoinDistinctRulePicks {
+        join,
+        left,
+        right,
+        cond,
+    }: SemiJoinDistinctRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let left_len = schema_len(optimizer, &left);
+    let mut conds = vec![];
+    split_conjuncts(Expr::from_rel_node(cond.into()).unwrap(), &mut conds);
+    let mut right_cols = BTreeSet::new();
+    for cond in &conds {
+        let Some((left_col, right_col)) = equated_columns(cond) else {
+            return vec![];
+        };
+        if left_col >= left_len || right_col < left_len {
+            return vec![];
+        }
+        right_cols.insert(right_col - left_len);
+    }
+    if right_cols.is_empty() {
+        return vec![];
+    }
+
+    // The columns of B are replaced by the group by columns of the DISTINCT.
+    let right_cols = right_cols.into_iter().collect_vec();
+    let cond = and_conjuncts(conds)
+        .rewrite_column_refs(&|col| {
+            if col < left_len {
+                Some(col)
+            } else {
+                Some(left_len + right_cols.binary_search(&(col - left_len)).unwrap())
+            }
+        })
+        .unwrap();
+    let groups = right_cols
+        .iter()
+        .map(|col| ColumnRefExpr::new(*col).into_expr())
+        .collect();
+    let right = LogicalAgg::new(
+        PlanNode::from_group(right.into()),
+        ExprList::new(vec![]),
+        ExprList::new(groups),
+    );
+    let join_width = left_len + right_cols.len();
+    let inner_join = logical_join(
+        JoinType::Inner,
+        left,
+        right.into_rel_node(),
+        cond,
+        &(0..join_width).collect_vec(),
+    );
+    // The output is still only the columns of A.
+    let exprs = join_mapping(&join.data)
+        .unwrap_or_else(|| (0..left_len).collect())
+        .into_iter()
+        .map(|col| ColumnRefExpr::new(col).into_expr())
+        .collect();
+    let node = LogicalProjection::new(
+        PlanNode::from_group(inner_join.into()),
+        ExprList::new(exprs),
+    );
+    vec![node.into_rel_node().as_ref().clone()]
+}
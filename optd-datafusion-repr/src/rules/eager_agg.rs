@@ -0,0 +1,467 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use datafusion_expr::AggregateFunction;
+use optd_core::optimizer::Optimizer;
+use optd_core::rel_node::RelNode;
+use optd_core::rules::{Rule, RuleMatcher};
+
+use super::joins::{schema_len, unmap_join_output};
+use super::macros::define_rule;
+use crate::plan_nodes::{
+    ColumnRefExpr, Expr, ExprList, FuncExpr, FuncType, JoinType, LogicalAgg, LogicalJoin,
+    OptRelNode, OptRelNodeTyp, PlanNode,
+};
+use crate::properties::keys::KeyPropertyBuilder;
+
+/// Gets the aggregate function of `expr` if it can be computed from partial aggregates, along with
+/// the function that combines the partial results.
+fn decomposable_agg(expr: &Expr) -> Option<(FuncExpr, AggregateFunction)> {
+    let func = FuncExpr::from_rel_node(expr.clone().into_rel_node())?;
+    let FuncType::Agg(agg) = func.func() else {
+        return None;
+    };
+    let combine = match agg {
+        AggregateFunction::Sum | AggregateFunction::Count => AggregateFunction::Sum,
+        AggregateFunction::Min => AggregateFunction::Min,
+        AggregateFunction::Max => AggregateFunction::Max,
+        _ => return None,
+    };
+    Some((func, combine))
+}
+
+/// Pushes a partial aggregation into the side of the join covering columns
+/// `side_start..side_start + side_len`. The partial aggregation groups by the columns of the side
+/// used by the join condition and the group by, so every partial group joins with the same rows of
+/// the other side and the aggregates can be combined above the join.
+#[allow(clippy::too_many_arguments)]
+fn push_agg_into_side(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    left: &RelNode<OptRelNodeTyp>,
+    right: &RelNode<OptRelNodeTyp>,
+    cond: &Expr,
+    exprs: &ExprList,
+    groups: &ExprList,
+    side_start: usize,
+    side_len: usize,
+) -> Option<RelNode<OptRelNodeTyp>> {
+    let side_end = side_start + side_len;
+    let side = if side_start == 0 { left } else { right };
+
+    // All aggregates must be decomposable and only read the pushed side.
+    let mut partial_aggs = vec![];
+    let mut combine_funcs = vec![];
+    for expr in exprs.to_vec() {
+        let (func, combine) = decomposable_agg(&expr)?;
+        // Without group by columns the aggregation returns a row on an empty join, where the count
+        // is 0 but the sum of the partial counts is null.
+        if groups.is_empty() && func.func() == FuncType::Agg(AggregateFunction::Count) {
+            return None;
+        }
+        let mut refs = BTreeSet::new();
+        func.children().collect_column_refs(&mut refs);
+        if refs.iter().any(|&col| col < side_start || col >= side_end) {
+            return None;
+        }
+        partial_aggs.push(
+            func.into_expr()
+                .rewrite_column_refs(&|col| Some(col - side_start))
+                .unwrap(),
+        );
+        combine_funcs.push(combine);
+    }
+
+    let mut used = BTreeSet::new();
+    cond.collect_column_refs(&mut used);
+    groups.collect_column_refs(&mut used);
+    let side_groups: Vec<usize> = used.range(side_start..side_end).copied().collect();
+    // Without group by columns the partial aggregation returns a row even on empty input.
+    if side_groups.is_empty() {
+        return None;
+    }
+    // The aggregation would not reduce the rows if the side is already unique on the groups.
+    let keys = optimizer.get_property::<KeyPropertyBuilder>(Arc::new(side.clone()), 1);
+    if keys.is_unique(&side_groups.iter().map(|col| col - side_start).collect()) {
+        return None;
+    }
+
+    let group_cnt = side_groups.len();
+    let agg_cnt = partial_aggs.len();
+    let partial = LogicalAgg::new(
+        PlanNode::from_group(side.clone().into()),
+        ExprList::new(partial_aggs),
+        ExprList::new(
+            side_groups
+                .iter()
+                .map(|col| ColumnRefExpr::new(col - side_start).into_expr())
+                .collect(),
+        ),
+    )
+    .into_plan_node();
+    let (new_left, new_right) = if side_start == 0 {
+        (partial, PlanNode::from_group(right.clone().into()))
+    } else {
+        (PlanNode::from_group(left.clone().into()), partial)
+    };
+
+    let rewrite = |col: usize| {
+        if col < side_start {
+            Some(col)
+        } else if col < side_end {
+            side_groups
+                .binary_search(&col)
+                .ok()
+                .map(|idx| side_start + idx)
+        } else {
+            Some(col - side_len + group_cnt + agg_cnt)
+        }
+    };
+    let join = LogicalJoin::new(
+        new_left,
+        new_right,
+        cond.rewrite_column_refs(&rewrite)?,
+        JoinType::Inner,
+    );
+    let final_aggs = combine_funcs
+        .into_iter()
+        .enumerate()
+        .map(|(idx, combine)| {
+            FuncExpr::new(
+                FuncType::new_agg(combine),
+                ExprList::new(vec![
+                    ColumnRefExpr::new(side_start + group_cnt + idx).into_expr()
+                ]),
+            )
+            .into_expr()
+        })
+        .collect();
+    let node = LogicalAgg::new(
+        join.into_plan_node(),
+        ExprList::new(final_aggs),
+        groups.rewrite_column_refs(&rewrite)?,
+    );
+    Some(node.into_rel_node().as_ref().clone())
+}
+
+// Agg (A join B) exprs groups -> Agg ((Agg A partial_exprs groups_a) join B) final_exprs groups,
+// and the same with the partial aggregation on B.
+define_rule!(
+    EagerAggregationRule,
+    apply_eager_aggregation,
+    (
+        Agg,
+        (Join(JoinType::Inner) => join, left, right, [cond]),
+        [exprs],
+        [groups]
+    )
+);
+
+fn apply_eager_aggregation(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    EagerAggregationRulePicks {
+        join,
+        left,
+        right,
+        cond,
+        exprs,
+        groups,
+    }: EagerAggregationRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let left_len = schema_len(optimizer, &left);
+    let right_len = schema_len(optimizer, &right);
+    let cond = Expr::from_rel_node(cond.into()).unwrap();
+    let exprs = unmap_join_output(&join, ExprList::from_rel_node(exprs.into()).unwrap());
+    let groups = unmap_join_output(&join, ExprList::from_rel_node(groups.into()).unwrap());
+
+    [(0, left_len), (left_len, right_len)]
+        .into_iter()
+        .filter_map(|(side_start, side_len)| {
+            push_agg_into_side(
+                optimizer, &left, &right, &cond, &exprs, &groups, side_start, side_len,
+            )
+        })
+        .collect()
+}
+
+// Agg ((Agg A partial_exprs groups_a) join B) final_exprs groups -> Agg (A join B) exprs groups
+define_rule!(
+    LazyAggregationRule,
+    apply_lazy_aggregation,
+    (
+        Agg,
+        (
+            Join(JoinType::Inner) => join,
+            (Agg, child, [partial_exprs], [partial_groups]),
+            right,
+            [cond]
+        ),
+        [exprs],
+        [groups]
+    )
+);
+
+fn apply_lazy_aggregation(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    LazyAggregationRulePicks {
+        join,
+        child,
+        partial_exprs,
+        partial_groups,
+        right,
+        cond,
+        exprs,
+        groups,
+    }: LazyAggregationRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let child_len = schema_len(optimizer, &child);
+    let cond = Expr::from_rel_node(cond.into()).unwrap();
+    let partial_exprs = ExprList::from_rel_node(partial_exprs.into()).unwrap();
+    let partial_groups = ExprList::from_rel_node(partial_groups.into()).unwrap();
+    let exprs = unmap_join_output(&join, ExprList::from_rel_node(exprs.into()).unwrap());
+    let groups = unmap_join_output(&join, ExprList::from_rel_node(groups.into()).unwrap());
+
+    let Some(partial_groups) = partial_groups
+        .to_vec()
+        .into_iter()
+        .map(|expr| ColumnRefExpr::from_rel_node(expr.into_rel_node()).map(|col| col.index()))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return vec![];
+    };
+    let group_cnt = partial_groups.len();
+    let agg_cnt = partial_exprs.len();
+    if group_cnt == 0 {
+        return vec![];
+    }
+
+    // The partial aggregates may only be read by the aggregates that combine them.
+    let mut used = BTreeSet::new();
+    cond.collect_column_refs(&mut used);
+    groups.collect_column_refs(&mut used);
+    if used.range(group_cnt..group_cnt + agg_cnt).next().is_some() {
+        return vec![];
+    }
+
+    let mut aggs = vec![];
+    for expr in exprs.to_vec() {
+        let Some(func) = FuncExpr::from_rel_node(expr.into_rel_node()) else {
+            return vec![];
+        };
+        let args = func.children();
+        if args.len() != 1 {
+            return vec![];
+        }
+        let Some(col) = ColumnRefExpr::from_rel_node(args.child(0).into_rel_node()) else {
+            return vec![];
+        };
+        if col.index() < group_cnt || col.index() >= group_cnt + agg_cnt {
+            return vec![];
+        }
+        let partial =
+            FuncExpr::from_rel_node(partial_exprs.child(col.index() - group_cnt).into_rel_node())
+                .unwrap();
+        let agg = match (func.func(), partial.func()) {
+            // The sum of counts is null on an empty join without group by columns, while the
+            // count is 0.
+            (FuncType::Agg(AggregateFunction::Sum), FuncType::Agg(AggregateFunction::Count))
+                if groups.is_empty() =>
+            {
+                return vec![]
+            }
+            (
+                FuncType::Agg(AggregateFunction::Sum),
+                FuncType::Agg(agg @ (AggregateFunction::Sum | AggregateFunction::Count)),
+            ) => agg,
+            (FuncType::Agg(AggregateFunction::Min), FuncType::Agg(AggregateFunction::Min)) => {
+                AggregateFunction::Min
+            }
+            (FuncType::Agg(AggregateFunction::Max), FuncType::Agg(AggregateFunction::Max)) => {
+                AggregateFunction::Max
+            }
+            _ => return vec![],
+        };
+        aggs.push(FuncExpr::new(FuncType::new_agg(agg), partial.children()).into_expr());
+    }
+
+    let rewrite = |col: usize| {
+        if col < group_cnt {
+            Some(partial_groups[col])
+        } else {
+            Some(col - group_cnt - agg_cnt + child_len)
+        }
+    };
+    let join = LogicalJoin::new(
+        PlanNode::from_group(child.into()),
+        PlanNode::from_group(right.into()),
+        cond.rewrite_column_refs(&rewrite).unwrap(),
+        JoinType::Inner,
+    );
+    let node = LogicalAgg::new(
+        join.into_plan_node(),
+        ExprList::new(aggs),
+        groups.rewrite_column_refs(&rewrite).unwrap(),
+    );
+    vec![node.into_rel_node().as_ref().clone()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan_nodes::OptRelNodeRef;
+    use crate::testing::{col, eq, new_heuristics_optimizer, scan};
+
+    fn agg(func: AggregateFunction, col_idx: usize) -> Expr {
+        FuncExpr::new(FuncType::new_agg(func), ExprList::new(vec![col(col_idx)])).into_expr()
+    }
+
+    /// Aggregates the join of `left` and `right` on `cond`.
+    fn agg_join(
+        left: PlanNode,
+        right: PlanNode,
+        cond: Expr,
+        exprs: Vec<Expr>,
+        groups: Vec<Expr>,
+    ) -> OptRelNodeRef {
+        let join = LogicalJoin::new(left, right, cond, JoinType::Inner);
+        LogicalAgg::new(
+            join.into_plan_node(),
+            ExprList::new(exprs),
+            ExprList::new(groups),
+        )
+        .into_rel_node()
+    }
+
+    fn eager(plan: &OptRelNodeRef) -> Vec<RelNode<OptRelNodeTyp>> {
+        let join = plan.child(0);
+        let picks = EagerAggregationRulePicks {
+            join: join.as_ref().clone(),
+            left: join.child(0).as_ref().clone(),
+            right: join.child(1).as_ref().clone(),
+            cond: join.child(2).as_ref().clone(),
+            exprs: plan.child(1).as_ref().clone(),
+            groups: plan.child(2).as_ref().clone(),
+        };
+        apply_eager_aggregation(&new_heuristics_optimizer(), picks)
+    }
+
+    fn lazy(plan: &OptRelNodeRef) -> Vec<RelNode<OptRelNodeTyp>> {
+        let join = plan.child(0);
+        let partial = join.child(0);
+        let picks = LazyAggregationRulePicks {
+            join: join.as_ref().clone(),
+            child: partial.child(0).as_ref().clone(),
+            partial_exprs: partial.child(1).as_ref().clone(),
+            partial_groups: partial.child(2).as_ref().clone(),
+            right: join.child(1).as_ref().clone(),
+            cond: join.child(2).as_ref().clone(),
+            exprs: plan.child(1).as_ref().clone(),
+            groups: plan.child(2).as_ref().clone(),
+        };
+        apply_lazy_aggregation(&new_heuristics_optimizer(), picks)
+    }
+
+    /// `select a, sum(b) from t2 group by a`
+    fn partial_sum() -> PlanNode {
+        LogicalAgg::new(
+            scan("t2", 3),
+            ExprList::new(vec![agg(AggregateFunction::Sum, 1)]),
+            ExprList::new(vec![col(0)]),
+        )
+        .into_plan_node()
+    }
+
+    #[test]
+    fn grouped_aggregations_are_pushed_below_joins() {
+        // select t1.a, sum(t2.b) from t1 join t2 on t1.a = t2.a group by t1.a
+        let plan = agg_join(
+            scan("t1", 3),
+            scan("t2", 3),
+            eq(col(0), col(3)),
+            vec![agg(AggregateFunction::Sum, 4)],
+            vec![col(0)],
+        );
+        // The sums of `t2.b` are pre-aggregated per `t2.a` and summed again above the join.
+        let expected = agg_join(
+            scan("t1", 3),
+            partial_sum(),
+            eq(col(0), col(3)),
+            vec![agg(AggregateFunction::Sum, 4)],
+            vec![col(0)],
+        );
+        assert_eq!(eager(&plan), vec![expected.as_ref().clone()]);
+    }
+
+    #[test]
+    fn grouped_aggregations_are_pulled_above_joins() {
+        // select t2.a, sum(t2.b) from (select a, sum(b) from t2 group by a) t2
+        // join t1 on t2.a = t1.a group by t2.a
+        let plan = agg_join(
+            partial_sum(),
+            scan("t1", 3),
+            eq(col(0), col(2)),
+            vec![agg(AggregateFunction::Sum, 1)],
+            vec![col(0)],
+        );
+        let expected = agg_join(
+            scan("t2", 3),
+            scan("t1", 3),
+            eq(col(0), col(3)),
+            vec![agg(AggregateFunction::Sum, 1)],
+            vec![col(0)],
+        );
+        let pulled = lazy(&plan);
+        assert_eq!(pulled, vec![expected.as_ref().clone()]);
+        // Pushing the aggregation down again gives back the original plan.
+        assert!(eager(&pulled[0].clone().into()).contains(plan.as_ref()));
+    }
+
+    #[test]
+    fn counts_without_groups_are_not_split() {
+        // select count(t2.b) from t1 join t2 on t1.a = t2.a, which is 0 on an empty join, while
+        // the sum of partial counts is null.
+        let plan = agg_join(
+            scan("t1", 3),
+            scan("t2", 3),
+            eq(col(0), col(3)),
+            vec![agg(AggregateFunction::Count, 4)],
+            vec![],
+        );
+        assert!(eager(&plan).is_empty());
+
+        // select sum(t2.b) from (select a, count(b) from t2 group by a) t2 join t1 on t2.a = t1.a
+        let partial_count = LogicalAgg::new(
+            scan("t2", 3),
+            ExprList::new(vec![agg(AggregateFunction::Count, 1)]),
+            ExprList::new(vec![col(0)]),
+        );
+        let plan = agg_join(
+            partial_count.into_plan_node(),
+            scan("t1", 3),
+            eq(col(0), col(2)),
+            vec![agg(AggregateFunction::Sum, 1)],
+            vec![],
+        );
+        assert!(lazy(&plan).is_empty());
+    }
+
+    #[test]
+    fn sums_without_groups_are_pushed_below_joins() {
+        // select sum(t2.b) from t1 join t2 on t1.a = t2.a, where the partial aggregation groups by
+        // the join key.
+        let plan = agg_join(
+            scan("t1", 3),
+            scan("t2", 3),
+            eq(col(0), col(3)),
+            vec![agg(AggregateFunction::Sum, 4)],
+            vec![],
+        );
+        let expected = agg_join(
+            scan("t1", 3),
+            partial_sum(),
+            eq(col(0), col(3)),
+            vec![agg(AggregateFunction::Sum, 4)],
+            vec![],
+        );
+        assert_eq!(eager(&plan), vec![expected.as_ref().clone()]);
+    }
+}
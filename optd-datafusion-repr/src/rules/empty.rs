@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use optd_core::optimizer::Optimizer;
+use optd_core::rel_node::RelNode;
+use optd_core::rules::{Rule, RuleMatcher};
+
+use crate::plan_nodes::{JoinType, LogicalEmpty, OptRelNode, OptRelNodeTyp};
+use crate::properties::schema::SchemaPropertyBuilder;
+
+/// Builds an empty relation with the schema of `node`.
+pub(super) fn empty_like(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    node: RelNode<OptRelNodeTyp>,
+) -> RelNode<OptRelNodeTyp> {
+    let schema = optimizer.get_property::<SchemaPropertyBuilder>(Arc::new(node), 0);
+    LogicalEmpty::new(&schema).into_rel_node().as_ref().clone()
+}
+
+/// Checks whether a plan node produces no rows if the inputs `is_empty` returns true for produce
+/// no rows.
+fn produces_no_rows(node: &RelNode<OptRelNodeTyp>, is_empty: impl Fn(usize) -> bool) -> bool {
+    match &node.typ {
+        OptRelNodeTyp::Filter | OptRelNodeTyp::Projection | OptRelNodeTyp::Sort => is_empty(0),
+        // An aggregation without group by columns produces one row on an empty input.
+        OptRelNodeTyp::Agg => is_empty(0) && !node.child(2).children.is_empty(),
+        OptRelNodeTyp::Join(join_type) => match join_type {
+            JoinType::Inner | JoinType::Cross | JoinType::LeftSemi | JoinType::RightSemi => {
+                is_empty(0) || is_empty(1)
+            }
+            JoinType::LeftOuter | JoinType::LeftAnti => is_empty(0),
+            JoinType::RightOuter | JoinType::RightAnti => is_empty(1),
+            JoinType::FullOuter => is_empty(0) && is_empty(1),
+        },
+        _ => false,
+    }
+}
+
+/// Replaces a plan node by an empty relation if it produces no rows because of an empty input.
+/// The inputs are only known to be empty in the heuristic stage, which keeps the full plan.
+pub struct EmptyPropagationRule {
+    matcher: RuleMatcher<OptRelNodeTyp>,
+}
+
+impl EmptyPropagationRule {
+    pub fn new(typ: OptRelNodeTyp) -> Self {
+        Self {
+            matcher: RuleMatcher::MatchAndPickNode {
+                typ,
+                pick_to: 0,
+                children: vec![RuleMatcher::IgnoreMany],
+            },
+        }
+    }
+
+    pub fn all<O: Optimizer<OptRelNodeTyp>>() -> Vec<Arc<dyn Rule<OptRelNodeTyp, O>>> {
+        let mut types = vec![
+            OptRelNodeTyp::Filter,
+            OptRelNodeTyp::Projection,
+            OptRelNodeTyp::Sort,
+            OptRelNodeTyp::Agg,
+        ];
+        types.extend(
+            [
+                JoinType::Inner,
+                JoinType::Cross,
+                JoinType::LeftOuter,
+                JoinType::RightOuter,
+                JoinType::FullOuter,
+                JoinType::LeftSemi,
+                JoinType::RightSemi,
+                JoinType::LeftAnti,
+                JoinType::RightAnti,
+            ]
+            .map(OptRelNodeTyp::Join),
+        );
+        types
+            .into_iter()
+            .map(|typ| Arc::new(Self::new(typ)) as Arc<dyn Rule<OptRelNodeTyp, O>>)
+            .collect()
+    }
+}
+
+impl<O: Optimizer<OptRelNodeTyp>> Rule<OptRelNodeTyp, O> for EmptyPropagationRule {
+    fn matcher(&self) -> &RuleMatcher<OptRelNodeTyp> {
+        &self.matcher
+    }
+
+    fn apply(
+        &self,
+        optimizer: &O,
+        mut input: HashMap<usize, RelNode<OptRelNodeTyp>>,
+    ) -> Vec<RelNode<OptRelNodeTyp>> {
+        let node = input.remove(&0).unwrap();
+        if !produces_no_rows(&node, |idx| node.child(idx).typ == OptRelNodeTyp::Empty) {
+            return vec![];
+        }
+        vec![empty_like(optimizer, node)]
+    }
+
+    fn name(&self) -> &'static str {
+        "empty_propagation"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion_expr::AggregateFunction;
+    use optd_core::heuristics::ApplyOrder;
+
+    use super::*;
+    use crate::plan_nodes::{
+        ExprList, FuncExpr, FuncType, LogicalAgg, LogicalFilter, LogicalJoin, LogicalProjection,
+        OptRelNodeRef, PlanNode,
+    };
+    use crate::properties::schema::Catalog;
+    use crate::testing::{col, eq, new_heuristics_optimizer_with_rules, scan, TestCatalog};
+
+    fn propagate(plan: PlanNode) -> OptRelNodeRef {
+        let mut optimizer =
+            new_heuristics_optimizer_with_rules(EmptyPropagationRule::all(), ApplyOrder::BottomUp);
+        optimizer.optimize(plan.into_rel_node()).unwrap()
+    }
+
+    /// An empty relation with the columns `a`, `b` and `c`.
+    fn empty() -> PlanNode {
+        LogicalEmpty::new(&TestCatalog.get("t")).into_plan_node()
+    }
+
+    fn join(left: PlanNode, right: PlanNode, join_type: JoinType) -> PlanNode {
+        LogicalJoin::new(left, right, eq(col(0), col(3)), join_type).into_plan_node()
+    }
+
+    /// Checks that `plan` is an empty relation of `width` columns.
+    fn assert_empty(plan: &OptRelNodeRef, width: usize) {
+        let empty = LogicalEmpty::from_rel_node(plan.clone()).expect("not an empty relation");
+        assert_eq!(empty.schema().len(), width);
+    }
+
+    #[test]
+    fn emptiness_propagates_through_inner_joins_and_filters() {
+        // select b from (t1 join empty on t1.a = empty.a) where t1.a = t1.b
+        let join = join(scan("t1", 3), empty(), JoinType::Inner);
+        let filter = LogicalFilter::new(join, eq(col(0), col(1)));
+        let plan = LogicalProjection::new(filter.into_plan_node(), ExprList::new(vec![col(1)]));
+        assert_empty(&propagate(plan.into_plan_node()), 1);
+    }
+
+    #[test]
+    fn outer_joins_keep_their_preserved_side() {
+        let plan = join(scan("t1", 3), empty(), JoinType::LeftOuter);
+        assert_eq!(
+            propagate(plan).typ,
+            OptRelNodeTyp::Join(JoinType::LeftOuter)
+        );
+        let plan = join(empty(), scan("t1", 3), JoinType::LeftOuter);
+        assert_empty(&propagate(plan), 6);
+        let plan = join(empty(), scan("t1", 3), JoinType::FullOuter);
+        assert_eq!(
+            propagate(plan).typ,
+            OptRelNodeTyp::Join(JoinType::FullOuter)
+        );
+        let plan = join(scan("t1", 3), empty(), JoinType::LeftAnti);
+        assert_eq!(propagate(plan).typ, OptRelNodeTyp::Join(JoinType::LeftAnti));
+    }
+
+    #[test]
+    fn aggregations_without_groups_return_a_row() {
+        let count = FuncExpr::new(
+            FuncType::new_agg(AggregateFunction::Count),
+            ExprList::new(vec![col(0)]),
+        );
+        let agg = |groups| {
+            LogicalAgg::new(
+                empty(),
+                ExprList::new(vec![count.clone().into_expr()]),
+                ExprList::new(groups),
+            )
+            .into_plan_node()
+        };
+        // select count(a) from empty
+        assert_eq!(propagate(agg(vec![])).typ, OptRelNodeTyp::Agg);
+        // select b, count(a) from empty group by b
+        assert_empty(&propagate(agg(vec![col(1)])), 2);
+    }
+}
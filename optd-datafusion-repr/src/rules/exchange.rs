@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use optd_core::optimizer::Optimizer;
+use optd_core::rel_node::RelNode;
+
+use crate::plan_nodes::{
+    ColumnRefExpr, ExchangeType, ExprList, OptRelNode, OptRelNodeTyp, PhysicalExchange, PlanNode,
+};
+use crate::properties::distribution::{Distribution, DistributionPropertyBuilder};
+
+pub(crate) fn distribution(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    node: &RelNode<OptRelNodeTyp>,
+) -> Distribution {
+    optimizer.get_property::<DistributionPropertyBuilder>(Arc::new(node.clone()), 2)
+}
+
+/// Gets the column indices of `keys` if all of them are column references.
+pub(crate) fn key_columns(keys: &ExprList) -> Option<Vec<usize>> {
+    keys.to_vec()
+        .into_iter()
+        .map(|expr| ColumnRefExpr::from_rel_node(expr.into_rel_node()).map(|col| col.index()))
+        .collect()
+}
+
+/// Requires `child` to be distributed like `required`. Exchanges are the enforcers of the
+/// distribution: `child` is kept as is if its derived distribution satisfies `required`, and is
+/// put below the exchange producing `required` otherwise.
+pub(crate) fn enforce(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    child: RelNode<OptRelNodeTyp>,
+    required: &Distribution,
+) -> PlanNode {
+    if distribution(optimizer, &child).satisfies(required) {
+        return PlanNode::from_group(child.into());
+    }
+    let (keys, exchange_type) = match required {
+        Distribution::Single => (vec![], ExchangeType::Single),
+        Distribution::Hash(columns) => (
+            columns
+                .iter()
+                .map(|&col| ColumnRefExpr::new(col).into_expr())
+                .collect(),
+            ExchangeType::Hash,
+        ),
+        Distribution::RoundRobin => (vec![], ExchangeType::RoundRobin),
+        Distribution::Broadcast => (vec![], ExchangeType::Broadcast),
+        Distribution::Any | Distribution::Columns(_) => unreachable!(),
+    };
+    PhysicalExchange::new(
+        PlanNode::from_group(child.into()),
+        ExprList::new(keys),
+        exchange_type,
+    )
+    .into_plan_node()
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use itertools::Itertools;
+use optd_core::optimizer::Optimizer;
+use optd_core::rel_node::RelNode;
+use optd_core::rules::{Rule, RuleMatcher};
+
+use crate::plan_nodes::{
+    BinOpType, ColumnRefExpr, ConstantExpr, Expr, ExprList, LogOpExpr, LogOpType, LogicalFilter,
+    LogicalScan, OptRelNode, OptRelNodeTyp,
+};
+use crate::properties::schema::{Catalog, FilterPushDown};
+
+/// Splits a predicate into its conjuncts.
+pub(crate) fn split_conjuncts(expr: Expr, conjuncts: &mut Vec<Expr>) {
+    match expr.typ() {
+        OptRelNodeTyp::LogOp(LogOpType::And) => {
+            let expr = LogOpExpr::from_rel_node(expr.into_rel_node()).unwrap();
+            for child in expr.children().to_vec() {
+                split_conjuncts(child, conjuncts);
+            }
+        }
+        OptRelNodeTyp::BinOp(BinOpType::And) => {
+            let expr = expr.into_rel_node();
+            split_conjuncts(Expr::from_rel_node(expr.child(0)).unwrap(), conjuncts);
+            split_conjuncts(Expr::from_rel_node(expr.child(1)).unwrap(), conjuncts);
+        }
+        _ => conjuncts.push(expr),
+    }
+}
+
+/// Combines conjuncts into a single predicate, which is `true` if there are none.
+pub(crate) fn and_conjuncts(mut conjuncts: Vec<Expr>) -> Expr {
+    match conjuncts.len() {
+        0 => ConstantExpr::bool(true).into_expr(),
+        1 => conjuncts.remove(0),
+        _ => LogOpExpr::new(LogOpType::And, ExprList::new(conjuncts)).into_expr(),
+    }
+}
+
+/// Filter (Scan t) cond -> Filter (Scan t filters) remaining_cond, where the predicates in `cond`
+/// the table can evaluate are moved into the scan. The scan node is picked as a whole to keep the
+/// table name stored in its data.
+pub struct FilterPushDownScan {
+    matcher: RuleMatcher<OptRelNodeTyp>,
+    catalog: Arc<dyn Catalog>,
+}
+
+impl FilterPushDownScan {
+    pub fn new(catalog: Arc<dyn Catalog>) -> Self {
+        Self {
+            matcher: RuleMatcher::MatchNode {
+                typ: OptRelNodeTyp::Filter,
+                children: vec![
+                    RuleMatcher::MatchAndPickNode {
+                        typ: OptRelNodeTyp::Scan,
+                        children: vec![
+                            RuleMatcher::PickOne {
+                                pick_to: 1,
+                                expand: true,
+                            },
+                            RuleMatcher::PickOne {
+                                pick_to: 2,
+                                expand: true,
+                            },
+                            RuleMatcher::PickOne {
+                                pick_to: 3,
+                                expand: true,
+                            },
+                            RuleMatcher::PickOne {
+                                pick_to: 5,
+                                expand: true,
+                            },
+                        ],
+                        pick_to: 0,
+                    },
+                    RuleMatcher::PickOne {
+                        pick_to: 4,
+                        expand: true,
+                    },
+                ],
+            },
+            catalog,
+        }
+    }
+}
+
+impl<O: Optimizer<OptRelNodeTyp>> Rule<OptRelNodeTyp, O> for FilterPushDownScan {
+    fn matcher(&self) -> &RuleMatcher<OptRelNodeTyp> {
+        &self.matcher
+    }
+
+    fn apply(
+        &self,
+        _optimizer: &O,
+        mut input: HashMap<usize, RelNode<OptRelNodeTyp>>,
+    ) -> Vec<RelNode<OptRelNodeTyp>> {
+        let scan = input.remove(&0).unwrap();
+        let table = scan.data.as_ref().unwrap().as_str().to_string();
+        let projection = ExprList::from_rel_node(Arc::new(input.remove(&1).unwrap())).unwrap();
+        let filters = ExprList::from_rel_node(Arc::new(input.remove(&2).unwrap())).unwrap();
+        let fetch = ConstantExpr::from_rel_node(Arc::new(input.remove(&3).unwrap())).unwrap();
+        let inexact_filters = ExprList::from_rel_node(Arc::new(input.remove(&5).unwrap())).unwrap();
+        let cond = Expr::from_rel_node(Arc::new(input.remove(&4).unwrap())).unwrap();
+        // Filtering below a limit changes which rows are returned.
+        if !fetch.is_null() {
+            return vec![];
+        }
+
+        // The predicates in the filter refer to the output of the scan, while the predicates in the
+        // scan refer to the table.
+        let table_columns = projection
+            .to_vec()
+            .into_iter()
+            .map(|expr| {
+                ColumnRefExpr::from_rel_node(expr.into_rel_node())
+                    .unwrap()
+                    .index()
+            })
+            .collect_vec();
+        let mut conjuncts = vec![];
+        split_conjuncts(cond, &mut conjuncts);
+        let mut pushed = filters.to_vec();
+        let mut pushed_inexact = inexact_filters.to_vec();
+        let mut remaining = vec![];
+        for conjunct in conjuncts {
+            let filter = conjunct
+                .rewrite_column_refs(&|col| Some(table_columns[col]))
+                .unwrap();
+            let filter_node = filter.clone().into_rel_node();
+            if pushed_inexact
+                .iter()
+                .any(|x| x.clone().into_rel_node() == filter_node)
+            {
+                // Already pushed as an inexact predicate.
+                remaining.push(conjunct);
+                continue;
+            }
+            match self.catalog.filter_pushdown(&table, &filter) {
+                FilterPushDown::Exact => pushed.push(filter),
+                FilterPushDown::Inexact => {
+                    pushed_inexact.push(filter);
+                    remaining.push(conjunct);
+                }
+                FilterPushDown::Unsupported => remaining.push(conjunct),
+            }
+        }
+        if pushed.len() == filters.len() && pushed_inexact.len() == inexact_filters.len() {
+            return vec![];
+        }
+
+        let scan = LogicalScan::new_with_pushdown(
+            table,
+            projection,
+            ExprList::new(pushed),
+            ExprList::new(pushed_inexact),
+            None,
+        );
+        if remaining.is_empty() {
+            return vec![scan.into_rel_node().as_ref().clone()];
+        }
+        let node = LogicalFilter::new(scan.into_plan_node(), and_conjuncts(remaining));
+        vec![node.into_rel_node().as_ref().clone()]
+    }
+
+    fn name(&self) -> &'static str {
+        "filter_push_down_scan"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use optd_core::heuristics::{ApplyOrder, HeuristicsOptimizer};
+    use optd_core::optimizer::Optimizer;
+
+    use super::*;
+    use crate::plan_nodes::{LogicalProjection, OptRelNodeRef, PlanNode};
+    use crate::properties::schema::Schema;
+    use crate::rules::ProjectionPushDownScan;
+    use crate::testing::{col, eq, TestCatalog};
+
+    /// The tables of the test catalog, which apply predicates on `a` exactly and predicates on `b`
+    /// inexactly, and cannot use predicates on `c`.
+    struct PushDownCatalog;
+
+    impl Catalog for PushDownCatalog {
+        fn get(&self, name: &str) -> Schema {
+            TestCatalog.get(name)
+        }
+
+        fn filter_pushdown(&self, _table: &str, filter: &Expr) -> FilterPushDown {
+            let mut refs = BTreeSet::new();
+            filter.collect_column_refs(&mut refs);
+            match refs.into_iter().collect_vec().as_slice() {
+                [0] => FilterPushDown::Exact,
+                [1] => FilterPushDown::Inexact,
+                _ => FilterPushDown::Unsupported,
+            }
+        }
+    }
+
+    fn push_down(plan: OptRelNodeRef) -> OptRelNodeRef {
+        let mut optimizer = HeuristicsOptimizer::new_with_rules(
+            vec![
+                Arc::new(FilterPushDownScan::new(Arc::new(PushDownCatalog))),
+                Arc::new(ProjectionPushDownScan::new()),
+            ],
+            ApplyOrder::TopDown,
+        );
+        optimizer.optimize(plan).unwrap()
+    }
+
+    fn int(value: i64) -> Expr {
+        ConstantExpr::int(value).into_expr()
+    }
+
+    /// Scans `t` with its columns in reverse order, so that the predicates of a filter above the
+    /// scan refer to other columns than the predicates in the scan.
+    fn reversed_projection() -> ExprList {
+        ExprList::new(vec![col(2), col(1), col(0)])
+    }
+
+    fn scan(filters: Vec<Expr>, inexact_filters: Vec<Expr>, fetch: Option<usize>) -> PlanNode {
+        LogicalScan::new_with_pushdown(
+            "t".to_string(),
+            reversed_projection(),
+            ExprList::new(filters),
+            ExprList::new(inexact_filters),
+            fetch,
+        )
+        .into_plan_node()
+    }
+
+    #[test]
+    fn filters_are_pushed_by_how_the_table_applies_them() {
+        // where a = 1 and b = 2 and c = 3
+        let cond = and_conjuncts(vec![
+            eq(col(2), int(1)),
+            eq(col(1), int(2)),
+            eq(col(0), int(3)),
+        ]);
+        let plan = LogicalFilter::new(scan(vec![], vec![], None), cond);
+        let expected = LogicalFilter::new(
+            scan(vec![eq(col(0), int(1))], vec![eq(col(1), int(2))], None),
+            and_conjuncts(vec![eq(col(1), int(2)), eq(col(0), int(3))]),
+        );
+        let pushed = push_down(plan.into_rel_node());
+        assert_eq!(pushed, expected.into_rel_node());
+        // The inexact filter is kept above the scan, but not pushed into it again.
+        assert_eq!(push_down(pushed.clone()), pushed);
+    }
+
+    #[test]
+    fn exact_filters_replace_the_filter() {
+        let plan = LogicalFilter::new(scan(vec![], vec![], None), eq(col(2), int(1)));
+        let expected = scan(vec![eq(col(0), int(1))], vec![], None);
+        assert_eq!(push_down(plan.into_rel_node()), expected.into_rel_node());
+    }
+
+    #[test]
+    fn filters_are_not_pushed_below_a_fetch() {
+        let plan = LogicalFilter::new(scan(vec![], vec![], Some(10)), eq(col(2), int(1)));
+        let plan = plan.into_rel_node();
+        assert_eq!(push_down(plan.clone()), plan);
+    }
+
+    #[test]
+    fn pruned_scans_keep_their_filters_and_fetch() {
+        // select a from t where a = 1 and b = 2 limit 10, where the scan applies the filters
+        let scan = scan(vec![eq(col(0), int(1))], vec![eq(col(1), int(2))], Some(10));
+        let plan = LogicalProjection::new(scan, ExprList::new(vec![col(2)]));
+        let expected = LogicalScan::new_with_pushdown(
+            "t".to_string(),
+            ExprList::new(vec![col(0)]),
+            ExprList::new(vec![eq(col(0), int(1))]),
+            ExprList::new(vec![eq(col(1), int(2))]),
+            Some(10),
+        );
+        assert_eq!(push_down(plan.into_rel_node()), expected.into_rel_node());
+    }
+}
@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
 use std::sync::Arc;
 
 use itertools::Itertools;
@@ -6,85 +7,239 @@ use optd_core::optimizer::Optimizer;
 use optd_core::rel_node::RelNode;
 use optd_core::rules::{Rule, RuleMatcher};
 
+use super::exchange::{distribution, enforce, key_columns};
+use super::filter::{and_conjuncts, split_conjuncts};
 use super::macros::{define_impl_rule, define_rule};
 use crate::plan_nodes::{
-    BinOpExpr, BinOpType, ColumnRefExpr, Expr, ExprList, JoinType, LogicalJoin, LogicalProjection,
-    OptRelNode, OptRelNodeTyp, PhysicalHashJoin, PlanNode,
+    join_mapping, join_mapping_data, BinOpExpr, BinOpType, ColumnRefExpr, Expr, ExprList, JoinType,
+    LogicalJoin, LogicalProjection, OptRelNode, OptRelNodeTyp, PhysicalFilter, PhysicalHashJoin,
+    PhysicalProjection, PhysicalRuntimeFilter, PlanNode,
 };
+use crate::properties::distribution::Distribution;
+use crate::properties::equivalence::{EquivalenceClasses, EquivalencePropertyBuilder};
 use crate::properties::schema::SchemaPropertyBuilder;
 
-// A join B -> B join A
+// A join B -> B join A, with the output columns mapped back to the order of A join B.
 define_rule!(
     JoinCommuteRule,
     apply_join_commute,
-    (Join(JoinType::Inner), left, right, [cond])
+    (Join(JoinType::Inner) => join, left, right, [cond])
 );
 
 fn apply_join_commute(
     optimizer: &impl Optimizer<OptRelNodeTyp>,
-    JoinCommuteRulePicks { left, right, cond }: JoinCommuteRulePicks,
+    JoinCommuteRulePicks {
+        join,
+        left,
+        right,
+        cond,
+    }: JoinCommuteRulePicks,
 ) -> Vec<RelNode<OptRelNodeTyp>> {
-    fn rewrite_column_refs(expr: Expr, left_size: usize, right_size: usize) -> Expr {
-        let expr = expr.into_rel_node();
-        if let Some(expr) = ColumnRefExpr::from_rel_node(expr.clone()) {
-            let index = expr.index();
-            if index < left_size {
-                return ColumnRefExpr::new(index + right_size).into_expr();
-            } else {
-                return ColumnRefExpr::new(index - left_size).into_expr();
-            }
+    vec![commute_join(
+        optimizer,
+        &join,
+        left,
+        right,
+        cond,
+        JoinType::Inner,
+    )]
+}
+
+/// Swaps the inputs of `join` into a join of `join_type`, with the output columns mapped back to
+/// their order in `join`.
+pub(super) fn commute_join(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    join: &RelNode<OptRelNodeTyp>,
+    left: RelNode<OptRelNodeTyp>,
+    right: RelNode<OptRelNodeTyp>,
+    cond: RelNode<OptRelNodeTyp>,
+    join_type: JoinType,
+) -> RelNode<OptRelNodeTyp> {
+    let left_len = schema_len(optimizer, &left);
+    let right_len = schema_len(optimizer, &right);
+    // Maps a column of the inputs of A join B to the same column of the inputs of B join A.
+    let swap = |col: usize| {
+        if col < left_len {
+            col + right_len
+        } else {
+            col - left_len
         }
-        let children = expr.children.clone();
-        let children = children
-            .into_iter()
-            .map(|x| {
-                rewrite_column_refs(Expr::from_rel_node(x).unwrap(), left_size, right_size)
-                    .into_rel_node()
-            })
-            .collect_vec();
-        Expr::from_rel_node(
-            RelNode {
-                typ: expr.typ.clone(),
-                children,
-                data: expr.data.clone(),
-            }
-            .into(),
-        )
+    };
+    let cond = Expr::from_rel_node(cond.into())
         .unwrap()
-    }
-
-    let left_schema = optimizer.get_property::<SchemaPropertyBuilder>(Arc::new(left.clone()), 0);
-    let right_schema = optimizer.get_property::<SchemaPropertyBuilder>(Arc::new(right.clone()), 0);
-    let cond = rewrite_column_refs(
-        Expr::from_rel_node(cond.into()).unwrap(),
-        left_schema.len(),
-        right_schema.len(),
-    );
+        .rewrite_column_refs(&|col| Some(swap(col)))
+        .unwrap();
+    let mapping = join_mapping(&join.data)
+        .unwrap_or_else(|| (0..left_len + right_len).collect())
+        .into_iter()
+        .map(swap)
+        .collect_vec();
     let node = LogicalJoin::new(
         PlanNode::from_group(right.into()),
         PlanNode::from_group(left.into()),
         cond,
-        JoinType::Inner,
-    );
-    let mut proj_expr = Vec::with_capacity(left_schema.0.len() + right_schema.0.len());
-    for i in 0..left_schema.len() {
-        proj_expr.push(ColumnRefExpr::new(right_schema.len() + i).into_expr());
+        join_type,
+    )
+    .with_mapping(&mapping);
+    node.into_rel_node().as_ref().clone()
+}
+
+pub(super) fn schema_len(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    node: &RelNode<OptRelNodeTyp>,
+) -> usize {
+    optimizer
+        .get_property::<SchemaPropertyBuilder>(Arc::new(node.clone()), 0)
+        .len()
+}
+
+/// Rewrites `exprs` over the output of a logical join to refer to the columns of its inputs, as if
+/// the join had no output column mapping.
+pub(crate) fn unmap_join_output(join: &RelNode<OptRelNodeTyp>, exprs: ExprList) -> ExprList {
+    match join_mapping(&join.data) {
+        Some(mapping) => exprs
+            .rewrite_column_refs(&|col| Some(mapping[col]))
+            .unwrap(),
+        None => exprs,
     }
-    for i in 0..right_schema.len() {
-        proj_expr.push(ColumnRefExpr::new(i).into_expr());
+}
+
+/// Puts a physical join below a projection reordering its columns by the output column mapping of
+/// the logical join it implements, as physical joins output the columns of their inputs in order.
+pub(crate) fn project_join_output(
+    node: RelNode<OptRelNodeTyp>,
+    mapping: Option<&[usize]>,
+) -> RelNode<OptRelNodeTyp> {
+    let Some(mapping) = mapping else {
+        return node;
+    };
+    let exprs = mapping
+        .iter()
+        .map(|col| ColumnRefExpr::new(*col).into_expr())
+        .collect();
+    PhysicalProjection::new(PlanNode::from_group(node.into()), ExprList::new(exprs))
+        .into_rel_node()
+        .as_ref()
+        .clone()
+}
+
+/// Collects the column equivalences of a tree joining `inputs` on `conds`, whose columns are
+/// numbered across all inputs.
+fn join_tree_classes(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    inputs: &[&RelNode<OptRelNodeTyp>],
+    conds: &[Expr],
+) -> EquivalenceClasses {
+    let mut classes = inputs
+        .iter()
+        .map(|input| equivalence_classes(optimizer, input))
+        .reduce(|left, right| left.concat(&right))
+        .unwrap();
+    for cond in conds {
+        classes.add_predicate(&cond.clone().into_rel_node());
+    }
+    classes
+}
+
+/// Redistributes the conjuncts of a tree of three joined inputs to a tree that first joins the
+/// columns in `inner`, the two inputs of which are split at column `split`. Returns the condition
+/// of the inner join, indexed within `inner`, and the condition of the top join. The inner join
+/// also gets the column equalities between its inputs implied by `classes`, e.g., b = c from
+/// a = b and a = c. Returns `None` if the inner join would be a cross product.
+fn redistribute_conds(
+    classes: &EquivalenceClasses,
+    conds: Vec<Expr>,
+    inner: Range<usize>,
+    split: usize,
+) -> Option<(Expr, Expr)> {
+    let mut inner_conds = vec![];
+    let mut top_conds = vec![];
+    let mut is_connected = false;
+    for cond in conds {
+        let mut cols = BTreeSet::new();
+        cond.collect_column_refs(&mut cols);
+        if cols.is_empty() || !cols.iter().all(|col| inner.contains(col)) {
+            top_conds.push(cond);
+            continue;
+        }
+        is_connected |= cols.iter().any(|col| *col < split) && cols.iter().any(|col| *col >= split);
+        inner_conds.push(
+            cond.rewrite_column_refs(&|col| Some(col - inner.start))
+                .unwrap(),
+        );
+    }
+    for class in classes.classes() {
+        let left_col = class.range(inner.start..split).next();
+        let right_col = class.range(split..inner.end).next();
+        let (Some(left_col), Some(right_col)) = (left_col, right_col) else {
+            continue;
+        };
+        is_connected = true;
+        let cond = BinOpExpr::new(
+            ColumnRefExpr::new(left_col - inner.start).into_expr(),
+            ColumnRefExpr::new(right_col - inner.start).into_expr(),
+            BinOpType::Eq,
+        )
+        .into_expr();
+        let cond_node = cond.clone().into_rel_node();
+        if !inner_conds
+            .iter()
+            .any(|x| x.clone().into_rel_node() == cond_node)
+        {
+            inner_conds.push(cond);
+        }
+    }
+    if !is_connected {
+        return None;
+    }
+    Some((and_conjuncts(inner_conds), and_conjuncts(top_conds)))
+}
+
+fn inner_join(
+    left: impl Into<Arc<RelNode<OptRelNodeTyp>>>,
+    right: impl Into<Arc<RelNode<OptRelNodeTyp>>>,
+    cond: Expr,
+    mapping: &[usize],
+) -> RelNode<OptRelNodeTyp> {
+    logical_join(JoinType::Inner, left, right, cond, mapping)
+}
+
+pub(super) fn logical_join(
+    join_type: JoinType,
+    left: impl Into<Arc<RelNode<OptRelNodeTyp>>>,
+    right: impl Into<Arc<RelNode<OptRelNodeTyp>>>,
+    cond: Expr,
+    mapping: &[usize],
+) -> RelNode<OptRelNodeTyp> {
+    RelNode {
+        typ: OptRelNodeTyp::Join(join_type),
+        children: vec![left.into(), right.into(), cond.into_rel_node()],
+        data: join_mapping_data(mapping),
     }
-    let node =
-        LogicalProjection::new(node.into_plan_node(), ExprList::new(proj_expr)).into_rel_node();
-    vec![node.as_ref().clone()]
 }
 
-// (A join B) join C -> A join (B join C)
+/// Maps the output columns of the top join of a tree of three joined inputs to their columns in
+/// A join B join C, given the mapping from the input columns of the top join.
+pub(super) fn tree_mapping(
+    top: &RelNode<OptRelNodeTyp>,
+    width: usize,
+    to_tree: impl Fn(usize) -> usize,
+) -> Vec<usize> {
+    join_mapping(&top.data)
+        .unwrap_or_else(|| (0..width).collect())
+        .into_iter()
+        .map(to_tree)
+        .collect()
+}
+
+// (A join B) join C -> A join (B join C), where the conjuncts of both conditions on B and C
+// join B and C, and the others join A.
 define_rule!(
     JoinAssocRule,
     apply_join_assoc,
     (
-        Join(JoinType::Inner),
-        (Join(JoinType::Inner), a, b, [cond1]),
+        Join(JoinType::Inner) => top,
+        (Join(JoinType::Inner) => inner, a, b, [cond1]),
         c,
         [cond2]
     )
@@ -93,6 +248,8 @@ define_rule!(
 fn apply_join_assoc(
     optimizer: &impl Optimizer<OptRelNodeTyp>,
     JoinAssocRulePicks {
+        top,
+        inner,
         a,
         b,
         c,
@@ -100,110 +257,307 @@ fn apply_join_assoc(
         cond2,
     }: JoinAssocRulePicks,
 ) -> Vec<RelNode<OptRelNodeTyp>> {
-    fn rewrite_column_refs(expr: Expr, a_size: usize) -> Option<Expr> {
-        let expr = expr.into_rel_node();
-        if let Some(expr) = ColumnRefExpr::from_rel_node(expr.clone()) {
-            let index = expr.index();
-            if index < a_size {
-                return None;
-            } else {
-                return Some(ColumnRefExpr::new(index - a_size).into_expr());
-            }
-        }
-        let children = expr.children.clone();
-        let children = children
-            .into_iter()
-            .map(|x| rewrite_column_refs(Expr::from_rel_node(x).unwrap(), a_size))
-            .collect::<Option<Vec<_>>>()?;
-        Some(
-            Expr::from_rel_node(
-                RelNode {
-                    typ: expr.typ.clone(),
-                    children: children
-                        .into_iter()
-                        .map(|x| x.into_rel_node())
-                        .collect_vec(),
-                    data: expr.data.clone(),
-                }
-                .into(),
-            )
+    let b_start = schema_len(optimizer, &a);
+    let c_start = b_start + schema_len(optimizer, &b);
+    let width = c_start + schema_len(optimizer, &c);
+    // The condition of A join B is indexed within A join B join C already, and the columns of A
+    // join B in the condition of the top join are taken through its mapping.
+    let inner_mapping = join_mapping(&inner.data);
+    let to_tree = |col: usize| match &inner_mapping {
+        Some(mapping) if col < c_start => mapping[col],
+        _ => col,
+    };
+    let mut conds = vec![];
+    split_conjuncts(Expr::from_rel_node(cond1.into()).unwrap(), &mut conds);
+    split_conjuncts(
+        Expr::from_rel_node(cond2.into())
+            .unwrap()
+            .rewrite_column_refs(&|col| Some(to_tree(col)))
             .unwrap(),
-        )
-    }
-    let a_schema = optimizer.get_property::<SchemaPropertyBuilder>(Arc::new(a.clone()), 0);
-    let b_schema = optimizer.get_property::<SchemaPropertyBuilder>(Arc::new(b.clone()), 0);
-    let c_schema = optimizer.get_property::<SchemaPropertyBuilder>(Arc::new(c.clone()), 0);
-    let cond2 = Expr::from_rel_node(cond2.into()).unwrap();
-    let Some(cond2) = rewrite_column_refs(cond2, a_schema.len()) else {
+        &mut conds,
+    );
+    let classes = join_tree_classes(optimizer, &[&a, &b, &c], &conds);
+    let Some((bc_cond, cond)) = redistribute_conds(&classes, conds, b_start..width, c_start) else {
         return vec![];
     };
-    let node = RelNode {
-        typ: OptRelNodeTyp::Join(JoinType::Inner),
-        children: vec![
-            a.into(),
-            RelNode {
-                typ: OptRelNodeTyp::Join(JoinType::Inner),
-                children: vec![b.into(), c.into(), cond2.into_rel_node()],
-                data: None,
+    let mapping = tree_mapping(&top, width, to_tree);
+    vec![inner_join(
+        a,
+        inner_join(b, c, bc_cond, &[]),
+        cond,
+        &mapping,
+    )]
+}
+
+// A join (B join C) -> (A join B) join C, where the conjuncts of both conditions on A and B
+// join A and B, and the others join C.
+define_rule!(
+    JoinAssocReverseRule,
+    apply_join_assoc_reverse,
+    (
+        Join(JoinType::Inner) => top,
+        a,
+        (Join(JoinType::Inner) => inner, b, c, [cond1]),
+        [cond2]
+    )
+);
+
+fn apply_join_assoc_reverse(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    JoinAssocReverseRulePicks {
+        top,
+        a,
+        inner,
+        b,
+        c,
+        cond1,
+        cond2,
+    }: JoinAssocReverseRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let b_start = schema_len(optimizer, &a);
+    let c_start = b_start + schema_len(optimizer, &b);
+    let width = c_start + schema_len(optimizer, &c);
+    // The condition of B join C is moved into the columns of A join B join C, and the columns of
+    // B join C in the condition of the top join are taken through its mapping.
+    let inner_mapping = join_mapping(&inner.data);
+    let to_tree = |col: usize| match &inner_mapping {
+        Some(mapping) if col >= b_start => b_start + mapping[col - b_start],
+        _ => col,
+    };
+    let mut conds = vec![];
+    split_conjuncts(
+        Expr::from_rel_node(cond1.into())
+            .unwrap()
+            .rewrite_column_refs(&|col| Some(col + b_start))
+            .unwrap(),
+        &mut conds,
+    );
+    split_conjuncts(
+        Expr::from_rel_node(cond2.into())
+            .unwrap()
+            .rewrite_column_refs(&|col| Some(to_tree(col)))
+            .unwrap(),
+        &mut conds,
+    );
+    let classes = join_tree_classes(optimizer, &[&a, &b, &c], &conds);
+    let Some((ab_cond, cond)) = redistribute_conds(&classes, conds, 0..c_start, b_start) else {
+        return vec![];
+    };
+    let mapping = tree_mapping(&top, width, to_tree);
+    vec![inner_join(
+        inner_join(a, b, ab_cond, &[]),
+        c,
+        cond,
+        &mapping,
+    )]
+}
+
+fn equivalence_classes(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    node: &RelNode<OptRelNodeTyp>,
+) -> EquivalenceClasses {
+    optimizer.get_property::<EquivalencePropertyBuilder>(Arc::new(node.clone()), 3)
+}
+
+/// Gets the column of the left side and the column of the right side an equality compares, the
+/// latter indexed within the right side.
+fn equi_join_pair(cond: &Expr, left_len: usize) -> Option<(ColumnRefExpr, ColumnRefExpr)> {
+    let OptRelNodeTyp::BinOp(BinOpType::Eq) = cond.typ() else {
+        return None;
+    };
+    let op = BinOpExpr::from_rel_node(cond.clone().into_rel_node()).unwrap();
+    let mut left_expr = ColumnRefExpr::from_rel_node(op.left_child().into_rel_node())?;
+    let mut right_expr = ColumnRefExpr::from_rel_node(op.right_child().into_rel_node())?;
+    if right_expr.index() < left_len && left_expr.index() >= left_len {
+        (left_expr, right_expr) = (right_expr, left_expr);
+    } else if left_expr.index() >= left_len || right_expr.index() < left_len {
+        return None;
+    }
+    Some((left_expr, ColumnRefExpr::new(right_expr.index() - left_len)))
+}
+
+/// Splits a join condition into the equi-join keys of both sides, the right keys indexed within
+/// the right side, and the remaining conjuncts, which are evaluated on the join output. Returns
+/// `None` if the condition has no equi-join conjunct.
+fn hash_join_keys(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    left: &RelNode<OptRelNodeTyp>,
+    cond: &RelNode<OptRelNodeTyp>,
+) -> Option<(ExprList, ExprList, Vec<Expr>)> {
+    let left_schema = optimizer.get_property::<SchemaPropertyBuilder>(Arc::new(left.clone()), 0);
+    let mut conjuncts = vec![];
+    split_conjuncts(
+        Expr::from_rel_node(Arc::new(cond.clone())).unwrap(),
+        &mut conjuncts,
+    );
+    let mut left_keys = vec![];
+    let mut right_keys = vec![];
+    let mut residual = vec![];
+    for conjunct in conjuncts {
+        match equi_join_pair(&conjunct, left_schema.len()) {
+            Some((left_key, right_key)) => {
+                left_keys.push(left_key.into_expr());
+                right_keys.push(right_key.into_expr());
             }
-            .into(),
-            cond1.into(),
-        ],
-        data: None,
+            None => residual.push(conjunct),
+        }
+    }
+    if left_keys.is_empty() {
+        return None;
+    }
+    Some((
+        ExprList::new(left_keys),
+        ExprList::new(right_keys),
+        residual,
+    ))
+}
+
+/// Builds inner hash joins of `left` and `right` with the hash table on either side, filtering
+/// the join output by the `residual` conjuncts and reordering it by the output column mapping of
+/// `join`. If `runtime_filter` is set, the probe side is filtered by the keys collected from the
+/// build side.
+fn hash_joins(
+    join: &RelNode<OptRelNodeTyp>,
+    left: PlanNode,
+    right: PlanNode,
+    left_keys: ExprList,
+    right_keys: ExprList,
+    residual: Vec<Expr>,
+    runtime_filter: bool,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let mapping = join_mapping(&join.data);
+    let probe = |child: &PlanNode, keys: &ExprList| {
+        if runtime_filter {
+            PhysicalRuntimeFilter::new(child.clone(), keys.clone()).into_plan_node()
+        } else {
+            child.clone()
+        }
     };
-    vec![node]
+    let residual = (!residual.is_empty()).then(|| and_conjuncts(residual));
+    [
+        (left.clone(), probe(&right, &right_keys), false),
+        (probe(&left, &left_keys), right.clone(), true),
+    ]
+    .into_iter()
+    .map(|(left, right, build_right)| {
+        let node = PhysicalHashJoin::new(
+            left,
+            right,
+            left_keys.clone(),
+            right_keys.clone(),
+            JoinType::Inner,
+        )
+        .with_build_right(build_right)
+        .into_plan_node();
+        let node = match &residual {
+            Some(cond) => PhysicalFilter::new(node, cond.clone()).into_plan_node(),
+            None => node,
+        };
+        project_join_output(node.into_rel_node().as_ref().clone(), mapping.as_deref())
+    })
+    .collect()
 }
 
 define_impl_rule!(
     HashJoinRule,
     apply_hash_join,
-    (Join(JoinType::Inner), left, right, [cond])
+    (Join(JoinType::Inner) => join, left, right, [cond])
 );
 
 fn apply_hash_join(
     optimizer: &impl Optimizer<OptRelNodeTyp>,
-    HashJoinRulePicks { left, right, cond }: HashJoinRulePicks,
+    HashJoinRulePicks {
+        join,
+        left,
+        right,
+        cond,
+    }: HashJoinRulePicks,
 ) -> Vec<RelNode<OptRelNodeTyp>> {
-    if let OptRelNodeTyp::BinOp(BinOpType::Eq) = cond.typ {
-        let left_schema =
-            optimizer.get_property::<SchemaPropertyBuilder>(Arc::new(left.clone()), 0);
-        // let right_schema =
-        //     optimizer.get_property::<SchemaPropertyBuilder>(Arc::new(right.clone()), 0);
-        let op = BinOpExpr::from_rel_node(Arc::new(cond.clone())).unwrap();
-        let left_expr: Expr = op.left_child();
-        let right_expr = op.right_child();
-        let Some(mut left_expr) = ColumnRefExpr::from_rel_node(left_expr.into_rel_node()) else {
-            return vec![];
-        };
-        let Some(mut right_expr) = ColumnRefExpr::from_rel_node(right_expr.into_rel_node()) else {
-            return vec![];
-        };
-        let can_convert = if left_expr.index() < left_schema.0.len()
-            && right_expr.index() >= left_schema.0.len()
-        {
-            true
-        } else if right_expr.index() < left_schema.0.len()
-            && left_expr.index() >= left_schema.0.len()
-        {
-            (left_expr, right_expr) = (right_expr, left_expr);
-            true
-        } else {
-            false
-        };
+    let Some((left_keys, right_keys, residual)) = hash_join_keys(optimizer, &left, &cond) else {
+        return vec![];
+    };
+    hash_joins(
+        &join,
+        PlanNode::from_group(left.into()),
+        PlanNode::from_group(right.into()),
+        left_keys,
+        right_keys,
+        residual,
+        false,
+    )
+}
 
-        if can_convert {
-            let right_expr = ColumnRefExpr::new(right_expr.index() - left_schema.0.len());
-            let node = PhysicalHashJoin::new(
-                PlanNode::from_group(left.into()),
-                PlanNode::from_group(right.into()),
-                ExprList::new(vec![left_expr.into_expr()]),
-                ExprList::new(vec![right_expr.into_expr()]),
-                JoinType::Inner,
-            );
-            return vec![node.into_rel_node().as_ref().clone()];
-        }
+// A join B -> A hash join (RuntimeFilter B), and the same with the hash table built on B, where
+// the probe side is filtered by the keys of the build side before reaching the join.
+define_impl_rule!(
+    RuntimeFilterHashJoinRule,
+    apply_runtime_filter_hash_join,
+    (Join(JoinType::Inner) => join, left, right, [cond])
+);
+
+fn apply_runtime_filter_hash_join(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    RuntimeFilterHashJoinRulePicks {
+        join,
+        left,
+        right,
+        cond,
+    }: RuntimeFilterHashJoinRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let Some((left_keys, right_keys, residual)) = hash_join_keys(optimizer, &left, &cond) else {
+        return vec![];
+    };
+    hash_joins(
+        &join,
+        PlanNode::from_group(left.into()),
+        PlanNode::from_group(right.into()),
+        left_keys,
+        right_keys,
+        residual,
+        true,
+    )
+}
+
+// A join B -> (Exchange A left_keys) hash join (Exchange B right_keys), where both sides are hash
+// partitioned on the join keys so that each partition builds and probes its own hash table.
+define_impl_rule!(
+    PartitionedHashJoinRule,
+    apply_partitioned_hash_join,
+    (Join(JoinType::Inner) => join, left, right, [cond])
+);
+
+fn apply_partitioned_hash_join(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    PartitionedHashJoinRulePicks {
+        join,
+        left,
+        right,
+        cond,
+    }: PartitionedHashJoinRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let Some((left_keys, right_keys, residual)) = hash_join_keys(optimizer, &left, &cond) else {
+        return vec![];
+    };
+    // A side that is gathered into one partition anyway is cheaper to build or probe as is.
+    if distribution(optimizer, &left) == Distribution::Single
+        || distribution(optimizer, &right) == Distribution::Single
+    {
+        return vec![];
     }
-    vec![]
+    // Both sides are required to be hash partitioned on their keys, in the same order.
+    let (Some(left_cols), Some(right_cols)) = (key_columns(&left_keys), key_columns(&right_keys))
+    else {
+        return vec![];
+    };
+    hash_joins(
+        &join,
+        enforce(optimizer, left, &Distribution::Hash(left_cols)),
+        enforce(optimizer, right, &Distribution::Hash(right_cols)),
+        left_keys,
+        right_keys,
+        residual,
+        false,
+    )
 }
 
 // (Proj A) join B -> (Proj (A join B))
@@ -211,7 +565,7 @@ define_rule!(
     ProjectionPullUpJoin,
     apply_projection_pull_up_join,
     (
-        Join(JoinType::Inner),
+        Join(JoinType::Inner) => join,
         (Projection, left, [list]),
         right,
         [cond]
@@ -250,6 +604,7 @@ impl ProjectionMapping {
 fn apply_projection_pull_up_join(
     optimizer: &impl Optimizer<OptRelNodeTyp>,
     ProjectionPullUpJoinPicks {
+        join,
         left,
         right,
         list,
@@ -328,6 +683,14 @@ fn apply_projection_pull_up_join(
         let col: Expr = ColumnRefExpr::new(i + left_schema.len()).into_expr();
         new_projection_exprs.push(col);
     }
+    // The new projection takes the place of the join, so it outputs the columns in the order of
+    // the join's mapping.
+    if let Some(mapping) = join_mapping(&join.data) {
+        new_projection_exprs = mapping
+            .iter()
+            .map(|col| new_projection_exprs[*col].clone())
+            .collect();
+    }
     let node = LogicalProjection::new(
         LogicalJoin::new(
             PlanNode::from_group(left),
@@ -345,3 +708,240 @@ fn apply_projection_pull_up_join(
     );
     vec![node.into_rel_node().as_ref().clone()]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan_nodes::LogicalFilter;
+    use crate::testing::{col, eq, new_heuristics_optimizer, scan};
+
+    fn lt(left: Expr, right: Expr) -> Expr {
+        BinOpExpr::new(left, right, BinOpType::Lt).into_expr()
+    }
+
+    fn join(left: PlanNode, right: PlanNode, cond: Expr) -> PlanNode {
+        LogicalJoin::new(left, right, cond, JoinType::Inner).into_plan_node()
+    }
+
+    fn rel_node(plan: PlanNode) -> RelNode<OptRelNodeTyp> {
+        plan.into_rel_node().as_ref().clone()
+    }
+
+    fn join_commute(join: PlanNode) -> Vec<RelNode<OptRelNodeTyp>> {
+        let join = rel_node(join);
+        let picks = JoinCommuteRulePicks {
+            left: join.child(0).as_ref().clone(),
+            right: join.child(1).as_ref().clone(),
+            cond: join.child(2).as_ref().clone(),
+            join,
+        };
+        apply_join_commute(&new_heuristics_optimizer(), picks)
+    }
+
+    /// Picks the inputs and conditions of `(A join B) join C`.
+    fn join_assoc(top: PlanNode) -> Vec<RelNode<OptRelNodeTyp>> {
+        let top = rel_node(top);
+        let inner = top.child(0).as_ref().clone();
+        let picks = JoinAssocRulePicks {
+            a: inner.child(0).as_ref().clone(),
+            b: inner.child(1).as_ref().clone(),
+            cond1: inner.child(2).as_ref().clone(),
+            c: top.child(1).as_ref().clone(),
+            cond2: top.child(2).as_ref().clone(),
+            inner,
+            top,
+        };
+        apply_join_assoc(&new_heuristics_optimizer(), picks)
+    }
+
+    /// Picks the inputs and conditions of `A join (B join C)`.
+    fn join_assoc_reverse(top: PlanNode) -> Vec<RelNode<OptRelNodeTyp>> {
+        let top = rel_node(top);
+        let inner = top.child(1).as_ref().clone();
+        let picks = JoinAssocReverseRulePicks {
+            a: top.child(0).as_ref().clone(),
+            b: inner.child(0).as_ref().clone(),
+            c: inner.child(1).as_ref().clone(),
+            cond1: inner.child(2).as_ref().clone(),
+            cond2: top.child(2).as_ref().clone(),
+            inner,
+            top,
+        };
+        apply_join_assoc_reverse(&new_heuristics_optimizer(), picks)
+    }
+
+    fn hash_join(join: PlanNode) -> Vec<RelNode<OptRelNodeTyp>> {
+        let join = rel_node(join);
+        let picks = HashJoinRulePicks {
+            left: join.child(0).as_ref().clone(),
+            right: join.child(1).as_ref().clone(),
+            cond: join.child(2).as_ref().clone(),
+            join,
+        };
+        apply_hash_join(&new_heuristics_optimizer(), picks)
+    }
+
+    #[test]
+    fn conjunctions_of_equalities_are_hashed_on_every_key() {
+        // t1 join t2 on t1.a = t2.a and t2.b = t1.b and t1.a = t1.c and t1.c < t2.c
+        let cond = and_conjuncts(vec![
+            eq(col(0), col(3)),
+            eq(col(4), col(1)),
+            eq(col(0), col(2)),
+            lt(col(2), col(5)),
+        ]);
+        let joins = hash_join(join(scan("t1", 3), scan("t2", 3), cond));
+        let expected = |build_right| {
+            let node = PhysicalHashJoin::new(
+                scan("t1", 3),
+                scan("t2", 3),
+                ExprList::new(vec![col(0), col(1)]),
+                ExprList::new(vec![col(0), col(1)]),
+                JoinType::Inner,
+            )
+            .with_build_right(build_right);
+            // Equalities within one side and other predicates are applied to the join output.
+            let residual = and_conjuncts(vec![eq(col(0), col(2)), lt(col(2), col(5))]);
+            PhysicalFilter::new(node.into_plan_node(), residual)
+                .into_rel_node()
+                .as_ref()
+                .clone()
+        };
+        assert_eq!(joins, vec![expected(false), expected(true)]);
+    }
+
+    #[test]
+    fn joins_without_equalities_are_not_hashed() {
+        let cond = and_conjuncts(vec![lt(col(0), col(3)), eq(col(0), col(1))]);
+        assert!(hash_join(join(scan("t1", 3), scan("t2", 3), cond)).is_empty());
+    }
+
+    #[test]
+    fn equalities_are_transitive_across_joins() {
+        // (t1 join t2 on t1.a = t2.a) join t3 on t2.a = t3.a where t3.b = t1.b
+        let inner = join(scan("t1", 2), scan("t2", 2), eq(col(0), col(2)));
+        let top = join(inner, scan("t3", 2), eq(col(2), col(4)));
+        let plan = LogicalFilter::new(top, eq(col(5), col(1))).into_rel_node();
+        let classes =
+            new_heuristics_optimizer().get_property::<EquivalencePropertyBuilder>(plan, 3);
+        assert_eq!(
+            classes.classes(),
+            &[BTreeSet::from([0, 2, 4]), BTreeSet::from([1, 5])]
+        );
+        assert!(classes.is_equal(0, 4));
+        assert!(!classes.is_equal(0, 1));
+    }
+
+    #[test]
+    fn associated_joins_get_implied_equalities() {
+        // (t1 join t2 on t1.a = t2.a) join t3 on t1.a = t3.a, where t2 and t3 only join through
+        // t1.a.
+        let inner = join(scan("t1", 2), scan("t2", 2), eq(col(0), col(2)));
+        let top = join(inner, scan("t3", 2), eq(col(0), col(4)));
+        // t1 join (t2 join t3 on t2.a = t3.a) on t1.a = t2.a and t1.a = t3.a
+        let bc = join(scan("t2", 2), scan("t3", 2), eq(col(0), col(2)));
+        let expected = join(
+            scan("t1", 2),
+            bc,
+            and_conjuncts(vec![eq(col(0), col(2)), eq(col(0), col(4))]),
+        );
+        assert_eq!(join_assoc(top), vec![rel_node(expected)]);
+    }
+
+    #[test]
+    fn implied_equalities_are_not_repeated() {
+        // (t1 join t2 on t1.a = t2.a) join t3 on t1.a = t3.a and t2.a = t3.a
+        let inner = join(scan("t1", 2), scan("t2", 2), eq(col(0), col(2)));
+        let cond = and_conjuncts(vec![eq(col(0), col(4)), eq(col(2), col(4))]);
+        let top = join(inner, scan("t3", 2), cond);
+        let joins = join_assoc(top);
+        assert_eq!(joins.len(), 1);
+        assert_eq!(
+            joins[0].child(1).child(2),
+            eq(col(0), col(2)).into_rel_node()
+        );
+    }
+
+    #[test]
+    fn joins_are_associated_to_the_left() {
+        // t1 join (t2 join t3 on t2.a = t3.a) on t1.a = t2.a
+        let bc = join(scan("t2", 2), scan("t3", 2), eq(col(0), col(2)));
+        let top = join(scan("t1", 2), bc, eq(col(0), col(2)));
+        // (t1 join t2 on t1.a = t2.a) join t3 on t2.a = t3.a
+        let ab = join(scan("t1", 2), scan("t2", 2), eq(col(0), col(2)));
+        let expected = join(ab, scan("t3", 2), eq(col(2), col(4)));
+        assert_eq!(join_assoc_reverse(top), vec![rel_node(expected)]);
+    }
+
+    #[test]
+    fn conjuncts_are_redistributed_when_associating_to_the_left() {
+        // t1 join (t2 join t3 on t2.a = t3.a) on t1.a = t3.a, where t1 and t2 only join through
+        // t3.a.
+        let bc = join(scan("t2", 2), scan("t3", 2), eq(col(0), col(2)));
+        let top = join(scan("t1", 2), bc, eq(col(0), col(4)));
+        // (t1 join t2 on t1.a = t2.a) join t3 on t2.a = t3.a and t1.a = t3.a
+        let ab = join(scan("t1", 2), scan("t2", 2), eq(col(0), col(2)));
+        let cond = and_conjuncts(vec![eq(col(2), col(4)), eq(col(0), col(4))]);
+        let expected = join(ab, scan("t3", 2), cond);
+        assert_eq!(join_assoc_reverse(top), vec![rel_node(expected)]);
+    }
+
+    /// `t2 join t1 on t2.a = t1.a`, with the columns in the order of `t1 join t2`.
+    fn commuted_join() -> PlanNode {
+        LogicalJoin::new(
+            scan("t2", 3),
+            scan("t1", 2),
+            eq(col(3), col(0)),
+            JoinType::Inner,
+        )
+        .with_mapping(&[3, 4, 0, 1, 2])
+        .into_plan_node()
+    }
+
+    #[test]
+    fn commuted_joins_map_their_columns_back() {
+        // t1 join t2 on t1.a = t2.a
+        let plan = join(scan("t1", 2), scan("t2", 3), eq(col(0), col(2)));
+        let commuted = join_commute(plan.clone());
+        assert_eq!(commuted, vec![rel_node(commuted_join())]);
+        // Commuting again cancels the mapping.
+        let commuted = PlanNode::from_rel_node(commuted[0].clone().into()).unwrap();
+        assert_eq!(join_commute(commuted), vec![rel_node(plan)]);
+    }
+
+    #[test]
+    fn mapped_joins_keep_the_schema_of_their_inputs() {
+        let plan = join(scan("t1", 2), scan("t2", 3), eq(col(0), col(2)));
+        let optimizer = new_heuristics_optimizer();
+        let schema = |plan: PlanNode| {
+            optimizer
+                .get_property::<SchemaPropertyBuilder>(plan.into_rel_node(), 0)
+                .fields
+        };
+        assert_eq!(schema(commuted_join()), schema(plan));
+        // Expressions over the output of the join refer to the columns of the inputs through
+        // the mapping.
+        let exprs = ExprList::new(vec![col(0), col(2)]);
+        assert_eq!(
+            unmap_join_output(&rel_node(commuted_join()), exprs).into_rel_node(),
+            ExprList::new(vec![col(3), col(0)]).into_rel_node()
+        );
+    }
+
+    #[test]
+    fn physical_joins_project_the_mapping() {
+        let joins = hash_join(commuted_join());
+        let node = PhysicalHashJoin::new(
+            scan("t2", 3),
+            scan("t1", 2),
+            ExprList::new(vec![col(0)]),
+            ExprList::new(vec![col(0)]),
+            JoinType::Inner,
+        );
+        let expected = PhysicalProjection::new(
+            node.into_plan_node(),
+            ExprList::new(vec![col(3), col(4), col(0), col(1), col(2)]),
+        );
+        assert_eq!(joins[0], rel_node(expected.into_plan_node()));
+    }
+}
@@ -1,4 +1,13 @@
 macro_rules! define_matcher {
+    ( $pick_num:ident, ( $typ:expr => $node:ident $(, $children:tt )* ) ) => {
+        RuleMatcher::MatchAndPickNode {
+            typ: $typ,
+            pick_to: { let x = $pick_num; $pick_num += 1; x },
+            children: vec![
+                $( crate::rules::macros::define_matcher!($pick_num, $children) ),*
+            ],
+        }
+    };
     ( $pick_num:ident, ( $typ:expr $(, $children:tt )* ) ) => {
         RuleMatcher::MatchNode {
             typ: $typ,
@@ -22,6 +31,10 @@ macro_rules! define_matcher {
 }
 
 macro_rules! define_picks {
+    ( ( $typ:expr => $node:ident $(, $children:tt )* ) ) => {
+        let $node : RelNode<OptRelNodeTyp>;
+        $( crate::rules::macros::define_picks!($children); )*
+    };
     ( ( $typ:expr $(, $children:tt )* ) ) => {
         $( crate::rules::macros::define_picks!($children); )*
     };
@@ -40,6 +53,13 @@ macro_rules! collect_picks {
         }
     );
 
+    ( @ $name:ident { ( $typ:expr => $node:ident $(, $children:tt )* ) } { $($rest:tt),* } -> ($($result:tt)*) ) => (
+        crate::rules::macros::collect_picks!(@@ $name { $($children),* $(, $rest)* } -> (
+            $($result)*
+            $node,
+        ))
+    );
+
     ( @ $name:ident { ( $typ:expr $(, $children:tt )* ) } { $($rest:tt),* } -> ($($result:tt)*) ) => (
         crate::rules::macros::collect_picks!(@@ $name { $($children),* $(, $rest)* } -> (
             $($result)*
@@ -86,6 +106,13 @@ macro_rules! define_picks_struct {
         }
     );
 
+    ( @ $name:ident { ( $typ:expr => $node:ident $(, $children:tt )* ) } { $($rest:tt),* } -> ($($result:tt)*) ) => (
+        crate::rules::macros::define_picks_struct!(@@ $name { $($children),* $(, $rest)* } -> (
+            $($result)*
+            pub $node: RelNode<OptRelNodeTyp>,
+        ));
+    );
+
     ( @ $name:ident { ( $typ:expr $(, $children:tt )* ) } { $($rest:tt),* } -> ($($result:tt)*) ) => (
         crate::rules::macros::define_picks_struct!(@@ $name { $($children),* $(, $rest)* } -> (
             $($result)*
@@ -127,6 +154,13 @@ macro_rules! define_picks_struct {
 }
 
 macro_rules! apply_matcher {
+    ( $pick_num:ident, $input:ident, ( $typ:expr => $node:ident $(, $children:tt )* ) ) => {
+        {
+            $node = $input.remove(&$pick_num).unwrap();
+            $pick_num += 1;
+        }
+        $( crate::rules::macros::apply_matcher!($pick_num, $input, $children) ;)*
+    };
     ( $pick_num:ident, $input:ident, ( $typ:expr $(, $children:tt )* ) ) => {
         $( crate::rules::macros::apply_matcher!($pick_num, $input, $children) ;)*
     };
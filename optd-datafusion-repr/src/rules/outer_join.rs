@@ -0,0 +1,421 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use optd_core::optimizer::Optimizer;
+use optd_core::rel_node::RelNode;
+use optd_core::rules::{Rule, RuleMatcher};
+
+use super::joins::{commute_join, logical_join, schema_len, tree_mapping};
+use crate::plan_nodes::{join_mapping, Expr, JoinType, OptRelNode, OptRelNodeTyp};
+
+/// The join type of an outer join once the rows padded with nulls on the `left` and/or `right`
+/// side are filtered out.
+fn simplified_join_type(join_type: JoinType, left: bool, right: bool) -> Option<JoinType> {
+    match (join_type, left, right) {
+        (JoinType::LeftOuter, _, true)
+        | (JoinType::RightOuter, true, _)
+        | (JoinType::FullOuter, true, true) => Some(JoinType::Inner),
+        (JoinType::FullOuter, true, false) => Some(JoinType::LeftOuter),
+        (JoinType::FullOuter, false, true) => Some(JoinType::RightOuter),
+        _ => None,
+    }
+}
+
+fn columns_of(cond: &Expr) -> BTreeSet<usize> {
+    let mut cols = BTreeSet::new();
+    cond.collect_column_refs(&mut cols);
+    cols
+}
+
+/// Filter (A outer join B) cond -> Filter (A join B) cond, where the outer join becomes a left
+/// outer, right outer or inner join if `cond` rejects nulls on the columns of the side the join
+/// pads with nulls, as the padded rows are filtered out anyways.
+pub struct OuterJoinSimplifyRule {
+    matcher: RuleMatcher<OptRelNodeTyp>,
+    join_type: JoinType,
+}
+
+impl OuterJoinSimplifyRule {
+    pub fn new(join_type: JoinType) -> Self {
+        Self {
+            matcher: RuleMatcher::MatchNode {
+                typ: OptRelNodeTyp::Filter,
+                children: vec![
+                    RuleMatcher::MatchAndPickNode {
+                        typ: OptRelNodeTyp::Join(join_type),
+                        pick_to: 0,
+                        children: vec![
+                            RuleMatcher::PickOne {
+                                pick_to: 1,
+                                expand: false,
+                            },
+                            RuleMatcher::PickOne {
+                                pick_to: 2,
+                                expand: false,
+                            },
+                            RuleMatcher::PickOne {
+                                pick_to: 3,
+                                expand: true,
+                            },
+                        ],
+                    },
+                    RuleMatcher::PickOne {
+                        pick_to: 4,
+                        expand: true,
+                    },
+                ],
+            },
+            join_type,
+        }
+    }
+
+    pub fn all<O: Optimizer<OptRelNodeTyp>>() -> Vec<Arc<dyn Rule<OptRelNodeTyp, O>>> {
+        vec![
+            Arc::new(Self::new(JoinType::LeftOuter)),
+            Arc::new(Self::new(JoinType::RightOuter)),
+            Arc::new(Self::new(JoinType::FullOuter)),
+        ]
+    }
+}
+
+impl<O: Optimizer<OptRelNodeTyp>> Rule<OptRelNodeTyp, O> for OuterJoinSimplifyRule {
+    fn matcher(&self) -> &RuleMatcher<OptRelNodeTyp> {
+        &self.matcher
+    }
+
+    fn apply(
+        &self,
+        optimizer: &O,
+        mut input: HashMap<usize, RelNode<OptRelNodeTyp>>,
+    ) -> Vec<RelNode<OptRelNodeTyp>> {
+        let join = input.remove(&0).unwrap();
+        let left = input.remove(&1).unwrap();
+        let right = input.remove(&2).unwrap();
+        let join_cond = input.remove(&3).unwrap();
+        let cond = input.remove(&4).unwrap();
+        // The filter refers to the output of the join, which is mapped from the columns of its
+        // inputs.
+        let mapping = join_mapping(&join.data);
+        let input_cond = Expr::from_rel_node(cond.clone().into())
+            .unwrap()
+            .rewrite_column_refs(&|col| Some(mapping.as_ref().map_or(col, |x| x[col])))
+            .unwrap();
+        let left_len = schema_len(optimizer, &left);
+        let Some(join_type) = simplified_join_type(
+            self.join_type,
+            input_cond.rejects_nulls(&|col| col < left_len),
+            input_cond.rejects_nulls(&|col| col >= left_len),
+        ) else {
+            return vec![];
+        };
+
+        let join = RelNode {
+            typ: OptRelNodeTyp::Join(join_type),
+            children: vec![left.into(), right.into(), join_cond.into()],
+            data: join.data,
+        };
+        vec![RelNode {
+            typ: OptRelNodeTyp::Filter,
+            children: vec![join.into(), cond.into()],
+            data: None,
+        }]
+    }
+
+    fn name(&self) -> &'static str {
+        "outer_join_simplify"
+    }
+}
+
+/// A left outer join B -> B right outer join A, and the same for right and full outer joins,
+/// with the output columns mapped back to the order of A outer join B.
+pub struct OuterJoinCommuteRule {
+    matcher: RuleMatcher<OptRelNodeTyp>,
+    join_type: JoinType,
+}
+
+impl OuterJoinCommuteRule {
+    pub fn new(join_type: JoinType) -> Self {
+        Self {
+            matcher: RuleMatcher::MatchAndPickNode {
+                typ: OptRelNodeTyp::Join(join_type),
+                pick_to: 0,
+                children: vec![
+                    RuleMatcher::PickOne {
+                        pick_to: 1,
+                        expand: false,
+                    },
+                    RuleMatcher::PickOne {
+                        pick_to: 2,
+                        expand: false,
+                    },
+                    RuleMatcher::PickOne {
+                        pick_to: 3,
+                        expand: true,
+                    },
+                ],
+            },
+            join_type,
+        }
+    }
+
+    pub fn all<O: Optimizer<OptRelNodeTyp>>() -> Vec<Arc<dyn Rule<OptRelNodeTyp, O>>> {
+        vec![
+            Arc::new(Self::new(JoinType::LeftOuter)),
+            Arc::new(Self::new(JoinType::RightOuter)),
+            Arc::new(Self::new(JoinType::FullOuter)),
+        ]
+    }
+}
+
+impl<O: Optimizer<OptRelNodeTyp>> Rule<OptRelNodeTyp, O> for OuterJoinCommuteRule {
+    fn matcher(&self) -> &RuleMatcher<OptRelNodeTyp> {
+        &self.matcher
+    }
+
+    fn apply(
+        &self,
+        optimizer: &O,
+        mut input: HashMap<usize, RelNode<OptRelNodeTyp>>,
+    ) -> Vec<RelNode<OptRelNodeTyp>> {
+        let join = input.remove(&0).unwrap();
+        let left = input.remove(&1).unwrap();
+        let right = input.remove(&2).unwrap();
+        let cond = input.remove(&3).unwrap();
+        let join_type = match self.join_type {
+            JoinType::LeftOuter => JoinType::RightOuter,
+            JoinType::RightOuter => JoinType::LeftOuter,
+            join_type => join_type,
+        };
+        vec![commute_join(optimizer, &join, left, right, cond, join_type)]
+    }
+
+    fn name(&self) -> &'static str {
+        "outer_join_commute"
+    }
+}
+
+/// (A join B) left outer join C -> A join (B left outer join C), where `join` is an inner or left
+/// outer join and the condition of the left outer join only refers to B and C. A left outer join
+/// below additionally needs the condition to reject nulls on B, so that the rows of A without a
+/// match in B, which are padded with nulls, do not join C on either side.
+pub struct OuterJoinAssocRule {
+    matcher: RuleMatcher<OptRelNodeTyp>,
+    join_type: JoinType,
+}
+
+impl OuterJoinAssocRule {
+    pub fn new(join_type: JoinType) -> Self {
+        Self {
+            matcher: RuleMatcher::MatchAndPickNode {
+                typ: OptRelNodeTyp::Join(JoinType::LeftOuter),
+                pick_to: 0,
+                children: vec![
+                    RuleMatcher::MatchAndPickNode {
+                        typ: OptRelNodeTyp::Join(join_type),
+                        pick_to: 1,
+                        children: vec![
+                            RuleMatcher::PickOne {
+                                pick_to: 2,
+                                expand: false,
+                            },
+                            RuleMatcher::PickOne {
+                                pick_to: 3,
+                                expand: false,
+                            },
+                            RuleMatcher::PickOne {
+                                pick_to: 4,
+                                expand: true,
+                            },
+                        ],
+                    },
+                    RuleMatcher::PickOne {
+                        pick_to: 5,
+                        expand: false,
+                    },
+                    RuleMatcher::PickOne {
+                        pick_to: 6,
+                        expand: true,
+                    },
+                ],
+            },
+            join_type,
+        }
+    }
+
+    pub fn all<O: Optimizer<OptRelNodeTyp>>() -> Vec<Arc<dyn Rule<OptRelNodeTyp, O>>> {
+        vec![
+            Arc::new(Self::new(JoinType::Inner)),
+            Arc::new(Self::new(JoinType::LeftOuter)),
+        ]
+    }
+}
+
+impl<O: Optimizer<OptRelNodeTyp>> Rule<OptRelNodeTyp, O> for OuterJoinAssocRule {
+    fn matcher(&self) -> &RuleMatcher<OptRelNodeTyp> {
+        &self.matcher
+    }
+
+    fn apply(
+        &self,
+        optimizer: &O,
+        mut input: HashMap<usize, RelNode<OptRelNodeTyp>>,
+    ) -> Vec<RelNode<OptRelNodeTyp>> {
+        let top = input.remove(&0).unwrap();
+        let inner = input.remove(&1).unwrap();
+        let a = input.remove(&2).unwrap();
+        let b = input.remove(&3).unwrap();
+        let cond1 = input.remove(&4).unwrap();
+        let c = input.remove(&5).unwrap();
+        let cond2 = input.remove(&6).unwrap();
+        let b_start = schema_len(optimizer, &a);
+        let c_start = b_start + schema_len(optimizer, &b);
+        let width = c_start + schema_len(optimizer, &c);
+        // The columns of A join B in the condition of the top join are taken through the mapping
+        // of the inner join into the columns of A join B join C.
+        let inner_mapping = join_mapping(&inner.data);
+        let to_tree = |col: usize| match &inner_mapping {
+            Some(mapping) if col < c_start => mapping[col],
+            _ => col,
+        };
+        let cond2 = Expr::from_rel_node(cond2.into())
+            .unwrap()
+            .rewrite_column_refs(&|col| Some(to_tree(col)))
+            .unwrap();
+        if columns_of(&cond2).iter().any(|col| *col < b_start) {
+            return vec![];
+        }
+        if self.join_type == JoinType::LeftOuter
+            && !cond2.rejects_nulls(&|col| (b_start..c_start).contains(&col))
+        {
+            return vec![];
+        }
+        let bc_cond = cond2
+            .rewrite_column_refs(&|col| Some(col - b_start))
+            .unwrap();
+        let mapping = tree_mapping(&top, width, to_tree);
+        vec![logical_join(
+            self.join_type,
+            a,
+            logical_join(JoinType::LeftOuter, b, c, bc_cond, &[]),
+            Expr::from_rel_node(cond1.into()).unwrap(),
+            &mapping,
+        )]
+    }
+
+    fn name(&self) -> &'static str {
+        "outer_join_assoc"
+    }
+}
+
+/// A join (B left outer join C) -> (A join B) left outer join C, the reverse of
+/// [`OuterJoinAssocRule`], where the condition of `join` only refers to A and B.
+pub struct OuterJoinAssocReverseRule {
+    matcher: RuleMatcher<OptRelNodeTyp>,
+    join_type: JoinType,
+}
+
+impl OuterJoinAssocReverseRule {
+    pub fn new(join_type: JoinType) -> Self {
+        Self {
+            matcher: RuleMatcher::MatchAndPickNode {
+                typ: OptRelNodeTyp::Join(join_type),
+                pick_to: 0,
+                children: vec![
+                    RuleMatcher::PickOne {
+                        pick_to: 1,
+                        expand: false,
+                    },
+                    RuleMatcher::MatchAndPickNode {
+                        typ: OptRelNodeTyp::Join(JoinType::LeftOuter),
+                        pick_to: 2,
+                        children: vec![
+                            RuleMatcher::PickOne {
+                                pick_to: 3,
+                                expand: false,
+                            },
+                            RuleMatcher::PickOne {
+                                pick_to: 4,
+                                expand: false,
+                            },
+                            RuleMatcher::PickOne {
+                                pick_to: 5,
+                                expand: true,
+                            },
+                        ],
+                    },
+                    RuleMatcher::PickOne {
+                        pick_to: 6,
+                        expand: true,
+                    },
+                ],
+            },
+            join_type,
+        }
+    }
+
+    pub fn all<O: Optimizer<OptRelNodeTyp>>() -> Vec<Arc<dyn Rule<OptRelNodeTyp, O>>> {
+        vec![
+            Arc::new(Self::new(JoinType::Inner)),
+            Arc::new(Self::new(JoinType::LeftOuter)),
+        ]
+    }
+}
+
+impl<O: Optimizer<OptRelNodeTyp>> Rule<OptRelNodeTyp, O> for OuterJoinAssocReverseRule {
+    fn matcher(&self) -> &RuleMatcher<OptRelNodeTyp> {
+        &self.matcher
+    }
+
+    fn apply(
+        &self,
+        optimizer: &O,
+        mut input: HashMap<usize, RelNode<OptRelNodeTyp>>,
+    ) -> Vec<RelNode<OptRelNodeTyp>> {
+        let top = input.remove(&0).unwrap();
+        let a = input.remove(&1).unwrap();
+        let inner = input.remove(&2).unwrap();
+        let b = input.remove(&3).unwrap();
+        let c = input.remove(&4).unwrap();
+        let cond1 = input.remove(&5).unwrap();
+        let cond2 = input.remove(&6).unwrap();
+        let b_start = schema_len(optimizer, &a);
+        let c_start = b_start + schema_len(optimizer, &b);
+        let width = c_start + schema_len(optimizer, &c);
+        // The condition of B left outer join C is moved into the columns of A join B join C, and
+        // the columns of B left outer join C in the condition of the top join are taken through
+        // its mapping.
+        let inner_mapping = join_mapping(&inner.data);
+        let to_tree = |col: usize| match &inner_mapping {
+            Some(mapping) if col >= b_start => b_start + mapping[col - b_start],
+            _ => col,
+        };
+        let cond1 = Expr::from_rel_node(cond1.into())
+            .unwrap()
+            .rewrite_column_refs(&|col| Some(col + b_start))
+            .unwrap();
+        let cond2 = Expr::from_rel_node(cond2.into())
+            .unwrap()
+            .rewrite_column_refs(&|col| Some(to_tree(col)))
+            .unwrap();
+        if columns_of(&cond2).iter().any(|col| *col >= c_start) {
+            return vec![];
+        }
+        if self.join_type == JoinType::LeftOuter
+            && !cond1.rejects_nulls(&|col| (b_start..c_start).contains(&col))
+        {
+            return vec![];
+        }
+        let mapping = tree_mapping(&top, width, to_tree);
+        vec![logical_join(
+            JoinType::LeftOuter,
+            logical_join(self.join_type, a, b, cond2, &[]),
+            c,
+            cond1,
+            &mapping,
+        )]
+    }
+
+    fn name(&self) -> &'static str {
+        "outer_join_assoc_reverse"
+    }
+}
@@ -5,7 +5,10 @@ use optd_core::optimizer::Optimizer;
 use optd_core::rel_node::RelNode;
 use optd_core::rules::{Rule, RuleMatcher};
 
-use crate::plan_nodes::{JoinType, OptRelNodeTyp};
+use super::exchange::enforce;
+use super::joins::project_join_output;
+use crate::plan_nodes::{join_mapping, JoinType, OptRelNodeTyp};
+use crate::properties::distribution::Distribution;
 
 pub struct PhysicalConversionRule {
     matcher: RuleMatcher<OptRelNodeTyp>,
@@ -27,6 +30,9 @@ impl PhysicalConversionRule {
     pub fn all_conversions<O: Optimizer<OptRelNodeTyp>>() -> Vec<Arc<dyn Rule<OptRelNodeTyp, O>>> {
         vec![
             Arc::new(PhysicalConversionRule::new(OptRelNodeTyp::Scan)),
+            Arc::new(PhysicalConversionRule::new(OptRelNodeTyp::Empty)),
+            Arc::new(PhysicalConversionRule::new(OptRelNodeTyp::Values)),
+            Arc::new(PhysicalConversionRule::new(OptRelNodeTyp::Union)),
             Arc::new(PhysicalConversionRule::new(OptRelNodeTyp::Projection)),
             Arc::new(PhysicalConversionRule::new(OptRelNodeTyp::Join(
                 JoinType::Inner,
@@ -34,9 +40,14 @@ impl PhysicalConversionRule {
             Arc::new(PhysicalConversionRule::new(OptRelNodeTyp::Join(
                 JoinType::LeftOuter,
             ))),
+            Arc::new(PhysicalConversionRule::new(OptRelNodeTyp::Join(
+                JoinType::RightOuter,
+            ))),
+            Arc::new(PhysicalConversionRule::new(OptRelNodeTyp::Join(
+                JoinType::FullOuter,
+            ))),
             Arc::new(PhysicalConversionRule::new(OptRelNodeTyp::Filter)),
             Arc::new(PhysicalConversionRule::new(OptRelNodeTyp::Sort)),
-            Arc::new(PhysicalConversionRule::new(OptRelNodeTyp::Agg)),
         ]
     }
 }
@@ -48,7 +59,7 @@ impl<O: Optimizer<OptRelNodeTyp>> Rule<OptRelNodeTyp, O> for PhysicalConversionR
 
     fn apply(
         &self,
-        _optimizer: &O,
+        optimizer: &O,
         mut input: HashMap<usize, RelNode<OptRelNodeTyp>>,
     ) -> Vec<RelNode<OptRelNodeTyp>> {
         let RelNode {
@@ -70,9 +81,9 @@ impl<O: Optimizer<OptRelNodeTyp>> Rule<OptRelNodeTyp, O> for PhysicalConversionR
                 let node = RelNode {
                     typ: OptRelNodeTyp::PhysicalNestedLoopJoin(x),
                     children,
-                    data,
+                    data: None,
                 };
-                vec![node]
+                vec![project_join_output(node, join_mapping(&data).as_deref())]
             }
             OptRelNodeTyp::Scan => {
                 let node = RelNode {
@@ -82,33 +93,57 @@ impl<O: Optimizer<OptRelNodeTyp>> Rule<OptRelNodeTyp, O> for PhysicalConversionR
                 };
                 vec![node]
             }
-            OptRelNodeTyp::Filter => {
+            OptRelNodeTyp::Empty => {
                 let node = RelNode {
-                    typ: OptRelNodeTyp::PhysicalFilter,
+                    typ: OptRelNodeTyp::PhysicalEmpty,
                     children,
                     data,
                 };
                 vec![node]
             }
-            OptRelNodeTyp::Projection => {
+            OptRelNodeTyp::Values => {
                 let node = RelNode {
-                    typ: OptRelNodeTyp::PhysicalProjection,
+                    typ: OptRelNodeTyp::PhysicalValues,
                     children,
                     data,
                 };
                 vec![node]
             }
-            OptRelNodeTyp::Sort => {
+            OptRelNodeTyp::Union => {
                 let node = RelNode {
-                    typ: OptRelNodeTyp::PhysicalSort,
+                    typ: OptRelNodeTyp::PhysicalUnion,
+                    children,
+                    data,
+                };
+                vec![node]
+            }
+            OptRelNodeTyp::Filter => {
+                let node = RelNode {
+                    typ: OptRelNodeTyp::PhysicalFilter,
                     children,
                     data,
                 };
                 vec![node]
             }
-            OptRelNodeTyp::Agg => {
+            OptRelNodeTyp::Projection => {
                 let node = RelNode {
-                    typ: OptRelNodeTyp::PhysicalAgg,
+                    typ: OptRelNodeTyp::PhysicalProjection,
+                    children,
+                    data,
+                };
+                vec![node]
+            }
+            OptRelNodeTyp::Sort => {
+                // The sort returns all rows in order from a single partition.
+                let mut children = children;
+                children[0] = enforce(
+                    optimizer,
+                    children[0].as_ref().clone(),
+                    &Distribution::Single,
+                )
+                .into_rel_node();
+                let node = RelNode {
+                    typ: OptRelNodeTyp::PhysicalSort,
                     children,
                     data,
                 };
@@ -0,0 +1,425 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use itertools::Itertools;
+use optd_core::optimizer::Optimizer;
+use optd_core::rel_node::RelNode;
+use optd_core::rules::{Rule, RuleMatcher};
+
+use super::joins::{schema_len, unmap_join_output};
+use super::macros::define_rule;
+use crate::plan_nodes::{
+    ColumnRefExpr, ConstantExpr, Expr, ExprList, JoinType, LogicalAgg, LogicalFilter, LogicalJoin,
+    LogicalProjection, LogicalScan, OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode,
+};
+
+/// The columns of a plan node that are required by its parent, in their original order.
+struct RequiredColumns(Vec<usize>);
+
+impl RequiredColumns {
+    /// Keeps the used columns within `0..size`. A plan node must produce at least one column, so
+    /// the first column is kept if nothing is used.
+    fn new(used: &BTreeSet<usize>, size: usize) -> Self {
+        let mut columns = used.range(0..size).copied().collect_vec();
+        if columns.is_empty() && size > 0 {
+            columns.push(0);
+        }
+        Self(columns)
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Gets the index of an original column after pruning.
+    fn new_index(&self, col: usize) -> Option<usize> {
+        self.0.binary_search(&col).ok()
+    }
+
+    /// Gets the projection list that prunes the node to the required columns.
+    fn projection(&self) -> ExprList {
+        ExprList::new(
+            self.0
+                .iter()
+                .map(|&col| ColumnRefExpr::new(col).into_expr())
+                .collect(),
+        )
+    }
+
+    /// Prunes `child` to the required columns, unless all of its `size` columns are required.
+    fn prune(&self, child: PlanNode, size: usize) -> PlanNode {
+        if self.len() == size {
+            child
+        } else {
+            LogicalProjection::new(child, self.projection()).into_plan_node()
+        }
+    }
+}
+
+fn is_identity(exprs: &ExprList, size: usize) -> bool {
+    exprs.len() == size
+        && exprs.to_vec().into_iter().enumerate().all(|(idx, expr)| {
+            ColumnRefExpr::from_rel_node(expr.into_rel_node())
+                .map(|col| col.index() == idx)
+                .unwrap_or(false)
+        })
+}
+
+// Proj (Proj A exprs2) exprs1 -> Proj A (exprs1 with column refs replaced by exprs2)
+define_rule!(
+    ProjectionMergeRule,
+    apply_projection_merge,
+    (Projection, (Projection, child, [exprs2]), [exprs1])
+);
+
+fn apply_projection_merge(
+    _optimizer: &impl Optimizer<OptRelNodeTyp>,
+    ProjectionMergeRulePicks {
+        child,
+        exprs2,
+        exprs1,
+    }: ProjectionMergeRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    fn substitute_column_refs(expr: OptRelNodeRef, exprs: &[OptRelNodeRef]) -> OptRelNodeRef {
+        if let Some(col) = ColumnRefExpr::from_rel_node(expr.clone()) {
+            return exprs[col.index()].clone();
+        }
+        Arc::new(RelNode {
+            typ: expr.typ.clone(),
+            children: expr
+                .children
+                .iter()
+                .map(|child| substitute_column_refs(child.clone(), exprs))
+                .collect(),
+            data: expr.data.clone(),
+        })
+    }
+
+    let exprs2 = exprs2.children;
+    let exprs = exprs1
+        .children
+        .into_iter()
+        .map(|expr| Expr::from_rel_node(substitute_column_refs(expr, &exprs2)).unwrap())
+        .collect_vec();
+    let node = LogicalProjection::new(PlanNode::from_group(child.into()), ExprList::new(exprs));
+    vec![node.into_rel_node().as_ref().clone()]
+}
+
+/// Proj (Scan t projection) exprs -> Proj (Scan t pruned_projection) exprs', or only the scan if
+/// the remaining projection is an identity. The scan node is picked as a whole so that the table
+/// name stored in its data is kept, which `define_rule!` cannot express.
+pub struct ProjectionPushDownScan {
+    matcher: RuleMatcher<OptRelNodeTyp>,
+}
+
+impl ProjectionPushDownScan {
+    pub fn new() -> Self {
+        Self {
+            matcher: RuleMatcher::MatchNode {
+                typ: OptRelNodeTyp::Projection,
+                children: vec![
+                    RuleMatcher::MatchAndPickNode {
+                        typ: OptRelNodeTyp::Scan,
+                        children: vec![
+                            RuleMatcher::PickOne {
+                                pick_to: 1,
+                                expand: true,
+                            },
+                            RuleMatcher::PickOne {
+                                pick_to: 3,
+                                expand: true,
+                            },
+                            RuleMatcher::PickOne {
+                                pick_to: 4,
+                                expand: true,
+                            },
+                            RuleMatcher::PickOne {
+                                pick_to: 5,
+                                expand: true,
+                            },
+                        ],
+                        pick_to: 0,
+                    },
+                    RuleMatcher::PickOne {
+                        pick_to: 2,
+                        expand: true,
+                    },
+                ],
+            },
+        }
+    }
+}
+
+impl<O: Optimizer<OptRelNodeTyp>> Rule<OptRelNodeTyp, O> for ProjectionPushDownScan {
+    fn matcher(&self) -> &RuleMatcher<OptRelNodeTyp> {
+        &self.matcher
+    }
+
+    fn apply(
+        &self,
+        _optimizer: &O,
+        mut input: HashMap<usize, RelNode<OptRelNodeTyp>>,
+    ) -> Vec<RelNode<OptRelNodeTyp>> {
+        let scan = input.remove(&0).unwrap();
+        let table = scan.data.as_ref().unwrap().as_str().to_string();
+        let projection = ExprList::from_rel_node(Arc::new(input.remove(&1).unwrap())).unwrap();
+        let exprs = ExprList::from_rel_node(Arc::new(input.remove(&2).unwrap())).unwrap();
+        let filters = ExprList::from_rel_node(Arc::new(input.remove(&3).unwrap())).unwrap();
+        let fetch = ConstantExpr::from_rel_node(Arc::new(input.remove(&4).unwrap())).unwrap();
+        let inexact_filters = ExprList::from_rel_node(Arc::new(input.remove(&5).unwrap())).unwrap();
+
+        let mut used = BTreeSet::new();
+        exprs.collect_column_refs(&mut used);
+        let required = RequiredColumns::new(&used, projection.len());
+        if required.len() == projection.len() && !is_identity(&exprs, projection.len()) {
+            return vec![];
+        }
+        let projection = projection.to_vec();
+        let fetch = (!fetch.is_null()).then(|| fetch.value().as_u64() as usize);
+        let scan = LogicalScan::new_with_pushdown(
+            table,
+            ExprList::new(
+                required
+                    .0
+                    .iter()
+                    .map(|&col| projection[col].clone())
+                    .collect(),
+            ),
+            filters,
+            inexact_filters,
+            fetch,
+        );
+        let exprs = exprs
+            .rewrite_column_refs(&|col| required.new_index(col))
+            .unwrap();
+        if is_identity(&exprs, required.len()) {
+            return vec![scan.into_rel_node().as_ref().clone()];
+        }
+        let node = LogicalProjection::new(scan.into_plan_node(), exprs);
+        vec![node.into_rel_node().as_ref().clone()]
+    }
+
+    fn name(&self) -> &'static str {
+        "projection_push_down_scan"
+    }
+}
+
+// Proj (Filter A cond) exprs -> Proj (Filter (Proj A required) cond') exprs'
+define_rule!(
+    ProjectionPushDownFilter,
+    apply_projection_push_down_filter,
+    (Projection, (Filter, child, [cond]), [exprs])
+);
+
+fn apply_projection_push_down_filter(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    ProjectionPushDownFilterPicks { child, cond, exprs }: ProjectionPushDownFilterPicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let child_len = schema_len(optimizer, &child);
+    let cond = Expr::from_rel_node(cond.into()).unwrap();
+    let exprs = ExprList::from_rel_node(exprs.into()).unwrap();
+
+    let mut used = BTreeSet::new();
+    cond.collect_column_refs(&mut used);
+    exprs.collect_column_refs(&mut used);
+    let required = RequiredColumns::new(&used, child_len);
+    if required.len() == child_len {
+        return vec![];
+    }
+
+    let rewrite = |col| required.new_index(col);
+    let filter = LogicalFilter::new(
+        required.prune(PlanNode::from_group(child.into()), child_len),
+        cond.rewrite_column_refs(&rewrite).unwrap(),
+    );
+    let node = LogicalProjection::new(
+        filter.into_plan_node(),
+        exprs.rewrite_column_refs(&rewrite).unwrap(),
+    );
+    vec![node.into_rel_node().as_ref().clone()]
+}
+
+// Proj (A join B) exprs -> Proj ((Proj A required_a) join (Proj B required_b)) exprs'
+define_rule!(
+    ProjectionPushDownJoin,
+    apply_projection_push_down_join,
+    (
+        Projection,
+        (Join(JoinType::Inner) => join, left, right, [cond]),
+        [exprs]
+    )
+);
+
+fn apply_projection_push_down_join(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    ProjectionPushDownJoinPicks {
+        join,
+        left,
+        right,
+        cond,
+        exprs,
+    }: ProjectionPushDownJoinPicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let left_len = schema_len(optimizer, &left);
+    let right_len = schema_len(optimizer, &right);
+    let cond = Expr::from_rel_node(cond.into()).unwrap();
+    let exprs = unmap_join_output(&join, ExprList::from_rel_node(exprs.into()).unwrap());
+
+    let mut used = BTreeSet::new();
+    cond.collect_column_refs(&mut used);
+    exprs.collect_column_refs(&mut used);
+    let left_required = RequiredColumns::new(&used, left_len);
+    let right_used = used
+        .range(left_len..)
+        .map(|&col| col - left_len)
+        .collect::<BTreeSet<_>>();
+    let right_required = RequiredColumns::new(&right_used, right_len);
+    if left_required.len() == left_len && right_required.len() == right_len {
+        return vec![];
+    }
+
+    let rewrite = |col| {
+        if col < left_len {
+            left_required.new_index(col)
+        } else {
+            right_required
+                .new_index(col - left_len)
+                .map(|col| col + left_required.len())
+        }
+    };
+    let join = LogicalJoin::new(
+        left_required.prune(PlanNode::from_group(left.into()), left_len),
+        right_required.prune(PlanNode::from_group(right.into()), right_len),
+        cond.rewrite_column_refs(&rewrite).unwrap(),
+        JoinType::Inner,
+    );
+    let node = LogicalProjection::new(
+        join.into_plan_node(),
+        exprs.rewrite_column_refs(&rewrite).unwrap(),
+    );
+    vec![node.into_rel_node().as_ref().clone()]
+}
+
+// Agg A exprs groups -> Agg (Proj A required) exprs' groups'
+define_rule!(
+    ProjectionPushDownAgg,
+    apply_projection_push_down_agg,
+    (Agg, child, [exprs], [groups])
+);
+
+fn apply_projection_push_down_agg(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    ProjectionPushDownAggPicks {
+        child,
+        exprs,
+        groups,
+    }: ProjectionPushDownAggPicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let child_len = schema_len(optimizer, &child);
+    let exprs = ExprList::from_rel_node(exprs.into()).unwrap();
+    let groups = ExprList::from_rel_node(groups.into()).unwrap();
+
+    let mut used = BTreeSet::new();
+    exprs.collect_column_refs(&mut used);
+    groups.collect_column_refs(&mut used);
+    let required = RequiredColumns::new(&used, child_len);
+    if required.len() == child_len {
+        return vec![];
+    }
+
+    let rewrite = |col| required.new_index(col);
+    let node = LogicalAgg::new(
+        required.prune(PlanNode::from_group(child.into()), child_len),
+        exprs.rewrite_column_refs(&rewrite).unwrap(),
+        groups.rewrite_column_refs(&rewrite).unwrap(),
+    );
+    vec![node.into_rel_node().as_ref().clone()]
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion_expr::AggregateFunction;
+
+    use super::*;
+    use crate::plan_nodes::{FuncExpr, FuncType};
+    use crate::testing::{col, eq, find_nodes, optimize, scan};
+
+    /// Gets the table columns read by each scan of an optimized plan, in sorted order.
+    fn scan_columns(plan: &OptRelNodeRef) -> Vec<Vec<usize>> {
+        find_nodes(plan, &|typ| *typ == OptRelNodeTyp::PhysicalScan)
+            .into_iter()
+            .map(|scan| {
+                scan.child(0)
+                    .children
+                    .iter()
+                    .map(|col| ColumnRefExpr::from_rel_node(col.clone()).unwrap().index())
+                    .collect_vec()
+            })
+            .sorted()
+            .collect()
+    }
+
+    #[test]
+    fn required_columns() {
+        let required = RequiredColumns::new(&BTreeSet::from([1, 2, 5]), 3);
+        assert_eq!(required.0, vec![1, 2]);
+        assert_eq!(required.new_index(2), Some(1));
+        assert_eq!(required.new_index(0), None);
+        // A pruned node keeps its first column if none is used.
+        assert_eq!(RequiredColumns::new(&BTreeSet::new(), 3).0, vec![0]);
+    }
+
+    #[test]
+    fn merged_projections_are_pushed_into_the_scan() {
+        let inner = LogicalProjection::new(scan("t", 3), ExprList::new(vec![col(1), col(0)]));
+        let plan = LogicalProjection::new(inner.into_plan_node(), ExprList::new(vec![col(0)]));
+        let plan = optimize(plan.into_plan_node());
+        assert_eq!(scan_columns(&plan), vec![vec![1]]);
+        assert!(find_nodes(&plan, &|typ| *typ == OptRelNodeTyp::PhysicalProjection).is_empty());
+    }
+
+    #[test]
+    fn columns_are_pruned_below_filters() {
+        // select a from t where b = 1
+        let filter = LogicalFilter::new(scan("t", 3), eq(col(1), ConstantExpr::int(1).into_expr()));
+        let plan = LogicalProjection::new(filter.into_plan_node(), ExprList::new(vec![col(0)]));
+        assert_eq!(
+            scan_columns(&optimize(plan.into_plan_node())),
+            vec![vec![0, 1]]
+        );
+    }
+
+    #[test]
+    fn columns_are_pruned_below_joins() {
+        // select t1.b from t1 join t2 on t1.a = t2.a
+        let join = LogicalJoin::new(
+            scan("t1", 3),
+            scan("t2", 3),
+            eq(col(0), col(3)),
+            JoinType::Inner,
+        );
+        let plan = LogicalProjection::new(join.into_plan_node(), ExprList::new(vec![col(1)]));
+        assert_eq!(
+            scan_columns(&optimize(plan.into_plan_node())),
+            vec![vec![0], vec![0, 1]]
+        );
+    }
+
+    #[test]
+    fn columns_are_pruned_below_aggregations() {
+        // select count(c) from t group by a
+        let count = FuncExpr::new(
+            FuncType::new_agg(AggregateFunction::Count),
+            ExprList::new(vec![col(2)]),
+        );
+        let plan = LogicalAgg::new(
+            scan("t", 3),
+            ExprList::new(vec![count.into_expr()]),
+            ExprList::new(vec![col(0)]),
+        );
+        assert_eq!(
+            scan_columns(&optimize(plan.into_plan_node())),
+            vec![vec![0, 2]]
+        );
+    }
+}
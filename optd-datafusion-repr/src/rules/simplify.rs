@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use optd_core::optimizer::Optimizer;
+use optd_core::rel_node::RelNode;
+use optd_core::rules::{Rule, RuleMatcher};
+
+use super::empty::empty_like;
+use super::macros::define_rule;
+use crate::expr_simplifier::{constant_bool, simplify_predicate};
+use crate::plan_nodes::{JoinType, OptRelNodeTyp};
+
+// Filter child cond -> Filter child cond', where `cond'` is the simplified `cond` in conjunctive
+// normal form. The filter is removed if `cond'` is true and replaced by an empty relation if it is
+// false or null.
+define_rule!(
+    SimplifyFilterRule,
+    apply_simplify_filter,
+    (Filter => filter, child, [cond])
+);
+
+fn apply_simplify_filter(
+    optimizer: &impl Optimizer<OptRelNodeTyp>,
+    SimplifyFilterRulePicks {
+        filter,
+        child,
+        cond,
+    }: SimplifyFilterRulePicks,
+) -> Vec<RelNode<OptRelNodeTyp>> {
+    let simplified = simplify_predicate(&Arc::new(cond.clone()));
+    match constant_bool(&simplified) {
+        Some(Some(true)) => vec![child],
+        Some(_) => vec![empty_like(optimizer, filter)],
+        None if simplified.to_string() == cond.to_string() => vec![],
+        None => vec![RelNode {
+            typ: OptRelNodeTyp::Filter,
+            children: vec![child.into(), simplified],
+            data: None,
+        }],
+    }
+}
+
+/// A join B on cond -> A join B on cond', where `cond'` is the simplified `cond` in conjunctive
+/// normal form. Inner and semi joins whose `cond'` is false or null are replaced by an empty
+/// relation.
+pub struct SimplifyJoinRule {
+    matcher: RuleMatcher<OptRelNodeTyp>,
+    join_type: JoinType,
+}
+
+impl SimplifyJoinRule {
+    pub fn new(join_type: JoinType) -> Self {
+        Self {
+            matcher: RuleMatcher::MatchAndPickNode {
+                typ: OptRelNodeTyp::Join(join_type),
+                pick_to: 0,
+                children: vec![
+                    RuleMatcher::PickOne {
+                        pick_to: 1,
+                        expand: false,
+                    },
+                    RuleMatcher::PickOne {
+                        pick_to: 2,
+                        expand: false,
+                    },
+                    RuleMatcher::PickOne {
+                        pick_to: 3,
+                        expand: true,
+                    },
+                ],
+            },
+            join_type,
+        }
+    }
+
+    pub fn all<O: Optimizer<OptRelNodeTyp>>() -> Vec<Arc<dyn Rule<OptRelNodeTyp, O>>> {
+        [
+            JoinType::Inner,
+            JoinType::Cross,
+            JoinType::LeftOuter,
+            JoinType::RightOuter,
+            JoinType::FullOuter,
+            JoinType::LeftSemi,
+            JoinType::RightSemi,
+            JoinType::LeftAnti,
+            JoinType::RightAnti,
+        ]
+        .into_iter()
+        .map(|join_type| Arc::new(Self::new(join_type)) as Arc<dyn Rule<OptRelNodeTyp, O>>)
+        .collect()
+    }
+}
+
+impl<O: Optimizer<OptRelNodeTyp>> Rule<OptRelNodeTyp, O> for SimplifyJoinRule {
+    fn matcher(&self) -> &RuleMatcher<OptRelNodeTyp> {
+        &self.matcher
+    }
+
+    fn apply(
+        &self,
+        optimizer: &O,
+        mut input: HashMap<usize, RelNode<OptRelNodeTyp>>,
+    ) -> Vec<RelNode<OptRelNodeTyp>> {
+        let join = input.remove(&0).unwrap();
+        let left = input.remove(&1).unwrap();
+        let right = input.remove(&2).unwrap();
+        let cond = input.remove(&3).unwrap();
+        let simplified = simplify_predicate(&Arc::new(cond.clone()));
+        let never_matches = matches!(constant_bool(&simplified), Some(None | Some(false)));
+        if never_matches
+            && matches!(
+                self.join_type,
+                JoinType::Inner | JoinType::Cross | JoinType::LeftSemi | JoinType::RightSemi
+            )
+        {
+            return vec![empty_like(optimizer, join)];
+        }
+        if simplified.to_string() == cond.to_string() {
+            return vec![];
+        }
+        vec![RelNode {
+            typ: join.typ,
+            children: vec![left.into(), right.into(), simplified],
+            data: join.data,
+        }]
+    }
+
+    fn name(&self) -> &'static str {
+        "simplify_join"
+    }
+}
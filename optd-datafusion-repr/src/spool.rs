@@ -0,0 +1,96 @@
+use std::collections::{HashMap, HashSet};
+
+use optd_core::cascades::{CascadesOptimizer, GroupId};
+use optd_core::rel_node::RelNode;
+use pretty_xmlish::Pretty;
+
+use crate::cost::OptCostModel;
+use crate::plan_nodes::{OptRelNode, OptRelNodeRef, OptRelNodeTyp, PlanNode};
+use crate::properties::schema::SchemaPropertyBuilder;
+
+/// The cost of writing a row into a spool, relative to processing it.
+const SPOOL_WRITE_COST: f64 = 1.0;
+
+/// The cost of reading a row from a spool.
+const SPOOL_READ_COST: f64 = 0.1;
+
+/// Executes its child once and replays the output to each of its parents. The spools of a group
+/// share their output, so the child of every spool but the first is ignored.
+#[derive(Clone, Debug)]
+pub struct PhysicalSpool(pub PlanNode);
+
+impl OptRelNode for PhysicalSpool {
+    fn into_rel_node(self) -> OptRelNodeRef {
+        self.0.into_rel_node()
+    }
+
+    fn from_rel_node(rel_node: OptRelNodeRef) -> Option<Self> {
+        if !matches!(rel_node.typ, OptRelNodeTyp::PhysicalSpool(_)) {
+            return None;
+        }
+        PlanNode::from_rel_node(rel_node).map(Self)
+    }
+
+    fn dispatch_explain(&self) -> Pretty<'static> {
+        Pretty::simple_record(
+            "PhysicalSpool",
+            vec![("group_id", self.group_id().to_string().into())],
+            vec![self.child().explain()],
+        )
+    }
+
+    fn dispatch_explain_with_names(&self, builder: &SchemaPropertyBuilder) -> Pretty<'static> {
+        Pretty::simple_record(
+            "PhysicalSpool",
+            vec![("group_id", self.group_id().to_string().into())],
+            vec![self.child().explain_with_names(builder)],
+        )
+    }
+}
+
+impl PhysicalSpool {
+    pub fn new(child: PlanNode, group_id: GroupId) -> PhysicalSpool {
+        PhysicalSpool(PlanNode(
+            RelNode {
+                typ: OptRelNodeTyp::PhysicalSpool(group_id),
+                children: vec![child.into_rel_node()],
+                data: None,
+            }
+            .into(),
+        ))
+    }
+
+    pub fn group_id(&self) -> GroupId {
+        if let OptRelNodeTyp::PhysicalSpool(group_id) = self.clone().into_rel_node().typ {
+            group_id
+        } else {
+            panic!("not a physical spool")
+        }
+    }
+
+    pub fn child(&self) -> PlanNode {
+        PlanNode::from_rel_node(self.clone().into_rel_node().child(0)).unwrap()
+    }
+}
+
+/// Chooses the groups referenced several times by the best plan whose output is cheaper to
+/// materialize once than to recompute for each reference, given the reference counts of the
+/// groups.
+pub fn groups_to_spool(
+    optimizer: &CascadesOptimizer<OptRelNodeTyp>,
+    ref_counts: &HashMap<GroupId, usize>,
+) -> HashSet<GroupId> {
+    ref_counts
+        .iter()
+        .filter(|(_, ref_cnt)| **ref_cnt > 1)
+        .filter_map(|(group_id, ref_cnt)| {
+            let cost = optimizer.get_winner_cost_of(*group_id)?;
+            let ref_cnt = *ref_cnt as f64;
+            let row_cnt = OptCostModel::row_cnt(&cost);
+            let recompute_cost = cost.0[0] * ref_cnt;
+            let spool_cost =
+                cost.0[0] + row_cnt * SPOOL_WRITE_COST + row_cnt * SPOOL_READ_COST * ref_cnt;
+            (spool_cost < recompute_cost).then_some(*group_id)
+        })
+        .collect()
+}
@@ -0,0 +1,127 @@
+//! Plans and optimizers shared by the unit tests.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use optd_core::cascades::CascadesOptimizer;
+use optd_core::heuristics::{ApplyOrder, HeuristicsOptimizer};
+use optd_core::rules::Rule;
+
+use crate::cost::OptCostModel;
+use crate::plan_nodes::{
+    BinOpExpr, BinOpType, ColumnRefExpr, ConstantType, Expr, ExprList, LogicalScan, OptRelNode,
+    OptRelNodeRef, OptRelNodeTyp, PlanNode,
+};
+use crate::properties::{
+    distribution::DistributionPropertyBuilder,
+    equivalence::EquivalencePropertyBuilder,
+    keys::KeyPropertyBuilder,
+    ordering::OrderingPropertyBuilder,
+    schema::{Catalog, Field, Schema, SchemaPropertyBuilder},
+};
+use crate::DatafusionOptimizer;
+
+/// Every table has the nullable integer columns `a`, `b` and `c`.
+pub(crate) struct TestCatalog;
+
+impl Catalog for TestCatalog {
+    fn get(&self, _name: &str) -> Schema {
+        Schema::new(
+            ["a", "b", "c"]
+                .into_iter()
+                .map(|name| Field::new(name, ConstantType::Int32, true))
+                .collect(),
+        )
+    }
+}
+
+/// Builds a cascades optimizer without rules, with the properties in the order the optimizer of
+/// the crate derives them.
+pub(crate) fn new_optimizer() -> CascadesOptimizer<OptRelNodeTyp> {
+    new_optimizer_with_rules(vec![], HashMap::new())
+}
+
+/// Builds a cascades optimizer with `rules` and the base cost model over the row counts in
+/// `table_stat`, which runs parallel operators on the default number of partitions.
+pub(crate) fn new_optimizer_with_rules(
+    rules: Vec<Arc<dyn Rule<OptRelNodeTyp, CascadesOptimizer<OptRelNodeTyp>>>>,
+    table_stat: HashMap<String, usize>,
+) -> CascadesOptimizer<OptRelNodeTyp> {
+    let catalog: Arc<dyn Catalog> = Arc::new(TestCatalog);
+    CascadesOptimizer::new(
+        rules,
+        Box::new(OptCostModel::new(table_stat)),
+        vec![
+            Box::new(SchemaPropertyBuilder::new(catalog.clone())),
+            Box::new(KeyPropertyBuilder::new(catalog)),
+            Box::new(DistributionPropertyBuilder::new()),
+            Box::new(EquivalencePropertyBuilder::new()),
+            Box::new(OrderingPropertyBuilder::new()),
+        ],
+    )
+}
+
+/// Builds a heuristics optimizer without rules, which derives the properties of the optimizer of
+/// the crate from the plans given to the rules.
+pub(crate) fn new_heuristics_optimizer() -> HeuristicsOptimizer<OptRelNodeTyp> {
+    new_heuristics_optimizer_with_rules(vec![], ApplyOrder::TopDown)
+}
+
+/// Builds a heuristics optimizer applying `rules` in `apply_order`, with the properties of the
+/// optimizer of the crate.
+pub(crate) fn new_heuristics_optimizer_with_rules(
+    rules: Vec<Arc<dyn Rule<OptRelNodeTyp, HeuristicsOptimizer<OptRelNodeTyp>>>>,
+    apply_order: ApplyOrder,
+) -> HeuristicsOptimizer<OptRelNodeTyp> {
+    let catalog: Arc<dyn Catalog> = Arc::new(TestCatalog);
+    HeuristicsOptimizer::new_with_properties(
+        rules,
+        apply_order,
+        vec![
+            Box::new(SchemaPropertyBuilder::new(catalog.clone())),
+            Box::new(KeyPropertyBuilder::new(catalog)),
+            Box::new(DistributionPropertyBuilder::new()),
+            Box::new(EquivalencePropertyBuilder::new()),
+            Box::new(OrderingPropertyBuilder::new()),
+        ],
+    )
+}
+
+/// Optimizes a plan over the test catalog with the rules of the default optimizer. Adaptive mode is
+/// disabled, so that the plan nodes are not wrapped into collectors.
+pub(crate) fn optimize(plan: PlanNode) -> OptRelNodeRef {
+    let mut optimizer = DatafusionOptimizer::new_physical(Box::new(TestCatalog));
+    optimizer.enable_adaptive(false);
+    optimizer.optimize(plan.into_rel_node()).unwrap().1
+}
+
+/// Collects the nodes of `rel_node` whose type matches `pred`, in pre-order.
+pub(crate) fn find_nodes(
+    rel_node: &OptRelNodeRef,
+    pred: &impl Fn(&OptRelNodeTyp) -> bool,
+) -> Vec<OptRelNodeRef> {
+    let mut nodes = vec![];
+    if pred(&rel_node.typ) {
+        nodes.push(rel_node.clone());
+    }
+    for child in &rel_node.children {
+        nodes.extend(find_nodes(child, pred));
+    }
+    nodes
+}
+
+/// Scans the first `width` columns of `table`.
+pub(crate) fn scan(table: &str, width: usize) -> PlanNode {
+    let projection = (0..width)
+        .map(|col| ColumnRefExpr::new(col).into_expr())
+        .collect();
+    LogicalScan::new(table.to_string(), ExprList::new(projection)).into_plan_node()
+}
+
+pub(crate) fn col(index: usize) -> Expr {
+    ColumnRefExpr::new(index).into_expr()
+}
+
+pub(crate) fn eq(left: Expr, right: Expr) -> Expr {
+    BinOpExpr::new(left, right, BinOpType::Eq).into_expr()
+}
@@ -0,0 +1,310 @@
+//! Type inference for expressions. Operand coercion and function return types are resolved with
+//! DataFusion's signature metadata, so that the optimizer sees the same types as the executor.
+
+use anyhow::{bail, Result};
+use arrow_schema::{DataType, IntervalUnit as ArrowIntervalUnit, TimeUnit as ArrowTimeUnit};
+use datafusion_expr::{
+    aggregate_function,
+    type_coercion::binary::{comparison_coercion, get_input_types, get_result_type},
+    Operator,
+};
+
+use crate::{
+    plan_nodes::{
+        BinOpType, ConstantType, FuncType, IntervalUnit, OptRelNodeRef, OptRelNodeTyp, TimeUnit,
+        UnOpType,
+    },
+    properties::schema::Schema,
+};
+
+impl ConstantType {
+    pub fn from_data_type(typ: &DataType) -> Result<Self> {
+        let typ = match typ {
+            DataType::Null => ConstantType::Null,
+            DataType::Boolean => ConstantType::Bool,
+            DataType::Int8 => ConstantType::Int8,
+            DataType::Int16 => ConstantType::Int16,
+            DataType::Int32 => ConstantType::Int32,
+            DataType::Int64 => ConstantType::Int64,
+            DataType::UInt8 => ConstantType::UInt8,
+            DataType::UInt16 => ConstantType::UInt16,
+            DataType::UInt32 => ConstantType::UInt32,
+            DataType::UInt64 => ConstantType::UInt64,
+            DataType::Float32 => ConstantType::Float32,
+            DataType::Float64 => ConstantType::Float64,
+            DataType::Decimal128(p, s) => ConstantType::Decimal128(*p, *s),
+            DataType::Date32 => ConstantType::Date32,
+            DataType::Date64 => ConstantType::Date64,
+            DataType::Timestamp(unit, tz) => {
+                let unit = match unit {
+                    ArrowTimeUnit::Second => TimeUnit::Second,
+                    ArrowTimeUnit::Millisecond => TimeUnit::Millisecond,
+                    ArrowTimeUnit::Microsecond => TimeUnit::Microsecond,
+                    ArrowTimeUnit::Nanosecond => TimeUnit::Nanosecond,
+                };
+                ConstantType::Timestamp(unit, tz.clone())
+            }
+            DataType::Interval(unit) => ConstantType::Interval(match unit {
+                ArrowIntervalUnit::YearMonth => IntervalUnit::YearMonth,
+                ArrowIntervalUnit::DayTime => IntervalUnit::DayTime,
+                ArrowIntervalUnit::MonthDayNano => IntervalUnit::MonthDayNano,
+            }),
+            DataType::Utf8 => ConstantType::Utf8String,
+            DataType::Binary => ConstantType::Binary,
+            typ => bail!("unsupported data type: {:?}", typ),
+        };
+        Ok(typ)
+    }
+
+    pub fn into_data_type(&self) -> Result<DataType> {
+        let typ = match self {
+            ConstantType::Any => bail!("cannot convert Any into an arrow type"),
+            ConstantType::Null => DataType::Null,
+            ConstantType::Bool => DataType::Boolean,
+            ConstantType::Int8 => DataType::Int8,
+            ConstantType::Int16 => DataType::Int16,
+            ConstantType::Int32 => DataType::Int32,
+            ConstantType::Int64 => DataType::Int64,
+            ConstantType::UInt8 => DataType::UInt8,
+            ConstantType::UInt16 => DataType::UInt16,
+            ConstantType::UInt32 => DataType::UInt32,
+            ConstantType::UInt64 => DataType::UInt64,
+            ConstantType::Float32 => DataType::Float32,
+            ConstantType::Float64 => DataType::Float64,
+            ConstantType::Decimal128(p, s) => DataType::Decimal128(*p, *s),
+            ConstantType::Date32 => DataType::Date32,
+            ConstantType::Date64 => DataType::Date64,
+            ConstantType::Timestamp(unit, tz) => {
+                let unit = match unit {
+                    TimeUnit::Second => ArrowTimeUnit::Second,
+                    TimeUnit::Millisecond => ArrowTimeUnit::Millisecond,
+                    TimeUnit::Microsecond => ArrowTimeUnit::Microsecond,
+                    TimeUnit::Nanosecond => ArrowTimeUnit::Nanosecond,
+                };
+                DataType::Timestamp(unit, tz.clone())
+            }
+            ConstantType::Interval(unit) => DataType::Interval(match unit {
+                IntervalUnit::YearMonth => ArrowIntervalUnit::YearMonth,
+                IntervalUnit::DayTime => ArrowIntervalUnit::DayTime,
+                IntervalUnit::MonthDayNano => ArrowIntervalUnit::MonthDayNano,
+            }),
+            ConstantType::Utf8String => DataType::Utf8,
+            ConstantType::Binary => DataType::Binary,
+        };
+        Ok(typ)
+    }
+}
+
+impl BinOpType {
+    pub fn into_operator(self) -> Operator {
+        match self {
+            BinOpType::Add => Operator::Plus,
+            BinOpType::Sub => Operator::Minus,
+            BinOpType::Mul => Operator::Multiply,
+            BinOpType::Div => Operator::Divide,
+            BinOpType::Mod => Operator::Modulo,
+            BinOpType::Eq => Operator::Eq,
+            BinOpType::Neq => Operator::NotEq,
+            BinOpType::Gt => Operator::Gt,
+            BinOpType::Lt => Operator::Lt,
+            BinOpType::Geq => Operator::GtEq,
+            BinOpType::Leq => Operator::LtEq,
+            BinOpType::And => Operator::And,
+            BinOpType::Or => Operator::Or,
+            BinOpType::Xor => Operator::BitwiseXor,
+        }
+    }
+}
+
+/// Gets the common type both sides of a comparison are coerced to, if they are comparable.
+pub fn comparison_type(left: &ConstantType, right: &ConstantType) -> Option<ConstantType> {
+    let left = left.into_data_type().ok()?;
+    let right = right.into_data_type().ok()?;
+    ConstantType::from_data_type(&comparison_coercion(&left, &right)?).ok()
+}
+
+/// Gets the types the operands of a binary operator are coerced to, if they can be coerced.
+pub fn binary_operand_types(
+    left: &ConstantType,
+    op: BinOpType,
+    right: &ConstantType,
+) -> Option<(ConstantType, ConstantType)> {
+    let left = left.into_data_type().ok()?;
+    let right = right.into_data_type().ok()?;
+    let (left, right) = get_input_types(&left, &op.into_operator(), &right).ok()?;
+    Some((
+        ConstantType::from_data_type(&left).ok()?,
+        ConstantType::from_data_type(&right).ok()?,
+    ))
+}
+
+/// Infers the result type of an expression evaluated over `input`, falling back to
+/// `ConstantType::Any` if the expression cannot be typed.
+pub fn expr_type(expr: &OptRelNodeRef, input: &Schema) -> ConstantType {
+    try_expr_type(expr, input).unwrap_or(ConstantType::Any)
+}
+
+/// Infers the result type of an expression evaluated over `input`.
+pub fn try_expr_type(expr: &OptRelNodeRef, input: &Schema) -> Result<ConstantType> {
+    let typ = match &expr.typ {
+        OptRelNodeTyp::ColumnRef => {
+            let idx = expr.data.as_ref().unwrap().as_i64() as usize;
+            let Some(field) = input.fields.get(idx) else {
+                bail!("column #{} out of range of {} columns", idx, input.len())
+            };
+            field.typ.clone()
+        }
+        OptRelNodeTyp::ColumnId => {
+            let id = expr.data.as_ref().unwrap().as_u64();
+            let Some(idx) = input.index_of_id(id) else {
+                bail!("column ${} not found", id)
+            };
+            input.field(idx).typ.clone()
+        }
+        OptRelNodeTyp::Constant(typ) | OptRelNodeTyp::Parameter(typ) => typ.clone(),
+        OptRelNodeTyp::UnOp(UnOpType::Not) => ConstantType::Bool,
+        OptRelNodeTyp::UnOp(UnOpType::Neg) => try_expr_type(&expr.child(0), input)?,
+        OptRelNodeTyp::BinOp(op) => {
+            let left = try_expr_type(&expr.child(0), input)?.into_data_type()?;
+            let right = try_expr_type(&expr.child(1), input)?.into_data_type()?;
+            let typ = get_result_type(&left, &op.into_operator(), &right)?;
+            ConstantType::from_data_type(&typ)?
+        }
+        OptRelNodeTyp::LogOp(_) => ConstantType::Bool,
+        OptRelNodeTyp::Func(func) => {
+            let args = expr_list_data_types(&expr.child(0), input)?;
+            match func {
+                FuncType::Scalar(func) => ConstantType::from_data_type(&func.return_type(&args)?)?,
+                FuncType::Agg(func) => {
+                    ConstantType::from_data_type(&aggregate_function::return_type(func, &args)?)?
+                }
+                FuncType::Case => {
+                    // CASE takes [when, then, else], and the result is the coerced type of then
+                    // and else.
+                    let then_typ = ConstantType::from_data_type(&args[1])?;
+                    let else_typ = ConstantType::from_data_type(&args[2])?;
+                    match (then_typ, else_typ) {
+                        (ConstantType::Null, typ) | (typ, ConstantType::Null) => typ,
+                        (then_typ, else_typ) => {
+                            let Some(typ) = comparison_type(&then_typ, &else_typ) else {
+                                bail!("cannot coerce {:?} and {:?}", then_typ, else_typ)
+                            };
+                            typ
+                        }
+                    }
+                }
+            }
+        }
+        OptRelNodeTyp::SortOrder(_) => try_expr_type(&expr.child(0), input)?,
+        typ => bail!("{} is not an expression", typ),
+    };
+    Ok(typ)
+}
+
+fn expr_list_data_types(exprs: &OptRelNodeRef, input: &Schema) -> Result<Vec<DataType>> {
+    exprs
+        .children
+        .iter()
+        .map(|expr| try_expr_type(expr, input)?.into_data_type())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan_nodes::{BinOpExpr, ConstantExpr, Expr, ExprList, FuncExpr, OptRelNode};
+    use crate::properties::schema::Field;
+    use crate::testing::{col, eq};
+
+    fn input() -> Schema {
+        Schema::new(vec![
+            Field::new("a", ConstantType::Int32, true),
+            Field::new("b", ConstantType::Float64, true),
+            Field::new("c", ConstantType::Decimal128(5, 2), true),
+        ])
+    }
+
+    fn bin_op(left: Expr, right: Expr, op: BinOpType) -> OptRelNodeRef {
+        BinOpExpr::new(left, right, op).into_rel_node()
+    }
+
+    #[test]
+    fn columns_take_their_input_type() {
+        let input = input();
+        assert_eq!(
+            try_expr_type(&col(0).into_rel_node(), &input).unwrap(),
+            ConstantType::Int32
+        );
+        assert!(try_expr_type(&col(3).into_rel_node(), &input).is_err());
+        assert_eq!(
+            expr_type(&col(3).into_rel_node(), &input),
+            ConstantType::Any
+        );
+    }
+
+    #[test]
+    fn arithmetic_operands_are_coerced() {
+        let input = input();
+        let int = ConstantExpr::int(1).into_expr();
+        let typ = |expr| try_expr_type(&expr, &input).unwrap();
+        assert_eq!(
+            typ(bin_op(col(0), int, BinOpType::Add)),
+            ConstantType::Int64
+        );
+        assert_eq!(
+            typ(bin_op(col(0), col(1), BinOpType::Mul)),
+            ConstantType::Float64
+        );
+        assert_eq!(
+            typ(bin_op(col(2), col(2), BinOpType::Add)),
+            ConstantType::Decimal128(6, 2)
+        );
+        assert_eq!(
+            typ(bin_op(col(2), col(2), BinOpType::Mul)),
+            ConstantType::Decimal128(11, 4)
+        );
+        assert_eq!(typ(eq(col(0), col(1)).into_rel_node()), ConstantType::Bool);
+    }
+
+    #[test]
+    fn case_coerces_then_and_else() {
+        let input = input();
+        let case = |then: Expr, otherwise: Expr| {
+            let args = ExprList::new(vec![eq(col(0), col(0)), then, otherwise]);
+            FuncExpr::new(FuncType::Case, args).into_rel_node()
+        };
+        assert_eq!(
+            try_expr_type(&case(col(0), ConstantExpr::int(0).into_expr()), &input).unwrap(),
+            ConstantType::Int64
+        );
+        let null = ConstantExpr::null(ConstantType::Null).into_expr();
+        assert_eq!(
+            try_expr_type(&case(col(1), null), &input).unwrap(),
+            ConstantType::Float64
+        );
+    }
+
+    #[test]
+    fn comparison_and_operand_types() {
+        assert_eq!(
+            comparison_type(&ConstantType::Int32, &ConstantType::Int64),
+            Some(ConstantType::Int64)
+        );
+        assert_eq!(
+            comparison_type(&ConstantType::Null, &ConstantType::Int32),
+            Some(ConstantType::Int32)
+        );
+        assert_eq!(
+            comparison_type(&ConstantType::Any, &ConstantType::Int32),
+            None
+        );
+        assert_eq!(
+            binary_operand_types(&ConstantType::Int32, BinOpType::Lt, &ConstantType::Int64),
+            Some((ConstantType::Int64, ConstantType::Int64))
+        );
+        assert_eq!(
+            binary_operand_types(&ConstantType::Int32, BinOpType::Add, &ConstantType::Float64),
+            Some((ConstantType::Float64, ConstantType::Float64))
+        );
+    }
+}
@@ -1,7 +1,10 @@
+use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
 use datafusion::execution::context::{SessionConfig, SessionState};
 use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
+use datafusion::physical_plan::collect;
 use datafusion::prelude::SessionContext;
+use datafusion::scalar::ScalarValue;
 use datafusion::sql::parser::DFParser;
 use datafusion::sql::sqlparser::dialect::GenericDialect;
 use datafusion_optd_cli::helper::unescape_input;
@@ -17,9 +20,11 @@ static GLOBAL: MiMalloc = MiMalloc;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 
-#[derive(Default)]
 pub struct DatafusionDb {
     ctx: SessionContext,
+    planner: Arc<OptdQueryPlanner>,
+    /// The catalog of the optimizer, which foreign keys are declared in.
+    catalog: DatafusionCatalog,
 }
 
 impl DatafusionDb {
@@ -29,17 +34,21 @@ impl DatafusionDb {
         let rn_config = RuntimeConfig::new();
         let runtime_env = RuntimeEnv::new(rn_config.clone())?;
 
-        let ctx = {
+        let (ctx, planner, catalog) = {
             let mut state =
                 SessionState::new_with_config_rt(session_config.clone(), Arc::new(runtime_env));
-            let optimizer = DatafusionOptimizer::new_physical(Box::new(DatafusionCatalog::new(
-                state.catalog_list(),
-            )));
-            state = state.with_query_planner(Arc::new(OptdQueryPlanner::new(optimizer)));
-            SessionContext::new_with_state(state)
+            let catalog = DatafusionCatalog::new(state.catalog_list());
+            let optimizer = DatafusionOptimizer::new_physical(Box::new(catalog.clone()));
+            let planner = Arc::new(OptdQueryPlanner::new(optimizer));
+            state = state.with_query_planner(planner.clone());
+            (SessionContext::new_with_state(state), planner, catalog)
         };
         ctx.refresh_catalogs().await?;
-        Ok(Self { ctx })
+        Ok(Self {
+            ctx,
+            planner,
+            catalog,
+        })
     }
 
     async fn execute(&self, sql: &str) -> Result<Vec<Vec<String>>> {
@@ -51,29 +60,64 @@ impl DatafusionDb {
             let plan = self.ctx.state().statement_to_plan(statement).await?;
 
             let df = self.ctx.execute_logical_plan(plan).await?;
-            let batches = df.collect().await?;
-
-            let options = FormatOptions::default();
+            format_batches(df.collect().await?, &mut result)?;
+        }
+        Ok(result)
+    }
 
-            for batch in batches {
-                let converters = batch
-                    .columns()
-                    .iter()
-                    .map(|a| ArrayFormatter::try_new(a.as_ref(), &options))
-                    .collect::<Result<Vec<_>, _>>()?;
-                for row_idx in 0..batch.num_rows() {
-                    let mut row = Vec::with_capacity(batch.num_columns());
-                    for (_, converter) in converters.iter().enumerate() {
-                        let mut buffer = String::with_capacity(8);
-                        converter.value(row_idx).write(&mut buffer)?;
-                        row.push(buffer);
-                    }
-                    result.push(row);
+    /// Executes the prepared statement `name` with the values `values`, which are integers or
+    /// quoted strings separated by commas.
+    async fn execute_prepared(&self, name: &str, values: &str) -> Result<Vec<Vec<String>>> {
+        let values = values
+            .split(',')
+            .map(|value| {
+                let value = value.trim();
+                if let Some(value) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+                    return Ok(ScalarValue::Utf8(Some(value.to_string())));
                 }
+                Ok(ScalarValue::Int64(Some(value.parse().with_context(
+                    || format!("expected an integer or a quoted string, got {}", value),
+                )?)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let state = self.ctx.state();
+        let plan = self.planner.execute_prepared(name, values, &state).await?;
+        let mut result = Vec::new();
+        format_batches(collect(plan, self.ctx.task_ctx()).await?, &mut result)?;
+        Ok(result)
+    }
+}
+
+fn format_batches(batches: Vec<RecordBatch>, result: &mut Vec<Vec<String>>) -> Result<()> {
+    let options = FormatOptions::default();
+
+    for batch in batches {
+        let converters = batch
+            .columns()
+            .iter()
+            .map(|a| ArrayFormatter::try_new(a.as_ref(), &options))
+            .collect::<Result<Vec<_>, _>>()?;
+        for row_idx in 0..batch.num_rows() {
+            let mut row = Vec::with_capacity(batch.num_columns());
+            for (_, converter) in converters.iter().enumerate() {
+                let mut buffer = String::with_capacity(8);
+                converter.value(row_idx).write(&mut buffer)?;
+                row.push(buffer);
             }
+            result.push(row);
         }
-        Ok(result)
     }
+    Ok(())
+}
+
+/// Parses `table(column, ...)`.
+fn parse_table_columns(s: &str) -> Result<(&str, Vec<&str>)> {
+    let (table, columns) = s
+        .trim()
+        .strip_suffix(')')
+        .and_then(|s| s.split_once('('))
+        .with_context(|| format!("expected table(columns), got {}", s))?;
+    Ok((table.trim(), columns.split(',').map(str::trim).collect()))
 }
 
 #[async_trait]
@@ -89,7 +133,36 @@ impl sqlplannertest::PlannerTestRunner for DatafusionDb {
         let mut result = String::new();
         let r = &mut result;
         for task in &test_case.tasks {
-            if task == "execute" {
+            if task == "enable_join_enumeration" {
+                self.planner.enable_join_enumeration();
+            } else if task == "disable_join_enumeration" {
+                self.planner.disable_join_enumeration();
+            } else if let Some(foreign_key) = task.strip_prefix("foreign_key:") {
+                // foreign_key:table(columns) references table(columns)
+                let (from, to) = foreign_key
+                    .split_once(" references ")
+                    .context("expected a references clause")?;
+                let (table, columns) = parse_table_columns(from)?;
+                let (referenced_table, referenced_columns) = parse_table_columns(to)?;
+                self.catalog.add_foreign_key(
+                    table,
+                    &columns,
+                    referenced_table,
+                    &referenced_columns,
+                )?;
+            } else if let Some(name) = task.strip_prefix("prepare:") {
+                // prepare:name(types) prepares the SQL of the test case.
+                self.execute(&format!("prepare {} as {}", name, test_case.sql))
+                    .await?;
+            } else if let Some(args) = task.strip_prefix("execute_prepared:") {
+                // execute_prepared:name values
+                let (name, values) = args
+                    .split_once(' ')
+                    .context("expected the name and the values of a prepared statement")?;
+                let result = self.execute_prepared(name, values).await?;
+                writeln!(r, "{}", result.into_iter().map(|x| x.join(" ")).join("\n"))?;
+                writeln!(r)?;
+            } else if task == "execute" {
                 let result = self.execute(&test_case.sql).await?;
                 writeln!(r, "{}", result.into_iter().map(|x| x.join(" ")).join("\n"))?;
                 writeln!(r)?;
@@ -108,6 +181,20 @@ impl sqlplannertest::PlannerTestRunner for DatafusionDb {
                                 .unwrap()
                         )?;
                         writeln!(r)?;
+                    } else if subtask == "logical_optd" || subtask == "physical_optd" {
+                        let plan_type = if subtask == "logical_optd" {
+                            "logical_plan after optd"
+                        } else {
+                            "physical_plan after optd"
+                        };
+                        let plan = result
+                            .iter()
+                            .find(|x| x[0] == plan_type)
+                            .map(|x| &x[1])
+                            .unwrap();
+                        // The explained plan pads its lines to the same width.
+                        writeln!(r, "{}", plan.lines().map(str::trim_end).join("\n"))?;
+                        writeln!(r)?;
                     } else if subtask == "logical_join_orders" {
                         writeln!(
                             r,
@@ -0,0 +1,69 @@
+-- (no id or description)
+create table t1(a int, b int);
+create table t2(c int);
+insert into t1 values (1, 10), (1, 10), (2, 20), (3, 30);
+insert into t2 values (1), (2), (2), (4);
+
+/*
+4
+4
+*/
+
+-- Test DISTINCT.
+select distinct a, b from t1 order by a;
+
+/*
+1 10
+2 20
+3 30
+*/
+
+-- Test aggregations of distinct values.
+select count(distinct a) from t1;
+
+/*
+3
+*/
+
+-- Test UNION.
+select a from t1 union select c from t2 order by a;
+
+/*
+1
+2
+3
+4
+*/
+
+-- Test UNION ALL.
+select a from t1 union all select c from t2 order by a;
+
+/*
+1
+1
+1
+2
+2
+2
+3
+4
+*/
+
+-- Test DISTINCT over UNION.
+select distinct a from (select a from t1 union select c from t2) order by a;
+
+/*
+1
+2
+3
+4
+*/
+
+-- Test DISTINCT over a semi join.
+select distinct a from t1 where a in (select c from t2) order by a;
+
+/*
+1
+2
+*/
+
@@ -0,0 +1,31 @@
+-- (no id or description)
+create table t1(t1v1 int, t1v2 int);
+create table t2(t2v1 int, t2v3 int);
+insert into t1 values (0, 0), (1, 1), (2, 2);
+insert into t2 values (0, 200), (1, 201), (2, 202);
+
+/*
+3
+3
+*/
+
+-- Test that explained plans print the names of the columns they refer to.
+select t1v2, t2v3 from t1 join t2 on t1v1 = t2v1 where t1v2 > 1;
+
+/*
+LogicalProjection { exprs: [ t1.t1v2, t2.t2v3 ] }
+└── LogicalJoin
+    ├── join_type: Inner
+    ├── cond:Eq
+    │   ├── t1.t1v1
+    │   └── t2.t2v1
+    ├── LogicalFilter
+    │   ├── cond:Gt
+    │   │   ├── t1.t1v2
+    │   │   └── 1
+    │   └── LogicalScan { table: t1, projection: [ t1.t1v1, t1.t1v2 ] }
+    └── LogicalScan { table: t2, projection: [ t2.t2v1, t2.t2v3 ] }
+
+2 202
+*/
+
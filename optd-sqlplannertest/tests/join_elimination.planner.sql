@@ -0,0 +1,29 @@
+-- (no id or description)
+create table customer(c_custkey int not null, c_name varchar, primary key (c_custkey));
+create table orders(o_id int not null, o_custkey int not null);
+insert into customer values (1, 'a'), (2, 'b'), (3, 'c');
+insert into orders values (10, 1), (11, 1), (12, 3);
+
+/*
+3
+3
+*/
+
+-- Test whether a join checking only a foreign key is eliminated.
+select o_id, o_custkey from orders join customer on o_custkey = c_custkey order by o_id;
+
+/*
+10 1
+11 1
+12 3
+*/
+
+-- Test whether a join whose output refers to the referenced table is kept.
+select o_id, c_name from orders join customer on o_custkey = c_custkey order by o_id;
+
+/*
+10 a
+11 a
+12 c
+*/
+
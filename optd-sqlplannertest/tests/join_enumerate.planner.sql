@@ -48,3 +48,39 @@ select * from t1, t2, t3 where t1v1 = t2v1 and t1v2 = t3v2;
 2 2 2 202 2 302
 */
 
+-- Test whether DPhyp enumerates all join orders without cross products.
+select * from t2, t1, t3 where t1v1 = t2v1 and t1v2 = t3v2 order by t1v1;
+
+/*
+(Join t2 (Join t1 t3))
+(Join t2 (Join t3 t1))
+(Join t3 (Join t1 t2))
+(Join t3 (Join t2 t1))
+(Join (Join t1 t2) t3)
+(Join (Join t1 t3) t2)
+(Join (Join t2 t1) t3)
+(Join (Join t3 t1) t2)
+
+0 200 0 0 0 300
+1 201 1 1 1 301
+2 202 2 2 2 302
+*/
+
+-- Test whether DPhyp enumerates the join orders of joins on the same column.
+select * from t1, t2, t3 where t1v1 = t2v1 and t1v1 = t3v2 order by t1v1;
+
+/*
+(Join t2 (Join t1 t3))
+(Join t2 (Join t3 t1))
+(Join t3 (Join t1 t2))
+(Join t3 (Join t2 t1))
+(Join (Join t1 t2) t3)
+(Join (Join t1 t3) t2)
+(Join (Join t2 t1) t3)
+(Join (Join t3 t1) t2)
+
+0 0 0 200 0 300
+1 1 1 201 1 301
+2 2 2 202 2 302
+*/
+
@@ -0,0 +1,62 @@
+-- (no id or description)
+create table t1(a int, b int);
+create table t2(c int, d int);
+insert into t1 values (1, 10), (2, 20), (3, 30);
+insert into t2 values (1, 100), (2, 200), (4, 400);
+
+/*
+3
+3
+*/
+
+-- Test left outer joins.
+select a, b, coalesce(c, -1), coalesce(d, -1) from t1 left join t2 on a = c order by a;
+
+/*
+1 10 1 100
+2 20 2 200
+3 30 -1 -1
+*/
+
+-- Test right outer joins.
+select coalesce(a, -1), coalesce(b, -1), c, d from t1 right join t2 on a = c order by c;
+
+/*
+1 10 1 100
+2 20 2 200
+-1 -1 4 400
+*/
+
+-- Test full outer joins.
+select coalesce(a, c) as k, coalesce(b, -1), coalesce(d, -1) from t1 full join t2 on a = c order by k;
+
+/*
+1 10 100
+2 20 200
+3 30 -1
+4 -1 400
+*/
+
+-- Test whether a left outer join is simplified to an inner join under a null-rejecting filter.
+select a, b, c, d from t1 left join t2 on a = c where d > 150 order by a;
+
+/*
+2 20 2 200
+*/
+
+-- Test whether a full outer join is simplified to a left outer join under a null-rejecting filter.
+select coalesce(a, c) as k, coalesce(b, -1), coalesce(d, -1) from t1 full join t2 on a = c where b < 25 order by k;
+
+/*
+1 10 100
+2 20 200
+*/
+
+-- Test whether a left outer join is kept under a filter that accepts nulls.
+select a, b, coalesce(c, -1), coalesce(d, -1) from t1 left join t2 on a = c where coalesce(d, 0) < 150 order by a;
+
+/*
+1 10 1 100
+3 30 -1 -1
+*/
+
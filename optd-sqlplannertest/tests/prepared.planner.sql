@@ -0,0 +1,39 @@
+-- (no id or description)
+create table t1(a int, b int);
+insert into t1 values (1, 10), (2, 20), (3, 30);
+
+/*
+3
+*/
+
+-- Test prepared statements.
+select a, b from t1 where a = $1;
+
+/*
+1 10
+
+3 30
+*/
+
+-- Test prepared statements with a parameter in a range predicate.
+select a, b from t1 where a > $1 order by a;
+
+/*
+2 20
+3 30
+
+1 10
+2 20
+3 30
+*/
+
+-- Test prepared statements with several parameters.
+select a, b from t1 where a >= $1 and b <= $2 order by a;
+
+/*
+2 20
+3 30
+
+1 10
+*/
+